@@ -0,0 +1,19 @@
+//! 编译任意字节串：不能panic，编译成功的程序跳转表必须一致
+#![no_main]
+
+use derstand::DerstandInterpreter;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(source) = std::str::from_utf8(data) else {
+        return;
+    };
+    let mut interpreter = DerstandInterpreter::new();
+    interpreter.set_allow_include(false);
+    // 编译指示可以要求很大的纸带
+    interpreter.set_memory_size(4096);
+    interpreter.set_assertions(true);
+    if interpreter.compile(source).is_ok() {
+        derstand_fuzz::check_jump_table(&interpreter);
+    }
+});
@@ -0,0 +1,50 @@
+//! 在步数上限内执行任意合法程序：输入输出全在内存中，不能panic
+//!
+//! 第一个0字节之前是源码，之后是程序的输入。
+#![no_main]
+
+use derstand::test_io::TestIo;
+use derstand::{DerstandInterpreter, Instruction};
+use libfuzzer_sys::fuzz_target;
+
+/// 每个输入最多执行的指令数
+const MAX_STEPS: u64 = 100_000;
+
+fuzz_target!(|data: &[u8]| {
+    let (source, input) = match data.iter().position(|&b| b == 0) {
+        Some(split) => (&data[..split], &data[split + 1..]),
+        None => (data, &[][..]),
+    };
+    let Ok(source) = std::str::from_utf8(source) else {
+        return;
+    };
+    let io = TestIo::new().input(input);
+    let mut interpreter = DerstandInterpreter::new().with_io(&io);
+    interpreter.set_allow_include(false);
+    interpreter.set_memory_size(4096);
+    interpreter.set_assertions(true);
+    interpreter.set_deterministic(true);
+    interpreter.set_seed(0);
+    interpreter.register_host_function(0, 4, |window| {
+        window.reverse();
+        Ok(())
+    });
+    if interpreter.compile(source).is_err() {
+        return;
+    }
+    derstand_fuzz::check_jump_table(&interpreter);
+    // 睡眠只会拖慢模糊测试
+    if interpreter.instructions().contains(&Instruction::Sleep) {
+        return;
+    }
+
+    interpreter.reset();
+    for _ in 0..MAX_STEPS {
+        match interpreter.step() {
+            Ok(true) => {},
+            Ok(false) | Err(_) => break,
+        }
+        assert!(interpreter.pointer() < interpreter.memory().len(), "pointer left the tape");
+        assert!(interpreter.pc() <= interpreter.instructions().len(), "pc ran past the program");
+    }
+});
@@ -0,0 +1,23 @@
+//! 模糊测试的共用检查 - 用`cargo +nightly fuzz run compile`或`cargo +nightly fuzz run execute`运行
+
+use derstand::{DerstandInterpreter, Instruction};
+
+/// 编译成功的程序：每个括号与过程边界都有互相指向的配对，过程表只指向过程开头
+pub fn check_jump_table(interpreter: &DerstandInterpreter) {
+    let instructions = interpreter.instructions();
+    assert_eq!(instructions.len(), interpreter.spans().len(), "every instruction has a span");
+    for (pc, &instruction) in instructions.iter().enumerate() {
+        let closer = match instruction {
+            Instruction::JumpIfZero => Instruction::JumpIfNotZero,
+            Instruction::ProcStart => Instruction::ProcEnd,
+            _ => continue,
+        };
+        let target = interpreter.jump_target(pc).unwrap_or_else(|| panic!("{:?} at {} has no jump target", instruction, pc));
+        assert!(target > pc, "{:?} at {} jumps backwards to {}", instruction, pc, target);
+        assert_eq!(instructions[target], closer, "{:?} at {} is paired with {:?} at {}", instruction, pc, instructions[target], target);
+        assert_eq!(interpreter.jump_target(target), Some(pc), "{:?} at {} does not jump back to {}", closer, target, pc);
+    }
+    for &start in interpreter.procedures() {
+        assert_eq!(instructions[start], Instruction::ProcStart, "procedure table points at {:?}", instructions[start]);
+    }
+}
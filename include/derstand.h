@@ -0,0 +1,47 @@
+/* Derstand C embedding API.
+ *
+ * Build the shared library with
+ * `cargo rustc --release --lib --features ffi --crate-type cdylib` and link
+ * against target/release/libderstand.so (derstand.dll / libderstand.dylib).
+ */
+#ifndef DERSTAND_H
+#define DERSTAND_H
+
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct DerstandProgram DerstandProgram;
+
+/* Returns the next input byte (0-255), or a negative value at end of input. */
+typedef int (*derstand_read_fn)(void *user);
+/* Writes len bytes of program output; returns 0 on success. */
+typedef int (*derstand_write_fn)(void *user, const unsigned char *data, size_t len);
+
+/* Compiles NUL-terminated UTF-8 source. Returns NULL only if source is NULL;
+ * on a compile error the handle is still returned and derstand_error() explains it. */
+DerstandProgram *derstand_compile(const char *source);
+
+/* Message of the last compile or run error, or NULL. Owned by the handle and
+ * valid until the next derstand_run() or derstand_free(). */
+const char *derstand_error(const DerstandProgram *program);
+
+/* Routes program I/O through callbacks; user is passed to both. A NULL read means
+ * no input, a NULL write keeps stdout. With a read callback, end of input reads 0. */
+void derstand_set_io_callbacks(DerstandProgram *program, derstand_read_fn read, derstand_write_fn write, void *user);
+
+/* Runs the program from the start, keeping the tape from the previous run.
+ * Returns 0 on success, 1 on a runtime error, 2 if the program did not compile,
+ * -1 if program is NULL. */
+int derstand_run(DerstandProgram *program);
+
+/* Frees the handle; NULL is ignored. */
+void derstand_free(DerstandProgram *program);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,47 @@
+/* Derstand plugin API: extra instructions loaded from a shared library.
+ *
+ * Build derstand with `--features plugins` and load a plugin with
+ * `derstand run --plugin ./libmyops.so prog.der` (also accepted by
+ * `derstand compile --target dbc`). The library exports
+ * derstand_plugin_register, which is called once and registers each
+ * instruction character through the register callback. Characters that are
+ * already instructions, syntax markers, letters, digits or whitespace are
+ * rejected. The library is never unloaded.
+ */
+#ifndef DERSTAND_PLUGIN_H
+#define DERSTAND_PLUGIN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define DERSTAND_PLUGIN_API 1
+
+/* The active tape. The handler may read and write memory[0..length) and move
+ * pointer, which must stay below length. */
+typedef struct DerstandMachine {
+    uint8_t *memory;
+    size_t length;
+    size_t pointer;
+} DerstandMachine;
+
+/* Runs one instruction. Returns 0 on success; any other value stops the
+ * program with runtime error E0117. */
+typedef int (*derstand_opcode_fn)(void *user, DerstandMachine *machine);
+
+/* Registers symbol (a Unicode code point) under a UTF-8 name used in error
+ * messages; user is passed to every call of run. Returns 0 on success. */
+typedef int (*derstand_register_fn)(void *registry, uint32_t symbol, const char *name, derstand_opcode_fn run, void *user);
+
+/* Exported by the plugin. api_version is DERSTAND_PLUGIN_API of the loading
+ * derstand; return nonzero to refuse an incompatible version. */
+int derstand_plugin_register(uint32_t api_version, void *registry, derstand_register_fn register_fn);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
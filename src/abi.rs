@@ -0,0 +1,50 @@
+//! 纸带ABI - 启动与结束时参数、输入长度和返回值在纸带上的位置，供以Derstand为目标的工具链互通
+//!
+//! 以`;! abi=1`声明(或`--abi`、`set_abi(true)`)的程序，每次运行前运行时在第一条纸带开头写入头部：
+//!
+//! ```text
+//! 单元格0        退出状态，启动时为0；程序结束时它的值就是进程的退出码
+//! 单元格1..=4    输入的字节数，u32小端；运行前无法知道(交互式或调用者提供的输入)时为0xFFFFFFFF
+//! 单元格5起      argc与各参数，布局同args模块；没有参数时为`0 0`
+//! ```
+//!
+//! 指针从头部之后的第一个单元格开始(`--start-pointer`可指定更靠后的起点)，`&`回到退出状态。
+//! 例如`--abi --args hi`、输入3个字节时纸带为`0 3 0 0 0 1 2 'h' 'i' 0`，指针在单元格10。
+//! 头部放不下时是编译错误E0017。
+//! 头部每次运行都会重写，持久纸带中同一位置的内容被覆盖；第二条纸带与辅助栈照常为空。
+
+use alloc::vec::Vec;
+
+/// 本版本实现的ABI版本，即`;! abi=N`中的N
+pub const VERSION: u8 = 1;
+
+/// 退出状态所在的单元格
+pub const STATUS_CELL: usize = 0;
+
+/// 输入长度的四个单元格的起点
+pub const INPUT_LENGTH_CELL: usize = 1;
+
+/// 参数区的起点
+pub const ARGS_CELL: usize = 5;
+
+/// 运行前不知道输入长度时写入的值
+pub const UNKNOWN_LENGTH: u32 = u32::MAX;
+
+/// 没有参数时的参数区：argc为0，随后是结束标记
+const NO_ARGS: &[u8] = &[0, 0];
+
+/// 头部的单元格数；args是args::encode的结果
+pub fn header_len(args: Option<&[u8]>) -> usize {
+    ARGS_CELL + args.unwrap_or(NO_ARGS).len()
+}
+
+/// 按上述布局编码头部；input_length为None表示长度未知
+pub fn header(args: Option<&[u8]>, input_length: Option<usize>) -> Vec<u8> {
+    // 长度恰为0xFFFFFFFF或更长的输入无法与未知区分，按未知写入
+    let length = input_length.and_then(|n| u32::try_from(n).ok()).unwrap_or(UNKNOWN_LENGTH);
+    let mut header = Vec::with_capacity(header_len(args));
+    header.push(0);
+    header.extend_from_slice(&length.to_le_bytes());
+    header.extend_from_slice(args.unwrap_or(NO_ARGS));
+    header
+}
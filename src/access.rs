@@ -0,0 +1,69 @@
+//! 指令的单元格访问 - 调试器的观察点与映射到设备的单元格共用
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::{Instruction, TIME_BYTES};
+
+/// 单元格访问类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Access {
+    Read,
+    Write,
+}
+
+/// 指令在当前指针下将访问的单元格(写入排在读取之前，优先报告写入)；memory是执行前的纸带
+pub(crate) fn accesses(instruction: Instruction, pointer: usize, memory: &[u8]) -> Vec<(usize, Access)> {
+    let memory_size = memory.len();
+    match instruction {
+        Instruction::Increment | Instruction::Decrement | Instruction::Add(_) | Instruction::Sub(_) => {
+            vec![(pointer, Access::Write), (pointer, Access::Read)]
+        },
+        Instruction::Zero
+        | Instruction::Set(_)
+        | Instruction::Input
+        | Instruction::InputDecimal
+        | Instruction::Random
+        | Instruction::Pop => vec![(pointer, Access::Write)],
+        // 自定义指令可以访问任意单元格，只按当前单元格报告
+        Instruction::Exchange | Instruction::Custom(_) => vec![(pointer, Access::Write), (pointer, Access::Read)],
+        // 窗口大小由宿主决定，只报告读取的编号
+        Instruction::HostCall => vec![(pointer, Access::Read)],
+        Instruction::Time => (pointer..(pointer + TIME_BYTES).min(memory_size)).map(|p| (p, Access::Write)).collect(),
+        Instruction::Output
+        | Instruction::OutputDecimal
+        | Instruction::Debug
+        | Instruction::Assert(_)
+        | Instruction::JumpIfZero
+        | Instruction::JumpIfNotZero
+        | Instruction::Push
+        | Instruction::Sleep
+        | Instruction::CallCell => vec![(pointer, Access::Read)],
+        Instruction::Copy if pointer + 1 < memory_size => vec![(pointer + 1, Access::Write), (pointer, Access::Read)],
+        Instruction::CopyLeft if pointer > 0 => vec![(pointer - 1, Access::Write), (pointer, Access::Read)],
+        Instruction::Copy | Instruction::CopyLeft => vec![(pointer, Access::Read)],
+        Instruction::AddNext if pointer + 1 < memory_size => {
+            vec![(pointer + 1, Access::Write), (pointer, Access::Write), (pointer, Access::Read), (pointer + 1, Access::Read)]
+        },
+        Instruction::AddNext => vec![(pointer, Access::Write), (pointer, Access::Read)],
+        // 扫描读取经过的每个单元格，直到停下的0单元格或边界
+        Instruction::ScanLeft => {
+            let stop = memory[..=pointer].iter().rposition(|&cell| cell == 0).unwrap_or(0);
+            (stop..=pointer).rev().map(|p| (p, Access::Read)).collect()
+        },
+        Instruction::ScanRight => {
+            let stop = memory[pointer..].iter().position(|&cell| cell == 0).map_or(memory_size - 1, |offset| pointer + offset);
+            (pointer..=stop).map(|p| (p, Access::Read)).collect()
+        },
+        Instruction::Right
+        | Instruction::Left
+        | Instruction::MoveRight(_)
+        | Instruction::MoveLeft(_)
+        | Instruction::MoveHigh
+        | Instruction::MoveLow
+        | Instruction::SwitchTape
+        | Instruction::ProcStart
+        | Instruction::ProcEnd
+        | Instruction::Call(_) => Vec::new(),
+    }
+}
@@ -0,0 +1,214 @@
+//! 静态指针范围分析 - 跟踪指针可能的取值区间，发现依赖边界钳制的代码
+//!
+//! `>`/`<` 在纸带两端被静默钳制，这与经典Brainfuck的语义不同；
+//! 可能触发钳制的位置往往意味着程序的含义与作者预期不符。
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::diagnostic::Diagnostic;
+use crate::lint::dead_loop_reason;
+use crate::{DerstandInterpreter, Instruction};
+
+/// 指针可能的取值区间(闭区间)
+///
+/// `*_widened` 标记该端点是因循环漂移被拓宽到边界的：这种情况只在漂移的循环处报告一次，
+/// 不再对之后的每条指令重复报告。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PointerRange {
+    pub lo: usize,
+    pub hi: usize,
+    lo_widened: bool,
+    hi_widened: bool,
+}
+
+impl PointerRange {
+    fn at(cell: usize) -> Self {
+        PointerRange { lo: cell, hi: cell, lo_widened: false, hi_widened: false }
+    }
+
+    /// 任意位置(切换纸带或调用过程后)；不据此报告钳制
+    fn anywhere(high: usize) -> Self {
+        PointerRange { lo: 0, hi: high, lo_widened: true, hi_widened: true }
+    }
+
+    fn join(self, other: PointerRange) -> PointerRange {
+        let pick = |a: usize, b: usize, wa: bool, wb: bool, smaller: bool| {
+            if a == b {
+                (a, wa || wb)
+            } else if (a < b) == smaller {
+                (a, wa)
+            } else {
+                (b, wb)
+            }
+        };
+        let (lo, lo_widened) = pick(self.lo, other.lo, self.lo_widened, other.lo_widened, true);
+        let (hi, hi_widened) = pick(self.hi, other.hi, self.hi_widened, other.hi_widened, false);
+        PointerRange { lo, hi, lo_widened, hi_widened }
+    }
+
+    fn shift(self, right: bool, amount: usize, high: usize) -> PointerRange {
+        let step = |p: usize| if right { (p + amount).min(high) } else { p.saturating_sub(amount) };
+        PointerRange { lo: step(self.lo), hi: step(self.hi), ..self }
+    }
+}
+
+/// 触发钳制的边界
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edge {
+    Low,
+    High,
+}
+
+/// 发现的类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Finding {
+    Definite, // 必然被钳制
+    May,      // 某些执行路径下被钳制
+    Drift,    // 循环每轮都朝边界移动，迭代足够多次就会被钳制
+}
+
+struct Analyzer<'a> {
+    interpreter: &'a DerstandInterpreter,
+    high: usize,
+    findings: BTreeMap<usize, (Edge, Finding)>,
+    // 循环入口区间 -> 出口区间
+    memo: HashMap<(usize, PointerRange), PointerRange>,
+}
+
+impl Analyzer<'_> {
+    /// 记录移动amount格时的钳制；端点来自循环漂移时已在循环处报告
+    fn check(&mut self, pc: usize, edge: Edge, range: PointerRange, amount: usize) {
+        let (at_edge, definite, widened) = match edge {
+            Edge::Low => (range.lo < amount, range.hi < amount, range.lo_widened),
+            Edge::High => (range.hi + amount > self.high, range.lo + amount > self.high, range.hi_widened),
+        };
+        if !at_edge || (widened && !definite) {
+            return;
+        }
+        let finding = if definite { Finding::Definite } else { Finding::May };
+        let entry = self.findings.entry(pc).or_insert((edge, finding));
+        if entry.1 == Finding::Definite && finding == Finding::May {
+            entry.1 = Finding::May; // 不同路径下只要有一次不是必然，就降为"可能"
+        }
+    }
+
+    /// 分析指令区间 [start, end)
+    fn block(&mut self, start: usize, end: usize, mut range: PointerRange) -> PointerRange {
+        let instructions = self.interpreter.instructions();
+        let mut pc = start;
+        while pc < end {
+            match instructions[pc] {
+                Instruction::JumpIfZero => {
+                    let close = self.interpreter.jump_target(pc).unwrap_or(pc);
+                    if dead_loop_reason(instructions, pc).is_none() {
+                        range = self.looped(pc, close, range);
+                    }
+                    pc = close + 1;
+                    continue;
+                },
+                Instruction::ProcStart => {
+                    // 过程定义在顺序执行时被跳过
+                    pc = self.interpreter.jump_target(pc).unwrap_or(pc) + 1;
+                    continue;
+                },
+                Instruction::Right | Instruction::MoveRight(_) => {
+                    let amount = instructions[pc].count() as usize;
+                    self.check(pc, Edge::High, range, amount);
+                    range = range.shift(true, amount, self.high);
+                },
+                Instruction::Left | Instruction::MoveLeft(_) => {
+                    let amount = instructions[pc].count() as usize;
+                    self.check(pc, Edge::Low, range, amount);
+                    range = range.shift(false, amount, self.high);
+                },
+                Instruction::Copy | Instruction::AddNext => self.check(pc, Edge::High, range, 1),
+                Instruction::CopyLeft => self.check(pc, Edge::Low, range, 1),
+                Instruction::MoveHigh => range = PointerRange::at(self.high),
+                Instruction::MoveLow => range = PointerRange::at(0),
+                // 扫描停在哪里取决于纸带内容；不据此报告钳制
+                Instruction::ScanLeft => range = PointerRange { lo: 0, lo_widened: true, ..range },
+                Instruction::ScanRight => range = PointerRange { hi: self.high, hi_widened: true, ..range },
+                Instruction::SwitchTape | Instruction::Call(_) | Instruction::CallCell => {
+                    range = PointerRange::anywhere(self.high)
+                },
+                _ => {},
+            }
+            pc += 1;
+        }
+        range
+    }
+
+    /// 循环的不动点：入口区间与每轮出口区间的并集；多轮后仍在扩大则拓宽到边界
+    fn looped(&mut self, open: usize, close: usize, range: PointerRange) -> PointerRange {
+        if let Some(&exit) = self.memo.get(&(open, range)) {
+            return exit;
+        }
+        let mut entry = range;
+        for round in 0.. {
+            let out = self.block(open + 1, close, entry);
+            let joined = entry.join(out);
+            if joined == entry {
+                break;
+            }
+            if round >= 2 {
+                // 仍在扩大：拓宽到边界；端点是本循环拓宽的则在循环处报告漂移
+                let mut widened = joined;
+                if joined.lo < entry.lo {
+                    widened.lo = 0;
+                    widened.lo_widened = true;
+                    if !joined.lo_widened {
+                        self.findings.entry(open).or_insert((Edge::Low, Finding::Drift));
+                    }
+                }
+                if joined.hi > entry.hi {
+                    widened.hi = self.high;
+                    widened.hi_widened = true;
+                    if !joined.hi_widened {
+                        self.findings.entry(open).or_insert((Edge::High, Finding::Drift));
+                    }
+                }
+                entry = widened;
+            } else {
+                entry = joined;
+            }
+        }
+        // 循环只在单元格为0时退出，退出时的指针位于入口不动点内
+        self.memo.insert((open, range), entry);
+        entry
+    }
+}
+
+/// 分析整个程序，返回依赖边界钳制的警告
+pub fn pointer_bounds(interpreter: &DerstandInterpreter) -> Vec<Diagnostic> {
+    let high = interpreter.memory_size() - 1;
+    let mut analyzer = Analyzer { interpreter, high, findings: BTreeMap::new(), memo: HashMap::new() };
+    analyzer.block(0, interpreter.instructions().len(), PointerRange::at(0));
+
+    let spans = interpreter.spans();
+    analyzer
+        .findings
+        .into_iter()
+        .map(|(pc, (edge, finding))| {
+            let instruction = interpreter.instructions()[pc];
+            let (cell, side, code) = match edge {
+                Edge::Low => (0, "low", "W0006"),
+                Edge::High => (high, "high", "W0007"),
+            };
+            let diagnostic = match finding {
+                Finding::Definite if instruction.count() > 1 => {
+                    Diagnostic::warning("W0002", format!("'{}' is always clamped at cell {}", instruction, cell))
+                        .with_label(format!("the pointer always reaches the {} edge here", side))
+                },
+                Finding::Definite => Diagnostic::warning("W0002", format!("'{}' at cell {} has no effect", instruction, cell))
+                    .with_label(format!("the pointer is always clamped at the {} edge here", side)),
+                Finding::May => Diagnostic::warning(code, format!("'{}' may be clamped at cell {}", instruction, cell))
+                    .with_label(format!("the pointer can reach the {} edge here", side)),
+                Finding::Drift => Diagnostic::warning(code, format!("Loop may drift the pointer to cell {}", cell))
+                    .with_label(format!("each iteration moves the pointer toward the {} edge", side)),
+            };
+            diagnostic.with_span(spans[pc]).with_hint(
+                "classic Brainfuck would move off the tape here; make sure the program does not rely on clamping",
+            )
+        })
+        .collect()
+}
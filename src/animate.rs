@@ -0,0 +1,166 @@
+//! 执行动画 - `derstand animate prog.dr -o run.gif`每隔N步给纸带拍一帧，编成循环播放的GIF
+//!
+//! 每个单元格画成scale×scale的方块，亮度就是单元格的值；指针所在的单元格加红框。只拍开头的若干单元格：
+//!
+//! ```text
+//! $ derstand animate hello.dr -o hello.gif --every 50 --cells 64 --width 16
+//! ```
+//!
+//! 程序结束或出错时再拍最后一帧。帧数达到上限后停止运行，写出已拍的帧。
+
+use std::path::PathBuf;
+
+use crate::diagnostic::stderr_color;
+use crate::{DerstandInterpreter, image, runtime_error};
+
+/// 帧间隔(百分之一秒)
+const DELAY: u16 = 10;
+
+/// 调色板：0..=127为灰度，128为指针的红色
+const POINTER: u8 = 128;
+
+#[derive(Debug, Clone)]
+struct Options {
+    output: Option<PathBuf>,
+    input: Option<PathBuf>,
+    every: u64,
+    cells: usize,
+    width: usize,
+    scale: usize,
+    max_frames: usize,
+}
+
+fn palette() -> [[u8; 3]; 256] {
+    let mut palette = [[0; 3]; 256];
+    for (i, color) in palette.iter_mut().enumerate().take(POINTER as usize) {
+        let level = (i * 255 / (POINTER as usize - 1)) as u8;
+        *color = [level, level, level];
+    }
+    palette[POINTER as usize] = [255, 0, 0];
+    palette
+}
+
+/// 拍一帧：前cells个单元格，每行width格
+fn frame(interpreter: &DerstandInterpreter, options: &Options) -> Vec<u8> {
+    let (columns, rows) = (options.width, options.cells.div_ceil(options.width));
+    let scale = options.scale;
+    let stride = columns * scale;
+    let mut pixels = vec![0; stride * rows * scale];
+    let pointer = (interpreter.active_tape() == 0).then(|| interpreter.pointer());
+    for (cell, &value) in interpreter.memory().iter().enumerate().take(options.cells) {
+        let (x0, y0) = ((cell % columns) * scale, (cell / columns) * scale);
+        for y in 0..scale {
+            for x in 0..scale {
+                // 指针处画一圈红框；方块太小时整块涂红
+                let border = x == 0 || y == 0 || x == scale - 1 || y == scale - 1 || scale < 3;
+                let color = if pointer == Some(cell) && border { POINTER } else { value >> 1 };
+                pixels[(y0 + y) * stride + x0 + x] = color;
+            }
+        }
+    }
+    pixels
+}
+
+/// `derstand animate <file> -o <out.gif> [--every N] [--cells N] [--width N] [--scale N] [--input FILE] [--max-frames N]`
+pub fn command(args: &[String]) -> i32 {
+    const USAGE: &str =
+        "Usage: derstand animate <file> -o <out.gif> [--every N] [--cells N] [--width N] [--scale N] [--input FILE] [--max-frames N]";
+    let mut options = Options { output: None, input: None, every: 100, cells: 256, width: 16, scale: 8, max_frames: 1000 };
+    let mut files = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let positive = |value: Option<&String>| value.and_then(|v| v.parse::<usize>().ok()).filter(|&n| n > 0);
+        let parsed = match arg.as_str() {
+            "-o" | "--output" => iter.next().ok_or(format!("Missing value for {}", arg)).map(|path| options.output = Some(path.into())),
+            "--input" => iter.next().ok_or("Missing value for --input".to_string()).map(|path| options.input = Some(path.into())),
+            "--every" => positive(iter.next()).map(|n| options.every = n as u64).ok_or("--every expects a positive number of steps".to_string()),
+            "--cells" => positive(iter.next()).map(|n| options.cells = n).ok_or("--cells expects a positive number".to_string()),
+            "--width" => positive(iter.next()).map(|n| options.width = n).ok_or("--width expects a positive number of cells".to_string()),
+            "--scale" => positive(iter.next()).map(|n| options.scale = n).ok_or("--scale expects a positive number of pixels".to_string()),
+            "--max-frames" => positive(iter.next()).map(|n| options.max_frames = n).ok_or("--max-frames expects a positive number".to_string()),
+            _ if arg.starts_with('-') => Err(format!("Unknown option: {}", arg)),
+            _ => {
+                files.push(arg.clone());
+                Ok(())
+            },
+        };
+        if let Err(e) = parsed {
+            eprintln!("{}", e);
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    }
+    let ([file], Some(output)) = (files.as_slice(), options.output.clone()) else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let rows = options.cells.div_ceil(options.width);
+    let (Ok(width), Ok(height)) = (u16::try_from(options.width.min(options.cells) * options.scale), u16::try_from(rows * options.scale)) else {
+        eprintln!("The animation would be larger than 65535 pixels; lower --cells, --width or --scale");
+        return 2;
+    };
+    options.width = options.width.min(options.cells);
+
+    let source = match std::fs::read_to_string(file) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error reading file {}: {}", file, e);
+            return 1;
+        },
+    };
+    let input = match options.input.as_ref().map(std::fs::read).transpose() {
+        Ok(input) => input.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("Error reading input: {}", e);
+            return 1;
+        },
+    };
+    let mut interpreter = DerstandInterpreter::new();
+    interpreter.set_source_path(file);
+    if let Err(e) = interpreter.compile(&source) {
+        eprintln!("{}", e.render(&source, Some(file), stderr_color()));
+        return 1;
+    }
+    // 动画只关心纸带，丢弃程序输出
+    interpreter.set_output(Box::new(std::io::sink()));
+    interpreter.preload_input(&input);
+    interpreter.reset();
+
+    let mut frames = vec![frame(&interpreter, &options)];
+    let mut failure = None;
+    loop {
+        match interpreter.step() {
+            Ok(true) if interpreter.steps.is_multiple_of(options.every) => {
+                frames.push(frame(&interpreter, &options));
+                if frames.len() >= options.max_frames {
+                    eprintln!("Stopped after {} frames (--max-frames)", frames.len());
+                    break;
+                }
+            },
+            Ok(true) => {},
+            Ok(false) => {
+                if !interpreter.steps.is_multiple_of(options.every) {
+                    frames.push(frame(&interpreter, &options));
+                }
+                break;
+            },
+            Err(e) => {
+                frames.push(frame(&interpreter, &options));
+                failure = Some(runtime_error(&interpreter, &source, e));
+                break;
+            },
+        }
+    }
+    if let Err(e) = std::fs::write(&output, image::gif(width, height, &palette(), &frames, DELAY)) {
+        eprintln!("Error writing {}: {}", output.display(), e);
+        return 1;
+    }
+    println!("Wrote {} frame(s) to {}", frames.len(), output.display());
+    match failure {
+        Some(e) => {
+            eprintln!("{}", e.render(&source, Some(file), stderr_color()));
+            1
+        },
+        None => 0,
+    }
+}
@@ -0,0 +1,40 @@
+//! 程序参数 - `--args a b c`在运行前把参数写进纸带开头的保留区
+//!
+//! 布局(指针从单元格0开始)：
+//!
+//! ```text
+//! 单元格0        参数个数argc
+//! 之后每个参数   一个长度字节，随后是参数的字节
+//! 最后           一个0，标记保留区结束
+//! ```
+//!
+//! 例如`--args ab c`得到`2 2 'a' 'b' 1 'c' 0`，程序的工作区从第8个单元格开始。
+//! 参数最多255个，每个最长255字节。每次运行(`execute`或`reset`)都会重新写入保留区。
+//! 按纸带ABI运行时保留区在ABI头部之内，从单元格5开始，见abi模块。
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// 参数个数与单个参数长度的上限
+pub const MAX_ARGS: usize = 255;
+pub const MAX_ARG_LEN: usize = 255;
+
+/// 按上述布局编码参数
+pub fn encode<S: AsRef<[u8]>>(args: &[S]) -> Result<Vec<u8>, String> {
+    if args.len() > MAX_ARGS {
+        return Err(format!("Too many program arguments: {} (at most {})", args.len(), MAX_ARGS));
+    }
+    let mut region = Vec::with_capacity(2 + args.iter().map(|a| a.as_ref().len() + 1).sum::<usize>());
+    region.push(args.len() as u8);
+    for (i, arg) in args.iter().enumerate() {
+        let arg = arg.as_ref();
+        if arg.len() > MAX_ARG_LEN {
+            return Err(format!("Program argument {} is {} bytes long (at most {})", i + 1, arg.len(), MAX_ARG_LEN));
+        }
+        region.push(arg.len() as u8);
+        region.extend_from_slice(arg);
+    }
+    region.push(0);
+    Ok(region)
+}
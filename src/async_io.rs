@@ -0,0 +1,159 @@
+//! 异步执行 - `execute_async`在异步服务器中运行程序，`,`等待异步输入而不阻塞工作线程
+//!
+//! 输入输出特征与tokio的`AsyncRead`/`AsyncWrite`同构，但不依赖任何运行时(本crate没有外部依赖)，
+//! 接入tokio只需几行适配：
+//!
+//! ```text
+//! struct Tokio<T>(T);
+//!
+//! impl<T: tokio::io::AsyncRead + Unpin> AsyncInput for Tokio<T> {
+//!     fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<Result<usize, String>> {
+//!         let mut buf = tokio::io::ReadBuf::new(buf);
+//!         Pin::new(&mut self.0).poll_read(cx, &mut buf).map_ok(|()| buf.filled().len()).map_err(|e| e.to_string())
+//!     }
+//! }
+//!
+//! interpreter.execute_async(&mut Tokio(socket_reader), &mut Tokio(socket_writer)).await?;
+//! ```
+//!
+//! `,`与`?`只在预置输入耗尽时才等待输入，`?`读完整个数字后才继续；每条输出指令的字节写完才执行下一条。
+//! 不做输入输出的长时间计算每`YIELD_EVERY`步让出一次。沙箱中输入输出仍只经沙箱，不使用这两个参数。
+//! 解释器不是`Send`，在tokio中应在`LocalSet`里运行(`spawn_local`)。
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use crate::diagnostic::Diagnostic;
+use crate::host::{Input, Output};
+use crate::{DerstandInterpreter, Instruction};
+
+/// 连续执行这么多步后让出一次
+pub const YIELD_EVERY: u64 = 10_000;
+
+/// 异步的输入来源；读到0字节表示输入结束
+pub trait AsyncInput {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<Result<usize, String>>;
+}
+
+/// 异步的输出目标
+pub trait AsyncOutput {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, String>>;
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), String>>;
+}
+
+/// 执行期间代替调用者的输入源：预读的字节都已放进输入缓冲，走到这里就是输入结束
+struct Exhausted;
+
+impl Input for Exhausted {
+    fn read_byte(&mut self) -> Result<Option<u8>, String> {
+        Ok(None)
+    }
+}
+
+/// 执行期间代替调用者的输出：收集一条指令的输出，之后异步写出
+#[derive(Clone, Default)]
+struct Collected(Rc<RefCell<Vec<u8>>>);
+
+impl Output for Collected {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+async fn read_byte<I: AsyncInput + Unpin>(input: &mut I) -> Result<Option<u8>, String> {
+    let mut byte = [0];
+    let read = poll_fn(|cx| Pin::new(&mut *input).poll_read(cx, &mut byte)).await?;
+    Ok((read > 0).then_some(byte[0]))
+}
+
+async fn write_all<O: AsyncOutput + Unpin>(output: &mut O, mut bytes: &[u8]) -> Result<(), String> {
+    while !bytes.is_empty() {
+        let written = poll_fn(|cx| Pin::new(&mut *output).poll_write(cx, bytes)).await?;
+        if written == 0 {
+            return Err("the output stopped accepting bytes".into());
+        }
+        bytes = &bytes[written..];
+    }
+    poll_fn(|cx| Pin::new(&mut *output).poll_flush(cx)).await
+}
+
+/// 让出一次：先唤醒自己再返回Pending，执行器稍后重新调度
+async fn yield_now() {
+    let mut yielded = false;
+    poll_fn(|cx| {
+        if yielded {
+            return Poll::Ready(());
+        }
+        yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    })
+    .await
+}
+
+impl DerstandInterpreter {
+    /// 从头执行已编译的程序，输入来自input，输出写到output
+    pub async fn execute_async<I: AsyncInput + Unpin, O: AsyncOutput + Unpin>(&mut self, input: &mut I, output: &mut O) -> Result<(), Diagnostic> {
+        let collected = Collected::default();
+        let source = self.input_source.replace(Box::new(Exhausted));
+        let sink = self.output.replace(Box::new(collected.clone()));
+        let result = self.drive(input, output, &collected).await;
+        self.input_source = source;
+        self.output = sink;
+        result
+    }
+
+    async fn drive<I: AsyncInput + Unpin, O: AsyncOutput + Unpin>(&mut self, input: &mut I, output: &mut O, collected: &Collected) -> Result<(), Diagnostic> {
+        self.reset();
+        let mut finished_input = false;
+        loop {
+            let pc = self.pc;
+            let reads = matches!(self.instructions.get(pc), Some(Instruction::Input | Instruction::InputDecimal));
+            if reads && self.input_buffer.is_empty() && self.sandbox.is_none() && !finished_input {
+                let decimal = self.instructions[pc] == Instruction::InputDecimal;
+                finished_input = self.prefetch(pc, input, decimal).await?;
+            }
+            if !self.step()? {
+                return Ok(());
+            }
+            let bytes = core::mem::take(&mut *collected.0.borrow_mut());
+            // 与同步执行一样，写入失败(如连接关闭)不中断程序
+            if !bytes.is_empty() {
+                let _ = write_all(output, &bytes).await;
+            }
+            if self.steps.is_multiple_of(YIELD_EVERY) {
+                yield_now().await;
+            }
+        }
+    }
+
+    /// 读入下一条输入指令需要的字节：`,`一个字节，`?`到数字之后的分隔符为止；返回输入是否已结束
+    async fn prefetch<I: AsyncInput + Unpin>(&mut self, pc: usize, input: &mut I, decimal: bool) -> Result<bool, Diagnostic> {
+        let mut bytes = Vec::new();
+        let mut digits = false;
+        let ended = loop {
+            let byte = read_byte(input).await.map_err(|e| Diagnostic::error("E0102", format!("Input error: {}", e)).with_span(self.spans[pc]))?;
+            let Some(byte) = byte else {
+                break true;
+            };
+            bytes.push(byte);
+            match byte {
+                _ if !decimal => break false,
+                b'0'..=b'9' => digits = true,
+                _ if byte.is_ascii_whitespace() && !digits => {},
+                _ => break false,
+            }
+        };
+        self.preload_input(&bytes);
+        Ok(ended)
+    }
+}
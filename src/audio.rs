@@ -0,0 +1,85 @@
+//! 音频导出 - `--emit-wav out.wav`把程序输出的字节当作8位PCM采样写成WAV文件
+//!
+//! 单声道、无符号8位(128为静音)，采样率默认8000Hz，用`--wav-rate`修改。
+//! `--wav-tape OFFSET+LEN`改为在运行结束后取纸带上的一段作为采样：
+//!
+//! ```text
+//! $ derstand run --emit-wav tone.wav --wav-rate 22050 tone.dr > /dev/null
+//! $ derstand run --emit-wav wave.wav --wav-tape 1024+8000 synth.dr
+//! ```
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::cell::RefCell;
+#[cfg(feature = "std")]
+use std::rc::Rc;
+
+#[cfg(feature = "std")]
+use crate::host::Output;
+
+/// 默认采样率(Hz)
+pub const DEFAULT_RATE: u32 = 8000;
+
+/// 编码为单声道8位PCM的WAV文件
+pub fn wav(rate: u32, samples: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(44 + samples.len() + 1);
+    out.extend_from_slice(b"RIFF");
+    // 数据块长度为奇数时补一个字节
+    let padded = samples.len() + samples.len() % 2;
+    out.extend_from_slice(&(36 + padded as u32).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    out.extend_from_slice(&1u16.to_le_bytes()); // 单声道
+    out.extend_from_slice(&rate.to_le_bytes());
+    out.extend_from_slice(&rate.to_le_bytes()); // 每秒字节数
+    out.extend_from_slice(&1u16.to_le_bytes()); // 每帧字节数
+    out.extend_from_slice(&8u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+    out.extend_from_slice(samples);
+    if samples.len() % 2 == 1 {
+        out.push(0);
+    }
+    out
+}
+
+/// 解析`OFFSET+LEN`
+pub fn parse_region(text: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("--wav-tape expects OFFSET+LEN, got '{}'", text);
+    let (offset, len) = text.split_once('+').ok_or_else(invalid)?;
+    let offset = offset.parse::<usize>().map_err(|_| invalid())?;
+    let len = len.parse::<usize>().ok().filter(|&n| n > 0).ok_or_else(invalid)?;
+    Ok((offset, len))
+}
+
+/// 纸带上从offset开始的len个单元格
+pub fn tape_samples(memory: &[u8], (offset, len): (usize, usize)) -> Result<&[u8], String> {
+    offset
+        .checked_add(len)
+        .and_then(|end| memory.get(offset..end))
+        .ok_or_else(|| format!("The {} samples at cell {} do not fit on the tape of {} cells", len, offset, memory.len()))
+}
+
+/// 照常写到标准输出，同时留下一份作为采样
+#[cfg(feature = "std")]
+#[derive(Clone, Default)]
+pub struct Recorder(Rc<RefCell<Vec<u8>>>);
+
+#[cfg(feature = "std")]
+impl Recorder {
+    /// 至今输出的全部字节
+    pub fn samples(&self) -> Vec<u8> {
+        self.0.borrow().clone()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Output for Recorder {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        std::io::stdout().write_bytes(bytes)
+    }
+}
@@ -0,0 +1,121 @@
+//! 后台执行 - 在专用线程上运行程序，经通道报告进度，图形界面不会因此卡住
+//!
+//! ```text
+//! let handle = DerstandInterpreter::spawn(Program::compile(source)?);
+//! for event in handle.events() {
+//!     match event {
+//!         Progress::Steps(steps) => status.set_text(format!("{} steps", steps)),
+//!         Progress::Output(bytes) => console.append(&bytes),
+//!     }
+//! }
+//! handle.join()?;
+//! ```
+//!
+//! 每执行`REPORT_EVERY`步(以及结束时)发送一次已执行的步数，并把期间的输出作为一块发出；
+//! 事件通道在运行结束后关闭。`cancel()`使运行以E0114停止。`,`只能读到`spawn_with`预置的输入。
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use crate::DerstandInterpreter;
+use crate::cancel::CancelToken;
+use crate::diagnostic::Diagnostic;
+use crate::host::Output;
+use crate::program::Program;
+
+/// 两次进度报告之间的步数
+pub const REPORT_EVERY: u64 = 65_536;
+
+/// 运行中发出的事件
+#[derive(Debug, Clone, PartialEq)]
+pub enum Progress {
+    Steps(u64),      // 至今已执行的步数
+    Output(Vec<u8>), // 自上次报告以来的输出
+}
+
+/// 后台运行的句柄
+pub struct RunHandle {
+    events: Receiver<Progress>,
+    token: CancelToken,
+    thread: JoinHandle<Result<(), Diagnostic>>,
+}
+
+impl RunHandle {
+    /// 进度事件；运行结束后迭代随之结束
+    pub fn events(&self) -> &Receiver<Progress> {
+        &self.events
+    }
+
+    /// 请求停止运行；程序在执行下一条指令前停止
+    pub fn cancel(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// 等待运行结束，返回结果；运行线程中的panic(如自定义指令的)在这里继续传播
+    pub fn join(self) -> Result<(), Diagnostic> {
+        self.thread.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+}
+
+/// 把输出攒到下一次进度报告
+#[derive(Clone, Default)]
+struct Batched(Rc<RefCell<Vec<u8>>>);
+
+impl Output for Batched {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// 发出攒下的输出与步数；接收端已丢弃时发送失败，忽略即可
+fn report(events: &Sender<Progress>, output: &Batched, steps: u64) {
+    let bytes = std::mem::take(&mut *output.0.borrow_mut());
+    if !bytes.is_empty() {
+        let _ = events.send(Progress::Output(bytes));
+    }
+    let _ = events.send(Progress::Steps(steps));
+}
+
+impl DerstandInterpreter {
+    /// 在新线程上以默认配置运行program
+    pub fn spawn(program: Program) -> RunHandle {
+        Self::spawn_with(program, |_| {})
+    }
+
+    /// 同上，运行前先在新线程上用setup配置解释器(预置输入、执行选项等)
+    pub fn spawn_with(program: Program, setup: impl FnOnce(&mut DerstandInterpreter) + Send + 'static) -> RunHandle {
+        let (sender, events) = mpsc::channel();
+        let token = CancelToken::new();
+        let cancel = token.clone();
+        let thread = thread::spawn(move || {
+            let mut interpreter = DerstandInterpreter::new();
+            setup(&mut interpreter);
+            interpreter.load_program(&program);
+            interpreter.set_cancel_token(Some(cancel));
+            let output = Batched::default();
+            interpreter.set_output(Box::new(output.clone()));
+            interpreter.reset();
+            let result = loop {
+                if let Err(e) = interpreter.check_cancelled(interpreter.pc) {
+                    break Err(e);
+                }
+                match interpreter.step() {
+                    Ok(true) if interpreter.steps.is_multiple_of(REPORT_EVERY) => report(&sender, &output, interpreter.steps),
+                    Ok(true) => {},
+                    Ok(false) => break Ok(()),
+                    Err(e) => break Err(e),
+                }
+            };
+            report(&sender, &output, interpreter.steps);
+            result
+        });
+        RunHandle { events, token, thread }
+    }
+}
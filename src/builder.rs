@@ -0,0 +1,144 @@
+//! 解释器构造器 - 嵌入时一次写出执行选项、输入输出与自定义指令
+//!
+//! ```text
+//! use derstand::DerstandInterpreter;
+//!
+//! let mut interpreter = DerstandInterpreter::builder()
+//!     .memory_size(256)
+//!     .instruction('×', |machine| {
+//!         machine.set_cell(machine.cell().wrapping_mul(2));
+//!         Ok(())
+//!     })
+//!     .build()
+//!     .unwrap();
+//! interpreter.compile("+++××").unwrap();
+//! interpreter.run().unwrap();
+//! assert_eq!(interpreter.memory()[0], 12);
+//! ```
+
+use alloc::boxed::Box;
+use alloc::string::String;
+
+use crate::custom::Machine;
+use crate::device::Device;
+use crate::host::{Input, Output};
+use crate::sandbox::SandboxProfile;
+use crate::{DerstandInterpreter, Dialect, EofBehavior, OverflowPolicy};
+
+/// 见模块说明；由`DerstandInterpreter::builder()`创建
+pub struct Builder {
+    interpreter: DerstandInterpreter,
+    error: Option<String>, // 第一个失败的设置(如自定义指令注册)，build()时报告
+}
+
+impl Builder {
+    pub(crate) fn new() -> Self {
+        Builder { interpreter: DerstandInterpreter::new(), error: None }
+    }
+
+    pub fn memory_size(mut self, size: usize) -> Self {
+        self.interpreter.set_memory_size(size);
+        self
+    }
+
+    pub fn overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.interpreter.set_overflow_policy(policy);
+        self
+    }
+
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.interpreter.set_dialect(dialect);
+        self
+    }
+
+    pub fn eof_behavior(mut self, eof: EofBehavior) -> Self {
+        self.interpreter.set_eof_behavior(eof);
+        self
+    }
+
+    /// 每次运行开始时第一条纸带的指针位置
+    pub fn start_pointer(mut self, cell: usize) -> Self {
+        self.interpreter.set_start_pointer(cell);
+        self
+    }
+
+    pub fn strict_bounds(mut self, strict: bool) -> Self {
+        self.interpreter.set_strict_bounds(strict);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.interpreter.set_seed(seed);
+        self
+    }
+
+    pub fn deterministic(mut self, deterministic: bool) -> Self {
+        self.interpreter.set_deterministic(deterministic);
+        self
+    }
+
+    pub fn input(mut self, source: Box<dyn Input>) -> Self {
+        self.interpreter.set_input(source);
+        self
+    }
+
+    pub fn output(mut self, sink: Box<dyn Output>) -> Self {
+        self.interpreter.set_output(sink);
+        self
+    }
+
+    /// 把未使用的字符symbol注册为自定义指令；handler返回的错误成为运行时错误E0117
+    pub fn instruction(mut self, symbol: char, handler: impl FnMut(&mut Machine) -> Result<(), String> + 'static) -> Self {
+        let name = String::from(symbol);
+        if let Err(e) = self.interpreter.register_instruction(symbol, &name, Box::new(handler)) {
+            self.error.get_or_insert(e);
+        }
+        self
+    }
+
+    /// 注册编号为number的宿主函数，`§`调用时读写当前单元格之后的window个单元格
+    pub fn host_function(mut self, number: u8, window: usize, function: impl FnMut(&mut [u8]) -> Result<(), String> + 'static) -> Self {
+        self.interpreter.register_host_function(number, window, function);
+        self
+    }
+
+    /// 把cell单元格映射到设备，见device模块
+    pub fn device(mut self, cell: usize, device: Device) -> Self {
+        self.interpreter.map_device(cell, device);
+        self
+    }
+
+    /// 程序参数，见args模块
+    pub fn args<S: AsRef<[u8]>>(mut self, args: &[S]) -> Self {
+        if let Err(e) = self.interpreter.set_args(args) {
+            self.error.get_or_insert(e);
+        }
+        self
+    }
+
+    /// 按纸带ABI运行，见abi模块
+    pub fn abi(mut self, enabled: bool) -> Self {
+        self.interpreter.set_abi(enabled);
+        self
+    }
+
+    /// 编译'=?{n}'断言；默认忽略
+    pub fn assertions(mut self, enabled: bool) -> Self {
+        self.interpreter.set_assertions(enabled);
+        self
+    }
+
+    /// 在沙箱中运行：只读预置输入、只捕获输出、禁用宿主调用并限制资源，见sandbox模块
+    pub fn sandbox(mut self, profile: SandboxProfile) -> Self {
+        self.interpreter.set_sandbox(Some(profile));
+        self
+    }
+
+    /// 完成构造；有自定义指令注册失败或参数无效时返回其原因
+    pub fn build(self) -> Result<DerstandInterpreter, String> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.interpreter),
+        }
+    }
+}
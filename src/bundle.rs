@@ -0,0 +1,247 @@
+//! 可重现的运行包 - `--bundle out.tar`把程序、预置输入、种子、配置与解释器版本打成一个tar文件
+//!
+//! `derstand run --bundle out.tar`(不给源文件)按包中的内容原样重放，适合附在错误报告里。
+//! 包是普通的ustar归档，可以用`tar -tf`查看：
+//!
+//! ```text
+//! bundle.json         格式版本、derstand版本、程序名与配置(含--args)
+//! program/<文件名>     程序文件的原始字节(源码或字节码)
+//! input.bin           预置输入(--replay-input的字节)
+//! ```
+//!
+//! `!include`的文件不在包内，重放时按记录的程序路径查找。未开启`--deterministic`时`` ` ``读取真实时间，无法重现。
+
+use std::path::Path;
+
+use crate::json::Json;
+use crate::{Dialect, EofBehavior, OverflowPolicy};
+
+/// bundle.json的格式版本
+const FORMAT: i64 = 1;
+
+/// tar的块大小
+const BLOCK: usize = 512;
+
+const METADATA: &str = "bundle.json";
+const PROGRAM_DIR: &str = "program/";
+const INPUT: &str = "input.bin";
+
+/// 重放需要的运行配置；None表示使用默认值或源码中的编译指示
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BundleConfig {
+    pub seed: u64,
+    pub memory: Option<usize>,
+    pub start_pointer: Option<usize>,
+    pub overflow: Option<OverflowPolicy>,
+    pub dialect: Option<Dialect>,
+    pub eof: Option<EofBehavior>,
+    pub timeout_ms: Option<u64>,
+    pub strict_bounds: bool,
+    pub deterministic: bool,
+    pub assertions: bool,
+    pub sandbox: bool,
+    pub detect_loops: bool,
+    pub args: Option<Vec<String>>, // --args给出的程序参数
+    pub abi: bool,
+}
+
+/// 一个运行包
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bundle {
+    pub version: String,      // 录制时的derstand版本
+    pub program_name: String, // 录制时的程序路径，用于诊断与`!include`
+    pub program: Vec<u8>,
+    pub input: Vec<u8>,
+    pub config: BundleConfig,
+}
+
+/// 录制时未指定种子时使用的新种子
+pub fn fresh_seed() -> u64 {
+    crate::random::Rng::entropy_seed()
+}
+
+impl Bundle {
+    /// 以当前版本的derstand录制
+    pub fn new(program_name: &str, program: Vec<u8>, input: Vec<u8>, config: BundleConfig) -> Self {
+        Bundle { version: env!("CARGO_PKG_VERSION").to_string(), program_name: program_name.to_string(), program, input, config }
+    }
+
+    fn metadata(&self) -> Json {
+        let config = &self.config;
+        let optional = |value: Option<u64>| value.map_or(Json::Null, |n| Json::Int(n as i64));
+        let name = |value: Option<&'static str>| value.map_or(Json::Null, |s| Json::Str(s.to_string()));
+        Json::object([
+            ("format", Json::Int(FORMAT)),
+            ("derstand", Json::Str(self.version.clone())),
+            ("program", Json::Str(self.program_name.clone())),
+            ("config", Json::object([
+                // 种子可能超出i64，按十进制字符串保存
+                ("seed", Json::Str(config.seed.to_string())),
+                ("memory", optional(config.memory.map(|n| n as u64))),
+                ("start_pointer", optional(config.start_pointer.map(|n| n as u64))),
+                ("overflow", name(config.overflow.map(OverflowPolicy::as_str))),
+                ("dialect", name(config.dialect.map(Dialect::as_str))),
+                ("eof", name(config.eof.map(EofBehavior::as_str))),
+                ("timeout_ms", optional(config.timeout_ms)),
+                ("strict_bounds", Json::Bool(config.strict_bounds)),
+                ("deterministic", Json::Bool(config.deterministic)),
+                ("assertions", Json::Bool(config.assertions)),
+                ("sandbox", Json::Bool(config.sandbox)),
+                ("detect_loops", Json::Bool(config.detect_loops)),
+                ("args", config.args.as_ref().map_or(Json::Null, |args| Json::Array(args.iter().map(|a| Json::Str(a.clone())).collect()))),
+                ("abi", Json::Bool(config.abi)),
+            ])),
+        ])
+    }
+
+    /// 程序在包中的文件名
+    fn program_entry(&self) -> String {
+        let file = Path::new(&self.program_name).file_name().map_or("program".into(), |n| n.to_string_lossy().into_owned());
+        format!("{}{}", PROGRAM_DIR, file)
+    }
+
+    /// 打包为tar归档
+    pub fn to_tar(&self) -> Result<Vec<u8>, String> {
+        let mut tar = Vec::new();
+        append(&mut tar, METADATA, self.metadata().to_string().as_bytes())?;
+        append(&mut tar, &self.program_entry(), &self.program)?;
+        append(&mut tar, INPUT, &self.input)?;
+        // 两个全零块表示归档结束
+        tar.resize(tar.len() + 2 * BLOCK, 0);
+        Ok(tar)
+    }
+
+    /// 从tar归档读取
+    pub fn from_tar(data: &[u8]) -> Result<Self, String> {
+        let entries = entries(data)?;
+        let find = |name: &str| entries.iter().find(|(n, _)| n == name).map(|(_, data)| *data);
+        let metadata = find(METADATA).ok_or("Not a derstand bundle: bundle.json is missing")?;
+        let metadata = Json::parse(&String::from_utf8_lossy(metadata)).map_err(|e| format!("Invalid bundle.json: {}", e))?;
+        match metadata.get("format").and_then(Json::as_i64) {
+            Some(FORMAT) => {},
+            Some(other) => return Err(format!("Unsupported bundle format {} (this derstand reads format {})", other, FORMAT)),
+            None => return Err("Invalid bundle.json: missing format".to_string()),
+        }
+        let text = |key: &str| metadata.get(key).and_then(Json::as_str).map(str::to_string).ok_or(format!("Invalid bundle.json: missing {}", key));
+        let version = text("derstand")?;
+        let program_name = text("program")?;
+        let config = parse_config(metadata.get("config").ok_or("Invalid bundle.json: missing config")?)?;
+        let mut bundle = Bundle { version, program_name, program: Vec::new(), input: Vec::new(), config };
+        bundle.program = find(&bundle.program_entry()).ok_or(format!("Bundle is missing {}", bundle.program_entry()))?.to_vec();
+        bundle.input = find(INPUT).unwrap_or_default().to_vec();
+        Ok(bundle)
+    }
+
+    /// 写入文件
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_tar()?).map_err(|e| format!("Error writing bundle {}: {}", path.display(), e))
+    }
+
+    /// 读取文件
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|e| format!("Error reading bundle {}: {}", path.display(), e))?;
+        Self::from_tar(&data).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+fn parse_config(config: &Json) -> Result<BundleConfig, String> {
+    let invalid = |key: &str| format!("Invalid bundle.json: bad value for config.{}", key);
+    let flag = |key: &str| config.get(key).map_or(Ok(false), |v| v.as_bool().ok_or(invalid(key)));
+    // null或缺省表示未设置
+    let optional = |key: &str| match config.get(key) {
+        None | Some(Json::Null) => Ok(None),
+        Some(value) => value.as_i64().and_then(|n| u64::try_from(n).ok()).map(Some).ok_or(invalid(key)),
+    };
+    let name = |key: &str| match config.get(key) {
+        None | Some(Json::Null) => Ok(None),
+        Some(value) => value.as_str().map(Some).ok_or(invalid(key)),
+    };
+    let seed = config.get("seed").and_then(Json::as_str).and_then(|s| s.parse().ok()).ok_or(invalid("seed"))?;
+    Ok(BundleConfig {
+        seed,
+        memory: optional("memory")?.map(|n| n as usize),
+        start_pointer: optional("start_pointer")?.map(|n| n as usize),
+        overflow: name("overflow")?.map(OverflowPolicy::parse).transpose()?,
+        dialect: name("dialect")?.map(Dialect::parse).transpose()?,
+        eof: name("eof")?.map(EofBehavior::parse).transpose()?,
+        timeout_ms: optional("timeout_ms")?,
+        strict_bounds: flag("strict_bounds")?,
+        deterministic: flag("deterministic")?,
+        assertions: flag("assertions")?,
+        sandbox: flag("sandbox")?,
+        detect_loops: flag("detect_loops")?,
+        args: match config.get("args") {
+            None | Some(Json::Null) => None,
+            Some(args) => {
+                let args = args.as_array().ok_or(invalid("args"))?;
+                Some(args.iter().map(|a| a.as_str().map(str::to_string).ok_or(invalid("args"))).collect::<Result<_, _>>()?)
+            },
+        },
+        abi: flag("abi")?,
+    })
+}
+
+/// 追加一个普通文件条目：ustar头后跟数据，补齐到整块
+fn append(tar: &mut Vec<u8>, name: &str, data: &[u8]) -> Result<(), String> {
+    if name.len() >= 100 {
+        return Err(format!("Bundle entry name is too long: {}", name));
+    }
+    let mut header = [0u8; BLOCK];
+    let mut field = |offset: usize, value: &[u8]| header[offset..offset + value.len()].copy_from_slice(value);
+    field(0, name.as_bytes());
+    field(100, b"0000644\0");
+    field(108, b"0000000\0");
+    field(116, b"0000000\0");
+    field(124, format!("{:011o}\0", data.len()).as_bytes());
+    // 修改时间固定为0，同样的内容得到同样的包
+    field(136, b"00000000000\0");
+    field(156, b"0");
+    field(257, b"ustar\0");
+    field(263, b"00");
+    // 校验和按校验和字段全为空格计算
+    field(148, b"        ");
+    let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+    header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+    tar.extend_from_slice(&header);
+    tar.extend_from_slice(data);
+    tar.resize(tar.len().next_multiple_of(BLOCK), 0);
+    Ok(())
+}
+
+/// tar头中的八进制数字段
+fn octal(field: &[u8]) -> Option<usize> {
+    let text = core::str::from_utf8(field).ok()?.trim_matches(|c| c == '\0' || c == ' ');
+    if text.is_empty() { Some(0) } else { usize::from_str_radix(text, 8).ok() }
+}
+
+/// 归档中的普通文件：(名称, 数据)
+fn entries(data: &[u8]) -> Result<Vec<(String, &[u8])>, String> {
+    let mut found = Vec::new();
+    let mut offset = 0;
+    while offset + BLOCK <= data.len() {
+        let header = &data[offset..offset + BLOCK];
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        let stored = octal(&header[148..156]).ok_or("Not a tar archive: bad header checksum")?;
+        let checksum: usize = header.iter().enumerate().map(|(i, &b)| if (148..156).contains(&i) { b' ' as usize } else { b as usize }).sum();
+        if stored != checksum {
+            return Err("Not a tar archive: bad header checksum".to_string());
+        }
+        let size = octal(&header[124..136]).ok_or("Not a tar archive: bad entry size")?;
+        let start = offset + BLOCK;
+        let contents = data.get(start..start + size).ok_or("Bundle is truncated")?;
+        // 只关心普通文件；目录等其他条目跳过
+        if matches!(header[156], b'0' | 0) {
+            let end = header[..100].iter().position(|&b| b == 0).unwrap_or(100);
+            let mut name = String::from_utf8_lossy(&header[..end]).into_owned();
+            let prefix_end = header[345..500].iter().position(|&b| b == 0).unwrap_or(155);
+            if prefix_end > 0 {
+                name = format!("{}/{}", String::from_utf8_lossy(&header[345..345 + prefix_end]), name);
+            }
+            found.push((name.trim_start_matches("./").to_string(), contents));
+        }
+        offset = start + size.next_multiple_of(BLOCK);
+    }
+    Ok(found)
+}
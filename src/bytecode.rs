@@ -0,0 +1,362 @@
+//! 字节码文件(.dbc) - 保存编译结果，运行时跳过预处理与解析
+//!
+//! 格式(整数均为小端)：
+//!
+//! ```text
+//! "DRBC" 版本:u32 标志:u32 指令集:u32
+//! 纸带大小:u64 输入耗尽行为:u8 越界处理:u8      (0表示未声明)
+//! 指令数:u64 {操作码:u8 操作数:u32}*            (括号的操作数是配对括号的下标，自定义指令的是字符)
+//! [源码映射] 名称长度:u64 名称 源码长度:u64 源码 {偏移:u32 行:u32 列:u32}*
+//! 校验和:u64                                     (之前所有字节的FNV-1a)
+//! ```
+//!
+//! 标志位1表示有源码映射，2表示压缩，4表示程序按纸带ABI运行。设置压缩标志时，纸带与编译指示之后直到校验和之前的部分是一个zstd帧。
+//!
+//! 指令集是写入时操作码表的长度。表只在末尾追加，所以较新版本写出的文件只要没有
+//! 用到本版本不认识的操作码就仍可加载；版本1的文件没有指令集与校验和字段。
+//! 加载时重新核对括号配对与过程编号，损坏的文件不会被误解。
+
+use alloc::borrow::Cow;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem::discriminant;
+
+use crate::pragma::{self, Pragma};
+use crate::{DerstandInterpreter, EofBehavior, Instruction, OverflowPolicy, Span, zstd};
+
+/// 文件魔数与格式版本
+pub const MAGIC: &[u8; 4] = b"DRBC";
+const VERSION: u32 = 2;
+
+/// 标志位：文件包含源码映射
+const HAS_SOURCE_MAP: u32 = 1;
+/// 标志位：指令流与源码映射经过zstd压缩
+const COMPRESSED: u32 = 2;
+/// 标志位：程序按纸带ABI运行，见abi模块
+const TAPE_ABI: u32 = 4;
+/// 本版本理解的全部标志位；含其他标志的文件布局未知，不能加载
+const KNOWN_FLAGS: u32 = HAS_SOURCE_MAP | COMPRESSED | TAPE_ABI;
+/// 压缩部分之前的字节数：魔数、版本、标志、指令集、纸带大小与两个编译指示
+const HEADER_SIZE: usize = 26;
+
+/// 操作码即指令在表中的下标，带数值的指令以0占位；只能在末尾追加
+const OPCODES: [Instruction; 38] = [
+    Instruction::Right,
+    Instruction::Left,
+    Instruction::Increment,
+    Instruction::Decrement,
+    Instruction::Output,
+    Instruction::Input,
+    Instruction::JumpIfZero,
+    Instruction::JumpIfNotZero,
+    Instruction::Zero,
+    Instruction::Copy,
+    Instruction::MoveHigh,
+    Instruction::MoveLow,
+    Instruction::Debug,
+    Instruction::Add(0),
+    Instruction::Sub(0),
+    Instruction::MoveRight(0),
+    Instruction::MoveLeft(0),
+    Instruction::Set(0),
+    Instruction::CopyLeft,
+    Instruction::AddNext,
+    Instruction::Push,
+    Instruction::Pop,
+    Instruction::SwitchTape,
+    Instruction::Exchange,
+    Instruction::ProcStart,
+    Instruction::ProcEnd,
+    Instruction::Call(0),
+    Instruction::CallCell,
+    Instruction::OutputDecimal,
+    Instruction::InputDecimal,
+    Instruction::Random,
+    Instruction::Time,
+    Instruction::Sleep,
+    Instruction::ScanLeft,
+    Instruction::ScanRight,
+    Instruction::Custom('\0'),
+    Instruction::HostCall,
+    Instruction::Assert(0),
+];
+
+/// FNV-1a 64位哈希 - 算法固定，跨版本与平台结果稳定
+pub fn fnv1a64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// 数据是否是字节码文件
+pub fn is_bytecode(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+fn opcode(instruction: Instruction) -> u8 {
+    OPCODES.iter().position(|i| discriminant(i) == discriminant(&instruction)).expect("every instruction has an opcode") as u8
+}
+
+/// 由操作码与操作数还原指令
+fn decode(opcode: u8, operand: u32) -> Option<Instruction> {
+    Some(match *OPCODES.get(opcode as usize)? {
+        Instruction::Add(_) => Instruction::Add(operand),
+        Instruction::Sub(_) => Instruction::Sub(operand),
+        Instruction::MoveRight(_) => Instruction::MoveRight(operand),
+        Instruction::MoveLeft(_) => Instruction::MoveLeft(operand),
+        Instruction::Set(_) => Instruction::Set(u8::try_from(operand).ok()?),
+        Instruction::Assert(_) => Instruction::Assert(u8::try_from(operand).ok()?),
+        Instruction::Call(_) => Instruction::Call(operand),
+        Instruction::Custom(_) => Instruction::Custom(char::from_u32(operand)?),
+        instruction => instruction,
+    })
+}
+
+fn eof_code(eof: Option<EofBehavior>) -> u8 {
+    match eof {
+        None => 0,
+        Some(EofBehavior::Zero) => 1,
+        Some(EofBehavior::Unchanged) => 2,
+        Some(EofBehavior::Max) => 3,
+        Some(EofBehavior::Error) => 4,
+    }
+}
+
+fn overflow_code(overflow: Option<OverflowPolicy>) -> u8 {
+    match overflow {
+        None => 0,
+        Some(OverflowPolicy::Wrap) => 1,
+        Some(OverflowPolicy::Saturate) => 2,
+        Some(OverflowPolicy::Trap) => 3,
+    }
+}
+
+/// 序列化已编译程序；调用者设置的执行选项作为编译指示保存，运行时仍可被命令行覆盖
+pub fn save(interpreter: &DerstandInterpreter, source: &str, source_name: &str) -> Vec<u8> {
+    let mut data = Vec::with_capacity(interpreter.instructions.len() * 17 + source.len() + 64);
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&VERSION.to_le_bytes());
+    let flags = if interpreter.abi_enabled() { HAS_SOURCE_MAP | TAPE_ABI } else { HAS_SOURCE_MAP };
+    data.extend_from_slice(&flags.to_le_bytes());
+    data.extend_from_slice(&(OPCODES.len() as u32).to_le_bytes());
+    data.extend_from_slice(&(interpreter.memory_size() as u64).to_le_bytes());
+    data.push(eof_code(interpreter.eof.or(interpreter.pragma.eof)));
+    data.push(overflow_code(interpreter.overflow.or(interpreter.pragma.overflow)));
+
+    data.extend_from_slice(&(interpreter.instructions.len() as u64).to_le_bytes());
+    for (pc, &instruction) in interpreter.instructions.iter().enumerate() {
+        let operand = match instruction {
+            Instruction::Add(n) | Instruction::Sub(n) | Instruction::MoveRight(n) | Instruction::MoveLeft(n) | Instruction::Call(n) => n,
+            Instruction::Set(value) | Instruction::Assert(value) => value as u32,
+            Instruction::Custom(symbol) => symbol as u32,
+            _ => interpreter.jump_target(pc).map_or(0, |target| target as u32),
+        };
+        data.push(opcode(instruction));
+        data.extend_from_slice(&operand.to_le_bytes());
+    }
+
+    for text in [source_name, source] {
+        data.extend_from_slice(&(text.len() as u64).to_le_bytes());
+        data.extend_from_slice(text.as_bytes());
+    }
+    for span in &interpreter.spans {
+        for value in [span.offset, span.line, span.column] {
+            data.extend_from_slice(&(value as u32).to_le_bytes());
+        }
+    }
+    let checksum = fnv1a64(&data);
+    data.extend_from_slice(&checksum.to_le_bytes());
+    data
+}
+
+/// 压缩save()写出的字节码
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let (header, payload) = data[..data.len() - 8].split_at(HEADER_SIZE);
+    let mut out = header.to_vec();
+    let flags = u32::from_le_bytes(out[8..12].try_into().unwrap()) | COMPRESSED;
+    out[8..12].copy_from_slice(&flags.to_le_bytes());
+    out.extend_from_slice(&zstd::compress(payload));
+    let checksum = fnv1a64(&out);
+    out.extend_from_slice(&checksum.to_le_bytes());
+    out
+}
+
+/// 顺序读取字节码字段
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.data.len() < len {
+            return Err("Bytecode file is truncated".to_string());
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn usize(&mut self) -> Result<usize, String> {
+        let value = u64::from_le_bytes(self.take(8)?.try_into().unwrap());
+        usize::try_from(value).map_err(|_| "Bytecode value out of range".to_string())
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.usize()?;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| "Bytecode contains invalid UTF-8 text".to_string())
+    }
+}
+
+/// 已加载程序的源码映射：源文件名与源码(用于诊断中的代码片段)
+pub struct SourceMap {
+    pub name: String,
+    pub source: String,
+}
+
+/// 加载字节码，替换解释器中的程序
+pub fn load(interpreter: &mut DerstandInterpreter, data: &[u8]) -> Result<SourceMap, String> {
+    let mut reader = Reader { data };
+    if reader.take(4)? != MAGIC {
+        return Err("Not a Derstand bytecode file".to_string());
+    }
+    let version = reader.u32()?;
+    if version == 0 || version > VERSION {
+        return Err(format!("Bytecode format version {} is not supported (this build reads versions 1 to {}); recompile the source", version, VERSION));
+    }
+    let flags = reader.u32()?;
+    if flags & !KNOWN_FLAGS != 0 {
+        return Err(format!("Bytecode uses features this build does not support (flags {:#x}); recompile the source", flags & !KNOWN_FLAGS));
+    }
+    // 版本1没有指令集字段，只用到最初的操作码
+    let instruction_set = if version >= 2 { reader.u32()? } else { OPCODES.len() as u32 };
+    if version >= 2 {
+        // 先校验整个文件，再解释其中的内容
+        let (body, checksum) = data.split_at(data.len().checked_sub(8).filter(|&n| n >= 16).ok_or("Bytecode file is truncated")?);
+        if fnv1a64(body).to_le_bytes() != checksum {
+            return Err("Bytecode checksum mismatch; the file is corrupted".to_string());
+        }
+        reader.data = &reader.data[..reader.data.len() - 8];
+    }
+    let memory = reader.usize()?;
+    let eof = match reader.u8()? {
+        0 => None,
+        1 => Some(EofBehavior::Zero),
+        2 => Some(EofBehavior::Unchanged),
+        3 => Some(EofBehavior::Max),
+        4 => Some(EofBehavior::Error),
+        _ => return Err("Invalid EOF behavior in bytecode".to_string()),
+    };
+    let overflow = match reader.u8()? {
+        0 => None,
+        1 => Some(OverflowPolicy::Wrap),
+        2 => Some(OverflowPolicy::Saturate),
+        3 => Some(OverflowPolicy::Trap),
+        _ => return Err("Invalid overflow policy in bytecode".to_string()),
+    };
+    if !(1..=pragma::MAX_MEMORY_SIZE).contains(&memory) {
+        return Err("Invalid tape size in bytecode".to_string());
+    }
+    let payload = if flags & COMPRESSED != 0 {
+        Cow::Owned(zstd::decompress(reader.data).map_err(|e| format!("Bytecode cannot be decompressed: {}", e))?)
+    } else {
+        Cow::Borrowed(reader.data)
+    };
+    let mut reader = Reader { data: &payload };
+
+    let count = reader.usize()?;
+    let mut instructions = Vec::with_capacity(count.min(reader.data.len() / 5));
+    let mut targets = Vec::with_capacity(instructions.capacity());
+    for _ in 0..count {
+        let (code, operand) = (reader.u8()?, reader.u32()?);
+        let instruction = match decode(code, operand) {
+            Some(instruction) => instruction,
+            None if usize::from(code) >= OPCODES.len() && u32::from(code) < instruction_set => {
+                return Err(format!("Bytecode uses instruction {} from a newer instruction set; upgrade derstand or recompile the source", code));
+            },
+            None => return Err(format!("Invalid opcode {} in bytecode", code)),
+        };
+        instructions.push(instruction);
+        targets.push(operand as usize);
+    }
+
+    let (name, source, spans) = if flags & HAS_SOURCE_MAP != 0 {
+        let name = reader.string()?;
+        let source = reader.string()?;
+        let mut spans = Vec::with_capacity(count);
+        for _ in 0..count {
+            let (offset, line, column) = (reader.u32()?, reader.u32()?, reader.u32()?);
+            spans.push(Span { offset: offset as usize, line: line as usize, column: column as usize });
+        }
+        (name, source, spans)
+    } else {
+        (String::from("<bytecode>"), String::new(), vec![Span { offset: 0, line: 1, column: 1 }; count])
+    };
+    if !reader.data.is_empty() {
+        return Err("Unexpected data after the end of the bytecode".to_string());
+    }
+
+    // 重建跳转表并核对保存的配对下标
+    let mut to_close = vec![0; count];
+    let mut to_open = vec![0; count];
+    let mut procedures = Vec::new();
+    let mut open = Vec::new();
+    let mut procedure = None;
+    let corrupted = || "Bytecode has mismatched brackets or procedures".to_string();
+    for (pc, &instruction) in instructions.iter().enumerate() {
+        match instruction {
+            Instruction::JumpIfZero => open.push(pc),
+            Instruction::JumpIfNotZero => {
+                let start = open.pop().filter(|&start| procedure.is_none_or(|p| start > p)).ok_or_else(corrupted)?;
+                (to_close[start], to_open[pc]) = (pc, start);
+            },
+            Instruction::ProcStart if procedure.is_none() && open.is_empty() => {
+                procedure = Some(pc);
+                procedures.push(pc);
+            },
+            Instruction::ProcEnd if open.is_empty() => {
+                let start = procedure.take().ok_or_else(corrupted)?;
+                (to_close[start], to_open[pc]) = (pc, start);
+            },
+            Instruction::ProcStart | Instruction::ProcEnd => return Err(corrupted()),
+            _ => {},
+        }
+    }
+    let jumps_match = instructions.iter().enumerate().all(|(pc, instruction)| match instruction {
+        Instruction::JumpIfZero | Instruction::ProcStart => targets[pc] == to_close[pc],
+        Instruction::JumpIfNotZero | Instruction::ProcEnd => targets[pc] == to_open[pc],
+        Instruction::Call(n) => (*n as usize) < procedures.len(),
+        _ => true,
+    });
+    if !open.is_empty() || procedure.is_some() || !jumps_match {
+        return Err(corrupted());
+    }
+
+    interpreter.pragma = Pragma { memory: Some(memory), eof, overflow, abi: flags & TAPE_ABI != 0, dialect: None };
+    let size = interpreter.memory_override.unwrap_or(memory);
+    interpreter.check_memory_limit(size).map_err(|e| e.message.clone())?;
+    if interpreter.memory.len() != size {
+        interpreter.memory = vec![0; size];
+        interpreter.pointer = interpreter.pointer.min(size - 1);
+        interpreter.other_tape = None;
+        interpreter.active_tape = 0;
+    }
+    interpreter.expansions = vec![None; count];
+    interpreter.expansion_notes.clear();
+    interpreter.instructions = instructions;
+    interpreter.spans = spans;
+    interpreter.jump_table.to_close = to_close;
+    interpreter.jump_table.to_open = to_open;
+    interpreter.procedures = procedures;
+    interpreter.pc = 0;
+    interpreter.loop_counts.clear();
+    interpreter.loop_counts.resize(count, 0);
+    Ok(SourceMap { name, source })
+}
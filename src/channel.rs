@@ -0,0 +1,335 @@
+//! 通道 - 可选的并发扩展，同时运行的程序经命名通道传递单元格的值
+//!
+//! `derstand run-set programs.txt`按清单同时启动一组程序，每个程序一个线程。清单每行一个程序，
+//! 随后是它使用的通道名，按顺序编为端口0、1、2……(`#`开始注释，路径相对于清单所在目录)：
+//!
+//! ```text
+//! # 程序          端口0    端口1
+//! producer.dr     jobs
+//! worker.dr       jobs     results
+//! printer.dr      results
+//! ```
+//!
+//! 清单中的程序多两条自定义指令，端口号取当前单元格右边一格的值(指针在最后一格时为0)：
+//!
+//! ```text
+//! ↑   把当前单元格的值发送到该端口的通道，不阻塞
+//! ↓   从该端口的通道接收一个值放进当前单元格；通道为空时等待
+//! ```
+//!
+//! 通道为空且清单中其他使用它的程序都已结束时，`↓`读到0。所有仍在运行的程序都在等待接收时是死锁，
+//! 它们都以运行时错误结束。`.`与`,`照常使用标准输出与标准输入，由所有程序共用。
+//!
+//! 线程的交错每次运行都不同。`--schedule SEED`改为在一个线程里轮流单步执行各程序，每轮连续执行的步数
+//! 由种子决定，同一种子总得到同样的交错与输出，便于测试；等待接收的程序在轮到时跳过。
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+use crate::diagnostic::stderr_color;
+use crate::random::Rng;
+use crate::{DerstandInterpreter, Instruction, runtime_error};
+
+/// 发送与接收指令
+pub const SEND: char = '↑';
+pub const RECEIVE: char = '↓';
+
+/// 确定性调度时一轮最多连续执行的步数
+const MAX_QUANTUM: u64 = 16;
+
+/// 端口号在当前单元格右边一格
+fn port(memory: &[u8], pointer: usize) -> u8 {
+    memory.get(pointer + 1).copied().unwrap_or(0)
+}
+
+/// 清单中的一个程序
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub channels: Vec<String>, // 以端口号索引的通道名
+}
+
+/// 解析清单；相对路径以base为起点
+pub fn parse_manifest(text: &str, base: &Path) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut words = line.split_whitespace();
+        let Some(path) = words.next() else {
+            continue;
+        };
+        let channels: Vec<String> = words.map(str::to_string).collect();
+        if channels.len() > u8::MAX as usize + 1 {
+            return Err(format!("line {}: a program can use at most 256 channels", number + 1));
+        }
+        entries.push(Entry { path: base.join(path), channels });
+    }
+    if entries.is_empty() {
+        return Err("the manifest lists no programs".to_string());
+    }
+    Ok(entries)
+}
+
+struct State {
+    queues: Vec<VecDeque<u8>>, // 以通道编号索引
+    ports: Vec<Vec<usize>>,    // 每个程序的端口对应的通道编号
+    live: Vec<bool>,           // 程序是否仍在运行
+    waiting: usize,            // 正在等待接收的程序数
+    deadlocked: bool,
+}
+
+impl State {
+    /// 除program之外是否还有运行中的程序使用channel
+    fn open(&self, channel: usize, program: usize) -> bool {
+        (0..self.live.len()).any(|p| p != program && self.live[p] && self.ports[p].contains(&channel))
+    }
+
+    fn channel(&self, program: usize, port: u8) -> Result<usize, String> {
+        self.ports[program].get(port as usize).copied().ok_or(format!("no channel on port {} (this program has {})", port, self.ports[program].len()))
+    }
+}
+
+/// 一组程序共用的通道；clone得到同一组通道
+#[derive(Clone)]
+pub struct Network {
+    shared: Arc<(Mutex<State>, Condvar)>,
+}
+
+impl Network {
+    /// 每个程序按端口顺序列出的通道名；同名即同一通道
+    pub fn new(programs: &[Vec<String>]) -> Self {
+        let mut names: Vec<&str> = Vec::new();
+        let ports = programs
+            .iter()
+            .map(|channels| {
+                channels.iter().map(|name| names.iter().position(|n| n == name).unwrap_or_else(|| {
+                    names.push(name);
+                    names.len() - 1
+                })).collect()
+            })
+            .collect();
+        let state = State { queues: vec![VecDeque::new(); names.len()], ports, live: vec![true; programs.len()], waiting: 0, deadlocked: false };
+        Network { shared: Arc::new((Mutex::new(state), Condvar::new())) }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.shared.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub(crate) fn send(&self, program: usize, port: u8, value: u8) -> Result<(), String> {
+        let mut state = self.lock();
+        let channel = state.channel(program, port)?;
+        state.queues[channel].push_back(value);
+        self.shared.1.notify_all();
+        Ok(())
+    }
+
+    pub(crate) fn receive(&self, program: usize, port: u8) -> Result<u8, String> {
+        let mut state = self.lock();
+        let channel = state.channel(program, port)?;
+        state.waiting += 1;
+        let received = loop {
+            if let Some(value) = state.queues[channel].pop_front() {
+                break Ok(value);
+            }
+            // 死锁时所有等待者都报错，即使随后有程序结束使通道关闭
+            if !state.deadlocked && !state.open(channel, program) {
+                break Ok(0);
+            }
+            if state.deadlocked || state.waiting == state.live.iter().filter(|&&live| live).count() {
+                state.deadlocked = true;
+                self.shared.1.notify_all();
+                break Err("deadlock: every running program is waiting to receive".to_string());
+            }
+            state = self.shared.1.wait(state).unwrap_or_else(|poisoned| poisoned.into_inner());
+        };
+        state.waiting -= 1;
+        received
+    }
+
+    /// 在interpreter上注册第program个程序的`↑`与`↓`；应在编译之前调用
+    pub fn attach(&self, interpreter: &mut DerstandInterpreter, program: usize) -> Result<(), String> {
+        let network = self.clone();
+        interpreter.register_instruction(SEND, "send", Box::new(move |machine| {
+            network.send(program, port(machine.memory(), machine.pointer()), machine.cell())
+        }))?;
+        let network = self.clone();
+        interpreter.register_instruction(RECEIVE, "receive", Box::new(move |machine| {
+            let value = network.receive(program, port(machine.memory(), machine.pointer()))?;
+            machine.set_cell(value);
+            Ok(())
+        }))
+    }
+
+    /// 第program个程序已结束：它使用的通道可能因此关闭
+    pub fn finish(&self, program: usize) {
+        self.lock().live[program] = false;
+        self.shared.1.notify_all();
+    }
+
+    /// 第program个程序的下一条指令是否会在空且未关闭的通道上等待
+    fn blocked(&self, program: usize, interpreter: &DerstandInterpreter) -> bool {
+        if interpreter.instructions().get(interpreter.pc()) != Some(&Instruction::Custom(RECEIVE)) {
+            return false;
+        }
+        let state = self.lock();
+        // 无效的端口与死锁都由接收指令报告
+        match state.channel(program, port(interpreter.memory(), interpreter.pointer())) {
+            Ok(channel) => !state.deadlocked && state.queues[channel].is_empty() && state.open(channel, program),
+            Err(_) => false,
+        }
+    }
+}
+
+/// 已编译、可以开始执行的程序
+struct Prepared {
+    interpreter: DerstandInterpreter,
+    source: String,
+    file: String,
+}
+
+impl Prepared {
+    /// 渲染运行时错误
+    fn failure(&self, error: crate::diagnostic::Diagnostic) -> String {
+        runtime_error(&self.interpreter, &self.source, error).render(&self.source, Some(&self.file), stderr_color())
+    }
+}
+
+/// 读取并编译一个程序，出错时返回渲染好的诊断
+fn prepare(network: &Network, index: usize, entry: &Entry) -> Result<Prepared, String> {
+    let file = entry.path.display().to_string();
+    let source = std::fs::read_to_string(&entry.path).map_err(|e| format!("Error reading file {}: {}", file, e))?;
+    let mut interpreter = DerstandInterpreter::new();
+    network.attach(&mut interpreter, index)?;
+    interpreter.set_source_path(&entry.path);
+    interpreter.compile(&source).map_err(|e| e.render(&source, Some(&file), stderr_color()))?;
+    Ok(Prepared { interpreter, source, file })
+}
+
+fn run_program(network: &Network, index: usize, entry: &Entry) -> Result<(), String> {
+    let mut program = prepare(network, index, entry)?;
+    program.interpreter.execute().map_err(|e| program.failure(e))
+}
+
+/// 同时运行清单中的所有程序，返回每个程序的结果
+pub fn run(entries: &[Entry]) -> Vec<Result<(), String>> {
+    let network = Network::new(&entries.iter().map(|e| e.channels.clone()).collect::<Vec<_>>());
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                let network = network.clone();
+                scope.spawn(move || {
+                    let result = run_program(&network, index, entry);
+                    network.finish(index);
+                    result
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap_or_else(|_| Err("the program's thread panicked".to_string()))).collect()
+    })
+}
+
+/// 在当前线程按种子确定的交错运行清单中的所有程序，见模块说明
+pub fn run_scheduled(entries: &[Entry], seed: u64) -> Vec<Result<(), String>> {
+    let network = Network::new(&entries.iter().map(|e| e.channels.clone()).collect::<Vec<_>>());
+    let mut results: Vec<Option<Result<(), String>>> = vec![None; entries.len()];
+    let mut programs = Vec::new();
+    for (index, entry) in entries.iter().enumerate() {
+        match prepare(&network, index, entry) {
+            Ok(mut program) => {
+                program.interpreter.reset();
+                programs.push(Some(program));
+            },
+            Err(e) => {
+                results[index] = Some(Err(e));
+                network.finish(index);
+                programs.push(None);
+            },
+        }
+    }
+    let mut rng = Rng::new(seed);
+    let mut next = 0;
+    // 依次轮到每个运行中的程序
+    while let Some(index) = (0..entries.len()).map(|i| (next + i) % entries.len()).find(|&i| results[i].is_none()) {
+        next = index + 1;
+        let running = || (0..entries.len()).filter(|&i| results[i].is_none());
+        if running().all(|i| programs[i].as_ref().is_some_and(|p| network.blocked(i, &p.interpreter))) {
+            // 之后的接收都报告死锁
+            network.lock().deadlocked = true;
+        }
+        let Some(program) = programs[index].as_mut() else {
+            continue;
+        };
+        for _ in 0..1 + rng.next_u64() % MAX_QUANTUM {
+            if network.blocked(index, &program.interpreter) {
+                break;
+            }
+            let finished = match program.interpreter.step() {
+                Ok(true) => continue,
+                Ok(false) => Ok(()),
+                Err(e) => Err(program.failure(e)),
+            };
+            results[index] = Some(finished);
+            network.finish(index);
+            break;
+        }
+    }
+    results.into_iter().map(|r| r.unwrap_or(Ok(()))).collect()
+}
+
+/// `derstand run-set [--schedule SEED] <manifest>`
+pub fn command(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: derstand run-set [--schedule SEED] <manifest>";
+    let mut seed = None;
+    let mut manifests = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let parsed = match arg.as_str() {
+            "--schedule" => iter.next().and_then(|v| v.parse::<u64>().ok()).map(|n| seed = Some(n)).ok_or("--schedule expects a non-negative integer seed".to_string()),
+            _ if arg.starts_with("--") => Err(format!("Unknown option: {}", arg)),
+            _ => {
+                manifests.push(arg);
+                Ok(())
+            },
+        };
+        if let Err(e) = parsed {
+            eprintln!("{}", e);
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    }
+    let [manifest] = manifests.as_slice() else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let path = Path::new(manifest);
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("Error reading manifest {}: {}", manifest, e);
+            return 1;
+        },
+    };
+    let entries = match parse_manifest(&text, path.parent().unwrap_or(Path::new(""))) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("{}: {}", manifest, e);
+            return 2;
+        },
+    };
+    let mut failed = false;
+    let results = match seed {
+        Some(seed) => run_scheduled(&entries, seed),
+        None => run(&entries),
+    };
+    for result in results {
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            failed = true;
+        }
+    }
+    if failed { 1 } else { 0 }
+}
@@ -0,0 +1,157 @@
+//! 执行检查点 - 长时间运行时周期性保存解释器状态，崩溃或重启后可恢复
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::bytecode::fnv1a64;
+use crate::random::Rng;
+use crate::{DerstandInterpreter, Tape};
+
+/// 文件魔数与格式版本
+const MAGIC: &[u8; 4] = b"DRCK";
+const VERSION: u32 = 5;
+
+/// 检查点配置
+#[derive(Debug, Clone)]
+pub struct CheckpointConfig {
+    pub every: u64, // 间隔步数
+    pub path: PathBuf,
+}
+
+/// 程序指纹 - 防止用不同程序恢复检查点
+fn program_fingerprint(interpreter: &DerstandInterpreter) -> u64 {
+    let code: String = interpreter.instructions.iter().map(|i| i.to_string()).collect();
+    fnv1a64(code.as_bytes())
+}
+
+/// 将解释器状态写入文件(先写临时文件再重命名，避免中途崩溃留下损坏的检查点)
+pub fn save(interpreter: &DerstandInterpreter, path: &Path) -> Result<(), String> {
+    let mut data = Vec::with_capacity(interpreter.memory.len() + 64);
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&VERSION.to_le_bytes());
+    data.extend_from_slice(&program_fingerprint(interpreter).to_le_bytes());
+    for value in [interpreter.pc, interpreter.pointer, interpreter.memory.len()] {
+        data.extend_from_slice(&(value as u64).to_le_bytes());
+    }
+    data.extend_from_slice(&interpreter.steps.to_le_bytes());
+    data.extend_from_slice(&interpreter.memory);
+    data.extend_from_slice(&(interpreter.loop_counts.len() as u64).to_le_bytes());
+    for count in &interpreter.loop_counts {
+        data.extend_from_slice(&count.to_le_bytes());
+    }
+    data.extend_from_slice(&(interpreter.input_buffer.len() as u64).to_le_bytes());
+    data.extend_from_slice(&interpreter.input_buffer);
+    data.extend_from_slice(&(interpreter.stack.len() as u64).to_le_bytes());
+    data.extend_from_slice(&interpreter.stack);
+    // 第二条纸带：未分配时长度为0
+    let (other, other_pointer) = interpreter.other_tape.as_ref().map_or((&[][..], 0), |t| (&t.memory[..], t.pointer));
+    for value in [interpreter.active_tape, other_pointer, other.len()] {
+        data.extend_from_slice(&(value as u64).to_le_bytes());
+    }
+    data.extend_from_slice(other);
+    data.extend_from_slice(&(interpreter.call_stack.len() as u64).to_le_bytes());
+    for &(ret, procedure) in &interpreter.call_stack {
+        for value in [ret, procedure] {
+            data.extend_from_slice(&(value as u64).to_le_bytes());
+        }
+    }
+    data.extend_from_slice(&interpreter.rng.state().to_le_bytes());
+
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, &data)
+        .and_then(|_| fs::rename(&temp, path))
+        .map_err(|e| format!("Checkpoint write error: {}", e))
+}
+
+/// 顺序读取检查点字段
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.data.len() < len {
+            return Err("Checkpoint file is truncated".to_string());
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn usize(&mut self) -> Result<usize, String> {
+        usize::try_from(self.u64()?).map_err(|_| "Checkpoint value out of range".to_string())
+    }
+}
+
+/// 从文件恢复解释器状态 - 程序需已编译
+pub fn load(interpreter: &mut DerstandInterpreter, path: &Path) -> Result<(), String> {
+    let data = fs::read(path).map_err(|e| format!("Checkpoint read error: {}", e))?;
+    let mut reader = Reader { data: &data };
+
+    if reader.take(4)? != MAGIC {
+        return Err("Not a Derstand checkpoint file".to_string());
+    }
+    let version = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+    if version != VERSION {
+        return Err(format!("Unsupported checkpoint version {}", version));
+    }
+    if reader.u64()? != program_fingerprint(interpreter) {
+        return Err("Checkpoint was created by a different program".to_string());
+    }
+
+    let pc = reader.usize()?;
+    let pointer = reader.usize()?;
+    let memory_size = reader.usize()?;
+    if memory_size != interpreter.memory.len() || pointer >= memory_size || pc > interpreter.instructions.len() {
+        return Err("Checkpoint does not match this interpreter".to_string());
+    }
+    let steps = reader.u64()?;
+    let memory = reader.take(memory_size)?;
+    let loop_count_len = reader.usize()?;
+    if loop_count_len != interpreter.instructions.len() {
+        return Err("Checkpoint does not match this interpreter".to_string());
+    }
+    let mut loop_counts = Vec::with_capacity(loop_count_len);
+    for _ in 0..loop_count_len {
+        loop_counts.push(reader.u64()?);
+    }
+    let input_len = reader.usize()?;
+    let input = reader.take(input_len)?;
+    let stack_len = reader.usize()?;
+    let stack = reader.take(stack_len)?;
+    let active_tape = reader.usize()?;
+    let other_pointer = reader.usize()?;
+    let other_len = reader.usize()?;
+    if active_tape > 1 || (other_len != 0 && (other_len != memory_size || other_pointer >= memory_size)) {
+        return Err("Checkpoint does not match this interpreter".to_string());
+    }
+    let other = reader.take(other_len)?;
+    let call_depth = reader.usize()?;
+    let mut call_stack = Vec::with_capacity(call_depth.min(crate::CALL_DEPTH_LIMIT));
+    for _ in 0..call_depth {
+        let (ret, procedure) = (reader.usize()?, reader.usize()?);
+        if ret == 0 || ret > interpreter.instructions.len() || procedure >= interpreter.procedures.len() {
+            return Err("Checkpoint does not match this interpreter".to_string());
+        }
+        call_stack.push((ret, procedure));
+    }
+    let rng = reader.u64()?;
+
+    interpreter.reset();
+    interpreter.memory.copy_from_slice(memory);
+    interpreter.pc = pc;
+    interpreter.pointer = pointer;
+    interpreter.steps = steps;
+    interpreter.loop_counts = loop_counts;
+    interpreter.input_buffer = input.to_vec();
+    interpreter.stack = stack.to_vec();
+    interpreter.other_tape = (other_len != 0).then(|| Tape { memory: other.to_vec(), pointer: other_pointer });
+    interpreter.active_tape = active_tape;
+    interpreter.call_stack = call_stack;
+    interpreter.rng = Rng::new(rng);
+    Ok(())
+}
@@ -0,0 +1,72 @@
+//! 时钟 - 解释器读取时间、睡眠与取随机种子的唯一入口
+//!
+//! 浏览器中的wasm32没有系统时钟，启用`wasm`特性并编译到wasm32时这三项改由宿主提供，
+//! 见`playground`模块。关闭`std`特性时没有时钟：时间恒为0、睡眠立即返回、种子恒为0，
+//! 需要随机数的程序应开启确定性模式并设置种子。
+
+#[cfg(all(feature = "std", not(all(feature = "wasm", target_arch = "wasm32"))))]
+mod system {
+    use std::sync::OnceLock;
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+    pub fn now_ms() -> u64 {
+        EPOCH.get_or_init(Instant::now).elapsed().as_millis() as u64
+    }
+
+    pub fn sleep_ms(ms: u64) {
+        std::thread::sleep(Duration::from_millis(ms));
+    }
+
+    pub fn entropy() -> u64 {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        nanos ^ (std::process::id() as u64).rotate_left(32)
+    }
+}
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+mod system {
+    // JavaScript的数值是f64，毫秒数与种子都以f64传递
+    #[link(wasm_import_module = "derstand")]
+    unsafe extern "C" {
+        fn now_ms() -> f64;
+        fn sleep_ms(ms: f64);
+        fn random_seed() -> f64;
+    }
+
+    pub fn now_ms() -> u64 {
+        // SAFETY: 宿主函数没有前置条件
+        unsafe { self::now_ms() as u64 }
+    }
+
+    pub fn sleep_ms(ms: u64) {
+        // SAFETY: 同上
+        unsafe { self::sleep_ms(ms as f64) }
+    }
+
+    pub fn entropy() -> u64 {
+        // SAFETY: 同上
+        unsafe { random_seed() as u64 }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+mod system {
+    pub fn now_ms() -> u64 {
+        0
+    }
+
+    pub fn sleep_ms(_ms: u64) {}
+
+    pub fn entropy() -> u64 {
+        0
+    }
+}
+
+/// 单调递增的毫秒数，起点任意
+pub use system::now_ms;
+/// 阻塞睡眠ms毫秒
+pub use system::sleep_ms;
+/// 每次运行都不同的随机种子
+pub use system::entropy;
@@ -0,0 +1,480 @@
+//! 提前编译 - 把优化后的程序翻译为其他语言的源码，再交给对应的编译器生成原生程序
+//!
+//! 翻译后的程序从标准输入读取、向标准输出写入；纸带大小、越界与输入耗尽行为
+//! 与解释器相同，在翻译时由编译指示或命令行选项确定。`bf`目标例外：它导出
+//! 标准Brainfuck源码，只能表达部分指令，见`bf`模块。`dbc`目标保存解释器自己的
+//! 字节码(`-o`以`.dbc`结尾时默认使用)，由`derstand run`直接执行，见`bytecode`模块。
+//!
+//! C、Rust与WASM目标在每条生成的语句后以注释标注它来自的源码行列(`3:7`)；可能在运行时
+//! 报错的语句先记录这个位置，错误信息与解释器一样指出原始源码中的位置。
+
+pub(crate) mod bf;
+mod c;
+#[cfg(feature = "llvm")]
+mod llvm;
+mod rust;
+mod wasm;
+
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+
+use crate::diagnostic::Diagnostic;
+use crate::{DerstandInterpreter, Dialect, bytecode, EofBehavior, Instruction, OverflowPolicy, Span, minify, pragma};
+
+/// 翻译目标
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    C,
+    Rust,
+    Wasm,
+    #[cfg(feature = "llvm")]
+    Llvm,
+    Brainfuck,
+    Bytecode,
+}
+
+impl Target {
+    /// 解析命令行参数值
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "c" => Ok(Target::C),
+            "rust" => Ok(Target::Rust),
+            "wasm" => Ok(Target::Wasm),
+            #[cfg(feature = "llvm")]
+            "llvm" => Ok(Target::Llvm),
+            #[cfg(not(feature = "llvm"))]
+            "llvm" => Err("Target 'llvm' requires building derstand with `--features llvm`".to_string()),
+            "bf" => Ok(Target::Brainfuck),
+            "dbc" => Ok(Target::Bytecode),
+            _ => Err(format!("Unknown target '{}' (expected: c, rust, wasm, llvm, bf, dbc)", value)),
+        }
+    }
+}
+
+/// 翻译单元：主程序与各过程体(不含花括号)及其中每条指令的源码位置，以及生效的运行选项
+#[derive(Debug, Clone)]
+pub struct Unit {
+    pub main: Vec<Instruction>,
+    pub procedures: Vec<Vec<Instruction>>,
+    pub main_spans: Vec<Span>,
+    pub procedure_spans: Vec<Vec<Span>>,
+    pub memory_size: usize,
+    pub overflow: OverflowPolicy,
+    pub eof: EofBehavior,
+}
+
+impl Unit {
+    /// 从已编译程序构造；指令先经过最小化与惯用法改写
+    pub fn new(interpreter: &DerstandInterpreter) -> Self {
+        let (mut main, mut main_spans) = (Vec::new(), Vec::new());
+        let (mut procedures, mut procedure_spans) = (Vec::new(), Vec::new());
+        let mut body: Option<(Vec<Instruction>, Vec<Span>)> = None;
+        let (instructions, spans) = minify::minify_spanned(interpreter, true);
+        for (instruction, span) in instructions.into_iter().zip(spans) {
+            match (instruction, &mut body) {
+                (Instruction::ProcStart, _) => body = Some((Vec::new(), Vec::new())),
+                // 翻译后的程序不检查断言
+                (Instruction::Assert(_), _) => {},
+                (Instruction::ProcEnd, _) => {
+                    if let Some((instructions, spans)) = body.take() {
+                        procedures.push(instructions);
+                        procedure_spans.push(spans);
+                    }
+                },
+                (_, Some((instructions, spans))) => {
+                    instructions.push(instruction);
+                    spans.push(span);
+                },
+                (_, None) => {
+                    main.push(instruction);
+                    main_spans.push(span);
+                },
+            }
+        }
+        Unit {
+            main,
+            procedures,
+            main_spans,
+            procedure_spans,
+            memory_size: interpreter.memory_size(),
+            overflow: interpreter.overflow_policy(),
+            // 翻译后的程序总是从标准输入读取，与交互式模式一样默认读到0
+            eof: interpreter.eof_behavior().unwrap_or(EofBehavior::Zero),
+        }
+    }
+
+    /// 程序(包括过程)是否用到满足条件的指令
+    pub fn uses(&self, predicate: impl Fn(Instruction) -> bool) -> bool {
+        self.main.iter().chain(self.procedures.iter().flatten()).any(|&i| predicate(i))
+    }
+
+    /// 指令在运行时是否可能报错；后端在这些指令前记录源码位置
+    pub fn can_fail(&self, instruction: Instruction) -> bool {
+        use Instruction::*;
+        match instruction {
+            Increment | Add(_) | AddNext | Decrement | Sub(_) => self.overflow == OverflowPolicy::Trap,
+            Input => self.eof == EofBehavior::Error,
+            InputDecimal | Push | Pop | Call(_) | CallCell => true,
+            _ => false,
+        }
+    }
+}
+
+/// 程序用到的运行时特性，后端据此只生成用到的辅助代码
+struct Features {
+    two_tapes: bool,
+    moves: bool,
+    // 越界时饱和或报错，而不是按字节回绕
+    checked_add: bool,
+    checked_sub: bool,
+    fail: bool,
+    output: bool,
+    input: bool,
+    input_decimal: bool,
+    stack: bool,
+    random: bool,
+    time: bool,
+    sleep: bool,
+    scan_right: bool,
+    debug: bool,
+}
+
+impl Features {
+    fn new(unit: &Unit) -> Self {
+        use Instruction::*;
+        let checked = unit.overflow != OverflowPolicy::Wrap;
+        let checked_add = checked && unit.uses(|i| matches!(i, Increment | Add(_) | AddNext));
+        let checked_sub = checked && unit.uses(|i| matches!(i, Decrement | Sub(_)));
+        let input = unit.uses(|i| i == Input);
+        let input_decimal = unit.uses(|i| i == InputDecimal);
+        let stack = unit.uses(|i| matches!(i, Push | Pop));
+        Features {
+            two_tapes: unit.uses(|i| matches!(i, SwitchTape | Exchange)),
+            moves: unit.uses(|i| matches!(i, Right | Left | MoveRight(_) | MoveLeft(_))),
+            checked_add,
+            checked_sub,
+            fail: unit.uses(|i| unit.can_fail(i)) || !unit.procedures.is_empty(),
+            output: unit.uses(|i| matches!(i, Output | OutputDecimal)),
+            input,
+            input_decimal,
+            stack,
+            random: unit.uses(|i| i == Random),
+            time: unit.uses(|i| i == Time),
+            sleep: unit.uses(|i| i == Sleep),
+            scan_right: unit.uses(|i| i == ScanRight),
+            debug: unit.uses(|i| i == Debug),
+        }
+    }
+}
+
+/// 按目标翻译已编译程序；`bf`目标可能因无法表达的指令而失败，自定义指令、宿主调用与纸带ABI只能保存为字节码
+pub fn render(target: Target, interpreter: &DerstandInterpreter, source: &str, source_name: &str) -> Result<Vec<u8>, Diagnostic> {
+    // 自定义指令与宿主函数只存在于解释器进程中；字节码保存它们，运行时再由插件或宿主提供
+    if !matches!(target, Target::Bytecode | Target::Brainfuck)
+        && let Some(pc) = interpreter.instructions().iter().position(|i| matches!(i, Instruction::Custom(_) | Instruction::HostCall))
+    {
+        let instruction = interpreter.instructions()[pc];
+        let what = if instruction == Instruction::HostCall { "Host call" } else { "Custom instruction" };
+        let error = Diagnostic::error("E0016", format!("{} '{}' cannot be compiled to native code", what, instruction.symbol()))
+            .with_span(interpreter.spans()[pc])
+            .with_label("its handler only exists inside the interpreter")
+            .with_hint("compile to bytecode (--target dbc) and run it where the handler is registered");
+        return Err(interpreter.with_expansion_note(pc, error));
+    }
+    // 纸带ABI的头部由解释器在每次运行前写入，翻译后的程序没有对应的启动代码
+    if target != Target::Bytecode && interpreter.abi_enabled() {
+        return Err(Diagnostic::error("E0016", "Programs that use the tape ABI can only be compiled to bytecode")
+            .with_hint("compile to bytecode (--target dbc), or remove the `;! abi=1` pragma"));
+    }
+    let code = match target {
+        Target::C => c::render(&Unit::new(interpreter), source_name),
+        Target::Rust => rust::render(&Unit::new(interpreter), source_name),
+        Target::Wasm => wasm::render(&Unit::new(interpreter), source_name),
+        #[cfg(feature = "llvm")]
+        Target::Llvm => llvm::render(&Unit::new(interpreter), source_name),
+        Target::Brainfuck => bf::render(interpreter)?,
+        // 字节码保存源码用于运行时诊断
+        Target::Bytecode => return Ok(bytecode::save(interpreter, source, source_name)),
+    };
+    Ok(code.into_bytes())
+}
+
+/// 交叉编译的目标平台，由`arch-vendor-os[-env]`形式的三元组(如`aarch64-unknown-linux-gnu`)确定
+struct Platform {
+    triple: String,
+    windows: bool,
+    msvc: bool,
+    apple: bool, // macOS不支持完全静态链接
+}
+
+impl Platform {
+    fn parse(triple: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = triple.split('-').collect();
+        if parts.len() < 2 || parts.iter().any(|part| part.is_empty() || !part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')) {
+            return Err(format!("Invalid target triple '{}' (expected arch-vendor-os[-env], e.g. aarch64-unknown-linux-gnu)", triple));
+        }
+        Ok(Platform {
+            triple: triple.to_string(),
+            windows: parts.contains(&"windows"),
+            msvc: parts.last() == Some(&"msvc"),
+            apple: parts.iter().any(|&part| matches!(part, "apple" | "darwin" | "macos" | "ios")),
+        })
+    }
+
+    fn exe_suffix(&self) -> &'static str {
+        if self.windows { ".exe" } else { "" }
+    }
+
+    /// 环境变量名中的三元组写法，与Cargo相同
+    fn variable(&self, prefix: &str, suffix: &str, upper: bool) -> String {
+        let triple = self.triple.replace(['-', '.'], "_");
+        format!("{}{}{}", prefix, if upper { triple.to_uppercase() } else { triple }, suffix)
+    }
+
+    /// 编译器参数：指定目标并静态链接运行库，生成的程序不依赖目标机器上的共享库
+    fn flags(&self, target: Target, compiler: &str) -> Vec<String> {
+        let mut flags = Vec::new();
+        match target {
+            Target::C => {
+                // clang一个可执行文件支持所有目标；gcc等须使用目标专用的交叉编译器
+                if Path::new(compiler).file_name().is_some_and(|name| name.to_string_lossy().contains("clang")) {
+                    flags.push(format!("--target={}", self.triple));
+                }
+                if self.msvc {
+                    flags.push("-fms-runtime-lib=static".to_string());
+                } else if !self.apple {
+                    flags.push("-static".to_string());
+                }
+            },
+            _ => {
+                flags.extend(["--target".to_string(), self.triple.clone()]);
+                if !self.apple {
+                    flags.extend(["-C".to_string(), "target-feature=+crt-static".to_string()]);
+                }
+                if let Ok(linker) = std::env::var(self.variable("CARGO_TARGET_", "_LINKER", true)) {
+                    flags.extend(["-C".to_string(), format!("linker={}", linker)]);
+                }
+            },
+        }
+        flags
+    }
+}
+
+/// compile与build共用的命令行选项
+struct Options {
+    target: Target,
+    output: Option<String>,
+    compiler: Option<String>, // 只用于build
+    platform: Option<Platform>, // 只用于build
+    compress: bool,           // 只用于dbc目标
+    file: String,
+    interpreter: DerstandInterpreter,
+}
+
+/// 解析选项；出错时打印说明并返回退出码
+/// `--help`中各目标的说明
+const TARGETS_HELP: &str = "\
+Targets:
+  c      C11 source (the default); build it with any C compiler
+  rust   a single-file Rust program; build it with rustc -O
+  wasm   a WebAssembly module in the WAT text format
+  llvm   LLVM IR with opaque pointers; needs LLVM 15 or newer (LLVM 14 only with -opaque-pointers)
+  bf     standard Brainfuck for a 30000-cell tape of wrapping 8-bit cells
+  dbc    Derstand bytecode for `derstand run` (the default when -o ends in .dbc)";
+
+fn parse_options(args: &[String], usage: &str, build: bool) -> Result<Options, i32> {
+    let mut target = None;
+    let mut output = None;
+    let mut compiler = None;
+    let mut platform = None;
+    let mut compress = false;
+    let mut file = None;
+    let mut interpreter = DerstandInterpreter::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let parsed = match arg.as_str() {
+            "-h" | "--help" => {
+                if build { println!("{}", usage) } else { println!("{}\n\n{}", usage, TARGETS_HELP) }
+                return Err(0);
+            },
+            "--target" => iter.next().ok_or("Missing value for --target".to_string())
+                .and_then(|v| Target::parse(v)).map(|t| target = Some(t)),
+            "--memory" => iter.next().and_then(|v| v.parse().ok())
+                .filter(|n| (1..=pragma::MAX_MEMORY_SIZE).contains(n))
+                .ok_or(format!("--memory expects a number of cells between 1 and {}", pragma::MAX_MEMORY_SIZE))
+                .map(|n| interpreter.set_memory_size(n)),
+            "--eof" => iter.next().ok_or("Missing value for --eof".to_string())
+                .and_then(|v| EofBehavior::parse(v)).map(|eof| interpreter.set_eof_behavior(eof)),
+            "--overflow" => iter.next().ok_or("Missing value for --overflow".to_string())
+                .and_then(|v| OverflowPolicy::parse(v)).map(|policy| interpreter.set_overflow_policy(policy)),
+            "--dialect" => iter.next().ok_or("Missing value for --dialect".to_string())
+                .and_then(|v| Dialect::parse(v)).map(|dialect| interpreter.set_dialect(dialect)),
+            "-o" | "--output" => iter.next().ok_or(format!("Missing value for {}", arg)).map(|path| output = Some(path.clone())),
+            "--compress" if !build => {
+                compress = true;
+                Ok(())
+            },
+            #[cfg(feature = "plugins")]
+            "--plugin" if !build => iter.next().ok_or("Missing value for --plugin".to_string())
+                .and_then(|path| crate::plugin::load(&mut interpreter, Path::new(path)).map(drop)),
+            #[cfg(not(feature = "plugins"))]
+            "--plugin" if !build => Err("--plugin requires building derstand with `--features plugins`".to_string()),
+            "--cc" if build => iter.next().ok_or("Missing value for --cc".to_string()).map(|path| compiler = Some(path.clone())),
+            "--target-triple" if build => iter.next().ok_or("Missing value for --target-triple".to_string())
+                .and_then(|v| Platform::parse(v)).map(|p| platform = Some(p)),
+            _ if arg.starts_with('-') => Err(format!("Unknown option: {}", arg)),
+            _ => {
+                file = Some(arg.clone());
+                Ok(())
+            },
+        };
+        if let Err(e) = parsed {
+            eprintln!("{}", e);
+            return Err(2);
+        }
+    }
+    let Some(file) = file else {
+        eprintln!("{}", usage);
+        return Err(2);
+    };
+    let bytecode = output.as_deref().is_some_and(|path| Path::new(path).extension().is_some_and(|e| e == "dbc"));
+    let target = target.unwrap_or(if bytecode { Target::Bytecode } else { Target::C });
+    if compress && target != Target::Bytecode {
+        eprintln!("--compress only applies to bytecode output (--target dbc)");
+        return Err(2);
+    }
+    Ok(Options { target, output, compiler, platform, compress, file, interpreter })
+}
+
+/// 读入并编译程序，再翻译为目标源码
+fn translate(options: &mut Options) -> Result<Vec<u8>, i32> {
+    let file = &options.file;
+    let source = match std::fs::read_to_string(file) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error reading file {}: {}", file, e);
+            return Err(1);
+        },
+    };
+    options.interpreter.set_source_path(file);
+    let result = options.interpreter.compile(&source).and_then(|()| render(options.target, &options.interpreter, &source, file));
+    if result.is_ok() && options.target == Target::Brainfuck {
+        for warning in bf::warnings(&options.interpreter) {
+            warning.report(Default::default(), &source, Some(file));
+        }
+    }
+    match result {
+        Ok(code) if options.compress => Ok(bytecode::compress(&code)),
+        Ok(code) => Ok(code),
+        Err(e) => {
+            e.report(Default::default(), &source, Some(file));
+            Err(1)
+        },
+    }
+}
+
+/// `derstand compile [--target c|rust|wasm|llvm|bf|dbc] [--compress] [--plugin LIB] [--memory N] [--eof MODE] [--overflow MODE] [--dialect D] [-o out] <file>`
+pub fn command(args: &[String]) -> i32 {
+    const USAGE: &str =
+        "Usage: derstand compile [--target c|rust|wasm|llvm|bf|dbc] [--compress] [--plugin LIB] [--memory N] [--eof MODE] [--overflow MODE] [--dialect D] [-o out] <file>";
+    let mut options = match parse_options(args, USAGE, false) {
+        Ok(options) => options,
+        Err(code) => return code,
+    };
+    let code = match translate(&mut options) {
+        Ok(code) => code,
+        Err(status) => return status,
+    };
+    match options.output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, &code) {
+                eprintln!("Error writing file {}: {}", path, e);
+                return 1;
+            }
+        },
+        None => {
+            if let Err(e) = std::io::stdout().write_all(&code) {
+                eprintln!("Error writing output: {}", e);
+                return 1;
+            }
+        },
+    }
+    0
+}
+
+/// `derstand build [--target c|rust] [--target-triple TRIPLE] [--cc PATH] [--memory N] [--eof MODE] [--overflow MODE] [--dialect D] [-o out] <file>`
+///
+/// 翻译为C或Rust源码后调用系统编译器(默认`$CC`或`cc`、`$RUSTC`或`rustc`)生成独立的可执行文件，
+/// 默认与源文件同名、去掉扩展名。
+///
+/// 指定`--target-triple`时为其他平台交叉编译并静态链接。C目标默认使用`$CC_<triple>`或`clang`
+/// (后者自动加上`--target`)；Rust目标向rustc传入`--target`，链接器取自`$CARGO_TARGET_<TRIPLE>_LINKER`，
+/// 需要先用`rustup target add`安装目标的标准库。
+pub fn build_command(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: derstand build [--target c|rust] [--target-triple TRIPLE] [--cc PATH] [--memory N] [--eof MODE] [--overflow MODE] [--dialect D] [-o out] <file>";
+    let mut options = match parse_options(args, USAGE, true) {
+        Ok(options) => options,
+        Err(code) => return code,
+    };
+    let (extension, variable, default_compiler, flags): (&str, &str, &str, &[&str]) = match options.target {
+        Target::C => ("c", "CC", "cc", &["-O2"]),
+        Target::Rust => ("rs", "RUSTC", "rustc", &["-O", "--edition", "2021"]),
+        _ => {
+            eprintln!("derstand build supports --target c or rust");
+            return 2;
+        },
+    };
+    let output = options.output.clone().unwrap_or_else(|| {
+        let stem = Path::new(&options.file).with_extension("");
+        let suffix = options.platform.as_ref().map_or(std::env::consts::EXE_SUFFIX, Platform::exe_suffix);
+        format!("{}{}", stem.display(), suffix)
+    });
+    if Path::new(&output) == Path::new(&options.file) {
+        eprintln!("Refusing to overwrite {}; choose another path with -o", options.file);
+        return 2;
+    }
+    let code = match translate(&mut options) {
+        Ok(code) => code,
+        Err(status) => return status,
+    };
+
+    let directory = std::env::temp_dir().join(format!("derstand-build-{}", std::process::id()));
+    let source = directory.join(format!("program.{}", extension));
+    if let Err(e) = std::fs::create_dir_all(&directory).and_then(|()| std::fs::write(&source, &code)) {
+        eprintln!("Error writing file {}: {}", source.display(), e);
+        return 1;
+    }
+    let compiler = options.compiler.clone().or_else(|| match (&options.platform, options.target) {
+        // 交叉编译时$CC是本机的编译器
+        (Some(platform), Target::C) => Some(std::env::var(platform.variable("CC_", "", false)).unwrap_or_else(|_| "clang".to_string())),
+        _ => std::env::var(variable).ok(),
+    });
+    let compiler = compiler.unwrap_or_else(|| default_compiler.to_string());
+    let cross_flags = options.platform.as_ref().map(|platform| platform.flags(options.target, &compiler)).unwrap_or_default();
+    let status = Command::new(&compiler).args(flags).args(&cross_flags).arg("-o").arg(&output).arg(&source).status();
+    let _ = std::fs::remove_dir_all(&directory);
+    match status {
+        Ok(status) if status.success() => {
+            match &options.platform {
+                Some(platform) => println!("Built {} for {}", output, platform.triple),
+                None => println!("Built {}", output),
+            }
+            0
+        },
+        Ok(status) => {
+            eprintln!("{} failed ({})", compiler, status);
+            if let Some(platform) = &options.platform {
+                match options.target {
+                    Target::C => eprintln!("hint: cross-compiling C needs a compiler and C library for {}; pass one with --cc", platform.triple),
+                    _ => eprintln!("hint: install the standard library with `rustup target add {}` and set a linker in CARGO_TARGET_{}_LINKER", platform.triple, platform.variable("", "", true)),
+                }
+            }
+            1
+        },
+        Err(e) => {
+            eprintln!("Error running {}: {}", compiler, e);
+            1
+        },
+    }
+}
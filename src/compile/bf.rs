@@ -0,0 +1,410 @@
+//! Brainfuck后端 - 把扩展指令降级为等价的标准Brainfuck，可在任何8位回绕的解释器上运行
+//!
+//! 只用到基本扩展(`# = ~ « » @`与重复计数)的程序逐格对应；用到`$ £ % & : ?`时
+//! 每个单元格展开为三个Brainfuck单元格(值、临时、访问标记)，纸带最左另留三格哨兵：
+//!
+//! ```text
+//! [哨兵 0 0 0][单元格0 值 临时 标记][单元格1 值 临时 标记]...
+//! ```
+//!
+//! 临时格在指令之间总为0；指针到过的单元格标记为1，`&`据此向左找到哨兵。
+//! `:`与`?`借用当前单元格与之后九个单元格的临时格作工作区，按计数与逐位乘10实现；
+//! `?`读不到数字时得到0而不是报错。静态调用`^{n}`就地展开；栈、第二条纸带、随机数、时间与断言
+//! 没有等价序列，报告E0015。
+//!
+//! 目标是30000格的标准纸带：宽布局需要`3×纸带+3`格，纸带超过9999格时报告E0015，须以`--memory`或
+//! 编译指示缩小。Brainfuck的指针不在两端钳制，指针范围分析认定必然被钳制的移动报告E0015，
+//! 可能被钳制的移动由`warnings`给出警告。
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::{DerstandInterpreter, Instruction, OverflowPolicy, analysis, lint};
+
+/// 标准Brainfuck解释器的纸带格数
+pub const TAPE: usize = 30000;
+
+/// 每行的最大字符数
+const LINE_WIDTH: usize = 72;
+
+/// 宽布局中从单元格的值走到下一个单元格的值并标记它
+const WIDE_RIGHT: &str = ">>>>>[-]+<<";
+/// 宽布局中回到单元格0
+const WIDE_MOVE_LOW: &str = ">>[<<<]>";
+
+/// `?`跳过的空白字符，与解释器相同
+const WHITESPACE: &[u8] = b"\t\n\x0c\r ";
+
+/// 十进制数字
+const DIGITS: &[u8] = b"0123456789";
+
+struct Lowering<'a> {
+    interpreter: &'a DerstandInterpreter,
+    wide: bool,
+    out: String,
+    active: Vec<u32>, // 正在展开的过程，用于发现递归
+}
+
+/// 降级整个程序
+pub fn render(interpreter: &DerstandInterpreter) -> Result<String, Diagnostic> {
+    if interpreter.overflow_policy() != OverflowPolicy::Wrap {
+        return Err(Diagnostic::error("E0015", "Only wrapping arithmetic can be lowered to Brainfuck")
+            .with_hint("Brainfuck cells wrap on overflow; compile with --overflow wrap"));
+    }
+    let wide = wide(interpreter);
+    let cells = interpreter.memory_size().saturating_mul(3).saturating_add(3);
+    if wide && cells > TAPE {
+        return Err(Diagnostic::error(
+            "E0015",
+            format!("The lowered program needs a tape of {} cells, more than the {} of standard Brainfuck", cells, TAPE),
+        )
+        .with_hint(format!("'$ £ % & : ?' take three Brainfuck cells per cell; shrink the tape to {} cells with --memory", (TAPE - 3) / 3)));
+    }
+    // 必然被钳制的移动在Brainfuck中会越过纸带两端
+    if let Some(mut clamped) = analysis::pointer_bounds(interpreter).into_iter().find(|d| d.code == "W0002") {
+        clamped.severity = Severity::Error;
+        clamped.code = "E0015";
+        clamped.hint = Some("Brainfuck does not clamp the pointer at the tape edges; remove the moves that are always clamped".to_string());
+        return Err(clamped);
+    }
+    let mut lowering = Lowering { interpreter, wide, out: String::new(), active: Vec::new() };
+    if wide {
+        // 跳过哨兵并标记单元格0
+        lowering.out.push_str(">>>>>+<<");
+    }
+    lowering.lower(0, interpreter.instructions().len())?;
+
+    let mut text = String::new();
+    for line in lowering.out.as_bytes().chunks(LINE_WIDTH) {
+        text.push_str(std::str::from_utf8(line).expect("Brainfuck output is ASCII"));
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+/// 是否要用三格一组的宽布局
+fn wide(interpreter: &DerstandInterpreter) -> bool {
+    use Instruction::*;
+    interpreter.instructions().iter().any(|i| matches!(i, Copy | CopyLeft | MoveHigh | MoveLow | OutputDecimal | InputDecimal))
+}
+
+/// render成功时导出结果可能与原程序不同的地方：可能被钳制的移动，以及比标准纸带长的纸带
+pub fn warnings(interpreter: &DerstandInterpreter) -> Vec<Diagnostic> {
+    let mut warnings: Vec<Diagnostic> = analysis::pointer_bounds(interpreter).into_iter().filter(|d| d.code != "W0002").collect();
+    for warning in &mut warnings {
+        warning.hint = Some("Brainfuck does not clamp the pointer; the exported program misbehaves if this move is ever clamped".to_string());
+    }
+    if !wide(interpreter) && interpreter.memory_size() > TAPE {
+        warnings.push(
+            Diagnostic::warning("W0009", format!("The program has a tape of {} cells; standard Brainfuck has {}", interpreter.memory_size(), TAPE))
+                .with_hint("the exported program only works if it stays within the first 30000 cells"),
+        );
+    }
+    warnings
+}
+
+impl Lowering<'_> {
+    /// 降级[start, end)内的指令，跳过过程定义与必然不执行的循环
+    fn lower(&mut self, start: usize, end: usize) -> Result<(), Diagnostic> {
+        let instructions = self.interpreter.instructions();
+        let mut pc = start;
+        while pc < end {
+            let instruction = instructions[pc];
+            match instruction {
+                Instruction::ProcStart => {
+                    pc = self.jump_target(pc);
+                },
+                Instruction::JumpIfZero if lint::dead_loop_reason(instructions, pc).is_some() => {
+                    pc = self.jump_target(pc);
+                },
+                Instruction::Assert(_) => {
+                    let error = Diagnostic::error("E0015", "Assertions cannot be lowered to Brainfuck")
+                        .with_span(self.interpreter.spans()[pc])
+                        .with_label("standard Brainfuck has no way to stop with an error")
+                        .with_hint("compile with assertions disabled; the '=?{n}' checks are then ignored");
+                    return Err(self.interpreter.with_expansion_note(pc, error));
+                },
+                Instruction::Call(n) => {
+                    if self.active.contains(&n) {
+                        let error = Diagnostic::error("E0015", format!("Recursive call to procedure {} cannot be lowered to Brainfuck", n))
+                            .with_span(self.interpreter.spans()[pc])
+                            .with_label("this call re-enters a procedure that is still running")
+                            .with_hint("procedures are inlined at each call, so only non-recursive calls can be exported");
+                        return Err(self.interpreter.with_expansion_note(pc, error));
+                    }
+                    let body = self.interpreter.procedures()[n as usize];
+                    self.active.push(n);
+                    self.lower(body + 1, self.jump_target(body))?;
+                    self.active.pop();
+                },
+                _ => match self.lowered(instruction) {
+                    Some(code) => self.out.push_str(&code),
+                    None => {
+                        let error = Diagnostic::error("E0015", format!("'{}' cannot be lowered to Brainfuck", instruction.symbol()))
+                            .with_span(self.interpreter.spans()[pc])
+                            .with_label(format!("standard Brainfuck has no equivalent for {}", instruction.name()))
+                            .with_hint("only single-tape programs without the stack, '*', '`' or '/' can be exported");
+                        return Err(self.interpreter.with_expansion_note(pc, error));
+                    },
+                },
+            }
+            pc += 1;
+        }
+        Ok(())
+    }
+
+    fn jump_target(&self, pc: usize) -> usize {
+        self.interpreter.jump_target(pc).expect("compiled brackets are matched")
+    }
+
+    /// 单条指令的等价序列；指针在指令前后都位于当前单元格的值上
+    fn lowered(&self, instruction: Instruction) -> Option<String> {
+        use Instruction::*;
+        let (right, left) = if self.wide { (WIDE_RIGHT, "<<<") } else { (">", "<") };
+        let code = match instruction {
+            Right | MoveRight(_) => right.repeat(instruction.count() as usize),
+            Left | MoveLeft(_) => left.repeat(instruction.count() as usize),
+            Increment | Add(_) => add(instruction.count()),
+            Decrement | Sub(_) => add(256 - instruction.count() % 256),
+            Zero => "[-]".to_string(),
+            Set(value) => format!("[-]{}", add(value as u32)),
+            Output => ".".to_string(),
+            Input => ",".to_string(),
+            JumpIfZero => "[".to_string(),
+            JumpIfNotZero => "]".to_string(),
+            // 调试转储只写标准错误，不影响程序行为
+            Debug => String::new(),
+            AddNext if self.wide => "[->>>+<<<]".to_string(),
+            AddNext => "[->+<]".to_string(),
+            ScanLeft if self.wide => "[<<<]".to_string(),
+            ScanLeft => "[<]".to_string(),
+            ScanRight if self.wide => format!("[{}]", WIDE_RIGHT),
+            ScanRight => "[>]".to_string(),
+            // 先清空目标，再借临时格把值同时搬到目标与临时格，最后把临时格搬回
+            Copy => ">>>[-]<<<[->+>>+<<<]>[-<+>]<".to_string(),
+            CopyLeft => "<<<[-]>>>[->+<<<<+>>>]>[-<+>]<".to_string(),
+            MoveLow => WIDE_MOVE_LOW.to_string(),
+            MoveHigh => {
+                // 回到单元格0，再用随指针搬运的临时格计数，每段最多走255格
+                let mut code = WIDE_MOVE_LOW.to_string();
+                let mut remaining = self.interpreter.memory_size() - 1;
+                while remaining > 0 {
+                    let steps = remaining.min(255);
+                    code.push('>');
+                    code.push_str(&"+".repeat(steps));
+                    code.push_str("[[->>>+<<<]>>>->[-]+<]<");
+                    remaining -= steps;
+                }
+                code
+            },
+            OutputDecimal => output_decimal(),
+            InputDecimal => input_decimal(),
+            Push | Pop | SwitchTape | Exchange | CallCell | Random | Time | Sleep | Custom(_) | HostCall => return None,
+            ProcStart | ProcEnd | Call(_) | Assert(_) => unreachable!("handled by lower()"),
+        };
+        Some(code)
+    }
+}
+
+/// 加n(模256)，取`+`与`-`中较短的写法
+fn add(n: u32) -> String {
+    match n % 256 {
+        n if n <= 128 => "+".repeat(n as usize),
+        n => "-".repeat(256 - n as usize),
+    }
+}
+
+/// 宽布局中当前单元格之后第k个单元格的临时格，相对当前单元格的值的偏移
+fn temp(k: isize) -> isize {
+    1 + 3 * k
+}
+
+/// 在宽布局的临时格上生成代码；位置是相对当前单元格的值的Brainfuck单元格偏移，0是值本身
+struct Scratch {
+    code: String,
+    at: isize,
+}
+
+impl Scratch {
+    fn go(&mut self, cell: isize) {
+        let step = if cell > self.at { '>' } else { '<' };
+        self.code.extend(std::iter::repeat_n(step, cell.abs_diff(self.at)));
+        self.at = cell;
+    }
+
+    fn put(&mut self, cell: isize, instruction: char) {
+        self.go(cell);
+        self.code.push(instruction);
+    }
+
+    fn add(&mut self, cell: isize, n: u32) {
+        self.go(cell);
+        self.code.push_str(&add(n));
+    }
+
+    fn sub(&mut self, cell: isize, n: u32) {
+        self.add(cell, 256 - n % 256);
+    }
+
+    fn clear(&mut self, cell: isize) {
+        self.go(cell);
+        self.code.push_str("[-]");
+    }
+
+    /// 以cell为条件的循环；循环体结束后回到cell
+    fn repeat(&mut self, cell: isize, body: impl FnOnce(&mut Self)) {
+        self.put(cell, '[');
+        body(self);
+        self.put(cell, ']');
+    }
+
+    /// 把from加到to上，from清零
+    fn move_to(&mut self, from: isize, to: isize) {
+        self.repeat(from, |s| {
+            s.sub(from, 1);
+            s.add(to, 1);
+        });
+    }
+
+    /// 把from加到to上，from不变
+    fn copy(&mut self, from: isize, to: isize, via: isize) {
+        self.repeat(from, |s| {
+            s.sub(from, 1);
+            s.add(to, 1);
+            s.add(via, 1);
+        });
+        self.move_to(via, from);
+    }
+
+    /// flag为0时设为cell是否为0，cell不变
+    fn is_zero(&mut self, cell: isize, flag: isize, via: isize) {
+        self.add(flag, 1);
+        self.move_to(cell, via);
+        self.repeat(via, |s| {
+            s.sub(via, 1);
+            s.add(cell, 1);
+            s.clear(flag);
+        });
+    }
+
+    /// dst为0时设为cell是否非0，cell不变
+    fn nonzero(&mut self, cell: isize, dst: isize, flag: isize, via: isize) {
+        self.is_zero(cell, flag, via);
+        self.add(dst, 1);
+        self.repeat(flag, |s| {
+            s.sub(flag, 1);
+            s.sub(dst, 1);
+        });
+    }
+
+    /// dst为0时设为cell的值是否在values(升序)中，cell不变
+    fn matches(&mut self, cell: isize, values: &[u8], dst: isize, t: isize, flag: isize, via: isize) {
+        self.copy(cell, t, via);
+        let mut previous = 0;
+        for &value in values {
+            self.sub(t, (value - previous) as u32);
+            previous = value;
+            self.is_zero(t, flag, via);
+            self.move_to(flag, dst);
+        }
+        self.clear(t);
+    }
+
+    /// 读一个字节；先清零，输入结束时不改变单元格的实现也读到0
+    fn read(&mut self, cell: isize) {
+        self.clear(cell);
+        self.put(cell, ',');
+    }
+
+    /// 输出0..=9的数字
+    fn print_digit(&mut self, cell: isize) {
+        self.add(cell, b'0' as u32);
+        self.put(cell, '.');
+        self.sub(cell, b'0' as u32);
+    }
+
+    fn finish(mut self) -> String {
+        self.go(0);
+        self.code
+    }
+}
+
+/// `:` - 把值逐次计入个、十、百位，再不带前导零地输出
+fn output_decimal() -> String {
+    let (count, ones, tens, hundreds, to_tens, to_hundreds) = (temp(0), temp(1), temp(2), temp(3), temp(4), temp(5));
+    let (flag, via, carry, shown) = (temp(6), temp(7), temp(8), temp(9));
+    let mut s = Scratch { code: String::new(), at: 0 };
+    s.copy(0, count, via);
+    s.add(to_tens, 10);
+    s.add(to_hundreds, 10);
+    s.repeat(count, |s| {
+        s.sub(count, 1);
+        s.add(ones, 1);
+        s.sub(to_tens, 1);
+        s.is_zero(to_tens, flag, via);
+        s.repeat(flag, |s| {
+            s.sub(flag, 1);
+            s.sub(ones, 10);
+            s.add(to_tens, 10);
+            s.add(tens, 1);
+            s.sub(to_hundreds, 1);
+            s.is_zero(to_hundreds, carry, via);
+            s.repeat(carry, |s| {
+                s.sub(carry, 1);
+                s.sub(tens, 10);
+                s.add(to_hundreds, 10);
+                s.add(hundreds, 1);
+            });
+        });
+    });
+    s.clear(to_tens);
+    s.clear(to_hundreds);
+    // 百位非0时十位即使为0也要输出
+    s.nonzero(hundreds, carry, flag, via);
+    s.repeat(carry, |s| {
+        s.sub(carry, 1);
+        s.print_digit(hundreds);
+        s.add(shown, 1);
+    });
+    s.nonzero(tens, carry, flag, via);
+    s.repeat(carry, |s| {
+        s.sub(carry, 1);
+        s.clear(shown);
+        s.add(shown, 1);
+    });
+    s.repeat(shown, |s| {
+        s.sub(shown, 1);
+        s.print_digit(tens);
+    });
+    s.print_digit(ones);
+    for digit in [ones, tens, hundreds] {
+        s.clear(digit);
+    }
+    s.finish()
+}
+
+/// `?` - 跳过空白，逐位把值乘10再加上读到的数字，直到第一个非数字字节(也被读走)
+fn input_decimal() -> String {
+    let (byte, more, digit, t, flag, via, scaled) = (temp(0), temp(1), temp(2), temp(3), temp(4), temp(5), temp(6));
+    let mut s = Scratch { code: String::new(), at: 0 };
+    s.clear(0);
+    s.add(more, 1);
+    s.repeat(more, |s| {
+        s.sub(more, 1);
+        s.read(byte);
+        s.matches(byte, WHITESPACE, more, t, flag, via);
+    });
+    s.matches(byte, DIGITS, digit, t, flag, via);
+    s.repeat(digit, |s| {
+        s.sub(digit, 1);
+        s.move_to(0, scaled);
+        s.repeat(scaled, |s| {
+            s.sub(scaled, 1);
+            s.add(0, 10);
+        });
+        s.sub(byte, b'0' as u32);
+        s.move_to(byte, 0);
+        s.read(byte);
+        s.matches(byte, DIGITS, digit, t, flag, via);
+    });
+    s.clear(byte);
+    s.finish()
+}
@@ -0,0 +1,484 @@
+//! C后端 - 生成只依赖C11标准库的可移植C源码(`/`睡眠另需POSIX或Windows接口)
+//!
+//! 只生成程序实际用到的辅助函数，避免 `-Wall` 报告未使用的函数。
+
+use std::fmt::Write;
+
+use super::{Features, Unit};
+use crate::{CALL_DEPTH_LIMIT, EofBehavior, Instruction, OverflowPolicy, STACK_LIMIT, Span, TIME_BYTES};
+
+/// C字符串字面量
+fn string_literal(text: &str) -> String {
+    let mut out = String::from("\"");
+    for &byte in text.as_bytes() {
+        match byte {
+            b'"' | b'\\' => {
+                out.push('\\');
+                out.push(byte as char);
+            },
+            b' '..=b'~' if byte != b'?' => out.push(byte as char), // 避开三字符序列
+            _ => {
+                let _ = write!(out, "\\{:03o}", byte);
+            },
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// 输入耗尽时的处理语句
+fn eof_statement(eof: EofBehavior) -> &'static str {
+    match eof {
+        EofBehavior::Zero => "t[p] = 0;",
+        EofBehavior::Unchanged => "/* keep the cell unchanged */",
+        EofBehavior::Max => "t[p] = 255;",
+        EofBehavior::Error => "fail(\"E0101\", \"Input instruction reached end of input\");",
+    }
+}
+
+/// 结果超出0..=255时的处理语句；cell是单元格下标的C表达式，message是fail()的格式与参数
+fn overflow_statement(policy: OverflowPolicy, cell: &str, saturated: u8, message: &str) -> String {
+    match policy {
+        OverflowPolicy::Wrap => unreachable!("wrapping arithmetic is emitted inline"),
+        OverflowPolicy::Saturate => format!("t[{}] = {};", cell, saturated),
+        OverflowPolicy::Trap => format!("fail(\"E0107\", {});", message),
+    }
+}
+
+/// 辅助函数与全局状态
+fn prelude(out: &mut String, unit: &Unit, features: &Features, source_name: &str) {
+    out.push_str("#define TAPE_SIZE ((size_t)");
+    let _ = writeln!(out, "{})", unit.memory_size);
+    if features.stack {
+        let _ = writeln!(out, "#define STACK_LIMIT ((size_t){})", STACK_LIMIT);
+    }
+    if !unit.procedures.is_empty() {
+        let _ = writeln!(out, "#define CALL_DEPTH_LIMIT {}u", CALL_DEPTH_LIMIT);
+    }
+    out.push('\n');
+    let _ = writeln!(out, "static uint8_t tapes[{}][TAPE_SIZE];", if features.two_tapes { 2 } else { 1 });
+    out.push_str("static uint8_t *t = tapes[0];\nstatic size_t p;\n");
+    if features.two_tapes {
+        out.push_str("static size_t other_p;\nstatic int active;\n");
+    }
+    if features.stack {
+        out.push_str("static uint8_t stack[STACK_LIMIT];\nstatic size_t sp;\n");
+    }
+    if !unit.procedures.is_empty() {
+        out.push_str("static unsigned depth;\n");
+    }
+    if features.random {
+        out.push_str("static uint64_t rng_state;\n");
+    }
+    if features.time {
+        out.push_str("static int64_t started_ms;\n");
+    }
+    if features.fail {
+        let _ = writeln!(out, "static const char source_name[] = {};", string_literal(source_name));
+        out.push_str("static unsigned long src_line, src_column;\n");
+    }
+
+    if features.fail {
+        out.push_str(
+            "
+/* record the source position of an instruction that may fail */
+static void at(unsigned long line, unsigned long column)
+{
+    src_line = line;
+    src_column = column;
+}
+
+static void fail(const char *code, const char *format, ...)
+{
+    va_list args;
+    fflush(stdout);
+    fprintf(stderr, \"\\nerror[%s]: \", code);
+    va_start(args, format);
+    vfprintf(stderr, format, args);
+    va_end(args);
+    fprintf(stderr, \"\\n --> %s:%lu:%lu\\n\", source_name, src_line, src_column);
+    exit(1);
+}
+",
+        );
+    }
+    if features.moves {
+        out.push_str(
+            "
+static void right(size_t n)
+{
+    p = n < TAPE_SIZE - p ? p + n : TAPE_SIZE - 1;
+}
+
+static void left(size_t n)
+{
+    p = n < p ? p - n : 0;
+}
+",
+        );
+    }
+    if features.checked_add {
+        let _ = write!(
+            out,
+            "
+static void add(size_t cell, uint32_t n)
+{{
+    if (n <= 255u - t[cell]) {{
+        t[cell] = (uint8_t)(t[cell] + n);
+    }} else {{
+        {}
+    }}
+}}
+",
+            overflow_statement(unit.overflow, "cell", 255, "\"Cell overflow: %u + %lu at cell %lu\", t[cell], (unsigned long)n, (unsigned long)cell"),
+        );
+    }
+    if features.checked_sub {
+        let _ = write!(
+            out,
+            "
+static void sub(size_t cell, uint32_t n)
+{{
+    if (n <= t[cell]) {{
+        t[cell] = (uint8_t)(t[cell] - n);
+    }} else {{
+        {}
+    }}
+}}
+",
+            overflow_statement(unit.overflow, "cell", 0, "\"Cell underflow: %u - %lu at cell %lu\", t[cell], (unsigned long)n, (unsigned long)cell"),
+        );
+    }
+    if features.input {
+        let _ = write!(
+            out,
+            "
+static void input(void)
+{{
+    int c;
+    fflush(stdout);
+    c = getchar();
+    if (c != EOF) {{
+        t[p] = (uint8_t)c;
+    }} else {{
+        {}
+    }}
+}}
+",
+            eof_statement(unit.eof)
+        );
+    }
+    if features.input_decimal {
+        let overflow = match unit.overflow {
+            OverflowPolicy::Wrap => "t[p] = (uint8_t)(n % 256);".to_string(),
+            policy => overflow_statement(policy, "p", 255, "\"Cell overflow: 0 + %lu at cell %lu\", (unsigned long)n, (unsigned long)p"),
+        };
+        let _ = write!(
+            out,
+            "
+/* skip leading whitespace, read digits; the first character after the number is consumed */
+static void input_decimal(void)
+{{
+    uint32_t n = 0;
+    int digits = 0;
+    int c;
+    fflush(stdout);
+    do {{
+        c = getchar();
+    }} while (c == ' ' || c == '\\t' || c == '\\n' || c == '\\f' || c == '\\r');
+    while (c >= '0' && c <= '9') {{
+        n = n > UINT32_MAX / 10 ? UINT32_MAX : n * 10;
+        n = n > UINT32_MAX - (uint32_t)(c - '0') ? UINT32_MAX : n + (uint32_t)(c - '0');
+        digits = 1;
+        c = getchar();
+    }}
+    if (!digits) {{
+        if (c == EOF) {{
+            {}
+        }} else {{
+            fail(\"E0113\", \"Expected a decimal number, found '%c'\", c);
+        }}
+    }} else if (n <= 255) {{
+        t[p] = (uint8_t)n;
+    }} else {{
+        {}
+    }}
+}}
+",
+            eof_statement(unit.eof),
+            overflow
+        );
+    }
+    if unit.uses(|i| i == Instruction::AddNext) {
+        let add = match unit.overflow {
+            OverflowPolicy::Wrap => "t[p + 1] = (uint8_t)(t[p + 1] + t[p]);",
+            _ => "add(p + 1, t[p]);",
+        };
+        let _ = write!(
+            out,
+            "
+/* the value is dropped at the high edge */
+static void add_next(void)
+{{
+    if (p + 1 < TAPE_SIZE) {{
+        {}
+    }}
+    t[p] = 0;
+}}
+",
+            add
+        );
+    }
+    if features.stack {
+        out.push_str(
+            "
+static void push(void)
+{
+    if (sp == STACK_LIMIT) {
+        fail(\"E0110\", \"Stack overflow: more than %lu values pushed\", (unsigned long)STACK_LIMIT);
+    }
+    stack[sp++] = t[p];
+}
+
+static void pop(void)
+{
+    if (sp == 0) {
+        fail(\"E0109\", \"Pop from an empty stack\");
+    }
+    t[p] = stack[--sp];
+}
+",
+        );
+    }
+    if features.two_tapes {
+        out.push_str(
+            "
+static void switch_tape(void)
+{
+    size_t q = p;
+    p = other_p;
+    other_p = q;
+    active ^= 1;
+    t = tapes[active];
+}
+
+static void exchange(void)
+{
+    uint8_t *other = tapes[active ^ 1];
+    uint8_t v = t[p];
+    t[p] = other[other_p];
+    other[other_p] = v;
+}
+",
+        );
+    }
+    if features.random {
+        out.push_str(
+            "
+/* SplitMix64, the same generator as the interpreter */
+static uint8_t random_byte(void)
+{
+    uint64_t z = rng_state += 0x9e3779b97f4a7c15ULL;
+    z = (z ^ (z >> 30)) * 0xbf58476d1ce4e5b9ULL;
+    z = (z ^ (z >> 27)) * 0x94d049bb133111ebULL;
+    return (uint8_t)((z ^ (z >> 31)) >> 56);
+}
+",
+        );
+    }
+    if features.time {
+        let _ = write!(
+            out,
+            "
+static int64_t now_ms(void)
+{{
+    struct timespec ts;
+    timespec_get(&ts, TIME_UTC);
+    return (int64_t)ts.tv_sec * 1000 + ts.tv_nsec / 1000000;
+}}
+
+/* little-endian milliseconds since start; bytes past the high edge are dropped */
+static void write_time(void)
+{{
+    uint64_t ms = (uint64_t)(now_ms() - started_ms);
+    size_t i;
+    for (i = 0; i < {} && p + i < TAPE_SIZE; i++) {{
+        t[p + i] = (uint8_t)(ms >> (8 * i));
+    }}
+}}
+",
+            TIME_BYTES
+        );
+    }
+    if features.sleep {
+        out.push_str(
+            "
+static void sleep_ms(unsigned ms)
+{
+    fflush(stdout);
+#ifdef _WIN32
+    Sleep(ms);
+#else
+    struct timespec ts;
+    ts.tv_sec = ms / 1000;
+    ts.tv_nsec = (long)(ms % 1000) * 1000000L;
+    nanosleep(&ts, NULL);
+#endif
+}
+",
+        );
+    }
+    if features.scan_right {
+        out.push_str(
+            "
+static void scan_right(void)
+{
+    uint8_t *zero = memchr(t + p, 0, TAPE_SIZE - p);
+    p = zero ? (size_t)(zero - t) : TAPE_SIZE - 1;
+}
+",
+        );
+    }
+    if features.debug {
+        let _ = write!(
+            out,
+            "
+static void debug(void)
+{{
+    uint8_t v = t[p];
+    fflush(stdout);
+    fprintf(stderr, \"[@] tape=%d pointer=%lu value=%u hex=0x%02x char=\", {}, (unsigned long)p, v, v);
+    if (v >= ' ' && v <= '~') {{
+        fprintf(stderr, \"'%c'\\n\", v);
+    }} else {{
+        fputs(\"-\\n\", stderr);
+    }}
+}}
+",
+            if features.two_tapes { "active" } else { "0" }
+        );
+    }
+    if !unit.procedures.is_empty() {
+        out.push('\n');
+        for n in 0..unit.procedures.len() {
+            let _ = writeln!(out, "static void proc_{}(void);", n);
+        }
+    }
+    if unit.uses(|i| i == Instruction::CallCell) {
+        out.push_str("\nstatic void call_cell(void)\n{\n    switch (t[p]) {\n");
+        for n in 0..unit.procedures.len() {
+            let _ = writeln!(out, "    case {}:\n        proc_{}();\n        break;", n, n);
+        }
+        out.push_str("    default:\n        fail(\"E0112\", \"Call to undefined procedure %u\", t[p]);\n    }\n}\n");
+    }
+}
+
+/// 一条非循环指令对应的C语句
+fn statement(instruction: Instruction, unit: &Unit) -> String {
+    let wrap = unit.overflow == OverflowPolicy::Wrap;
+    match instruction {
+        Instruction::Increment | Instruction::Add(_) if wrap => format!("t[p] += {};", instruction.count() % 256),
+        Instruction::Decrement | Instruction::Sub(_) if wrap => format!("t[p] -= {};", instruction.count() % 256),
+        Instruction::Increment | Instruction::Add(_) => format!("add(p, {});", instruction.count()),
+        Instruction::Decrement | Instruction::Sub(_) => format!("sub(p, {});", instruction.count()),
+        Instruction::Right | Instruction::MoveRight(_) => format!("right({});", instruction.count()),
+        Instruction::Left | Instruction::MoveLeft(_) => format!("left({});", instruction.count()),
+        Instruction::Output => "putchar(t[p]);".to_string(),
+        Instruction::OutputDecimal => "printf(\"%u\", t[p]);".to_string(),
+        Instruction::Input => "input();".to_string(),
+        Instruction::InputDecimal => "input_decimal();".to_string(),
+        Instruction::Zero => "t[p] = 0;".to_string(),
+        Instruction::Set(value) => format!("t[p] = {};", value),
+        Instruction::Copy => "if (p + 1 < TAPE_SIZE) t[p + 1] = t[p];".to_string(),
+        Instruction::CopyLeft => "if (p > 0) t[p - 1] = t[p];".to_string(),
+        Instruction::AddNext => "add_next();".to_string(),
+        Instruction::MoveHigh => "p = TAPE_SIZE - 1;".to_string(),
+        Instruction::MoveLow => "p = 0;".to_string(),
+        Instruction::Push => "push();".to_string(),
+        Instruction::Pop => "pop();".to_string(),
+        Instruction::SwitchTape => "switch_tape();".to_string(),
+        Instruction::Exchange => "exchange();".to_string(),
+        Instruction::Call(n) => format!("proc_{}();", n),
+        Instruction::CallCell => "call_cell();".to_string(),
+        Instruction::Random => "t[p] = random_byte();".to_string(),
+        Instruction::Time => "write_time();".to_string(),
+        Instruction::Sleep => "sleep_ms(t[p]);".to_string(),
+        Instruction::ScanLeft => "while (p > 0 && t[p]) p--;".to_string(),
+        Instruction::ScanRight => "scan_right();".to_string(),
+        Instruction::Debug => "debug();".to_string(),
+        Instruction::Custom(_) | Instruction::HostCall => unreachable!("host extensions are rejected by compile::render"),
+        Instruction::Assert(_) => unreachable!("assertions are dropped by Unit::new"),
+        Instruction::JumpIfZero | Instruction::JumpIfNotZero | Instruction::ProcStart | Instruction::ProcEnd => {
+            unreachable!("structural instructions are emitted by block()")
+        },
+    }
+}
+
+/// 输出一段指令序列，循环翻译为while；每行注释源码位置，可能报错的语句先记录位置
+fn block(out: &mut String, instructions: &[Instruction], spans: &[Span], unit: &Unit) {
+    let mut indent = 1;
+    for (&instruction, span) in instructions.iter().zip(spans) {
+        match instruction {
+            Instruction::JumpIfZero => {
+                let _ = writeln!(out, "{}while (t[p]) {{ /* {}:{} */", "    ".repeat(indent), span.line, span.column);
+                indent += 1;
+            },
+            Instruction::JumpIfNotZero => {
+                indent -= 1;
+                let _ = writeln!(out, "{}}} /* {}:{} */", "    ".repeat(indent), span.line, span.column);
+            },
+            _ if unit.can_fail(instruction) => {
+                let _ = writeln!(out, "{}at({}, {}); {}", "    ".repeat(indent), span.line, span.column, statement(instruction, unit));
+            },
+            _ => {
+                let _ = writeln!(out, "{}{} /* {}:{} */", "    ".repeat(indent), statement(instruction, unit), span.line, span.column);
+            },
+        }
+    }
+}
+
+/// 生成完整的C源码
+pub fn render(unit: &Unit, source_name: &str) -> String {
+    let features = Features::new(unit);
+    let mut out = String::new();
+    let _ = writeln!(out, "/* Generated by `derstand compile --target c` from {}. */", source_name);
+    out.push_str("/* Comments and at() calls give the line:column of each statement in the source. */\n");
+    if features.sleep {
+        out.push_str("#ifndef _WIN32\n#define _POSIX_C_SOURCE 199309L\n#endif\n");
+    }
+    if features.fail {
+        out.push_str("#include <stdarg.h>\n");
+    }
+    out.push_str("#include <stdint.h>\n#include <stdio.h>\n#include <stdlib.h>\n");
+    if features.scan_right {
+        out.push_str("#include <string.h>\n");
+    }
+    if features.random || features.time || features.sleep {
+        out.push_str("#include <time.h>\n");
+    }
+    if features.sleep {
+        out.push_str("#ifdef _WIN32\n#include <windows.h>\n#endif\n");
+    }
+    out.push('\n');
+    prelude(&mut out, unit, &features, source_name);
+
+    for (n, (body, spans)) in unit.procedures.iter().zip(&unit.procedure_spans).enumerate() {
+        let _ = writeln!(out, "\nstatic void proc_{}(void)\n{{", n);
+        out.push_str("    if (depth == CALL_DEPTH_LIMIT) {\n");
+        out.push_str("        fail(\"E0111\", \"Call stack overflow: more than %u nested calls\", CALL_DEPTH_LIMIT);\n    }\n");
+        out.push_str("    depth++;\n");
+        block(&mut out, body, spans, unit);
+        out.push_str("    depth--;\n}\n");
+    }
+
+    out.push_str("\nint main(void)\n{\n");
+    if features.random {
+        out.push_str("    rng_state = (uint64_t)time(NULL) ^ ((uint64_t)clock() << 32);\n");
+    }
+    if features.time {
+        out.push_str("    started_ms = now_ms();\n");
+    }
+    block(&mut out, &unit.main, &unit.main_spans, unit);
+    out.push_str("    return 0;\n}\n");
+    out
+}
@@ -0,0 +1,743 @@
+//! LLVM后端 - 生成文本形式的LLVM IR，可用`llc`或`clang -O2`编译为原生程序
+//!
+//! 与C后端使用相同的I/O接口：从C标准库的`getchar`读入、`putchar`/`printf`写出；
+//! 错误用POSIX的`dprintf`写到标准错误，`/`睡眠使用`nanosleep`。直接输出文本IR，
+//! 不链接LLVM库，因此不依赖本机安装的LLVM开发包。
+//!
+//! 指针使用不透明的`ptr`类型，需要LLVM 15或更新的`lli`、`llc`与`clang`；LLVM 14只在加上
+//! `-opaque-pointers`时接受，更早的版本不支持。
+
+use std::fmt::Write;
+
+use super::{Features, Unit};
+use crate::{CALL_DEPTH_LIMIT, EofBehavior, Instruction, OverflowPolicy, STACK_LIMIT, TIME_BYTES};
+
+struct Generator<'a> {
+    unit: &'a Unit,
+    features: Features,
+    strings: Vec<String>, // 字符串常量，下标即名字中的编号
+    out: String,
+    next: usize,          // 函数体内临时值与标签的编号
+}
+
+/// C字符串常量的IR写法(含结尾的0)
+fn c_string(text: &str) -> String {
+    let mut out = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b' '..=b'~' if byte != b'"' && byte != b'\\' => out.push(byte as char),
+            _ => {
+                let _ = write!(out, "\\{:02X}", byte);
+            },
+        }
+    }
+    out + "\\00"
+}
+
+impl Generator<'_> {
+    /// 登记字符串常量，返回其全局名
+    fn string(&mut self, text: &str) -> String {
+        let index = self.strings.iter().position(|s| s == text).unwrap_or_else(|| {
+            self.strings.push(text.to_string());
+            self.strings.len() - 1
+        });
+        format!("@.str.{}", index)
+    }
+
+    /// 新的临时值或标签编号
+    fn fresh(&mut self) -> usize {
+        self.next += 1;
+        self.next
+    }
+
+    /// 报告错误并退出的指令序列；args是dprintf的附加参数(含前导逗号)
+    fn fail(&mut self, code: &str, message: &str, args: &str) -> String {
+        let format = self.string(&format!("\nerror[{}]: {}\n", code, message));
+        format!(
+            "  call i32 @fflush(ptr null)
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr {}{})
+  call void @exit(i32 1)
+  unreachable
+",
+            format, args
+        )
+    }
+
+    /// 输入耗尽时的处理；cell是当前单元格的指针
+    fn eof_statement(&mut self, cell: &str) -> String {
+        match self.unit.eof {
+            EofBehavior::Zero => format!("  store i8 0, ptr {}\n  ret void\n", cell),
+            EofBehavior::Unchanged => "  ret void\n".to_string(),
+            EofBehavior::Max => format!("  store i8 -1, ptr {}\n  ret void\n", cell),
+            EofBehavior::Error => self.fail("E0101", "Input instruction reached end of input", ""),
+        }
+    }
+
+    /// 结果超出0..=255时的处理；message与args是陷阱时的说明
+    fn overflow_statement(&mut self, cell: &str, saturated: i8, message: &str, args: &str) -> String {
+        match self.unit.overflow {
+            OverflowPolicy::Wrap => unreachable!("wrapping arithmetic is emitted inline"),
+            OverflowPolicy::Saturate => format!("  store i8 {}, ptr {}\n  ret void\n", saturated, cell),
+            OverflowPolicy::Trap => self.fail("E0107", message, args),
+        }
+    }
+
+    /// 全局状态、外部声明与辅助函数
+    fn prelude(&mut self) {
+        let size = self.unit.memory_size;
+        let mut out = String::new();
+        let _ = writeln!(out, "@tape0 = internal global [{} x i8] zeroinitializer", size);
+        if self.features.two_tapes {
+            let _ = writeln!(out, "@tape1 = internal global [{} x i8] zeroinitializer", size);
+            out.push_str("@other = internal global ptr @tape1\n@other_p = internal global i64 0\n");
+        }
+        out.push_str("@t = internal global ptr @tape0\n@p = internal global i64 0\n");
+        if self.features.stack {
+            let _ = writeln!(out, "@stack = internal global [{} x i8] zeroinitializer\n@sp = internal global i64 0", STACK_LIMIT);
+        }
+        if !self.unit.procedures.is_empty() {
+            out.push_str("@depth = internal global i32 0\n");
+        }
+        if self.features.random {
+            out.push_str("@rng = internal global i64 0\n");
+        }
+        if self.features.time {
+            out.push_str("@started = internal global i64 0\n");
+        }
+
+        out.push('\n');
+        let declarations = [
+            (self.features.output, "declare i32 @putchar(i32)"),
+            (self.unit.uses(|i| i == Instruction::OutputDecimal), "declare i32 @printf(ptr, ...)"),
+            (self.features.input || self.features.input_decimal, "declare i32 @getchar()"),
+            (true, "declare i32 @fflush(ptr)"),
+            (self.features.fail || self.features.debug, "declare i32 @dprintf(i32, ptr, ...)"),
+            (self.features.fail, "declare void @exit(i32)"),
+            (self.features.scan_right, "declare ptr @memchr(ptr, i32, i64)"),
+            (self.features.random, "declare i64 @time(ptr)\ndeclare i64 @clock()"),
+            (self.features.time, "declare i32 @timespec_get(ptr, i32)"),
+            (self.features.sleep, "declare i32 @nanosleep(ptr, ptr)"),
+        ];
+        for (used, declaration) in declarations {
+            if used {
+                let _ = writeln!(out, "{}", declaration);
+            }
+        }
+
+        out.push_str(
+            "
+define internal ptr @cell() alwaysinline {
+  %t = load ptr, ptr @t
+  %p = load i64, ptr @p
+  %c = getelementptr i8, ptr %t, i64 %p
+  ret ptr %c
+}
+",
+        );
+        if self.features.moves {
+            let _ = write!(
+                out,
+                "
+define internal void @right(i64 %n) {{
+  %p = load i64, ptr @p
+  %room = sub i64 {size}, %p
+  %fits = icmp ult i64 %n, %room
+  %moved = add i64 %p, %n
+  %q = select i1 %fits, i64 %moved, i64 {last}
+  store i64 %q, ptr @p
+  ret void
+}}
+
+define internal void @left(i64 %n) {{
+  %p = load i64, ptr @p
+  %fits = icmp ult i64 %n, %p
+  %moved = sub i64 %p, %n
+  %q = select i1 %fits, i64 %moved, i64 0
+  store i64 %q, ptr @p
+  ret void
+}}
+",
+                size = size,
+                last = size - 1
+            );
+        }
+        if self.features.checked_add {
+            let overflow = self.overflow_statement("%c", -1, "Cell overflow: %lu + %lu at cell %lu", ", i64 %v64, i64 %n, i64 %index");
+            let _ = write!(
+                out,
+                "
+define internal void @add(ptr %c, i64 %index, i64 %n) {{
+  %v = load i8, ptr %c
+  %v64 = zext i8 %v to i64
+  %room = sub i64 255, %v64
+  %fits = icmp ule i64 %n, %room
+  br i1 %fits, label %store, label %overflow
+store:
+  %n8 = trunc i64 %n to i8
+  %r = add i8 %v, %n8
+  store i8 %r, ptr %c
+  ret void
+overflow:
+{}}}
+",
+                overflow
+            );
+        }
+        if self.features.checked_sub {
+            let overflow = self.overflow_statement("%c", 0, "Cell underflow: %lu - %lu at cell %lu", ", i64 %v64, i64 %n, i64 %index");
+            let _ = write!(
+                out,
+                "
+define internal void @sub(ptr %c, i64 %index, i64 %n) {{
+  %v = load i8, ptr %c
+  %v64 = zext i8 %v to i64
+  %fits = icmp ule i64 %n, %v64
+  br i1 %fits, label %store, label %overflow
+store:
+  %n8 = trunc i64 %n to i8
+  %r = sub i8 %v, %n8
+  store i8 %r, ptr %c
+  ret void
+overflow:
+{}}}
+",
+                overflow
+            );
+        }
+        if self.features.input {
+            let eof = self.eof_statement("%c");
+            let _ = write!(
+                out,
+                "
+define internal void @input() {{
+  call i32 @fflush(ptr null)
+  %byte = call i32 @getchar()
+  %c = call ptr @cell()
+  %eof = icmp slt i32 %byte, 0
+  br i1 %eof, label %end, label %store
+store:
+  %b = trunc i32 %byte to i8
+  store i8 %b, ptr %c
+  ret void
+end:
+{}}}
+",
+                eof
+            );
+        }
+        if self.features.input_decimal {
+            let eof = self.eof_statement("%c");
+            let invalid = self.fail("E0113", "Expected a decimal number, found '%c'", ", i32 %byte");
+            let number = match self.unit.overflow {
+                OverflowPolicy::Wrap => "  %n8 = trunc i64 %n to i8\n  store i8 %n8, ptr %c\n  ret void\n".to_string(),
+                _ => format!(
+                    "  %fits = icmp ule i64 %n, 255
+  br i1 %fits, label %store, label %overflow
+store:
+  %n8 = trunc i64 %n to i8
+  store i8 %n8, ptr %c
+  ret void
+overflow:
+  %p = load i64, ptr @p
+{}",
+                    self.overflow_statement("%c", -1, "Cell overflow: 0 + %lu at cell %lu", ", i64 %n, i64 %p")
+                ),
+            };
+            let _ = write!(
+                out,
+                "
+; skip leading whitespace, read digits; the first character after the number is consumed
+define internal void @input_decimal() {{
+entry:
+  call i32 @fflush(ptr null)
+  br label %skip
+skip:
+  %first = call i32 @getchar()
+  %space = icmp eq i32 %first, 32
+  %control = sub i32 %first, 9
+  %in_range = icmp ult i32 %control, 5
+  %vertical_tab = icmp eq i32 %first, 11
+  %not_vt = xor i1 %vertical_tab, true
+  %control_ws = and i1 %in_range, %not_vt
+  %whitespace = or i1 %space, %control_ws
+  br i1 %whitespace, label %skip, label %digits
+digits:
+  %byte = phi i32 [ %first, %skip ], [ %following, %digit ]
+  %n = phi i64 [ 0, %skip ], [ %saturated, %digit ]
+  %any = phi i1 [ false, %skip ], [ true, %digit ]
+  %d = sub i32 %byte, 48
+  %is_digit = icmp ult i32 %d, 10
+  br i1 %is_digit, label %digit, label %done
+digit:
+  %d64 = zext i32 %d to i64
+  %n10 = mul i64 %n, 10
+  %sum = add i64 %n10, %d64
+  %big = icmp ugt i64 %sum, 4294967295
+  %saturated = select i1 %big, i64 4294967295, i64 %sum
+  %following = call i32 @getchar()
+  br label %digits
+done:
+  %c = call ptr @cell()
+  br i1 %any, label %number, label %missing
+missing:
+  %eof = icmp slt i32 %byte, 0
+  br i1 %eof, label %end, label %invalid
+end:
+{}invalid:
+{}number:
+{}}}
+",
+                eof, invalid, number
+            );
+        }
+        if self.unit.uses(|i| i == Instruction::AddNext) {
+            let add = match self.unit.overflow {
+                OverflowPolicy::Wrap => "  %w = load i8, ptr %next\n  %r = add i8 %w, %v\n  store i8 %r, ptr %next\n".to_string(),
+                _ => "  %v64 = zext i8 %v to i64\n  %q = add i64 %p, 1\n  call void @add(ptr %next, i64 %q, i64 %v64)\n".to_string(),
+            };
+            let _ = write!(
+                out,
+                "
+; the value is dropped at the high edge
+define internal void @add_next() {{
+entry:
+  %p = load i64, ptr @p
+  %c = call ptr @cell()
+  %v = load i8, ptr %c
+  %inside = icmp ult i64 %p, {}
+  br i1 %inside, label %add, label %clear
+add:
+  %next = getelementptr i8, ptr %c, i64 1
+{}  br label %clear
+clear:
+  store i8 0, ptr %c
+  ret void
+}}
+",
+                size - 1,
+                add
+            );
+        }
+        if self.features.stack {
+            let overflow = self.fail("E0110", &format!("Stack overflow: more than {} values pushed", STACK_LIMIT), "");
+            let empty = self.fail("E0109", "Pop from an empty stack", "");
+            let _ = write!(
+                out,
+                "
+define internal void @push() {{
+entry:
+  %sp = load i64, ptr @sp
+  %full = icmp eq i64 %sp, {}
+  br i1 %full, label %overflow, label %push
+overflow:
+{}push:
+  %c = call ptr @cell()
+  %v = load i8, ptr %c
+  %slot = getelementptr i8, ptr @stack, i64 %sp
+  store i8 %v, ptr %slot
+  %next = add i64 %sp, 1
+  store i64 %next, ptr @sp
+  ret void
+}}
+
+define internal void @pop() {{
+entry:
+  %sp = load i64, ptr @sp
+  %none = icmp eq i64 %sp, 0
+  br i1 %none, label %empty, label %pop
+empty:
+{}pop:
+  %top = sub i64 %sp, 1
+  store i64 %top, ptr @sp
+  %slot = getelementptr i8, ptr @stack, i64 %top
+  %v = load i8, ptr %slot
+  %c = call ptr @cell()
+  store i8 %v, ptr %c
+  ret void
+}}
+",
+                STACK_LIMIT, overflow, empty
+            );
+        }
+        if self.features.two_tapes {
+            out.push_str(
+                "
+define internal void @switch_tape() {
+  %t = load ptr, ptr @t
+  %other = load ptr, ptr @other
+  store ptr %other, ptr @t
+  store ptr %t, ptr @other
+  %p = load i64, ptr @p
+  %q = load i64, ptr @other_p
+  store i64 %q, ptr @p
+  store i64 %p, ptr @other_p
+  ret void
+}
+
+define internal void @exchange() {
+  %c = call ptr @cell()
+  %other = load ptr, ptr @other
+  %q = load i64, ptr @other_p
+  %d = getelementptr i8, ptr %other, i64 %q
+  %v = load i8, ptr %c
+  %w = load i8, ptr %d
+  store i8 %w, ptr %c
+  store i8 %v, ptr %d
+  ret void
+}
+",
+            );
+        }
+        if self.features.random {
+            out.push_str(
+                "
+; SplitMix64, the same generator as the interpreter
+define internal i8 @random_byte() {
+  %s = load i64, ptr @rng
+  %state = add i64 %s, -7046029254386353131
+  store i64 %state, ptr @rng
+  %a1 = lshr i64 %state, 30
+  %a2 = xor i64 %state, %a1
+  %a = mul i64 %a2, -4658895280553007687
+  %b1 = lshr i64 %a, 27
+  %b2 = xor i64 %a, %b1
+  %b = mul i64 %b2, -7723592293110705685
+  %c1 = lshr i64 %b, 31
+  %c = xor i64 %b, %c1
+  %high = lshr i64 %c, 56
+  %byte = trunc i64 %high to i8
+  ret i8 %byte
+}
+",
+            );
+        }
+        if self.features.time {
+            let _ = write!(
+                out,
+                "
+define internal i64 @now_ms() {{
+  %ts = alloca {{ i64, i64 }}
+  call i32 @timespec_get(ptr %ts, i32 1)
+  %sec_ptr = getelementptr {{ i64, i64 }}, ptr %ts, i32 0, i32 0
+  %nsec_ptr = getelementptr {{ i64, i64 }}, ptr %ts, i32 0, i32 1
+  %sec = load i64, ptr %sec_ptr
+  %nsec = load i64, ptr %nsec_ptr
+  %ms = mul i64 %sec, 1000
+  %extra = sdiv i64 %nsec, 1000000
+  %total = add i64 %ms, %extra
+  ret i64 %total
+}}
+
+; little-endian milliseconds since start; bytes past the high edge are dropped
+define internal void @write_time() {{
+entry:
+  %now = call i64 @now_ms()
+  %start = load i64, ptr @started
+  %elapsed = sub i64 %now, %start
+  %p = load i64, ptr @p
+  %c = call ptr @cell()
+  br label %byte
+byte:
+  %i = phi i64 [ 0, %entry ], [ %next, %store ]
+  %ms = phi i64 [ %elapsed, %entry ], [ %shifted, %store ]
+  %more = icmp ult i64 %i, {}
+  %index = add i64 %p, %i
+  %inside = icmp ult i64 %index, {}
+  %go = and i1 %more, %inside
+  br i1 %go, label %store, label %done
+store:
+  %b = trunc i64 %ms to i8
+  %slot = getelementptr i8, ptr %c, i64 %i
+  store i8 %b, ptr %slot
+  %shifted = lshr i64 %ms, 8
+  %next = add i64 %i, 1
+  br label %byte
+done:
+  ret void
+}}
+",
+                TIME_BYTES, size
+            );
+        }
+        if self.features.sleep {
+            out.push_str(
+                "
+define internal void @sleep_ms(i8 %v) {
+  %ts = alloca { i64, i64 }
+  %ms = zext i8 %v to i64
+  %sec = udiv i64 %ms, 1000
+  %rest = urem i64 %ms, 1000
+  %nsec = mul i64 %rest, 1000000
+  %sec_ptr = getelementptr { i64, i64 }, ptr %ts, i32 0, i32 0
+  %nsec_ptr = getelementptr { i64, i64 }, ptr %ts, i32 0, i32 1
+  store i64 %sec, ptr %sec_ptr
+  store i64 %nsec, ptr %nsec_ptr
+  call i32 @fflush(ptr null)
+  call i32 @nanosleep(ptr %ts, ptr null)
+  ret void
+}
+",
+            );
+        }
+        if self.unit.uses(|i| i == Instruction::ScanLeft) {
+            out.push_str(
+                "
+define internal void @scan_left() {
+entry:
+  %t = load ptr, ptr @t
+  %start = load i64, ptr @p
+  br label %scan
+scan:
+  %p = phi i64 [ %start, %entry ], [ %prev, %step ]
+  store i64 %p, ptr @p
+  %at_edge = icmp eq i64 %p, 0
+  br i1 %at_edge, label %done, label %test
+test:
+  %c = getelementptr i8, ptr %t, i64 %p
+  %v = load i8, ptr %c
+  %zero = icmp eq i8 %v, 0
+  br i1 %zero, label %done, label %step
+step:
+  %prev = sub i64 %p, 1
+  br label %scan
+done:
+  ret void
+}
+",
+            );
+        }
+        if self.features.scan_right {
+            let _ = write!(
+                out,
+                "
+define internal void @scan_right() {{
+entry:
+  %t = load ptr, ptr @t
+  %p = load i64, ptr @p
+  %c = getelementptr i8, ptr %t, i64 %p
+  %len = sub i64 {}, %p
+  %zero = call ptr @memchr(ptr %c, i32 0, i64 %len)
+  %found = icmp ne ptr %zero, null
+  br i1 %found, label %found_zero, label %edge
+found_zero:
+  %z = ptrtoint ptr %zero to i64
+  %base = ptrtoint ptr %t to i64
+  %q = sub i64 %z, %base
+  store i64 %q, ptr @p
+  ret void
+edge:
+  store i64 {}, ptr @p
+  ret void
+}}
+",
+                size,
+                size - 1
+            );
+        }
+        if self.features.debug {
+            let printable = self.string("[@] tape=%d pointer=%lu value=%u hex=0x%02x char='%c'\n");
+            let other = self.string("[@] tape=%d pointer=%lu value=%u hex=0x%02x char=-\n");
+            let tape = if self.features.two_tapes {
+                "  %t = load ptr, ptr @t\n  %second = icmp eq ptr %t, @tape1\n  %tape = zext i1 %second to i32\n"
+            } else {
+                "  %tape = add i32 0, 0\n"
+            };
+            let _ = write!(
+                out,
+                "
+define internal void @debug() {{
+entry:
+{}  %p = load i64, ptr @p
+  %c = call ptr @cell()
+  %v = load i8, ptr %c
+  %v32 = zext i8 %v to i32
+  call i32 @fflush(ptr null)
+  %offset = sub i32 %v32, 32
+  %is_printable = icmp ult i32 %offset, 95
+  br i1 %is_printable, label %printable, label %other
+printable:
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr {}, i32 %tape, i64 %p, i32 %v32, i32 %v32, i32 %v32)
+  ret void
+other:
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr {}, i32 %tape, i64 %p, i32 %v32, i32 %v32)
+  ret void
+}}
+",
+                tape, printable, other
+            );
+        }
+        if self.unit.uses(|i| i == Instruction::CallCell) {
+            let undefined = self.fail("E0112", "Call to undefined procedure %u", ", i32 %v32");
+            out.push_str("\ndefine internal void @call_cell() {\nentry:\n  %c = call ptr @cell()\n  %v = load i8, ptr %c\n");
+            out.push_str("  switch i8 %v, label %undefined [");
+            for n in 0..self.unit.procedures.len() {
+                let _ = write!(out, " i8 {}, label %case{}", n, n);
+            }
+            out.push_str(" ]\n");
+            for n in 0..self.unit.procedures.len() {
+                let _ = writeln!(out, "case{}:\n  call void @proc_{}()\n  ret void", n, n);
+            }
+            let _ = write!(out, "undefined:\n  %v32 = zext i8 %v to i32\n{}}}\n", undefined);
+        }
+        self.out.push_str(&out);
+    }
+
+    /// 一条非循环指令对应的IR
+    fn statement(&mut self, instruction: Instruction) {
+        let wrap = self.unit.overflow == OverflowPolicy::Wrap;
+        let size = self.unit.memory_size;
+        let n = self.fresh();
+        let code = match instruction {
+            Instruction::Increment | Instruction::Add(_) | Instruction::Decrement | Instruction::Sub(_) if wrap => {
+                let op = if matches!(instruction, Instruction::Increment | Instruction::Add(_)) { "add" } else { "sub" };
+                format!(
+                    "  %c{n} = call ptr @cell()\n  %v{n} = load i8, ptr %c{n}\n  %r{n} = {} i8 %v{n}, {}\n  store i8 %r{n}, ptr %c{n}\n",
+                    op,
+                    (instruction.count() % 256) as u8 as i8,
+                )
+            },
+            Instruction::Increment | Instruction::Add(_) | Instruction::Decrement | Instruction::Sub(_) => {
+                let helper = if matches!(instruction, Instruction::Increment | Instruction::Add(_)) { "add" } else { "sub" };
+                format!(
+                    "  %c{n} = call ptr @cell()\n  %p{n} = load i64, ptr @p\n  call void @{}(ptr %c{n}, i64 %p{n}, i64 {})\n",
+                    helper,
+                    instruction.count(),
+                )
+            },
+            Instruction::Right | Instruction::MoveRight(_) => format!("  call void @right(i64 {})\n", instruction.count()),
+            Instruction::Left | Instruction::MoveLeft(_) => format!("  call void @left(i64 {})\n", instruction.count()),
+            Instruction::Output => {
+                format!("  %c{n} = call ptr @cell()\n  %v{n} = load i8, ptr %c{n}\n  %w{n} = zext i8 %v{n} to i32\n  call i32 @putchar(i32 %w{n})\n")
+            },
+            Instruction::OutputDecimal => {
+                let format = self.string("%u");
+                format!(
+                    "  %c{n} = call ptr @cell()\n  %v{n} = load i8, ptr %c{n}\n  %w{n} = zext i8 %v{n} to i32\n  call i32 (ptr, ...) @printf(ptr {}, i32 %w{n})\n",
+                    format
+                )
+            },
+            Instruction::Input => "  call void @input()\n".to_string(),
+            Instruction::InputDecimal => "  call void @input_decimal()\n".to_string(),
+            Instruction::Zero => format!("  %c{n} = call ptr @cell()\n  store i8 0, ptr %c{n}\n"),
+            Instruction::Set(value) => format!("  %c{n} = call ptr @cell()\n  store i8 {}, ptr %c{n}\n", value as i8),
+            Instruction::Copy | Instruction::CopyLeft => {
+                let (test, offset) = if instruction == Instruction::Copy {
+                    (format!("icmp ult i64 %p{n}, {}", size - 1), 1)
+                } else {
+                    (format!("icmp ugt i64 %p{n}, 0"), -1)
+                };
+                format!(
+                    "  %p{n} = load i64, ptr @p
+  %ok{n} = {}
+  br i1 %ok{n}, label %copy{n}, label %copied{n}
+copy{n}:
+  %c{n} = call ptr @cell()
+  %v{n} = load i8, ptr %c{n}
+  %d{n} = getelementptr i8, ptr %c{n}, i64 {}
+  store i8 %v{n}, ptr %d{n}
+  br label %copied{n}
+copied{n}:
+",
+                    test, offset
+                )
+            },
+            Instruction::AddNext => "  call void @add_next()\n".to_string(),
+            Instruction::MoveHigh => format!("  store i64 {}, ptr @p\n", size - 1),
+            Instruction::MoveLow => "  store i64 0, ptr @p\n".to_string(),
+            Instruction::Push => "  call void @push()\n".to_string(),
+            Instruction::Pop => "  call void @pop()\n".to_string(),
+            Instruction::SwitchTape => "  call void @switch_tape()\n".to_string(),
+            Instruction::Exchange => "  call void @exchange()\n".to_string(),
+            Instruction::Call(procedure) => format!("  call void @proc_{}()\n", procedure),
+            Instruction::CallCell => "  call void @call_cell()\n".to_string(),
+            Instruction::Random => format!("  %r{n} = call i8 @random_byte()\n  %c{n} = call ptr @cell()\n  store i8 %r{n}, ptr %c{n}\n"),
+            Instruction::Time => "  call void @write_time()\n".to_string(),
+            Instruction::Sleep => format!("  %c{n} = call ptr @cell()\n  %v{n} = load i8, ptr %c{n}\n  call void @sleep_ms(i8 %v{n})\n"),
+            Instruction::ScanLeft => "  call void @scan_left()\n".to_string(),
+            Instruction::ScanRight => "  call void @scan_right()\n".to_string(),
+            Instruction::Debug => "  call void @debug()\n".to_string(),
+            Instruction::Custom(_) | Instruction::HostCall => unreachable!("host extensions are rejected by compile::render"),
+            Instruction::Assert(_) => unreachable!("assertions are dropped by Unit::new"),
+            Instruction::JumpIfZero | Instruction::JumpIfNotZero | Instruction::ProcStart | Instruction::ProcEnd => {
+                unreachable!("structural instructions are emitted by block()")
+            },
+        };
+        self.out.push_str(&code);
+    }
+
+    /// 输出一段指令序列，循环翻译为条件跳转的基本块
+    fn block(&mut self, instructions: &[Instruction]) {
+        let mut loops = Vec::new();
+        for &instruction in instructions {
+            match instruction {
+                Instruction::JumpIfZero => {
+                    let n = self.fresh();
+                    let _ = write!(
+                        self.out,
+                        "  br label %loop{n}
+loop{n}:
+  %c{n} = call ptr @cell()
+  %v{n} = load i8, ptr %c{n}
+  %z{n} = icmp eq i8 %v{n}, 0
+  br i1 %z{n}, label %end{n}, label %body{n}
+body{n}:
+"
+                    );
+                    loops.push(n);
+                },
+                Instruction::JumpIfNotZero => {
+                    let n = loops.pop().expect("compiled brackets are matched");
+                    let _ = write!(self.out, "  br label %loop{n}\nend{n}:\n");
+                },
+                _ => self.statement(instruction),
+            }
+        }
+    }
+}
+
+/// 生成完整的LLVM IR模块
+pub fn render(unit: &Unit, source_name: &str) -> String {
+    let mut generator = Generator { unit, features: Features::new(unit), strings: Vec::new(), out: String::new(), next: 0 };
+    generator.prelude();
+
+    for (n, body) in unit.procedures.iter().enumerate() {
+        let overflow = generator.fail("E0111", &format!("Call stack overflow: more than {} nested calls", CALL_DEPTH_LIMIT), "");
+        let _ = write!(
+            generator.out,
+            "
+define internal void @proc_{}() {{
+entry:
+  %depth = load i32, ptr @depth
+  %full = icmp eq i32 %depth, {}
+  br i1 %full, label %overflow, label %enter
+overflow:
+{}enter:
+  %deeper = add i32 %depth, 1
+  store i32 %deeper, ptr @depth
+",
+            n, CALL_DEPTH_LIMIT, overflow
+        );
+        generator.block(body);
+        generator.out.push_str("  %after = load i32, ptr @depth\n  %shallower = sub i32 %after, 1\n  store i32 %shallower, ptr @depth\n  ret void\n}\n");
+    }
+
+    generator.out.push_str("\ndefine i32 @main() {\nentry:\n");
+    if generator.features.random {
+        generator.out.push_str(
+            "  %now = call i64 @time(ptr null)\n  %ticks = call i64 @clock()\n  %high = shl i64 %ticks, 32\n  %seed = xor i64 %now, %high\n  store i64 %seed, ptr @rng\n",
+        );
+    }
+    if generator.features.time {
+        generator.out.push_str("  %start = call i64 @now_ms()\n  store i64 %start, ptr @started\n");
+    }
+    generator.block(&unit.main);
+    generator.out.push_str("  call i32 @fflush(ptr null)\n  ret i32 0\n}\n");
+
+    let mut out = String::new();
+    let _ = writeln!(out, "; Generated by `derstand compile --target llvm` from {}.\n", source_name);
+    for (index, text) in generator.strings.iter().enumerate() {
+        let _ = writeln!(out, "@.str.{} = private unnamed_addr constant [{} x i8] c\"{}\"", index, text.len() + 1, c_string(text));
+    }
+    out.push_str(&generator.out);
+    out
+}
@@ -0,0 +1,452 @@
+//! Rust后端 - 生成只依赖标准库的单文件`main.rs`，可直接用`rustc -O`编译
+//!
+//! 机器状态是一个结构体，主程序与每个过程都是它的方法；只生成程序实际用到的字段与方法。
+
+use std::fmt::Write;
+
+use super::{Features, Unit};
+use crate::{CALL_DEPTH_LIMIT, EofBehavior, Instruction, OverflowPolicy, STACK_LIMIT, Span, TIME_BYTES};
+
+/// 输入耗尽时的处理表达式
+fn eof_expression(eof: EofBehavior) -> &'static str {
+    match eof {
+        EofBehavior::Zero => "self.t[self.p] = 0",
+        EofBehavior::Unchanged => "{}",
+        EofBehavior::Max => "self.t[self.p] = 255",
+        EofBehavior::Error => "self.fail(\"E0101\", \"Input instruction reached end of input\".to_string())",
+    }
+}
+
+/// 结果超出0..=255时的处理表达式；cell是单元格下标的表达式，message是说明的format!参数
+fn overflow_expression(policy: OverflowPolicy, cell: &str, saturated: u8, message: &str) -> String {
+    match policy {
+        OverflowPolicy::Wrap => unreachable!("wrapping arithmetic is emitted inline"),
+        OverflowPolicy::Saturate => format!("self.t[{}] = {}", cell, saturated),
+        OverflowPolicy::Trap => format!("self.fail(\"E0107\", format!({}))", message),
+    }
+}
+
+/// 状态结构体与辅助方法
+fn prelude(out: &mut String, unit: &Unit, features: &Features, source_name: &str) {
+    let _ = writeln!(out, "const TAPE_SIZE: usize = {};", unit.memory_size);
+    if features.stack {
+        let _ = writeln!(out, "const STACK_LIMIT: usize = {};", STACK_LIMIT);
+    }
+    if !unit.procedures.is_empty() {
+        let _ = writeln!(out, "const CALL_DEPTH_LIMIT: usize = {};", CALL_DEPTH_LIMIT);
+    }
+    if features.fail {
+        let _ = writeln!(out, "const SOURCE_NAME: &str = {:?};", source_name);
+    }
+
+    // 字段与初始值
+    let mut fields = vec![("t", "Vec<u8>", "vec![0; TAPE_SIZE]"), ("p", "usize", "0")];
+    if features.two_tapes {
+        fields.extend([("other", "Vec<u8>", "vec![0; TAPE_SIZE]"), ("other_p", "usize", "0"), ("active", "usize", "0")]);
+    }
+    if features.stack {
+        fields.push(("stack", "Vec<u8>", "Vec::new()"));
+    }
+    if !unit.procedures.is_empty() {
+        fields.push(("depth", "usize", "0"));
+    }
+    if features.random {
+        fields.push((
+            "rng",
+            "u64",
+            "SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64)",
+        ));
+    }
+    if features.time {
+        fields.push(("started", "Instant", "Instant::now()"));
+    }
+    if features.fail {
+        fields.push(("at", "(usize, usize)", "(0, 0)")); // 可能报错的指令的源码行列
+    }
+    fields.push(("out", "BufWriter<Stdout>", "BufWriter::new(io::stdout())"));
+    if features.input || features.input_decimal {
+        fields.push(("input", "StdinLock<'static>", "io::stdin().lock()"));
+    }
+    out.push_str("\nstruct State {\n");
+    for (name, ty, _) in &fields {
+        let _ = writeln!(out, "    {}: {},", name, ty);
+    }
+    out.push_str("}\n\nimpl State {\n    fn new() -> Self {\n        State {\n");
+    for (name, _, init) in &fields {
+        let _ = writeln!(out, "            {}: {},", name, init);
+    }
+    out.push_str("        }\n    }\n");
+
+    if features.output {
+        out.push_str(
+            "
+    fn output(&mut self, bytes: &[u8]) {
+        let _ = self.out.write_all(bytes);
+    }
+",
+        );
+    }
+    if features.fail {
+        out.push_str(
+            "
+    fn fail(&mut self, code: &str, message: String) -> ! {
+        let _ = self.out.flush();
+        eprintln!(\"\\nerror[{}]: {}\\n --> {}:{}:{}\", code, message, SOURCE_NAME, self.at.0, self.at.1);
+        process::exit(1);
+    }
+",
+        );
+    }
+    if features.moves {
+        out.push_str(
+            "
+    fn right(&mut self, n: usize) {
+        self.p = if n < TAPE_SIZE - self.p { self.p + n } else { TAPE_SIZE - 1 };
+    }
+
+    fn left(&mut self, n: usize) {
+        self.p = self.p.saturating_sub(n);
+    }
+",
+        );
+    }
+    if features.checked_add {
+        let _ = write!(
+            out,
+            "
+    fn add(&mut self, cell: usize, n: u32) {{
+        match u8::try_from(n).ok().and_then(|n| self.t[cell].checked_add(n)) {{
+            Some(value) => self.t[cell] = value,
+            None => {},
+        }}
+    }}
+",
+            overflow_expression(unit.overflow, "cell", 255, "\"Cell overflow: {} + {} at cell {}\", self.t[cell], n, cell"),
+        );
+    }
+    if features.checked_sub {
+        let _ = write!(
+            out,
+            "
+    fn sub(&mut self, cell: usize, n: u32) {{
+        match u8::try_from(n).ok().and_then(|n| self.t[cell].checked_sub(n)) {{
+            Some(value) => self.t[cell] = value,
+            None => {},
+        }}
+    }}
+",
+            overflow_expression(unit.overflow, "cell", 0, "\"Cell underflow: {} - {} at cell {}\", self.t[cell], n, cell"),
+        );
+    }
+    if features.input || features.input_decimal {
+        out.push_str(
+            "
+    fn read_byte(&mut self) -> Option<u8> {
+        let _ = self.out.flush();
+        let mut byte = [0u8];
+        match self.input.read(&mut byte) {
+            Ok(1) => Some(byte[0]),
+            _ => None,
+        }
+    }
+",
+        );
+    }
+    if features.input {
+        let _ = write!(
+            out,
+            "
+    fn input(&mut self) {{
+        match self.read_byte() {{
+            Some(byte) => self.t[self.p] = byte,
+            None => {},
+        }}
+    }}
+",
+            eof_expression(unit.eof)
+        );
+    }
+    if features.input_decimal {
+        let overflow = match unit.overflow {
+            OverflowPolicy::Wrap => "self.t[self.p] = (n % 256) as u8".to_string(),
+            policy => overflow_expression(policy, "self.p", 255, "\"Cell overflow: 0 + {} at cell {}\", n, self.p"),
+        };
+        let _ = write!(
+            out,
+            "
+    /// Skips leading whitespace and reads digits; the first byte after the number is consumed.
+    fn input_decimal(&mut self) {{
+        let mut byte = self.read_byte();
+        while byte.is_some_and(|b| b.is_ascii_whitespace()) {{
+            byte = self.read_byte();
+        }}
+        let mut number = None;
+        while let Some(digit) = byte.filter(u8::is_ascii_digit) {{
+            number = Some(number.unwrap_or(0u32).saturating_mul(10).saturating_add((digit - b'0') as u32));
+            byte = self.read_byte();
+        }}
+        match (number, byte) {{
+            (Some(n), _) => match u8::try_from(n) {{
+                Ok(value) => self.t[self.p] = value,
+                Err(_) => {},
+            }},
+            (None, None) => {},
+            (None, Some(found)) => self.fail(\"E0113\", format!(\"Expected a decimal number, found {{:?}}\", found as char)),
+        }}
+    }}
+",
+            overflow,
+            eof_expression(unit.eof)
+        );
+    }
+    if unit.uses(|i| i == Instruction::AddNext) {
+        let add = match unit.overflow {
+            OverflowPolicy::Wrap => "self.t[self.p + 1] = self.t[self.p + 1].wrapping_add(self.t[self.p]);",
+            _ => "self.add(self.p + 1, self.t[self.p] as u32);",
+        };
+        let _ = write!(
+            out,
+            "
+    /// The value is dropped at the high edge.
+    fn add_next(&mut self) {{
+        if self.p + 1 < TAPE_SIZE {{
+            {}
+        }}
+        self.t[self.p] = 0;
+    }}
+",
+            add
+        );
+    }
+    if features.stack {
+        out.push_str(
+            "
+    fn push(&mut self) {
+        if self.stack.len() == STACK_LIMIT {
+            self.fail(\"E0110\", format!(\"Stack overflow: more than {} values pushed\", STACK_LIMIT));
+        }
+        self.stack.push(self.t[self.p]);
+    }
+
+    fn pop(&mut self) {
+        match self.stack.pop() {
+            Some(value) => self.t[self.p] = value,
+            None => self.fail(\"E0109\", \"Pop from an empty stack\".to_string()),
+        }
+    }
+",
+        );
+    }
+    if features.two_tapes {
+        out.push_str(
+            "
+    fn switch_tape(&mut self) {
+        std::mem::swap(&mut self.t, &mut self.other);
+        std::mem::swap(&mut self.p, &mut self.other_p);
+        self.active ^= 1;
+    }
+
+    fn exchange(&mut self) {
+        std::mem::swap(&mut self.t[self.p], &mut self.other[self.other_p]);
+    }
+",
+        );
+    }
+    if features.random {
+        out.push_str(
+            "
+    /// SplitMix64, the same generator as the interpreter.
+    fn random_byte(&mut self) -> u8 {
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        ((z ^ (z >> 31)) >> 56) as u8
+    }
+",
+        );
+    }
+    if features.time {
+        let _ = write!(
+            out,
+            "
+    /// Little-endian milliseconds since start; bytes past the high edge are dropped.
+    fn write_time(&mut self) {{
+        let bytes = (self.started.elapsed().as_millis() as u64).to_le_bytes();
+        let fit = (TAPE_SIZE - self.p).min({});
+        self.t[self.p..self.p + fit].copy_from_slice(&bytes[..fit]);
+    }}
+",
+            TIME_BYTES
+        );
+    }
+    if features.sleep {
+        out.push_str(
+            "
+    fn sleep(&mut self) {
+        let _ = self.out.flush();
+        thread::sleep(Duration::from_millis(self.t[self.p] as u64));
+    }
+",
+        );
+    }
+    if unit.uses(|i| i == Instruction::ScanLeft) {
+        out.push_str(
+            "
+    fn scan_left(&mut self) {
+        self.p = self.t[..=self.p].iter().rposition(|&cell| cell == 0).unwrap_or(0);
+    }
+",
+        );
+    }
+    if features.scan_right {
+        out.push_str(
+            "
+    fn scan_right(&mut self) {
+        self.p = self.t[self.p..].iter().position(|&cell| cell == 0).map_or(TAPE_SIZE - 1, |offset| self.p + offset);
+    }
+",
+        );
+    }
+    if features.debug {
+        let _ = write!(
+            out,
+            "
+    fn debug(&mut self) {{
+        let _ = self.out.flush();
+        let v = self.t[self.p];
+        let shown = if v.is_ascii_graphic() || v == b' ' {{ format!(\"'{{}}'\", v as char) }} else {{ \"-\".to_string() }};
+        eprintln!(\"[@] tape={{}} pointer={{}} value={{}} hex=0x{{:02x}} char={{}}\", {}, self.p, v, v, shown);
+    }}
+",
+            if features.two_tapes { "self.active" } else { "0" }
+        );
+    }
+    if unit.uses(|i| i == Instruction::CallCell) {
+        out.push_str("\n    fn call_cell(&mut self) {\n        match self.t[self.p] {\n");
+        for n in 0..unit.procedures.len() {
+            let _ = writeln!(out, "            {} => self.proc_{}(),", n, n);
+        }
+        out.push_str("            n => self.fail(\"E0112\", format!(\"Call to undefined procedure {}\", n)),\n        }\n    }\n");
+    }
+}
+
+/// 一条非循环指令对应的Rust语句
+fn statement(instruction: Instruction, unit: &Unit) -> String {
+    let wrap = unit.overflow == OverflowPolicy::Wrap;
+    match instruction {
+        Instruction::Increment | Instruction::Add(_) if wrap => {
+            format!("self.t[self.p] = self.t[self.p].wrapping_add({});", instruction.count() % 256)
+        },
+        Instruction::Decrement | Instruction::Sub(_) if wrap => {
+            format!("self.t[self.p] = self.t[self.p].wrapping_sub({});", instruction.count() % 256)
+        },
+        Instruction::Increment | Instruction::Add(_) => format!("self.add(self.p, {});", instruction.count()),
+        Instruction::Decrement | Instruction::Sub(_) => format!("self.sub(self.p, {});", instruction.count()),
+        Instruction::Right | Instruction::MoveRight(_) => format!("self.right({});", instruction.count()),
+        Instruction::Left | Instruction::MoveLeft(_) => format!("self.left({});", instruction.count()),
+        Instruction::Output => "self.output(&[self.t[self.p]]);".to_string(),
+        Instruction::OutputDecimal => "self.output(self.t[self.p].to_string().as_bytes());".to_string(),
+        Instruction::Input => "self.input();".to_string(),
+        Instruction::InputDecimal => "self.input_decimal();".to_string(),
+        Instruction::Zero => "self.t[self.p] = 0;".to_string(),
+        Instruction::Set(value) => format!("self.t[self.p] = {};", value),
+        Instruction::Copy => "if self.p + 1 < TAPE_SIZE { self.t[self.p + 1] = self.t[self.p]; }".to_string(),
+        Instruction::CopyLeft => "if self.p > 0 { self.t[self.p - 1] = self.t[self.p]; }".to_string(),
+        Instruction::AddNext => "self.add_next();".to_string(),
+        Instruction::MoveHigh => "self.p = TAPE_SIZE - 1;".to_string(),
+        Instruction::MoveLow => "self.p = 0;".to_string(),
+        Instruction::Push => "self.push();".to_string(),
+        Instruction::Pop => "self.pop();".to_string(),
+        Instruction::SwitchTape => "self.switch_tape();".to_string(),
+        Instruction::Exchange => "self.exchange();".to_string(),
+        Instruction::Call(n) => format!("self.proc_{}();", n),
+        Instruction::CallCell => "self.call_cell();".to_string(),
+        Instruction::Random => "self.t[self.p] = self.random_byte();".to_string(),
+        Instruction::Time => "self.write_time();".to_string(),
+        Instruction::Sleep => "self.sleep();".to_string(),
+        Instruction::ScanLeft => "self.scan_left();".to_string(),
+        Instruction::ScanRight => "self.scan_right();".to_string(),
+        Instruction::Debug => "self.debug();".to_string(),
+        Instruction::Custom(_) | Instruction::HostCall => unreachable!("host extensions are rejected by compile::render"),
+        Instruction::Assert(_) => unreachable!("assertions are dropped by Unit::new"),
+        Instruction::JumpIfZero | Instruction::JumpIfNotZero | Instruction::ProcStart | Instruction::ProcEnd => {
+            unreachable!("structural instructions are emitted by method()")
+        },
+    }
+}
+
+/// 输出一个方法，循环翻译为while；每行注释源码位置，可能报错的语句先记录位置
+fn method(out: &mut String, name: &str, instructions: &[Instruction], spans: &[Span], unit: &Unit, procedure: bool) {
+    let _ = writeln!(out, "\n    fn {}(&mut self) {{", name);
+    if procedure {
+        out.push_str("        if self.depth == CALL_DEPTH_LIMIT {\n");
+        out.push_str("            self.fail(\"E0111\", format!(\"Call stack overflow: more than {} nested calls\", CALL_DEPTH_LIMIT));\n");
+        out.push_str("        }\n        self.depth += 1;\n");
+    }
+    let mut indent = 2;
+    for (&instruction, span) in instructions.iter().zip(spans) {
+        match instruction {
+            Instruction::JumpIfZero => {
+                let _ = writeln!(out, "{}while self.t[self.p] != 0 {{ // {}:{}", "    ".repeat(indent), span.line, span.column);
+                indent += 1;
+            },
+            Instruction::JumpIfNotZero => {
+                indent -= 1;
+                let _ = writeln!(out, "{}}} // {}:{}", "    ".repeat(indent), span.line, span.column);
+            },
+            _ if unit.can_fail(instruction) => {
+                let _ = writeln!(out, "{}self.at = ({}, {}); {}", "    ".repeat(indent), span.line, span.column, statement(instruction, unit));
+            },
+            _ => {
+                let _ = writeln!(out, "{}{} // {}:{}", "    ".repeat(indent), statement(instruction, unit), span.line, span.column);
+            },
+        }
+    }
+    if procedure {
+        out.push_str("        self.depth -= 1;\n");
+    }
+    out.push_str("    }\n");
+}
+
+/// 生成完整的Rust源码
+pub fn render(unit: &Unit, source_name: &str) -> String {
+    let features = Features::new(unit);
+    let mut out = String::new();
+    let _ = writeln!(out, "// Generated by `derstand compile --target rust` from {}.", source_name);
+    out.push_str("// Comments and `self.at` give the line:column of each statement in the source.\n");
+    let mut imports = vec!["BufWriter", "Stdout", "Write"];
+    if features.input || features.input_decimal {
+        imports.extend(["Read", "StdinLock"]);
+    }
+    imports.sort_unstable();
+    let _ = writeln!(out, "\nuse std::io::{{self, {}}};", imports.join(", "));
+    if features.fail {
+        out.push_str("use std::process;\n");
+    }
+    if features.sleep {
+        out.push_str("use std::thread;\n");
+    }
+    let times: Vec<&str> = [(features.sleep, "Duration"), (features.time, "Instant"), (features.random, "SystemTime"), (features.random, "UNIX_EPOCH")]
+        .into_iter()
+        .filter_map(|(used, name)| used.then_some(name))
+        .collect();
+    match times.as_slice() {
+        [] => {},
+        [name] => {
+            let _ = writeln!(out, "use std::time::{};", name);
+        },
+        names => {
+            let _ = writeln!(out, "use std::time::{{{}}};", names.join(", "));
+        },
+    }
+    out.push('\n');
+    prelude(&mut out, unit, &features, source_name);
+
+    method(&mut out, "run", &unit.main, &unit.main_spans, unit, false);
+    for (n, (body, spans)) in unit.procedures.iter().zip(&unit.procedure_spans).enumerate() {
+        method(&mut out, &format!("proc_{}", n), body, spans, unit, true);
+    }
+    out.push_str("}\n\nfn main() {\n    let mut state = State::new();\n    state.run();\n    let _ = state.out.flush();\n}\n");
+    out
+}
@@ -0,0 +1,484 @@
+//! WebAssembly后端 - 生成WAT文本格式的模块，可用`wat2wasm`等工具转换为二进制
+//!
+//! 线性内存依次存放纸带(用到`|`时两条)与栈，导出为`memory`；入口导出为`run`。
+//! 输入输出与时间由宿主以`env`模块中的函数提供，只导入程序实际用到的函数：
+//!
+//! ```text
+//! output(byte: i32)          写一个字节
+//! input() -> i32             读一个字节，输入耗尽时返回-1
+//! fail(code, line, column: i32) 报告运行时错误(如107表示E0107)与出错指令的源码行列，随后模块执行unreachable
+//! random_seed() -> i64       `*`的随机数种子
+//! now_ms() -> i64            毫秒时钟，起点任意
+//! sleep_ms(ms: i32)          睡眠
+//! debug(tape, pointer, value: i32) `@`的调试输出
+//! ```
+//!
+//! 函数体中每条指令后的注释(`;; 3:7`)给出它来自的源码行列。
+
+use std::fmt::Write;
+
+use super::{Features, Unit};
+use crate::{CALL_DEPTH_LIMIT, EofBehavior, Instruction, OverflowPolicy, STACK_LIMIT, Span, TIME_BYTES};
+
+/// 线性内存的页大小
+const PAGE_SIZE: usize = 65536;
+
+/// 当前单元格(加偏移)的地址表达式
+fn cell(features: &Features, offset: i32) -> String {
+    let base = if features.two_tapes { "(i32.add (global.get $base) (global.get $p))" } else { "(global.get $p)" };
+    match offset {
+        0 => base.to_string(),
+        _ => format!("(i32.add {} (i32.const {}))", base, offset),
+    }
+}
+
+/// 输入耗尽时的处理指令
+fn eof_statement(eof: EofBehavior, features: &Features) -> String {
+    match eof {
+        EofBehavior::Zero => format!("(i32.store8 {} (i32.const 0))", cell(features, 0)),
+        EofBehavior::Unchanged => "(nop)".to_string(),
+        EofBehavior::Max => format!("(i32.store8 {} (i32.const 255))", cell(features, 0)),
+        EofBehavior::Error => "(call $fail (i32.const 101)) (unreachable)".to_string(),
+    }
+}
+
+/// 结果超出0..=255时的处理指令；address是单元格地址的表达式
+fn overflow_statement(policy: OverflowPolicy, address: &str, saturated: u8) -> String {
+    match policy {
+        OverflowPolicy::Wrap => unreachable!("wrapping arithmetic is emitted inline"),
+        OverflowPolicy::Saturate => format!("(i32.store8 {} (i32.const {}))", address, saturated),
+        OverflowPolicy::Trap => "(call $fail (i32.const 107)) (unreachable)".to_string(),
+    }
+}
+
+/// 导入、内存、全局变量与辅助函数
+fn prelude(out: &mut String, unit: &Unit, features: &Features) {
+    let size = unit.memory_size;
+    let imports = [
+        (features.output, "output", "(param i32)"),
+        (features.input || features.input_decimal, "input", "(result i32)"),
+        (features.fail, "fail", "(param i32 i32 i32)"),
+        (features.random, "random_seed", "(result i64)"),
+        (features.time, "now_ms", "(result i64)"),
+        (features.sleep, "sleep_ms", "(param i32)"),
+        (features.debug, "debug", "(param i32 i32 i32)"),
+    ];
+    for (used, name, signature) in imports {
+        // $fail是补上源码位置的包装
+        let id = if name == "fail" { "report_error" } else { name };
+        if used {
+            let _ = writeln!(out, "  (import \"env\" \"{}\" (func ${} {}))", name, id, signature);
+        }
+    }
+
+    let tapes = if features.two_tapes { 2 } else { 1 };
+    let stack_base = tapes * size;
+    let bytes = stack_base + if features.stack { STACK_LIMIT } else { 0 };
+    let _ = writeln!(out, "  (memory (export \"memory\") {})", bytes.div_ceil(PAGE_SIZE).max(1));
+    out.push_str("  (global $p (mut i32) (i32.const 0))\n");
+    if features.two_tapes {
+        out.push_str("  (global $base (mut i32) (i32.const 0))\n  (global $other_p (mut i32) (i32.const 0))\n");
+    }
+    if features.stack {
+        out.push_str("  (global $sp (mut i32) (i32.const 0))\n");
+    }
+    if !unit.procedures.is_empty() {
+        out.push_str("  (global $depth (mut i32) (i32.const 0))\n");
+    }
+    if features.random {
+        out.push_str("  (global $rng (mut i64) (i64.const 0))\n");
+    }
+    if features.time {
+        out.push_str("  (global $started (mut i64) (i64.const 0))\n");
+    }
+    if features.fail {
+        out.push_str("  (global $line (mut i32) (i32.const 0))\n  (global $column (mut i32) (i32.const 0))\n");
+    }
+
+    let here = cell(features, 0);
+    if features.fail {
+        out.push_str(
+            "
+  ;; record the source position of an instruction that may fail
+  (func $at (param $line i32) (param $column i32)
+    (global.set $line (local.get $line))
+    (global.set $column (local.get $column)))
+
+  (func $fail (param $code i32)
+    (call $report_error (local.get $code) (global.get $line) (global.get $column)))
+",
+        );
+    }
+    if features.moves {
+        let _ = write!(
+            out,
+            "
+  (func $right (param $n i32)
+    (global.set $p
+      (select (i32.add (global.get $p) (local.get $n)) (i32.const {})
+        (i32.lt_u (local.get $n) (i32.sub (i32.const {}) (global.get $p))))))
+
+  (func $left (param $n i32)
+    (global.set $p
+      (select (i32.sub (global.get $p) (local.get $n)) (i32.const 0)
+        (i32.lt_u (local.get $n) (global.get $p)))))
+",
+            size - 1,
+            size
+        );
+    }
+    if features.checked_add {
+        let _ = write!(
+            out,
+            "
+  (func $add (param $cell i32) (param $n i32)
+    (if (i32.le_u (local.get $n) (i32.sub (i32.const 255) (i32.load8_u (local.get $cell))))
+      (then (i32.store8 (local.get $cell) (i32.add (i32.load8_u (local.get $cell)) (local.get $n))))
+      (else {})))
+",
+            overflow_statement(unit.overflow, "(local.get $cell)", 255)
+        );
+    }
+    if features.checked_sub {
+        let _ = write!(
+            out,
+            "
+  (func $sub (param $cell i32) (param $n i32)
+    (if (i32.le_u (local.get $n) (i32.load8_u (local.get $cell)))
+      (then (i32.store8 (local.get $cell) (i32.sub (i32.load8_u (local.get $cell)) (local.get $n))))
+      (else {})))
+",
+            overflow_statement(unit.overflow, "(local.get $cell)", 0)
+        );
+    }
+    if unit.uses(|i| i == Instruction::OutputDecimal) {
+        out.push_str(
+            "
+  (func $output_decimal (param $v i32)
+    (if (i32.ge_u (local.get $v) (i32.const 100))
+      (then (call $output (i32.add (i32.const 48) (i32.div_u (local.get $v) (i32.const 100))))))
+    (if (i32.ge_u (local.get $v) (i32.const 10))
+      (then (call $output (i32.add (i32.const 48) (i32.rem_u (i32.div_u (local.get $v) (i32.const 10)) (i32.const 10))))))
+    (call $output (i32.add (i32.const 48) (i32.rem_u (local.get $v) (i32.const 10)))))
+",
+        );
+    }
+    if features.input {
+        let _ = write!(
+            out,
+            "
+  (func $read_byte (local $c i32)
+    (local.set $c (call $input))
+    (if (i32.ge_s (local.get $c) (i32.const 0))
+      (then (i32.store8 {} (local.get $c)))
+      (else {})))
+",
+            here,
+            eof_statement(unit.eof, features)
+        );
+    }
+    if features.input_decimal {
+        let store = match unit.overflow {
+            OverflowPolicy::Wrap => format!("(i32.store8 {} (i32.wrap_i64 (local.get $n)))", here),
+            policy => format!(
+                "(if (i64.le_u (local.get $n) (i64.const 255))
+        (then (i32.store8 {} (i32.wrap_i64 (local.get $n))))
+        (else {}))",
+                here,
+                overflow_statement(policy, &here, 255)
+            ),
+        };
+        let _ = write!(
+            out,
+            "
+  ;; skip leading whitespace, read digits; the first character after the number is consumed
+  (func $read_decimal (local $c i32) (local $n i64) (local $digits i32)
+    (loop $skip
+      (local.set $c (call $input))
+      (br_if $skip
+        (i32.or (i32.or (i32.eq (local.get $c) (i32.const 32)) (i32.eq (local.get $c) (i32.const 9)))
+          (i32.or (i32.eq (local.get $c) (i32.const 10)) (i32.or (i32.eq (local.get $c) (i32.const 12)) (i32.eq (local.get $c) (i32.const 13)))))))
+    (block $done
+      (loop $digit
+        (br_if $done (i32.gt_u (i32.sub (local.get $c) (i32.const 48)) (i32.const 9)))
+        (local.set $n (i64.add (i64.mul (local.get $n) (i64.const 10)) (i64.extend_i32_u (i32.sub (local.get $c) (i32.const 48)))))
+        (local.set $n (select (local.get $n) (i64.const 4294967295) (i64.lt_u (local.get $n) (i64.const 4294967295))))
+        (local.set $digits (i32.const 1))
+        (local.set $c (call $input))
+        (br $digit)))
+    (if (i32.eqz (local.get $digits))
+      (then
+        (if (i32.lt_s (local.get $c) (i32.const 0))
+          (then {})
+          (else (call $fail (i32.const 113)) (unreachable))))
+      (else
+        {})))
+",
+            eof_statement(unit.eof, features),
+            store
+        );
+    }
+    if unit.uses(|i| i == Instruction::AddNext) {
+        let next = cell(features, 1);
+        let add = match unit.overflow {
+            OverflowPolicy::Wrap => format!("(i32.store8 {} (i32.add (i32.load8_u {}) (i32.load8_u {})))", next, next, here),
+            _ => format!("(call $add {} (i32.load8_u {}))", next, here),
+        };
+        let _ = write!(
+            out,
+            "
+  ;; the value is dropped at the high edge
+  (func $add_next
+    (if (i32.lt_u (global.get $p) (i32.const {}))
+      (then {}))
+    (i32.store8 {} (i32.const 0)))
+",
+            size - 1,
+            add,
+            here
+        );
+    }
+    if features.stack {
+        let _ = write!(
+            out,
+            "
+  (func $push
+    (if (i32.eq (global.get $sp) (i32.const {limit}))
+      (then (call $fail (i32.const 110)) (unreachable)))
+    (i32.store8 (i32.add (i32.const {base}) (global.get $sp)) (i32.load8_u {here}))
+    (global.set $sp (i32.add (global.get $sp) (i32.const 1))))
+
+  (func $pop
+    (if (i32.eqz (global.get $sp))
+      (then (call $fail (i32.const 109)) (unreachable)))
+    (global.set $sp (i32.sub (global.get $sp) (i32.const 1)))
+    (i32.store8 {here} (i32.load8_u (i32.add (i32.const {base}) (global.get $sp)))))
+",
+            limit = STACK_LIMIT,
+            base = stack_base,
+            here = here
+        );
+    }
+    if features.two_tapes {
+        let _ = write!(
+            out,
+            "
+  (func $switch_tape (local $q i32)
+    (local.set $q (global.get $p))
+    (global.set $p (global.get $other_p))
+    (global.set $other_p (local.get $q))
+    (global.set $base (i32.sub (i32.const {size}) (global.get $base))))
+
+  (func $exchange (local $other i32) (local $v i32)
+    (local.set $other (i32.add (i32.sub (i32.const {size}) (global.get $base)) (global.get $other_p)))
+    (local.set $v (i32.load8_u {here}))
+    (i32.store8 {here} (i32.load8_u (local.get $other)))
+    (i32.store8 (local.get $other) (local.get $v)))
+",
+            size = size,
+            here = here
+        );
+    }
+    if features.random {
+        out.push_str(
+            "
+  ;; SplitMix64, the same generator as the interpreter
+  (func $random_byte (result i32) (local $z i64)
+    (global.set $rng (i64.add (global.get $rng) (i64.const 0x9e3779b97f4a7c15)))
+    (local.set $z (global.get $rng))
+    (local.set $z (i64.mul (i64.xor (local.get $z) (i64.shr_u (local.get $z) (i64.const 30))) (i64.const 0xbf58476d1ce4e5b9)))
+    (local.set $z (i64.mul (i64.xor (local.get $z) (i64.shr_u (local.get $z) (i64.const 27))) (i64.const 0x94d049bb133111eb)))
+    (i32.wrap_i64 (i64.shr_u (i64.xor (local.get $z) (i64.shr_u (local.get $z) (i64.const 31))) (i64.const 56))))
+",
+        );
+    }
+    if features.time {
+        let _ = write!(
+            out,
+            "
+  ;; little-endian milliseconds since start; bytes past the high edge are dropped
+  (func $write_time (local $ms i64) (local $i i32)
+    (local.set $ms (i64.sub (call $now_ms) (global.get $started)))
+    (block $done
+      (loop $byte
+        (br_if $done (i32.ge_u (local.get $i) (i32.const {})))
+        (br_if $done (i32.ge_u (i32.add (global.get $p) (local.get $i)) (i32.const {})))
+        (i32.store8 (i32.add {} (local.get $i)) (i32.wrap_i64 (local.get $ms)))
+        (local.set $ms (i64.shr_u (local.get $ms) (i64.const 8)))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $byte))))
+",
+            TIME_BYTES,
+            size,
+            here
+        );
+    }
+    if unit.uses(|i| i == Instruction::ScanLeft) {
+        let _ = write!(
+            out,
+            "
+  (func $scan_left
+    (block $done
+      (loop $scan
+        (br_if $done (i32.eqz (global.get $p)))
+        (br_if $done (i32.eqz (i32.load8_u {})))
+        (global.set $p (i32.sub (global.get $p) (i32.const 1)))
+        (br $scan))))
+",
+            here
+        );
+    }
+    if features.scan_right {
+        let _ = write!(
+            out,
+            "
+  (func $scan_right
+    (block $done
+      (loop $scan
+        (br_if $done (i32.eq (global.get $p) (i32.const {})))
+        (br_if $done (i32.eqz (i32.load8_u {})))
+        (global.set $p (i32.add (global.get $p) (i32.const 1)))
+        (br $scan))))
+",
+            size - 1,
+            here
+        );
+    }
+    if unit.uses(|i| i == Instruction::CallCell) {
+        // 每个过程一层block，br_table按单元格的值跳出到对应层之后调用
+        let count = unit.procedures.len();
+        let indent = |n: usize| "  ".repeat(2 + count - n);
+        out.push_str("\n  (func $call_cell\n    (block $undefined\n");
+        for n in (0..count).rev() {
+            let _ = writeln!(out, "{}(block $case{}", indent(n), n);
+        }
+        let targets: String = (0..count).map(|n| format!("$case{} ", n)).collect();
+        let _ = writeln!(out, "{}  (br_table {}$undefined (i32.load8_u {})))", indent(0), targets, here);
+        for n in 0..count {
+            let _ = writeln!(out, "{}(call $proc_{}) (return))", indent(n), n);
+        }
+        out.push_str("    (call $fail (i32.const 112)) (unreachable))\n");
+    }
+}
+
+/// 一条非循环指令对应的WAT指令
+fn statement(instruction: Instruction, unit: &Unit, features: &Features) -> String {
+    let wrap = unit.overflow == OverflowPolicy::Wrap;
+    let here = cell(features, 0);
+    match instruction {
+        Instruction::Increment | Instruction::Add(_) if wrap => {
+            format!("(i32.store8 {} (i32.add (i32.load8_u {}) (i32.const {})))", here, here, instruction.count() % 256)
+        },
+        Instruction::Decrement | Instruction::Sub(_) if wrap => {
+            format!("(i32.store8 {} (i32.sub (i32.load8_u {}) (i32.const {})))", here, here, instruction.count() % 256)
+        },
+        Instruction::Increment | Instruction::Add(_) => format!("(call $add {} (i32.const {}))", here, instruction.count()),
+        Instruction::Decrement | Instruction::Sub(_) => format!("(call $sub {} (i32.const {}))", here, instruction.count()),
+        Instruction::Right | Instruction::MoveRight(_) => format!("(call $right (i32.const {}))", instruction.count()),
+        Instruction::Left | Instruction::MoveLeft(_) => format!("(call $left (i32.const {}))", instruction.count()),
+        Instruction::Output => format!("(call $output (i32.load8_u {}))", here),
+        Instruction::OutputDecimal => format!("(call $output_decimal (i32.load8_u {}))", here),
+        Instruction::Input => "(call $read_byte)".to_string(),
+        Instruction::InputDecimal => "(call $read_decimal)".to_string(),
+        Instruction::Zero => format!("(i32.store8 {} (i32.const 0))", here),
+        Instruction::Set(value) => format!("(i32.store8 {} (i32.const {}))", here, value),
+        Instruction::Copy => format!(
+            "(if (i32.lt_u (global.get $p) (i32.const {})) (then (i32.store8 {} (i32.load8_u {}))))",
+            unit.memory_size - 1,
+            cell(features, 1),
+            here
+        ),
+        Instruction::CopyLeft => format!("(if (global.get $p) (then (i32.store8 {} (i32.load8_u {}))))", cell(features, -1), here),
+        Instruction::AddNext => "(call $add_next)".to_string(),
+        Instruction::MoveHigh => format!("(global.set $p (i32.const {}))", unit.memory_size - 1),
+        Instruction::MoveLow => "(global.set $p (i32.const 0))".to_string(),
+        Instruction::Push => "(call $push)".to_string(),
+        Instruction::Pop => "(call $pop)".to_string(),
+        Instruction::SwitchTape => "(call $switch_tape)".to_string(),
+        Instruction::Exchange => "(call $exchange)".to_string(),
+        Instruction::Call(n) => format!("(call $proc_{})", n),
+        Instruction::CallCell => "(call $call_cell)".to_string(),
+        Instruction::Random => format!("(i32.store8 {} (call $random_byte))", here),
+        Instruction::Time => "(call $write_time)".to_string(),
+        Instruction::Sleep => format!("(call $sleep_ms (i32.load8_u {}))", here),
+        Instruction::ScanLeft => "(call $scan_left)".to_string(),
+        Instruction::ScanRight => "(call $scan_right)".to_string(),
+        Instruction::Debug => {
+            let tape = if features.two_tapes { "(i32.ne (global.get $base) (i32.const 0))" } else { "(i32.const 0)" };
+            format!("(call $debug {} (global.get $p) (i32.load8_u {}))", tape, here)
+        },
+        Instruction::Custom(_) | Instruction::HostCall => unreachable!("host extensions are rejected by compile::render"),
+        Instruction::Assert(_) => unreachable!("assertions are dropped by Unit::new"),
+        Instruction::JumpIfZero | Instruction::JumpIfNotZero | Instruction::ProcStart | Instruction::ProcEnd => {
+            unreachable!("structural instructions are emitted by block()")
+        },
+    }
+}
+
+/// 输出一段指令序列，循环翻译为block与loop；每行注释源码位置，可能报错的指令先记录位置
+fn block(out: &mut String, instructions: &[Instruction], spans: &[Span], unit: &Unit, features: &Features) {
+    let mut labels = Vec::new();
+    let mut next_label = 0;
+    let indent = |depth: usize| "  ".repeat(depth + 2);
+    for (&instruction, span) in instructions.iter().zip(spans) {
+        match instruction {
+            Instruction::JumpIfZero => {
+                let depth = labels.len();
+                let _ = writeln!(out, "{}(block $end{} ;; {}:{}", indent(depth * 2), next_label, span.line, span.column);
+                let _ = writeln!(out, "{}  (loop $loop{}", indent(depth * 2), next_label);
+                let _ = writeln!(out, "{}    (br_if $end{} (i32.eqz (i32.load8_u {})))", indent(depth * 2), next_label, cell(features, 0));
+                labels.push(next_label);
+                next_label += 1;
+            },
+            Instruction::JumpIfNotZero => {
+                let label = labels.pop().expect("compiled brackets are matched");
+                let _ = writeln!(out, "{}    (br $loop{}))) ;; {}:{}", indent(labels.len() * 2), label, span.line, span.column);
+            },
+            _ if unit.can_fail(instruction) => {
+                let _ = writeln!(
+                    out,
+                    "{}(call $at (i32.const {}) (i32.const {})) {}",
+                    indent(labels.len() * 2),
+                    span.line,
+                    span.column,
+                    statement(instruction, unit, features)
+                );
+            },
+            _ => {
+                let _ = writeln!(out, "{}{} ;; {}:{}", indent(labels.len() * 2), statement(instruction, unit, features), span.line, span.column);
+            },
+        }
+    }
+}
+
+/// 生成完整的WAT模块
+pub fn render(unit: &Unit, source_name: &str) -> String {
+    let features = Features::new(unit);
+    let mut out = String::new();
+    let _ = writeln!(out, ";; Generated by `derstand compile --target wasm` from {}.", source_name);
+    out.push_str("(module\n");
+    prelude(&mut out, unit, &features);
+
+    for (n, (body, spans)) in unit.procedures.iter().zip(&unit.procedure_spans).enumerate() {
+        let _ = writeln!(out, "\n  (func $proc_{}", n);
+        let _ = writeln!(
+            out,
+            "    (if (i32.eq (global.get $depth) (i32.const {}))\n      (then (call $fail (i32.const 111)) (unreachable)))",
+            CALL_DEPTH_LIMIT
+        );
+        out.push_str("    (global.set $depth (i32.add (global.get $depth) (i32.const 1)))\n");
+        block(&mut out, body, spans, unit, &features);
+        out.push_str("    (global.set $depth (i32.sub (global.get $depth) (i32.const 1))))\n");
+    }
+
+    out.push_str("\n  (func (export \"run\")\n");
+    if features.random {
+        out.push_str("    (global.set $rng (call $random_seed))\n");
+    }
+    if features.time {
+        out.push_str("    (global.set $started (call $now_ms))\n");
+    }
+    block(&mut out, &unit.main, &unit.main_spans, unit, &features);
+    out.push_str("  )\n)\n");
+    out
+}
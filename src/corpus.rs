@@ -0,0 +1,84 @@
+//! 标准程序集 - `corpus::all()`给出内置示例连同输入、期望输出与典型步数，供下游工具、模糊测试与CI遍历
+//!
+//! 输入与期望输出就是`src/examples/`下同名的`.in`与`.expected`文件，`derstand test src/examples`
+//! 检查的是同一组用例。步数是默认设置下执行的指令数(相邻的`+`、`>`等融合后算一步)，可作性能基准的参照：
+//!
+//! ```text
+//! for program in derstand::corpus::all() {
+//!     let steps = program.check()?;
+//!     println!("{}: {} steps (typically {})", program.name(), steps, program.steps);
+//! }
+//! ```
+
+use crate::examples::{EXAMPLES, Example};
+#[cfg(feature = "std")]
+use crate::sandbox::SandboxProfile;
+#[cfg(feature = "std")]
+use crate::{DerstandInterpreter, runtime_error};
+
+/// 程序集中的一个程序
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Program {
+    pub example: &'static Example,
+    /// 运行时提供的输入，为空表示程序不读输入
+    pub input: &'static [u8],
+    pub output: &'static [u8],
+    /// 在input上运行到结束执行的步数
+    pub steps: u64,
+}
+
+impl Program {
+    pub fn name(&self) -> &'static str {
+        self.example.name
+    }
+
+    pub fn source(&self) -> &'static str {
+        self.example.source
+    }
+}
+
+/// 与EXAMPLES同序
+const PROGRAMS: &[Program] = &[
+    Program { example: &EXAMPLES[0], input: b"", output: include_bytes!("examples/hello.expected"), steps: 31 },
+    Program { example: &EXAMPLES[1], input: include_bytes!("examples/cat.in"), output: include_bytes!("examples/cat.expected"), steps: 137 },
+    Program { example: &EXAMPLES[2], input: include_bytes!("examples/rot13.in"), output: include_bytes!("examples/rot13.expected"), steps: 133_945 },
+    Program { example: &EXAMPLES[3], input: b"", output: include_bytes!("examples/fibonacci.expected"), steps: 201 },
+    Program { example: &EXAMPLES[4], input: b"", output: include_bytes!("examples/sierpinski.expected"), steps: 257_749 },
+    Program { example: &EXAMPLES[5], input: b"", output: include_bytes!("examples/showcase.expected"), steps: 14 },
+];
+
+/// 全部程序，顺序与examples::EXAMPLES相同
+pub fn all() -> &'static [Program] {
+    PROGRAMS
+}
+
+/// 按名称查找程序
+pub fn get(name: &str) -> Option<&'static Program> {
+    PROGRAMS.iter().find(|program| program.name() == name)
+}
+
+#[cfg(feature = "std")]
+impl Program {
+    /// 在沙箱中以input运行并与期望输出比较；返回执行的步数
+    pub fn check(&self) -> Result<u64, String> {
+        let source = self.source();
+        let mut interpreter = DerstandInterpreter::new();
+        // 留出余量，解释器的改进可能改变步数
+        let max_steps = self.steps.saturating_mul(10).max(1_000_000);
+        interpreter.set_sandbox(Some(SandboxProfile { input: self.input.to_vec(), max_steps, ..Default::default() }));
+        interpreter.compile(source).map_err(|e| e.render(source, Some(self.name()), false))?;
+        if let Err(e) = interpreter.execute() {
+            return Err(runtime_error(&interpreter, source, e).render(source, Some(self.name()), false));
+        }
+        let output = interpreter.captured_output();
+        if output != self.output {
+            return Err(format!(
+                "{}: expected {:?} but the program printed {:?}",
+                self.name(),
+                String::from_utf8_lossy(self.output),
+                String::from_utf8_lossy(output)
+            ));
+        }
+        Ok(interpreter.steps)
+    }
+}
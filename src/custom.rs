@@ -0,0 +1,86 @@
+//! 自定义指令 - 把未使用的字符映射到宿主提供的处理函数
+//!
+//! 编译时遇到已注册的字符生成`Instruction::Custom`，执行时调用对应的处理函数；
+//! 处理函数经`Machine`读写当前纸带与指针。嵌入时用`Builder::instruction`注册闭包，
+//! 动态库插件见`plugin`模块。
+//!
+//! 宿主函数是另一种扩展：程序用`§`(扩展方言)调用编号为当前单元格值的函数，函数只能读写
+//! 当前单元格之后固定大小的窗口，类似系统调用。
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+
+use crate::preprocess::DIRECTIVE_MARKER;
+use crate::{COMMENT_MARKER, Instruction, SET_MARKER, STRING_MARKER};
+
+/// 处理函数看到的解释器状态：当前纸带与指针
+pub struct Machine<'a> {
+    pub(crate) memory: &'a mut [u8],
+    pub(crate) pointer: &'a mut usize,
+}
+
+impl Machine<'_> {
+    /// 当前单元格的值
+    pub fn cell(&self) -> u8 {
+        self.memory[*self.pointer]
+    }
+
+    pub fn set_cell(&mut self, value: u8) {
+        self.memory[*self.pointer] = value;
+    }
+
+    pub fn pointer(&self) -> usize {
+        *self.pointer
+    }
+
+    /// 移动指针；越出纸带时返回错误，指针不变
+    pub fn set_pointer(&mut self, pointer: usize) -> Result<(), String> {
+        if pointer >= self.memory.len() {
+            return Err(format!("pointer {} is outside the tape of {} cells", pointer, self.memory.len()));
+        }
+        *self.pointer = pointer;
+        Ok(())
+    }
+
+    /// 整条纸带
+    pub fn memory(&self) -> &[u8] {
+        self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        self.memory
+    }
+}
+
+/// 处理函数；返回的错误成为运行时错误E0117
+pub type Handler = Box<dyn FnMut(&mut Machine) -> Result<(), String>>;
+
+/// 一条已注册的自定义指令
+pub(crate) struct CustomInstruction {
+    pub symbol: char,
+    pub name: String,
+    pub handler: Handler,
+}
+
+/// 宿主函数：参数与结果经窗口(当前单元格之后的若干单元格)传递；返回的错误成为运行时错误E0119
+pub type HostFunction = Box<dyn FnMut(&mut [u8]) -> Result<(), String>>;
+
+/// 一个已注册的宿主函数与它的窗口大小
+pub(crate) struct HostEntry {
+    pub window: usize,
+    pub function: HostFunction,
+}
+
+/// 字符能否用作自定义指令：不能是已有指令、语法标记、字母数字(宏名与编译指示)或空白
+pub fn check_symbol(symbol: char) -> Result<(), String> {
+    let reserved = Instruction::from_char(symbol).is_some()
+        || matches!(symbol, COMMENT_MARKER | SET_MARKER | STRING_MARKER | DIRECTIVE_MARKER | '{' | '}')
+        || symbol.is_alphanumeric()
+        || symbol.is_whitespace()
+        || symbol.is_control();
+    if reserved {
+        return Err(format!("'{}' is already used by the language and cannot be a custom instruction", symbol.escape_default()));
+    }
+    Ok(())
+}
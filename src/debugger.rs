@@ -0,0 +1,174 @@
+//! 交互式调试器 - 单步、继续运行与内存观察点
+
+use std::io::{self, BufRead, Write};
+use std::ops::RangeInclusive;
+
+use crate::access::{Access, accesses};
+use crate::diagnostic::Diagnostic;
+use crate::hexdump;
+use crate::step::excerpt;
+use crate::{DerstandInterpreter, Instruction};
+
+impl Access {
+    fn verb(self) -> &'static str {
+        match self {
+            Access::Read => "read",
+            Access::Write => "written",
+        }
+    }
+}
+
+/// 解析单元格或区间: `4` 或 `10..20`(含两端)
+fn parse_range(text: &str, memory_size: usize) -> Result<RangeInclusive<usize>, String> {
+    let parse = |t: &str| t.trim().parse::<usize>().map_err(|_| format!("Invalid cell index: {}", t));
+    let range = match text.split_once("..") {
+        Some((start, end)) => parse(start)?..=parse(end)?,
+        None => {
+            let cell = parse(text)?;
+            cell..=cell
+        },
+    };
+    if range.start() > range.end() || *range.end() >= memory_size {
+        return Err(format!("Cell range out of bounds: {}", text));
+    }
+    Ok(range)
+}
+
+/// 调试会话状态
+struct Debugger {
+    watchpoints: Vec<RangeInclusive<usize>>,
+}
+
+impl Debugger {
+    /// 命中的观察点：返回被访问的单元格及访问类型
+    fn hit(&self, interpreter: &DerstandInterpreter, instruction: Instruction) -> Option<(usize, Access)> {
+        accesses(instruction, interpreter.pointer(), interpreter.memory())
+            .into_iter()
+            .find(|(cell, _)| self.watchpoints.iter().any(|w| w.contains(cell)))
+    }
+
+    /// 执行一条指令；命中观察点时报告负责的指令并返回true
+    fn step(&self, interpreter: &mut DerstandInterpreter, source: &str) -> Result<bool, Diagnostic> {
+        let pc = interpreter.pc();
+        let Some(&instruction) = interpreter.instructions().get(pc) else {
+            return Ok(false);
+        };
+        let hit = self.hit(interpreter, instruction);
+        let before = hit.map(|(cell, _)| interpreter.cell(cell));
+        interpreter.step()?;
+
+        if let (Some((cell, access)), Some(before)) = (hit, before) {
+            let span = interpreter.spans()[pc];
+            let after = interpreter.cell(cell);
+            let change = if access == Access::Write { format!(" ({} → {})", before, after) } else { format!(" (value {})", before) };
+            eprintln!(
+                "Watchpoint: cell {} {} by '{}' (#{}) at {}:{}{}\n{}",
+                cell,
+                access.verb(),
+                instruction,
+                pc,
+                span.line,
+                span.column,
+                change,
+                excerpt(source, span)
+            );
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+const HELP: &str = "Commands:
+  s, step [n]         execute n instructions (default 1)
+  c, continue         run until a watchpoint triggers or the program ends
+  w, watch <i|a..b>   pause when a cell (or inclusive range) is read or written
+  u, unwatch <i|a..b> remove a watchpoint
+  l, list             list watchpoints
+  p, print [i]        print a cell (default: the current cell)
+  m, mem [start [len]] hexdump the tape
+  q, quit             stop debugging";
+
+/// 以调试器方式运行已编译程序
+pub fn debug(interpreter: &mut DerstandInterpreter, source: &str) -> Result<(), Diagnostic> {
+    interpreter.reset();
+    let mut debugger = Debugger { watchpoints: Vec::new() };
+    let stdin = io::stdin();
+
+    eprintln!("Derstand debugger - type 'help' for commands.");
+    loop {
+        let pc = interpreter.pc();
+        match interpreter.spans().get(pc) {
+            Some(&span) => eprintln!("#{} pointer={} cell={}\n{}", pc, interpreter.pointer(), interpreter.cell(interpreter.pointer()), excerpt(source, span)),
+            None => {
+                eprintln!("Program finished.");
+                return Ok(());
+            },
+        }
+
+        eprint!("(dbg) ");
+        let _ = io::stderr().flush();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).map_err(|e| Diagnostic::error("E0102", format!("Input error: {}", e)))? == 0 {
+            return Ok(());
+        }
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("s");
+        let argument = words.next();
+
+        match command {
+            "s" | "step" => {
+                let count = argument.map_or(Ok(1), |n| n.parse::<usize>()).map_err(|_| "Invalid step count".to_string());
+                match count {
+                    Ok(count) => {
+                        for _ in 0..count {
+                            if debugger.step(interpreter, source)? || interpreter.pc() >= interpreter.instructions().len() {
+                                break;
+                            }
+                        }
+                    },
+                    Err(e) => eprintln!("{}", e),
+                }
+            },
+            "c" | "continue" => {
+                while interpreter.pc() < interpreter.instructions().len() {
+                    if debugger.step(interpreter, source)? {
+                        break;
+                    }
+                }
+            },
+            "w" | "watch" | "u" | "unwatch" => match argument.map(|a| parse_range(a, interpreter.memory_size())) {
+                Some(Ok(range)) if command.starts_with('w') => {
+                    eprintln!("Watching cells {}..{}", range.start(), range.end());
+                    debugger.watchpoints.push(range);
+                },
+                Some(Ok(range)) => debugger.watchpoints.retain(|w| *w != range),
+                Some(Err(e)) => eprintln!("{}", e),
+                None => eprintln!("Usage: {} <cell|start..end>", command),
+            },
+            "l" | "list" => {
+                for (i, w) in debugger.watchpoints.iter().enumerate() {
+                    eprintln!("  {}: cells {}..{}", i, w.start(), w.end());
+                }
+            },
+            "p" | "print" => {
+                let cell = argument.map_or(Ok(interpreter.pointer()), |a| {
+                    parse_range(a, interpreter.memory_size()).map(|r| *r.start())
+                });
+                match cell {
+                    Ok(cell) => eprintln!("cell {} = {}", cell, interpreter.cell(cell)),
+                    Err(e) => eprintln!("{}", e),
+                }
+            },
+            "m" | "mem" => {
+                let args = line.split_whitespace().skip(1);
+                match hexdump::parse_range(args, interpreter.memory_size()) {
+                    Ok((start, len)) => eprintln!("{}", hexdump::hexdump(interpreter.memory(), start, len, interpreter.pointer())),
+                    Err(e) => eprintln!("{}", e),
+                }
+            },
+            "q" | "quit" => return Ok(()),
+            "h" | "help" => eprintln!("{}", HELP),
+            _ => eprintln!("Unknown command '{}' - type 'help' for commands.", command),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! 映射到设备的单元格 - 宿主把纸带上的某些地址绑定到设备，不需要新语法
+//!
+//! 读取设备单元格的指令执行前先从设备取值放进单元格，写入设备单元格的指令执行后把新值交给设备：
+//!
+//! ```text
+//! let mut interpreter = DerstandInterpreter::builder()
+//!     .device(29998, Device::new().on_write(|byte| { eprint!("{}", byte as char); Ok(()) }))
+//!     .device(29997, Device::new().on_read(|| Ok(next_random_byte())))
+//!     .build()?;
+//! ```
+//!
+//! 读写按指令对当前指针的访问判断(`[`与`]`也读取单元格)；自定义指令与`§`的窗口只按当前单元格计算。
+//! 只作用于第一条纸带；沙箱中设备不起作用。设备返回的错误成为运行时错误E0123。
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::DerstandInterpreter;
+use crate::access::{Access, accesses};
+use crate::diagnostic::Diagnostic;
+
+/// 读取设备单元格时调用，返回值写入单元格
+pub type ReadHandler = Box<dyn FnMut() -> Result<u8, String>>;
+
+/// 写入设备单元格后以新值调用
+pub type WriteHandler = Box<dyn FnMut(u8) -> Result<(), String>>;
+
+/// 一个设备；未设置的一侧按普通单元格处理
+#[derive(Default)]
+pub struct Device {
+    read: Option<ReadHandler>,
+    write: Option<WriteHandler>,
+}
+
+impl Device {
+    pub fn new() -> Self {
+        Device::default()
+    }
+
+    pub fn on_read(mut self, handler: impl FnMut() -> Result<u8, String> + 'static) -> Self {
+        self.read = Some(Box::new(handler));
+        self
+    }
+
+    pub fn on_write(mut self, handler: impl FnMut(u8) -> Result<(), String> + 'static) -> Self {
+        self.write = Some(Box::new(handler));
+        self
+    }
+}
+
+fn failed(interpreter: &DerstandInterpreter, pc: usize, cell: usize, error: String) -> Diagnostic {
+    Diagnostic::error("E0123", format!("Device at cell {} failed: {}", cell, error))
+        .with_span(interpreter.spans[pc])
+        .with_label("this instruction accessed the device")
+}
+
+impl DerstandInterpreter {
+    /// 执行pc处的指令之前：从被读取的设备取值；返回之后要交给设备的单元格
+    pub(crate) fn read_devices(&mut self, pc: usize) -> Result<Vec<usize>, Diagnostic> {
+        if self.active_tape != 0 || self.sandbox.is_some() {
+            return Ok(Vec::new());
+        }
+        let mut written = Vec::new();
+        for (cell, access) in accesses(self.instructions[pc], self.pointer, &self.memory) {
+            let Some((_, device)) = self.devices.iter_mut().find(|(c, _)| *c == cell) else {
+                continue;
+            };
+            match access {
+                Access::Read => {
+                    if let Some(read) = &mut device.read {
+                        match read() {
+                            Ok(value) => self.memory[cell] = value,
+                            Err(e) => return Err(failed(self, pc, cell, e)),
+                        }
+                    }
+                },
+                Access::Write => {
+                    if device.write.is_some() {
+                        written.push(cell);
+                    }
+                },
+            }
+        }
+        Ok(written)
+    }
+
+    /// pc处的指令是否会从设备取值
+    #[cfg(feature = "std")]
+    pub(crate) fn reads_device(&self, pc: usize) -> bool {
+        if self.active_tape != 0 || self.sandbox.is_some() {
+            return false;
+        }
+        accesses(self.instructions[pc], self.pointer, &self.memory).into_iter().any(|(cell, access)| {
+            matches!(access, Access::Read) && self.devices.iter().any(|(c, device)| *c == cell && device.read.is_some())
+        })
+    }
+
+    /// 执行之后：把写入的值交给设备
+    pub(crate) fn write_devices(&mut self, pc: usize, written: &[usize]) -> Result<(), Diagnostic> {
+        for &cell in written {
+            let value = self.memory[cell];
+            if let Some(write) = self.devices.iter_mut().find(|(c, _)| *c == cell).and_then(|(_, d)| d.write.as_mut())
+                && let Err(e) = write(value)
+            {
+                return Err(failed(self, pc, cell, e));
+            }
+        }
+        Ok(())
+    }
+}
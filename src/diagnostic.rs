@@ -0,0 +1,215 @@
+//! 诊断信息 - 错误码、源码位置与提示，渲染为带源码片段的终端输出
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+#[cfg(feature = "std")]
+use std::io::IsTerminal;
+
+use crate::Span;
+use crate::json::Json;
+
+/// 取出源码中的第line行(从1开始)
+pub fn source_line(source: &str, line: usize) -> &str {
+    source.lines().nth(line.saturating_sub(1)).unwrap_or("")
+}
+
+/// 诊断输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum DiagnosticFormat {
+    #[default]
+    Human, // 带源码片段的终端渲染
+    Json,  // 每行一个JSON对象，见docs/diagnostics.md
+}
+
+impl DiagnosticFormat {
+    /// 解析命令行参数值
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "human" => Ok(DiagnosticFormat::Human),
+            "json" => Ok(DiagnosticFormat::Json),
+            _ => Err(format!("Unknown diagnostics format '{}' (expected: human, json)", value)),
+        }
+    }
+}
+
+/// 严重程度
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// 编译或运行时诊断 - 装箱保存，使`Result<_, Diagnostic>`在执行热路径上保持小巧
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic(Box<DiagnosticData>);
+
+/// 诊断内容
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagnosticData {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Option<Span>,
+    pub label: Option<String>, // 脱字符旁的说明
+    pub hint: Option<String>,
+    pub notes: Vec<String>,    // 附加说明(如循环回溯)
+}
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic(Box::new(DiagnosticData {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            span: None,
+            label: None,
+            hint: None,
+            notes: Vec::new(),
+        }))
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>) -> Self {
+        let mut diagnostic = Diagnostic::error(code, message);
+        diagnostic.severity = Severity::Warning;
+        diagnostic
+    }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// 渲染为多行文本：标题、位置、源码行与下划线、提示
+    pub fn render(&self, source: &str, file: Option<&str>, color: bool) -> String {
+        let paint = |code: &str, text: &str| if color { format!("\x1b[{}m{}\x1b[0m", code, text) } else { text.to_string() };
+        let accent = match self.severity {
+            Severity::Error => "1;31",
+            Severity::Warning => "1;33",
+        };
+
+        let mut out = format!(
+            "{}: {}",
+            paint(accent, &format!("{}[{}]", self.severity.as_str(), self.code)),
+            paint("1", &self.message)
+        );
+        if let Some(span) = self.span {
+            let gutter = span.line.to_string();
+            let pad = " ".repeat(gutter.len());
+            let location = format!("{}:{}:{}", file.unwrap_or("<input>"), span.line, span.column);
+            out.push_str(&format!("\n{}{} {}", pad, paint("1;34", "-->"), location));
+            out.push_str(&format!("\n{} {}", pad, paint("1;34", "|")));
+            out.push_str(&format!("\n{} {} {}", paint("1;34", &gutter), paint("1;34", "|"), source_line(source, span.line)));
+            let marker = format!("^{}", self.label.as_ref().map(|l| format!(" {}", l)).unwrap_or_default());
+            out.push_str(&format!(
+                "\n{} {} {}{}",
+                pad,
+                paint("1;34", "|"),
+                " ".repeat(span.column.saturating_sub(1)),
+                paint(accent, &marker)
+            ));
+        }
+        let pad = " ".repeat(self.span.map_or(1, |s| s.line.to_string().len()));
+        for note in &self.notes {
+            out.push_str(&format!("\n{} {} note: {}", pad, paint("1;34", "="), note));
+        }
+        if let Some(hint) = &self.hint {
+            out.push_str(&format!("\n{} {} hint: {}", pad, paint("1;34", "="), hint));
+        }
+        out
+    }
+}
+
+impl Diagnostic {
+    /// JSON表示 - 字段含义见docs/diagnostics.md
+    pub fn to_json(&self, file: Option<&str>) -> Json {
+        let position = |line: usize, column: usize, offset: usize| {
+            Json::object([("line", line.into()), ("column", column.into()), ("offset", offset.into())])
+        };
+        let range = self.span.map_or(Json::Null, |span| {
+            Json::object([
+                ("start", position(span.line, span.column, span.offset)),
+                ("end", position(span.line, span.column + 1, span.offset + 1)),
+            ])
+        });
+        Json::object([
+            ("file", file.into()),
+            ("severity", self.severity.as_str().into()),
+            ("code", self.code.into()),
+            ("message", self.message.clone().into()),
+            ("range", range),
+            ("label", self.label.clone().into()),
+            ("hint", self.hint.clone().into()),
+            ("notes", self.notes.clone().into()),
+        ])
+    }
+
+    /// 按格式写到stderr
+    #[cfg(feature = "std")]
+    pub fn report(&self, format: DiagnosticFormat, source: &str, file: Option<&str>) {
+        match format {
+            DiagnosticFormat::Human => eprintln!("{}", self.render(source, file, stderr_color())),
+            DiagnosticFormat::Json => eprintln!("{}", self.to_json(file)),
+        }
+    }
+}
+
+impl Deref for Diagnostic {
+    type Target = DiagnosticData;
+
+    fn deref(&self) -> &DiagnosticData {
+        &self.0
+    }
+}
+
+impl DerefMut for Diagnostic {
+    fn deref_mut(&mut self) -> &mut DiagnosticData {
+        &mut self.0
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    /// 单行形式 - 只包含消息本身
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// stderr是终端且未设置NO_COLOR时启用颜色
+#[cfg(feature = "std")]
+pub fn stderr_color() -> bool {
+    std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+/// stdout是终端且未设置NO_COLOR时启用颜色
+#[cfg(feature = "std")]
+pub fn stdout_color() -> bool {
+    std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
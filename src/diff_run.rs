@@ -0,0 +1,185 @@
+//! 两次运行的对比 - `derstand diff-run`以两份输入各运行一次程序，比较最终纸带与输出
+//!
+//! 用于排查对输入敏感的行为：列出值不同的单元格，并按16格一行并排显示两条纸带，不同的字节以颜色或`^`标出。
+//! 超出预算或以运行时错误结束的运行仍参与比较，停下时的纸带就是它的最终纸带。
+//!
+//! ```text
+//! use derstand::diff_run::diff_runs;
+//! use derstand::golden::TestOptions;
+//!
+//! let diff = diff_runs(",>,[<+>-]<.", "add.dr".as_ref(), b"\x01\x02", b"\x01\x03", TestOptions::default()).unwrap();
+//! assert_eq!(diff.cells, [(0, 3, 4)]);
+//! assert_eq!(diff.output, Some(0));
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use crate::diagnostic::{Diagnostic, stderr_color, stdout_color};
+use crate::equiv::{self, Run};
+use crate::golden::{self, TestOptions};
+
+/// 并排显示时每行的单元格数
+const CELLS_PER_ROW: usize = 16;
+
+/// 两次运行的结果与它们的差异
+#[derive(Debug)]
+pub struct RunDiff {
+    pub first: Run,
+    pub second: Run,
+    pub output: Option<usize>,       // 第一个不同的输出字节
+    pub cells: Vec<(usize, u8, u8)>, // 值不同的单元格：(下标, 第一次, 第二次)
+}
+
+impl RunDiff {
+    /// 纸带、指针与输出都相同
+    pub fn is_identical(&self) -> bool {
+        self.output.is_none() && self.cells.is_empty() && self.first.pointer == self.second.pointer
+    }
+}
+
+/// 编译source并分别以两份输入在预算内运行
+pub fn diff_runs(source: &str, path: &Path, first_input: &[u8], second_input: &[u8], budget: TestOptions) -> Result<RunDiff, Diagnostic> {
+    let first = equiv::run(source, path, first_input, budget)?;
+    let second = equiv::run(source, path, second_input, budget)?;
+    let output = equiv::output_difference(&first.output, &second.output);
+    let cells = equiv::tape_differences(&first.memory, &second.memory);
+    Ok(RunDiff { first, second, output, cells })
+}
+
+/// 并排显示含有差异的纸带行；labels是两次运行在行首的名字
+pub fn render_tape(diff: &RunDiff, labels: (&str, &str), color: bool) -> String {
+    let width = labels.0.len().max(labels.1.len());
+    let cell = |memory: &[u8], index: usize| memory.get(index).copied().unwrap_or(0);
+    let mut rows: Vec<usize> = diff.cells.iter().map(|&(index, _, _)| index / CELLS_PER_ROW).collect();
+    rows.dedup();
+
+    let mut out = String::new();
+    let mut previous = None;
+    for row in rows {
+        if previous.is_some_and(|p| p + 1 != row) {
+            out.push_str("*\n");
+        }
+        previous = Some(row);
+        let start = row * CELLS_PER_ROW;
+        let differs = |index: usize| cell(&diff.first.memory, index) != cell(&diff.second.memory, index);
+        for (label, run) in [(labels.0, &diff.first), (labels.1, &diff.second)] {
+            let offset = if core::ptr::eq(run, &diff.first) { format!("{:08x}", start) } else { String::new() };
+            out.push_str(&format!("{:8}  {:>width$}:", offset, label, width = width));
+            for index in start..start + CELLS_PER_ROW {
+                // 指针所在单元格用方括号标出，与hexdump一致
+                let marker = if index == run.pointer { '[' } else if index > start && index - 1 == run.pointer { ']' } else { ' ' };
+                let byte = format!("{:02x}", cell(&run.memory, index));
+                out.push(marker);
+                if color && differs(index) {
+                    out.push_str(&format!("\x1b[1;31m{}\x1b[0m", byte));
+                } else {
+                    out.push_str(&byte);
+                }
+            }
+            out.push(if start + CELLS_PER_ROW - 1 == run.pointer { ']' } else { ' ' });
+            out.push('\n');
+        }
+        if !color {
+            let markers: String = (start..start + CELLS_PER_ROW).map(|index| if differs(index) { " ^^" } else { "   " }).collect();
+            out.push_str(&format!("{:8}  {:width$} {}\n", "", "", markers.trim_end(), width = width));
+        }
+    }
+    out
+}
+
+/// 运行没有正常结束时的说明
+fn stopped(run: &Run) -> Option<String> {
+    match (&run.exhausted, &run.error) {
+        (Some(reason), _) => Some(format!("stopped: {}", reason)),
+        (None, Some(e)) => Some(format!("failed with {}: {}", e.code, e.message)),
+        (None, None) => None,
+    }
+}
+
+/// `derstand diff-run prog.dr --input a.txt --input b.txt [--steps N] [--timeout MS]`
+pub fn command(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: derstand diff-run <file> --input A --input B [--steps N] [--timeout MS]";
+    let mut budget = TestOptions::default();
+    let mut inputs = Vec::new();
+    let mut program = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let positive = |value: Option<&String>| value.and_then(|v| v.parse::<u64>().ok()).filter(|&n| n > 0);
+        let parsed = match arg.as_str() {
+            "--input" => iter.next().ok_or("Missing value for --input".to_string()).map(|path| inputs.push(PathBuf::from(path))),
+            "--steps" => positive(iter.next()).map(|n| budget.max_steps = n).ok_or("--steps expects a positive number".to_string()),
+            "--timeout" => positive(iter.next())
+                .map(|n| budget.timeout_ms = n)
+                .ok_or("--timeout expects a positive number of milliseconds".to_string()),
+            _ if arg.starts_with("--") => Err(format!("Unknown option: {}", arg)),
+            _ if program.is_none() => {
+                program = Some(arg.clone());
+                Ok(())
+            },
+            _ => Err(format!("Unexpected argument: {}", arg)),
+        };
+        if let Err(e) = parsed {
+            eprintln!("{}", e);
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    }
+    let (Some(file), [first_path, second_path]) = (program, inputs.as_slice()) else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let source = match std::fs::read_to_string(&file) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error reading file {}: {}", file, e);
+            return 1;
+        },
+    };
+    let read = |path: &PathBuf| std::fs::read(path).map_err(|e| eprintln!("Error reading input {}: {}", path.display(), e));
+    let (Ok(first_input), Ok(second_input)) = (read(first_path), read(second_path)) else {
+        return 1;
+    };
+    let diff = match diff_runs(&source, Path::new(&file), &first_input, &second_input, budget) {
+        Ok(diff) => diff,
+        Err(e) => {
+            eprintln!("{}", e.render(&source, Some(&file), stderr_color()));
+            return 1;
+        },
+    };
+
+    let labels = (first_path.display().to_string(), second_path.display().to_string());
+    for (label, run) in [(&labels.0, &diff.first), (&labels.1, &diff.second)] {
+        if let Some(reason) = stopped(run) {
+            println!("note: the run with {} {}", label, reason);
+        }
+    }
+    if diff.is_identical() {
+        println!("No differences: both runs end with the same tape, pointer and output");
+        return 0;
+    }
+    if let Some(offset) = diff.output {
+        println!(
+            "output differs at byte {}: {} wrote {}, {} wrote {} (- {}, + {})",
+            offset,
+            labels.0,
+            equiv::describe_byte(&diff.first.output, offset),
+            labels.1,
+            equiv::describe_byte(&diff.second.output, offset),
+            labels.0,
+            labels.1
+        );
+        println!("{}", golden::diff(&diff.first.output, &diff.second.output).trim_end());
+    } else {
+        println!("output: identical ({} bytes)", diff.first.output.len());
+    }
+    if diff.first.pointer != diff.second.pointer {
+        println!("pointer ends at {} with {} and at {} with {}", diff.first.pointer, labels.0, diff.second.pointer, labels.1);
+    }
+    if diff.cells.is_empty() {
+        println!("tape: identical");
+    } else {
+        println!("tape: {} cell(s) differ: {}", diff.cells.len(), equiv::describe_cells(&diff.cells));
+        print!("{}", render_tape(&diff, (&labels.0, &labels.1), stdout_color()));
+    }
+    1
+}
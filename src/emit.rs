@@ -0,0 +1,212 @@
+//! 编译产物导出 - 供外部工具(可视化、转译器)直接使用
+
+use crate::json::Json;
+use crate::{DerstandInterpreter, Instruction, Span};
+
+/// IR格式版本 - 结构不兼容变化时递增
+const IR_VERSION: i64 = 1;
+
+/// `--emit` 支持的输出类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmitKind {
+    Ir,  // 结构化IR(JSON)
+    Cfg, // 控制流图(Graphviz DOT)
+}
+
+impl EmitKind {
+    /// 解析命令行参数值
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "ir" => Ok(EmitKind::Ir),
+            "cfg" => Ok(EmitKind::Cfg),
+            _ => Err(format!("Unknown emit kind '{}' (expected: ir, cfg)", value)),
+        }
+    }
+}
+
+/// 按类型渲染已编译程序
+pub fn render(kind: EmitKind, interpreter: &DerstandInterpreter) -> String {
+    match kind {
+        EmitKind::Ir => ir_json(interpreter).to_string(),
+        EmitKind::Cfg => cfg_dot(interpreter),
+    }
+}
+
+fn span_json(span: Span) -> Json {
+    Json::object([
+        ("offset", span.offset.into()),
+        ("line", span.line.into()),
+        ("column", span.column.into()),
+    ])
+}
+
+/// 构造IR的JSON表示：指令(种类、操作数、源码位置)及循环结构
+pub fn ir_json(interpreter: &DerstandInterpreter) -> Json {
+    let instructions = interpreter.instructions();
+    let spans = interpreter.spans();
+
+    let mut ops = Vec::with_capacity(instructions.len());
+    let mut loops = Vec::new();
+    // 当前打开的循环编号
+    let mut open_loops: Vec<usize> = Vec::new();
+
+    for (pc, &instruction) in instructions.iter().enumerate() {
+        if instruction == Instruction::JumpIfNotZero {
+            open_loops.pop();
+        }
+
+        let mut fields = vec![
+            ("index", pc.into()),
+            ("kind", instruction.name().into()),
+            ("symbol", instruction.symbol().to_string().into()),
+            ("span", span_json(spans[pc])),
+            ("depth", open_loops.len().into()),
+        ];
+        if instruction.count() > 1 {
+            fields.push(("count", (instruction.count() as usize).into()));
+        }
+        if let Instruction::Set(value) | Instruction::Assert(value) = instruction {
+            fields.push(("value", (value as usize).into()));
+        }
+        if let Instruction::Call(n) = instruction {
+            fields.push(("procedure", (n as usize).into()));
+        }
+        if let Some(target) = interpreter.jump_target(pc) {
+            fields.push(("target", target.into()));
+        }
+        ops.push(Json::object(fields));
+
+        if instruction == Instruction::JumpIfZero {
+            let close = interpreter.jump_target(pc).unwrap_or(pc);
+            loops.push(Json::object([
+                ("id", loops.len().into()),
+                ("open", pc.into()),
+                ("close", close.into()),
+                ("depth", open_loops.len().into()),
+                ("parent", open_loops.last().copied().into()),
+                ("span", span_json(spans[pc])),
+            ]));
+            open_loops.push(loops.len() - 1);
+        }
+    }
+
+    let procedures = interpreter
+        .procedures()
+        .iter()
+        .enumerate()
+        .map(|(id, &open)| {
+            Json::object([
+                ("id", id.into()),
+                ("open", open.into()),
+                ("close", interpreter.jump_target(open).unwrap_or(open).into()),
+                ("span", span_json(spans[open])),
+            ])
+        })
+        .collect();
+
+    Json::object([
+        ("version", IR_VERSION.into()),
+        ("instructions", Json::Array(ops)),
+        ("loops", Json::Array(loops)),
+        ("procedures", Json::Array(procedures)),
+    ])
+}
+
+/// 基本块 - 指令区间 [start, end)
+#[derive(Debug, Clone, Copy)]
+struct BasicBlock {
+    start: usize,
+    end: usize,
+}
+
+/// 划分基本块：括号、过程边界与调用指令是分支，结束当前块；分支后的指令开始新块
+fn basic_blocks(instructions: &[Instruction]) -> Vec<BasicBlock> {
+    let mut blocks = Vec::new();
+    let mut start = 0;
+    for (pc, instruction) in instructions.iter().enumerate() {
+        if matches!(
+            instruction,
+            Instruction::JumpIfZero
+                | Instruction::JumpIfNotZero
+                | Instruction::ProcStart
+                | Instruction::ProcEnd
+                | Instruction::Call(_)
+                | Instruction::CallCell
+        ) {
+            blocks.push(BasicBlock { start, end: pc + 1 });
+            start = pc + 1;
+        }
+    }
+    if start < instructions.len() {
+        blocks.push(BasicBlock { start, end: instructions.len() });
+    }
+    blocks
+}
+
+/// 转义DOT标签中的特殊字符
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// 生成控制流图的DOT描述：基本块为节点，分支与循环回边为边
+pub fn cfg_dot(interpreter: &DerstandInterpreter) -> String {
+    let instructions = interpreter.instructions();
+    let spans = interpreter.spans();
+    let blocks = basic_blocks(instructions);
+    // 指令下标 -> 节点名，越界(程序结束)指向exit
+    let node = |pc: usize| -> String {
+        match blocks.iter().position(|b| b.start == pc) {
+            Some(id) => format!("b{}", id),
+            None => "exit".to_string(),
+        }
+    };
+
+    let mut dot = String::from("digraph cfg {\n");
+    dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+    dot.push_str("    entry [shape=oval];\n");
+    dot.push_str("    exit [shape=oval];\n");
+
+    for (id, block) in blocks.iter().enumerate() {
+        let code: String = instructions[block.start..block.end].iter().map(|i| i.to_string()).collect();
+        let span = spans[block.start];
+        dot.push_str(&format!(
+            "    b{} [label=\"#{} [{}..{}) {}:{}\\n{}\"];\n",
+            id, id, block.start, block.end, span.line, span.column, dot_escape(&code)
+        ));
+    }
+
+    dot.push_str(&format!("    entry -> {};\n", node(0)));
+    for (id, block) in blocks.iter().enumerate() {
+        let last = block.end - 1;
+        match instructions[last] {
+            Instruction::JumpIfZero => {
+                let close = interpreter.jump_target(last).unwrap_or(last);
+                dot.push_str(&format!("    b{} -> {} [label=\"nonzero\"];\n", id, node(block.end)));
+                dot.push_str(&format!("    b{} -> {} [label=\"zero\", style=dashed];\n", id, node(close + 1)));
+            },
+            Instruction::JumpIfNotZero => {
+                let open = interpreter.jump_target(last).unwrap_or(last);
+                dot.push_str(&format!("    b{} -> {} [label=\"loop\", color=blue];\n", id, node(open + 1)));
+                dot.push_str(&format!("    b{} -> {} [label=\"zero\", style=dashed];\n", id, node(block.end)));
+            },
+            Instruction::ProcStart => {
+                let close = interpreter.jump_target(last).unwrap_or(last);
+                dot.push_str(&format!("    b{} -> {} [label=\"skip\", style=dashed];\n", id, node(close + 1)));
+            },
+            // 返回边由调用处给出
+            Instruction::ProcEnd => {},
+            Instruction::Call(n) => {
+                let open = interpreter.procedures()[n as usize];
+                let close = interpreter.jump_target(open).unwrap_or(open);
+                dot.push_str(&format!("    b{} -> {} [label=\"call\", color=darkgreen];\n", id, node(open + 1)));
+                if let Some(end) = blocks.iter().position(|b| b.end == close + 1) {
+                    dot.push_str(&format!("    b{} -> {} [label=\"return\", style=dotted];\n", end, node(block.end)));
+                }
+            },
+            _ => dot.push_str(&format!("    b{} -> {};\n", id, node(block.end))),
+        }
+    }
+
+    dot.push('}');
+    dot
+}
@@ -0,0 +1,215 @@
+//! 有界等价检查 - `derstand equiv a.dr b.dr`在同一组输入上运行两个程序，报告输出或最终纸带的差异
+//!
+//! 每个输入各运行两个程序一次，两边都在步数与时间预算内。输出、是否以错误结束与最终纸带都一致
+//! 才算等价；纸带长度不同时缺少的单元格按0比较。只有一边超出预算算作差异，两边都超出则无法判断。
+//! 这只是在给定输入上的检查，不是证明。
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::diagnostic::{Diagnostic, stderr_color};
+use crate::golden::{self, TestOptions};
+use crate::test_io::TestIo;
+use crate::{DerstandInterpreter, runtime_error};
+
+/// 报告中最多列出的不同单元格数
+const MAX_LISTED_CELLS: usize = 8;
+
+/// 一次有界运行的结果
+#[derive(Debug)]
+pub struct Run {
+    pub output: Vec<u8>,
+    pub memory: Vec<u8>,
+    pub pointer: usize,
+    pub error: Option<Diagnostic>, // 程序自身的运行时错误
+    pub exhausted: Option<String>, // 超出预算时的原因
+}
+
+/// 编译source并在预算内以input运行一次；编译失败时返回编译错误
+pub fn run(source: &str, path: &Path, input: &[u8], budget: TestOptions) -> Result<Run, Diagnostic> {
+    let io = TestIo::new().input(input);
+    let mut interpreter = DerstandInterpreter::new().with_io(&io);
+    interpreter.set_source_path(path);
+    interpreter.compile(source)?;
+    let (error, exhausted) = match golden::execute(&mut interpreter, budget) {
+        Ok(error) => (error.map(|e| runtime_error(&interpreter, source, e)), None),
+        Err(reason) => (None, Some(reason)),
+    };
+    Ok(Run { output: io.output(), memory: interpreter.memory().to_vec(), pointer: interpreter.pointer(), error, exhausted })
+}
+
+/// 两条纸带上值不同的单元格：(下标, a中的值, b中的值)；较短的纸带按0补齐
+pub fn tape_differences(a: &[u8], b: &[u8]) -> Vec<(usize, u8, u8)> {
+    (0..a.len().max(b.len()))
+        .map(|i| (i, a.get(i).copied().unwrap_or(0), b.get(i).copied().unwrap_or(0)))
+        .filter(|&(_, x, y)| x != y)
+        .collect()
+}
+
+/// 第一个不同的输出字节的下标
+pub fn output_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    (0..a.len().max(b.len())).find(|&i| a.get(i) != b.get(i))
+}
+
+/// 输出中某个位置的字节，用于报告
+pub(crate) fn describe_byte(output: &[u8], offset: usize) -> String {
+    output.get(offset).map_or("nothing more".to_string(), |&b| format!("{:?}", b as char))
+}
+
+/// 单元格差异列表，如"#3: 5 vs 7, #9: 0 vs 1"
+pub(crate) fn describe_cells(differences: &[(usize, u8, u8)]) -> String {
+    let mut text = differences.iter().take(MAX_LISTED_CELLS).map(|(i, a, b)| format!("#{}: {} vs {}", i, a, b)).collect::<Vec<_>>().join(", ");
+    if differences.len() > MAX_LISTED_CELLS {
+        let _ = write!(text, ", ... ({} more)", differences.len() - MAX_LISTED_CELLS);
+    }
+    text
+}
+
+/// 两次运行之间的差异，每条一行；都超出预算时返回None表示无法判断
+pub(crate) fn divergences(a: &Run, b: &Run, names: (&str, &str), compare_tape: bool) -> Option<Vec<String>> {
+    match (&a.exhausted, &b.exhausted) {
+        (Some(_), Some(_)) => return None,
+        (Some(reason), None) => return Some(vec![format!("{} stopped ({}) but {} finished", names.0, reason, names.1)]),
+        (None, Some(reason)) => return Some(vec![format!("{} stopped ({}) but {} finished", names.1, reason, names.0)]),
+        (None, None) => {},
+    }
+    let mut found = Vec::new();
+    if let Some(offset) = output_difference(&a.output, &b.output) {
+        found.push(format!(
+            "output differs at byte {}: {} wrote {}, {} wrote {}",
+            offset,
+            names.0,
+            describe_byte(&a.output, offset),
+            names.1,
+            describe_byte(&b.output, offset)
+        ));
+    }
+    match (&a.error, &b.error) {
+        (Some(e), None) => found.push(format!("{} failed with {} ({}) but {} finished", names.0, e.code, e.message, names.1)),
+        (None, Some(e)) => found.push(format!("{} failed with {} ({}) but {} finished", names.1, e.code, e.message, names.0)),
+        _ => {},
+    }
+    let cells = tape_differences(&a.memory, &b.memory);
+    if compare_tape && !cells.is_empty() {
+        found.push(format!("final tape differs in {} cell(s): {}", cells.len(), describe_cells(&cells)));
+    }
+    Some(found)
+}
+
+/// 输入：目录中的所有文件(按名称排序)或单个文件
+fn inputs(path: &Path) -> Result<Vec<PathBuf>, String> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let entries = std::fs::read_dir(path).map_err(|e| format!("Error reading directory {}: {}", path.display(), e))?;
+    let mut files = Vec::new();
+    for entry in entries {
+        let file = entry.map_err(|e| format!("Error reading directory {}: {}", path.display(), e))?.path();
+        if file.is_file() {
+            files.push(file);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// `derstand equiv a.dr b.dr [--inputs DIR] [--steps N] [--timeout MS] [--output-only]`
+pub fn command(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: derstand equiv <a.dr> <b.dr> [--inputs DIR|FILE] [--steps N] [--timeout MS] [--output-only]";
+    let mut budget = TestOptions::default();
+    let mut input_path = None;
+    let mut compare_tape = true;
+    let mut programs = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let positive = |value: Option<&String>| value.and_then(|v| v.parse::<u64>().ok()).filter(|&n| n > 0);
+        let parsed = match arg.as_str() {
+            "--inputs" => iter.next().ok_or("Missing value for --inputs".to_string()).map(|path| input_path = Some(PathBuf::from(path))),
+            "--steps" => positive(iter.next()).map(|n| budget.max_steps = n).ok_or("--steps expects a positive number".to_string()),
+            "--timeout" => positive(iter.next())
+                .map(|n| budget.timeout_ms = n)
+                .ok_or("--timeout expects a positive number of milliseconds".to_string()),
+            "--output-only" => {
+                compare_tape = false;
+                Ok(())
+            },
+            _ if arg.starts_with("--") => Err(format!("Unknown option: {}", arg)),
+            _ => {
+                programs.push(arg.clone());
+                Ok(())
+            },
+        };
+        if let Err(e) = parsed {
+            eprintln!("{}", e);
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    }
+    let [a, b] = programs.as_slice() else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let read = |file: &str| std::fs::read_to_string(file).map_err(|e| eprintln!("Error reading file {}: {}", file, e));
+    let (Ok(source_a), Ok(source_b)) = (read(a), read(b)) else {
+        return 1;
+    };
+    // 没有指定输入时只用空输入检查一次
+    let files = match &input_path {
+        Some(path) => match inputs(path) {
+            Ok(files) if !files.is_empty() => files.into_iter().map(Some).collect(),
+            Ok(_) => {
+                eprintln!("No input files in {}", path.display());
+                return 2;
+            },
+            Err(e) => {
+                eprintln!("{}", e);
+                return 2;
+            },
+        },
+        None => vec![None],
+    };
+
+    let (mut diverged, mut inconclusive) = (0, 0);
+    for file in &files {
+        let label = file.as_ref().map_or("(empty input)".to_string(), |f| f.display().to_string());
+        let input = match file.as_ref().map(std::fs::read).transpose() {
+            Ok(input) => input.unwrap_or_default(),
+            Err(e) => {
+                eprintln!("Error reading input {}: {}", label, e);
+                return 1;
+            },
+        };
+        let compiled = run(&source_a, Path::new(a), &input, budget)
+            .map_err(|e| (e, &source_a, a))
+            .and_then(|run_a| Ok((run_a, run(&source_b, Path::new(b), &input, budget).map_err(|e| (e, &source_b, b))?)));
+        let (run_a, run_b) = match compiled {
+            Ok(runs) => runs,
+            Err((e, source, file)) => {
+                eprintln!("{}", e.render(source, Some(file), stderr_color()));
+                return 1;
+            },
+        };
+        match divergences(&run_a, &run_b, (a, b), compare_tape) {
+            None => {
+                println!("input {} ... inconclusive (both programs exceeded the budget)", label);
+                inconclusive += 1;
+            },
+            Some(found) if found.is_empty() => println!("input {} ... equivalent", label),
+            Some(found) => {
+                println!("input {} ... DIVERGES", label);
+                for line in found {
+                    println!("    {}", line);
+                }
+                diverged += 1;
+            },
+        }
+    }
+    println!(
+        "\nequiv result: {}. {} input(s), {} divergent, {} inconclusive",
+        if diverged == 0 { "equivalent" } else { "DIVERGENT" },
+        files.len(),
+        diverged,
+        inconclusive
+    );
+    if diverged == 0 { 0 } else { 1 }
+}
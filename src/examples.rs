@@ -0,0 +1,81 @@
+//! 内置示例 - `derstand examples`列出随程序附带的示例，`derstand examples NAME`取出源码
+//!
+//! 示例的源码在`src/examples/`下，编译时嵌入，新用户不必另找材料，测试与工具也可以共用：
+//!
+//! ```text
+//! $ derstand examples                       # 名称与说明
+//! $ derstand examples rot13 -o rot13.dr     # 写到文件；不带-o时打印到标准输出
+//! ```
+
+/// 一个内置示例
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Example {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub source: &'static str,
+}
+
+/// 全部内置示例，按介绍的顺序
+pub const EXAMPLES: &[Example] = &[
+    Example { name: "hello", description: "Print \"Hello, world!\"", source: include_str!("examples/hello.dr") },
+    Example { name: "cat", description: "Copy the input to the output", source: include_str!("examples/cat.dr") },
+    Example { name: "rot13", description: "Rotate the letters of the input by 13 places", source: include_str!("examples/rot13.dr") },
+    Example { name: "fibonacci", description: "Print the Fibonacci numbers that fit in a byte", source: include_str!("examples/fibonacci.dr") },
+    Example { name: "sierpinski", description: "Draw a Sierpinski triangle", source: include_str!("examples/sierpinski.dr") },
+    Example { name: "showcase", description: "The # $ % & shortcuts in one program", source: include_str!("examples/showcase.dr") },
+];
+
+/// 按名称查找示例
+pub fn find(name: &str) -> Option<&'static Example> {
+    EXAMPLES.iter().find(|example| example.name == name)
+}
+
+/// `derstand examples [NAME] [-o out]`
+#[cfg(feature = "std")]
+pub fn command(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: derstand examples [NAME] [-o out]";
+    let (mut name, mut output) = (None, None);
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let parsed = match arg.as_str() {
+            "-o" | "--output" => iter.next().map(|path| output = Some(path.clone())).ok_or(format!("Missing value for {}", arg)),
+            _ if arg.starts_with('-') => Err(format!("Unknown option: {}", arg)),
+            _ if name.is_none() => {
+                name = Some(arg.clone());
+                Ok(())
+            },
+            _ => Err(USAGE.to_string()),
+        };
+        if let Err(e) = parsed {
+            eprintln!("{}", e);
+            return 2;
+        }
+    }
+    let Some(name) = name else {
+        if output.is_some() {
+            eprintln!("-o needs the name of an example");
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+        let width = EXAMPLES.iter().map(|example| example.name.len()).max().unwrap_or(0);
+        for example in EXAMPLES {
+            println!("{:width$}  {}", example.name, example.description, width = width);
+        }
+        return 0;
+    };
+    let Some(example) = find(&name) else {
+        let names: Vec<&str> = EXAMPLES.iter().map(|example| example.name).collect();
+        eprintln!("Unknown example '{}' (available: {})", name, names.join(", "));
+        return 1;
+    };
+    match output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, example.source) {
+                eprintln!("Error writing file {}: {}", path, e);
+                return 1;
+            }
+        },
+        None => print!("{}", example.source),
+    }
+    0
+}
@@ -0,0 +1,173 @@
+//! C接口 - 供C、C++与游戏引擎等嵌入解释器，启用`ffi`特性后导出，声明见`include/derstand.h`
+//!
+//! 句柄持有编译好的程序与它的解释器，不能跨线程共享。`derstand_error`返回的字符串
+//! 属于句柄，在下一次调用`derstand_run`或`derstand_free`之前有效。
+//! 动态库由`cargo rustc --release --lib --features ffi --crate-type cdylib`构建。
+
+use std::ffi::{CStr, CString, c_char, c_int, c_void};
+use std::io::{self, Read, Write};
+
+use crate::{DerstandInterpreter, runtime_error};
+
+/// 读取一个字节：返回0到255，输入结束时返回负数
+pub type ReadCallback = unsafe extern "C" fn(user: *mut c_void) -> c_int;
+/// 写出len个字节：成功时返回0
+pub type WriteCallback = unsafe extern "C" fn(user: *mut c_void, data: *const u8, len: usize) -> c_int;
+
+/// 不透明句柄
+pub struct DerstandProgram {
+    interpreter: DerstandInterpreter,
+    source: String,
+    compiled: bool,
+    error: Option<CString>,
+}
+
+impl DerstandProgram {
+    fn set_error(&mut self, message: String) {
+        // 源码片段中可能含有NUL，C字符串无法表示
+        self.error = Some(CString::new(message.replace('\0', "")).expect("NUL bytes were removed"));
+    }
+}
+
+/// 调用者的读回调
+struct HostInput {
+    read: ReadCallback,
+    user: *mut c_void,
+}
+
+impl Read for HostInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // SAFETY: 调用者保证回调与user在句柄的有效期内可用
+        match unsafe { (self.read)(self.user) } {
+            byte @ 0..=255 => {
+                buf[0] = byte as u8;
+                Ok(1)
+            },
+            _ => Ok(0),
+        }
+    }
+}
+
+/// 调用者的写回调
+struct HostOutput {
+    write: WriteCallback,
+    user: *mut c_void,
+}
+
+impl Write for HostOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // SAFETY: 同上；buf在调用期间有效
+        match unsafe { (self.write)(self.user, buf.as_ptr(), buf.len()) } {
+            0 => Ok(buf.len()),
+            status => Err(io::Error::other(format!("write callback failed with status {}", status))),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 编译以NUL结尾的UTF-8源码。source为NULL时返回NULL；编译失败时仍返回句柄，
+/// 错误信息由`derstand_error`取得
+///
+/// # Safety
+///
+/// source必须为NULL或指向以NUL结尾的字符串
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn derstand_compile(source: *const c_char) -> *mut DerstandProgram {
+    if source.is_null() {
+        return std::ptr::null_mut();
+    }
+    // SAFETY: 见函数说明
+    let bytes = unsafe { CStr::from_ptr(source) }.to_bytes();
+    let mut program = DerstandProgram { interpreter: DerstandInterpreter::new(), source: String::new(), compiled: false, error: None };
+    match std::str::from_utf8(bytes) {
+        Ok(source) => {
+            program.source = source.to_string();
+            match program.interpreter.compile(source) {
+                Ok(()) => program.compiled = true,
+                Err(e) => program.set_error(e.render(source, None, false)),
+            }
+        },
+        Err(_) => program.set_error("Source is not valid UTF-8".to_string()),
+    }
+    Box::into_raw(Box::new(program))
+}
+
+/// 最近一次编译或运行的错误信息；没有错误时返回NULL
+///
+/// # Safety
+///
+/// program必须为NULL或`derstand_compile`返回且尚未释放的句柄
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn derstand_error(program: *const DerstandProgram) -> *const c_char {
+    // SAFETY: 见函数说明
+    match unsafe { program.as_ref() }.and_then(|program| program.error.as_ref()) {
+        Some(error) => error.as_ptr(),
+        None => std::ptr::null(),
+    }
+}
+
+/// 设置输入输出回调，user原样传给回调。read为NULL时程序没有输入，write为NULL时写到标准输出；
+/// 提供read时输入结束默认读到0，与交互式模式相同
+///
+/// # Safety
+///
+/// program必须为NULL或有效句柄；回调与user在句柄释放前必须可用
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn derstand_set_io_callbacks(
+    program: *mut DerstandProgram,
+    read: Option<ReadCallback>,
+    write: Option<WriteCallback>,
+    user: *mut c_void,
+) {
+    // SAFETY: 见函数说明
+    let Some(program) = (unsafe { program.as_mut() }) else { return };
+    if let Some(read) = read {
+        program.interpreter.set_input(Box::new(HostInput { read, user }));
+    }
+    if let Some(write) = write {
+        program.interpreter.set_output(Box::new(HostOutput { write, user }));
+    }
+}
+
+/// 从头运行程序(保留上次运行后的纸带)。返回0表示成功，1表示运行时错误，
+/// 2表示程序没有编译成功，-1表示program为NULL
+///
+/// # Safety
+///
+/// program必须为NULL或有效句柄
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn derstand_run(program: *mut DerstandProgram) -> c_int {
+    // SAFETY: 见函数说明
+    let Some(program) = (unsafe { program.as_mut() }) else { return -1 };
+    if !program.compiled {
+        return 2;
+    }
+    program.error = None;
+    match program.interpreter.execute() {
+        Ok(()) => 0,
+        Err(e) => {
+            let message = runtime_error(&program.interpreter, &program.source, e).render(&program.source, None, false);
+            program.set_error(message);
+            1
+        },
+    }
+}
+
+/// 释放句柄；program为NULL时什么也不做
+///
+/// # Safety
+///
+/// program必须为NULL或有效句柄，释放后不能再使用
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn derstand_free(program: *mut DerstandProgram) {
+    if !program.is_null() {
+        // SAFETY: 句柄由derstand_compile中的Box::into_raw创建
+        drop(unsafe { Box::from_raw(program) });
+    }
+}
@@ -0,0 +1,449 @@
+//! 代码格式化 - 按循环深度缩进、同类指令成组、保留注释
+
+use crate::pragma;
+use crate::preprocess::{DIRECTIVE_MARKER, is_ident, is_ident_start};
+use crate::{COMMENT_MARKER, DerstandInterpreter, Dialect, Instruction, SET_MARKER, STRING_MARKER};
+
+/// 内联显示的最内层循环的最大长度(如 `[->+<]`)
+const INLINE_LOOP_WIDTH: usize = 16;
+
+/// 格式化选项
+#[derive(Debug, Clone, Copy)]
+pub struct FormatOptions {
+    pub width: usize,
+    pub indent: usize,
+    pub normalize_comments: bool, // 合并注释中的空白，而非逐行保留
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions { width: 80, indent: 4, normalize_comments: false }
+    }
+}
+
+/// 源码记号
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Code(Instruction),
+    Comment(String), // 连续的非指令字符(含空白)
+    LineComment(String), // ';'到行尾或整个指令行(不含换行)，原样保留
+    Macro(String), // 宏调用(含参数)，作为一组代码
+    Str(String), // 字符串字面量(含引号)，原样保留
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut comment = String::new();
+    let mut macros = Vec::new(); // 已定义的宏名
+    let mut line_start = true;
+    let dialect = pragma::dialect(source);
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == STRING_MARKER && dialect == Dialect::Extended {
+            if !comment.is_empty() {
+                tokens.push(Token::Comment(std::mem::take(&mut comment)));
+            }
+            let mut text = c.to_string();
+            while let Some(c) = chars.next_if(|&c| c != '\n') {
+                text.push(c);
+                if c == '\\' {
+                    text.extend(chars.next_if(|&c| c != '\n'));
+                } else if c == STRING_MARKER {
+                    break;
+                }
+            }
+            line_start = false;
+            tokens.push(Token::Str(text));
+            continue;
+        }
+        if c == COMMENT_MARKER || (line_start && c == DIRECTIVE_MARKER) {
+            // 同一行中';'之前的文字也是注释，与行注释合在一起
+            let split = comment.rfind('\n').map_or(0, |i| i + 1);
+            let prose = comment.split_off(split);
+            if !comment.is_empty() {
+                tokens.push(Token::Comment(std::mem::take(&mut comment)));
+            }
+            let mut text = format!("{}{}", prose.trim_start(), c);
+            while let Some(c) = chars.next_if(|&c| c != '\n') {
+                text.push(c);
+            }
+            if let Some(definition) = text.strip_prefix("!define") {
+                macros.push(definition.trim_start().chars().take_while(|&c| is_ident(c)).collect::<String>());
+            }
+            tokens.push(Token::LineComment(text.trim_end().to_string()));
+            continue;
+        }
+        if is_ident_start(c) {
+            let mut word = c.to_string();
+            while let Some(c) = chars.next_if(|&c| is_ident(c)) {
+                word.push(c);
+            }
+            line_start = false;
+            // 带参数的调用即使宏定义在被包含文件中也必须留在同一行
+            if macros.contains(&word) || chars.peek() == Some(&'(') {
+                if !comment.is_empty() {
+                    tokens.push(Token::Comment(std::mem::take(&mut comment)));
+                }
+                if chars.peek() == Some(&'(') {
+                    let mut depth = 0usize;
+                    while let Some(c) = chars.next_if(|&c| c != '\n') {
+                        word.push(c);
+                        match c {
+                            '(' => depth += 1,
+                            ')' => depth -= 1,
+                            _ => {},
+                        }
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                }
+                tokens.push(Token::Macro(word));
+            } else {
+                comment.push_str(&word);
+            }
+            continue;
+        }
+        if c == '\n' {
+            line_start = true;
+        } else if !c.is_whitespace() {
+            line_start = false;
+        }
+        // ={n} 赋值、=?{n} 断言；其余的'='是注释
+        let assert = c == SET_MARKER && chars.clone().take(2).eq(['?', '{']);
+        if assert {
+            chars.next();
+        }
+        let set = if assert {
+            Some(Instruction::Assert(0))
+        } else {
+            (c == SET_MARKER && chars.peek() == Some(&'{')).then_some(Instruction::Set(0))
+        };
+        match set.or_else(|| Instruction::from_char_in(c, dialect)) {
+            Some(mut instruction) => {
+                if !comment.is_empty() {
+                    tokens.push(Token::Comment(std::mem::take(&mut comment)));
+                }
+                // 重复计数、赋值与调用的花括号数值与指令是一个整体
+                let braced = set.is_some() || instruction == Instruction::CallCell || instruction.repeated(2).is_some();
+                if braced && chars.peek() == Some(&'{') {
+                    let count: String = chars.clone().skip(1).take_while(|&c| c != '}' && c != '\n').collect();
+                    if let Ok(n) = count.trim().parse::<u32>() {
+                        chars.nth(count.chars().count() + 1);
+                        instruction = match instruction {
+                            Instruction::Set(_) => Instruction::Set(n.min(255) as u8),
+                            Instruction::Assert(_) => Instruction::Assert(n.min(255) as u8),
+                            Instruction::CallCell => Instruction::Call(n),
+                            _ => instruction.repeated(n).unwrap_or(instruction),
+                        };
+                    }
+                }
+                tokens.push(Token::Code(instruction));
+            },
+            None => comment.push(c),
+        }
+    }
+    if !comment.is_empty() {
+        tokens.push(Token::Comment(comment));
+    }
+    tokens
+}
+
+/// 显示宽度(按字符计)
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// 逐行构造输出
+struct Printer {
+    options: FormatOptions,
+    lines: Vec<String>,
+    current: String,
+    depth: usize,
+}
+
+impl Printer {
+    fn indent(&self) -> String {
+        " ".repeat(self.depth * self.options.indent)
+    }
+
+    fn flush(&mut self) {
+        if !self.current.is_empty() {
+            let line = format!("{}{}", self.indent(), std::mem::take(&mut self.current));
+            self.lines.push(line);
+        }
+    }
+
+    fn line(&mut self, text: &str) {
+        self.flush();
+        let line = format!("{}{}", self.indent(), text);
+        self.lines.push(line.trim_end().to_string());
+    }
+
+    fn blank(&mut self) {
+        self.flush();
+        if self.lines.last().is_some_and(|l| !l.is_empty()) {
+            self.lines.push(String::new());
+        }
+    }
+
+    /// 追加一组代码，超出行宽时换行；过长的组按行宽切分
+    fn atom(&mut self, text: &str) {
+        let available = self.options.width.saturating_sub(self.depth * self.options.indent).max(8);
+        let mut rest = text;
+        while !rest.is_empty() {
+            let needed = if self.current.is_empty() { 0 } else { 1 };
+            if !self.current.is_empty() && width(&self.current) + needed + width(rest) > available {
+                self.flush();
+                continue;
+            }
+            if self.current.is_empty() && width(rest) > available {
+                // 超长的指令组或注释单词在行宽处切分
+                let split = rest.char_indices().nth(available).map_or(rest.len(), |(i, _)| i);
+                let (head, tail) = rest.split_at(split);
+                self.current.push_str(head);
+                self.flush();
+                rest = tail;
+                continue;
+            }
+            if needed == 1 {
+                self.current.push(' ');
+            }
+            self.current.push_str(rest);
+            rest = "";
+        }
+    }
+
+    /// 追加一个不可切分的整体(字符串、宏调用、带花括号数值的指令)，超出行宽时换行
+    fn unit(&mut self, text: &str) {
+        let available = self.options.width.saturating_sub(self.depth * self.options.indent).max(8);
+        if !self.current.is_empty() && width(&self.current) + 1 + width(text) > available {
+            self.flush();
+        }
+        if !self.current.is_empty() {
+            self.current.push(' ');
+        }
+        self.current.push_str(text);
+    }
+
+    /// 行注释跟在同一行的代码之后，否则独占一行；指令行总是独占一行
+    fn line_comment(&mut self, text: &str) {
+        if self.current.is_empty() || text.starts_with(DIRECTIVE_MARKER) {
+            self.line(text);
+        } else {
+            self.current.push(' ');
+            self.current.push_str(text);
+            self.flush();
+        }
+    }
+
+    fn comment(&mut self, text: &str) {
+        // 注释前后的空白中含两个以上换行视为段落分隔
+        let breaks = |ws: &str| ws.matches('\n').count() >= 2;
+        let body = text.trim();
+        if body.is_empty() {
+            if breaks(text) {
+                self.blank();
+            }
+            return;
+        }
+        if breaks(&text[..text.find(body).unwrap_or(0)]) {
+            self.blank();
+        }
+        if self.options.normalize_comments {
+            self.flush();
+            for word in body.split_whitespace() {
+                self.atom(word);
+            }
+            self.flush();
+        } else {
+            for line in body.lines().map(str::trim).filter(|l| !l.is_empty()) {
+                self.line(line);
+            }
+        }
+        if breaks(&text[text.find(body).unwrap_or(0) + body.len()..]) {
+            self.blank();
+        }
+    }
+}
+
+/// 若tokens[start]处的循环足够短且不含嵌套与注释，返回它的长度
+fn inline_loop(tokens: &[Token], start: usize) -> Option<usize> {
+    let mut text = 0;
+    for (offset, token) in tokens[start..].iter().enumerate() {
+        match token {
+            Token::Code(Instruction::JumpIfZero) if offset > 0 => return None,
+            Token::Code(Instruction::JumpIfNotZero) => return Some(offset + 1),
+            Token::Code(c) => text += width(&c.to_string()),
+            Token::Comment(_) | Token::LineComment(_) | Token::Macro(_) | Token::Str(_) => return None,
+        }
+        if text > INLINE_LOOP_WIDTH {
+            return None;
+        }
+    }
+    None
+}
+
+/// 格式化源码；结果的指令序列与原程序完全一致
+pub fn format_source(source: &str, options: FormatOptions) -> String {
+    let tokens = tokenize(source);
+    let mut printer = Printer { options, lines: Vec::new(), current: String::new(), depth: 0 };
+
+    let mut i = 0;
+    while i < tokens.len() {
+        match &tokens[i] {
+            Token::Comment(text) => printer.comment(text),
+            Token::LineComment(text) => printer.line_comment(text),
+            Token::Macro(text) | Token::Str(text) => printer.unit(text),
+            Token::Code(Instruction::JumpIfZero) => {
+                if let Some(len) = inline_loop(&tokens, i) {
+                    let text: String = tokens[i..i + len]
+                        .iter()
+                        .filter_map(|t| if let Token::Code(c) = t { Some(c.to_string()) } else { None })
+                        .collect();
+                    printer.unit(&text);
+                    i += len;
+                    continue;
+                }
+                printer.line("[");
+                printer.depth += 1;
+            },
+            Token::Code(Instruction::JumpIfNotZero) => {
+                printer.flush();
+                printer.depth = printer.depth.saturating_sub(1);
+                printer.line("]");
+            },
+            // 过程定义与循环一样缩进
+            Token::Code(Instruction::ProcStart) => {
+                printer.line("{");
+                printer.depth += 1;
+            },
+            Token::Code(Instruction::ProcEnd) => {
+                printer.flush();
+                printer.depth = printer.depth.saturating_sub(1);
+                printer.line("}");
+            },
+            Token::Code(instruction) => {
+                // 同类指令成组；带花括号数值的指令单独成组
+                let mut run = String::new();
+                while let Some(Token::Code(c)) = tokens.get(i) {
+                    if c != instruction || (!run.is_empty() && c.to_string().len() > 1) {
+                        break;
+                    }
+                    run.push_str(&c.to_string());
+                    i += 1;
+                }
+                if run.contains('{') { printer.unit(&run) } else { printer.atom(&run) }
+                continue;
+            },
+        }
+        i += 1;
+    }
+    printer.flush();
+    while printer.lines.last().is_some_and(|l| l.is_empty()) {
+        printer.lines.pop();
+    }
+
+    let mut out = printer.lines.join("\n");
+    out.push('\n');
+    out
+}
+
+/// 校验格式化没有改变程序
+fn same_program(a: &str, b: &str) -> bool {
+    let code = |s: &str| {
+        tokenize(s)
+            .into_iter()
+            .filter_map(|t| match t {
+                Token::Code(c) => Some(c.to_string()),
+                Token::Str(text) => Some(text),
+                _ => None,
+            })
+            .collect::<String>()
+    };
+    code(a) == code(b)
+}
+
+/// `derstand fmt [--check] [--width N] [--indent N] [--normalize-comments] [file...]`
+pub fn command(args: &[String]) -> i32 {
+    let mut options = FormatOptions::default();
+    let mut check = false;
+    let mut files = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let number = |value: Option<&String>| value.and_then(|v| v.parse::<usize>().ok());
+        match arg.as_str() {
+            "--check" => check = true,
+            "--normalize-comments" => options.normalize_comments = true,
+            "--width" | "--indent" => match number(iter.next()) {
+                Some(n) if arg == "--width" => options.width = n,
+                Some(n) => options.indent = n,
+                None => {
+                    eprintln!("{} expects a number", arg);
+                    return 2;
+                },
+            },
+            _ if arg.starts_with("--") => {
+                eprintln!("Unknown option: {}", arg);
+                return 2;
+            },
+            _ => files.push(arg.clone()),
+        }
+    }
+
+    // 无文件时从标准输入读取并写到标准输出
+    if files.is_empty() {
+        let mut source = String::new();
+        if let Err(e) = std::io::Read::read_to_string(&mut std::io::stdin(), &mut source) {
+            eprintln!("Error reading stdin: {}", e);
+            return 1;
+        }
+        if let Err(e) = DerstandInterpreter::new().compile(&source) {
+            eprintln!("<stdin>: {}", e);
+            return 1;
+        }
+        let formatted = format_source(&source, options);
+        if check {
+            return if formatted == source { 0 } else { 1 };
+        }
+        print!("{}", formatted);
+        return 0;
+    }
+
+    let mut status = 0;
+    for file in &files {
+        let source = match std::fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("Error reading file {}: {}", file, e);
+                status = 1;
+                continue;
+            },
+        };
+        // 括号不匹配的程序无法可靠缩进
+        let mut interpreter = DerstandInterpreter::new();
+        interpreter.set_source_path(file);
+        if let Err(e) = interpreter.compile(&source) {
+            eprintln!("{}: {}", file, e);
+            status = 1;
+            continue;
+        }
+        let formatted = format_source(&source, options);
+        if !same_program(&source, &formatted) {
+            eprintln!("{}: internal formatter error, file left unchanged", file);
+            status = 1;
+            continue;
+        }
+        if formatted == source {
+            continue;
+        }
+        if check {
+            println!("{}", file);
+            status = 1;
+        } else if let Err(e) = std::fs::write(file, &formatted) {
+            eprintln!("Error writing file {}: {}", file, e);
+            status = 1;
+        }
+    }
+    status
+}
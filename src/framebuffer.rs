@@ -0,0 +1,62 @@
+//! 帧缓冲 - `--framebuffer WxH[+OFFSET] out.png`把纸带上的一段当作W×H的灰度图像输出
+//!
+//! 从OFFSET(默认0)开始的W×H个单元格逐行排列，单元格的值就是像素亮度(0黑、255白)。
+//! 程序执行`▣`(present)时写出当前画面，覆盖上一帧；运行正常结束时再写出一次，不用`▣`的程序也有输出：
+//!
+//! ```text
+//! $ derstand run --framebuffer 64x64+16 mandel.png mandel.dr
+//! ```
+//!
+//! 文件格式按扩展名选择：`.png`、`.ppm`或`.pgm`。
+
+use std::path::PathBuf;
+
+use crate::DerstandInterpreter;
+use crate::image::{self, Format};
+
+/// 画面符号
+pub const PRESENT: char = '▣';
+
+/// 帧缓冲的位置与输出文件
+#[derive(Debug, Clone, PartialEq)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pub offset: usize,
+    pub path: PathBuf,
+    format: Format,
+}
+
+impl Framebuffer {
+    /// 解析`WxH[+OFFSET]`与输出路径
+    pub fn parse(geometry: &str, path: &str) -> Result<Self, String> {
+        let invalid = || format!("--framebuffer expects WxH or WxH+OFFSET, got '{}'", geometry);
+        let (size, offset) = geometry.split_once('+').unwrap_or((geometry, "0"));
+        let (width, height) = size.split_once(['x', 'X']).ok_or_else(invalid)?;
+        let positive = |text: &str| text.parse::<usize>().ok().filter(|&n| n > 0).ok_or_else(invalid);
+        let (width, height) = (positive(width)?, positive(height)?);
+        let offset = offset.parse::<usize>().map_err(|_| invalid())?;
+        Ok(Framebuffer { width, height, offset, path: PathBuf::from(path), format: Format::from_path(path)? })
+    }
+
+    /// 整个区域都在memory之内时编码为图像文件
+    pub fn encode(&self, memory: &[u8]) -> Result<Vec<u8>, String> {
+        let end = self.width.checked_mul(self.height).and_then(|n| n.checked_add(self.offset));
+        let Some(pixels) = end.and_then(|end| memory.get(self.offset..end)) else {
+            return Err(format!("The {}x{} framebuffer at cell {} does not fit on the tape of {} cells", self.width, self.height, self.offset, memory.len()));
+        };
+        Ok(image::encode_gray(self.format, self.width, self.height, pixels))
+    }
+
+    /// 写出memory中的当前画面
+    pub fn present(&self, memory: &[u8]) -> Result<(), String> {
+        let data = self.encode(memory)?;
+        std::fs::write(&self.path, data).map_err(|e| format!("Error writing framebuffer {}: {}", self.path.display(), e))
+    }
+
+    /// 在interpreter上注册`▣`；应在编译之前调用
+    pub fn attach(&self, interpreter: &mut DerstandInterpreter) -> Result<(), String> {
+        let framebuffer = self.clone();
+        interpreter.register_instruction(PRESENT, "present", Box::new(move |machine| framebuffer.present(machine.memory())))
+    }
+}
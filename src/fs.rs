@@ -0,0 +1,149 @@
+//! 文件读写 - `--allow-fs [dir]`开启的一组`§`宿主函数，只能访问dir(默认当前目录)之内的文件
+//!
+//! 与其他宿主函数一样，程序把函数编号放在当前单元格，参数与结果放在之后的单元格里，再执行`§`(扩展方言)：
+//!
+//! ```text
+//! 240 open   [模式, 句柄, 路径..., 0]   模式为'r'读、'w'写(截断)或'a'追加；成功时句柄为1..=255，失败为0
+//! 241 read   [句柄, 状态, 字节]         读一个字节；状态0成功、1文件结束、2出错
+//! 242 write  [句柄, 状态, 字节]         写一个字节；状态0成功、2出错
+//! 243 close  [句柄, 状态]               关闭并写出缓冲；状态0成功、2出错
+//! ```
+//!
+//! 路径相对于dir，以0结束，最长255字节；不能是绝对路径，不能含`..`，文件本身不能是符号链接，
+//! 路径中的目录也不能经符号链接离开dir。
+//! 打不开的文件与无效的句柄只体现在结果中，不会中止程序。沙箱禁用一切宿主调用，因此不能与`--sandbox`同用。
+
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+
+use crate::DerstandInterpreter;
+
+/// 各函数的编号
+pub const OPEN: u8 = 240;
+pub const READ: u8 = 241;
+pub const WRITE: u8 = 242;
+pub const CLOSE: u8 = 243;
+
+/// 路径的最大字节数(不含结尾的0)
+pub const MAX_PATH: usize = 255;
+
+/// 同时打开的文件数上限：句柄占一个单元格，0表示失败
+const MAX_HANDLES: usize = 255;
+
+/// 读写状态
+const OK: u8 = 0;
+const END: u8 = 1;
+const FAILED: u8 = 2;
+
+enum Handle {
+    Read(BufReader<File>),
+    Write(BufWriter<File>),
+}
+
+/// 一个宿主函数的实现：读写窗口
+type Operation = fn(&mut Files, &mut [u8]);
+
+struct Files {
+    root: PathBuf,                // 已规范化的根目录
+    handles: Vec<Option<Handle>>, // 下标加1即句柄
+}
+
+impl Files {
+    /// 解析程序给出的路径；离开根目录时返回None
+    fn resolve(&self, path: &[u8]) -> Option<PathBuf> {
+        let relative = Path::new(std::str::from_utf8(path).ok()?);
+        if path.is_empty() || relative.components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return None;
+        }
+        let joined = self.root.join(relative);
+        // 最后一段不能是符号链接：悬空的链接指向根目录之外时，写入会在那里创建文件
+        if std::fs::symlink_metadata(&joined).is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+            return None;
+        }
+        // 中间的目录可能是指向根目录之外的符号链接，按规范化后的真实路径检查
+        let resolved = joined.parent()?.canonicalize().ok()?.join(joined.file_name()?);
+        resolved.starts_with(&self.root).then_some(resolved)
+    }
+
+    fn open(&mut self, window: &mut [u8]) {
+        // 没有结尾0的路径超过了长度上限
+        let path = &window[2..];
+        let path = path.iter().position(|&b| b == 0).and_then(|end| self.resolve(&path[..end]));
+        let file = path.and_then(|path| match window[0] {
+            b'r' => File::open(path).ok().map(|f| Handle::Read(BufReader::new(f))),
+            b'w' => File::create(path).ok().map(|f| Handle::Write(BufWriter::new(f))),
+            b'a' => OpenOptions::new().append(true).create(true).open(path).ok().map(|f| Handle::Write(BufWriter::new(f))),
+            _ => None,
+        });
+        window[1] = match file {
+            Some(file) => match self.handles.iter().position(Option::is_none) {
+                Some(free) => {
+                    self.handles[free] = Some(file);
+                    free as u8 + 1
+                },
+                None if self.handles.len() < MAX_HANDLES => {
+                    self.handles.push(Some(file));
+                    self.handles.len() as u8
+                },
+                None => 0,
+            },
+            None => 0,
+        };
+    }
+
+    fn handle(&mut self, handle: u8) -> Option<&mut Handle> {
+        self.handles.get_mut((handle as usize).checked_sub(1)?)?.as_mut()
+    }
+
+    fn read(&mut self, window: &mut [u8]) {
+        let mut byte = [0];
+        window[1] = match self.handle(window[0]) {
+            Some(Handle::Read(file)) => match file.read(&mut byte) {
+                Ok(0) => END,
+                Ok(_) => OK,
+                Err(_) => FAILED,
+            },
+            _ => FAILED,
+        };
+        window[2] = byte[0];
+    }
+
+    fn write(&mut self, window: &mut [u8]) {
+        let byte = window[2];
+        window[1] = match self.handle(window[0]) {
+            Some(Handle::Write(file)) => if file.write_all(&[byte]).is_ok() { OK } else { FAILED },
+            _ => FAILED,
+        };
+    }
+
+    fn close(&mut self, window: &mut [u8]) {
+        let slot = (window[0] as usize).checked_sub(1).and_then(|i| self.handles.get_mut(i));
+        window[1] = match slot.and_then(Option::take) {
+            Some(Handle::Write(mut file)) => if file.flush().is_ok() { OK } else { FAILED },
+            Some(Handle::Read(_)) => OK,
+            None => FAILED,
+        };
+    }
+}
+
+/// 注册文件读写宿主函数，访问限制在root之内
+pub fn enable(interpreter: &mut DerstandInterpreter, root: &Path) -> Result<(), String> {
+    let root = root.canonicalize().map_err(|e| format!("Cannot use {} for --allow-fs: {}", root.display(), e))?;
+    if !root.is_dir() {
+        return Err(format!("Cannot use {} for --allow-fs: not a directory", root.display()));
+    }
+    let files = Rc::new(RefCell::new(Files { root, handles: Vec::new() }));
+    let functions: [(u8, usize, Operation); 4] =
+        [(OPEN, MAX_PATH + 3, Files::open), (READ, 3, Files::read), (WRITE, 3, Files::write), (CLOSE, 2, Files::close)];
+    for (number, window, function) in functions {
+        let files = Rc::clone(&files);
+        interpreter.register_host_function(number, window, move |cells| {
+            function(&mut files.borrow_mut(), cells);
+            Ok(())
+        });
+    }
+    Ok(())
+}
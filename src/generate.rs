@@ -0,0 +1,161 @@
+//! 随机程序生成 - `derstand gen --size N --seed S`生成括号配对、语法正确的随机程序
+//!
+//! 用于模糊测试解释器、性能测试和课堂练习。指令按权重抽取，`--mix`修改权重，`[`的权重决定循环有多常见：
+//!
+//! ```text
+//! $ derstand gen --size 200 --seed 7
+//! $ derstand gen --size 1000 --mix '+:5,-:5,>:3,<:3,[:2,.:1,(:1,):1' --depth 4 -o fuzz.dr
+//! ```
+//!
+//! 生成的程序恰好有N条指令，不含空循环`[]`；程序不保证会结束。同一种子总是生成同一个程序。
+//! 用到`~`等扩展指令时程序以`;! dialect=extended`开头。
+
+use crate::Instruction;
+use crate::pragma::EXTENDED_DIALECT;
+use crate::random::Rng;
+
+/// 默认的指令权重：不读输入、不依赖时钟，结果只取决于种子
+pub const DEFAULT_MIX: &[(char, u32)] = &[('+', 6), ('-', 4), ('>', 4), ('<', 3), ('.', 2), ('[', 2), ('#', 1), ('$', 1), ('~', 1)];
+
+/// 默认的最大循环嵌套深度
+pub const DEFAULT_DEPTH: usize = 3;
+
+/// 每行的指令数
+const LINE_WIDTH: usize = 64;
+
+/// 生成选项
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub size: usize,
+    pub seed: u64,
+    pub mix: Vec<(char, u32)>,
+    pub depth: usize,
+}
+
+/// 解析`--mix`：逗号分隔的`符号:权重`；符号总是一个字符，所以`,:1`与`::1`也能解析
+pub fn parse_mix(text: &str) -> Result<Vec<(char, u32)>, String> {
+    let invalid = || format!("--mix expects entries like '+:4,-:2', got '{}'", text);
+    let mut mix = Vec::new();
+    let mut rest = text.trim();
+    while let Some(symbol) = rest.chars().next() {
+        let entry = rest[symbol.len_utf8()..].strip_prefix(':').ok_or_else(invalid)?;
+        let digits = entry.find(|c: char| !c.is_ascii_digit()).unwrap_or(entry.len());
+        let weight = entry[..digits].parse::<u32>().map_err(|_| invalid())?;
+        rest = match entry[digits..].strip_prefix(',') {
+            Some(next) => next.trim_start(),
+            None if entry[digits..].trim().is_empty() => "",
+            None => return Err(invalid()),
+        };
+        // 循环由'['成对生成，过程定义需要配对的大括号
+        if Instruction::from_char(symbol).is_none() || matches!(symbol, ']' | '{' | '}') {
+            return Err(format!("'{}' cannot be used in --mix", symbol));
+        }
+        mix.push((symbol, weight));
+    }
+    if mix.iter().all(|&(symbol, weight)| symbol == '[' || weight == 0) {
+        return Err("--mix needs at least one instruction other than '[' with a positive weight".to_string());
+    }
+    Ok(mix)
+}
+
+/// 按权重抽取一个符号
+fn pick(rng: &mut Rng, mix: &[(char, u32)], total: u64) -> char {
+    let mut roll = rng.next_u64() % total;
+    for &(symbol, weight) in mix {
+        if roll < weight as u64 {
+            return symbol;
+        }
+        roll -= weight as u64;
+    }
+    unreachable!("the roll is below the total weight")
+}
+
+/// 生成恰好size条指令的程序
+pub fn generate(options: &Options) -> String {
+    let mut rng = Rng::new(options.seed);
+    let mix: Vec<(char, u32)> = options.mix.iter().copied().filter(|&(_, weight)| weight > 0).collect();
+    let plain: Vec<(char, u32)> = mix.iter().copied().filter(|&(symbol, _)| symbol != '[').collect();
+    let (total, plain_total) = (mix.iter().map(|&(_, w)| w as u64).sum::<u64>(), plain.iter().map(|&(_, w)| w as u64).sum::<u64>());
+    let loop_weight = total - plain_total;
+
+    let mut program = Vec::with_capacity(options.size);
+    let mut open = 0;
+    let mut fresh = false; // 上一条是'['，循环体还是空的
+    while program.len() < options.size {
+        let remaining = options.size - program.len();
+        let symbol = if remaining == open {
+            ']'
+        } else if open > 0 && !fresh && rng.next_u64() % (total + loop_weight) < loop_weight {
+            // 关闭循环与打开循环一样常见
+            ']'
+        } else {
+            let symbol = pick(&mut rng, &mix, total);
+            // 新循环至少要放下'['、一条循环体与']'
+            if symbol == '[' && (open >= options.depth || remaining < open + 3) { pick(&mut rng, &plain, plain_total) } else { symbol }
+        };
+        match symbol {
+            '[' => open += 1,
+            ']' => open -= 1,
+            _ => {},
+        }
+        fresh = symbol == '[';
+        program.push(symbol);
+    }
+    let extended = program.iter().any(|&symbol| Instruction::from_char(symbol).is_some_and(Instruction::is_extended));
+    let lines: Vec<String> = program.chunks(LINE_WIDTH).map(|line| line.iter().collect()).collect();
+    format!("{}{}", if extended { EXTENDED_DIALECT } else { "" }, lines.join("\n"))
+}
+
+/// `derstand gen [--size N] [--seed S] [--mix LIST] [--depth D] [-o out]`
+pub fn command(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: derstand gen [--size N] [--seed S] [--mix SYMBOL:WEIGHT,...] [--depth D] [-o out]";
+    let mut options = Options { size: 100, seed: Rng::entropy_seed(), mix: DEFAULT_MIX.to_vec(), depth: DEFAULT_DEPTH };
+    let mut output = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let Some(value) = iter.next() else {
+            eprintln!("{}", if arg.starts_with('-') { format!("Missing value for {}", arg) } else { USAGE.to_string() });
+            return 2;
+        };
+        let parsed = match arg.as_str() {
+            "--size" => value.parse().map(|n| options.size = n).map_err(|_| "--size expects a number of instructions".to_string()),
+            "--seed" => value.parse().map(|n| options.seed = n).map_err(|_| "--seed expects a number".to_string()),
+            "--depth" => value.parse().map(|n| options.depth = n).map_err(|_| "--depth expects a number".to_string()),
+            "--mix" => parse_mix(value).map(|mix| options.mix = mix),
+            "-o" | "--output" => {
+                output = Some(value.clone());
+                Ok(())
+            },
+            _ => Err(format!("Unknown option: {}", arg)),
+        };
+        if let Err(e) = parsed {
+            eprintln!("{}", e);
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    }
+    // 记下全部选项，便于重现
+    let mix: Vec<String> = options.mix.iter().map(|(symbol, weight)| format!("{}:{}", symbol, weight)).collect();
+    let generated = generate(&options);
+    // 编译指示必须在文件开头，放在这行注释之前
+    let (pragma, code) = generated.split_at(if generated.starts_with(EXTENDED_DIALECT) { EXTENDED_DIALECT.len() } else { 0 });
+    let program = format!(
+        "{}; derstand gen --size {} --seed {} --depth {} --mix '{}'\n{}\n",
+        pragma,
+        options.size,
+        options.seed,
+        options.depth,
+        mix.join(","),
+        code
+    );
+    match output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, &program) {
+                eprintln!("Error writing file {}: {}", path, e);
+                return 1;
+            }
+        },
+        None => print!("{}", program),
+    }
+    0
+}
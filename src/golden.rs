@@ -0,0 +1,325 @@
+//! 黄金文件测试 - `derstand test`运行目录下的`*.dr`程序并与`*.expected`比较输出
+//!
+//! 有同名`.expected`文件或期望注释的`.dr`文件是一个测试；同名`.in`文件(可选)是它的输入，
+//! 没有时输入为空。程序在步数与时间上限内运行，`\r\n`按`\n`比较，末尾的一个换行不计。
+//! 程序中的`=?{n}`断言生效。超限总是失败。
+//!
+//! 期望注释让单个文件自带检查，每条占一行：
+//!
+//! ```text
+//! ;; expect-output: Hello    期望输出的一行，多条按顺序组成多行
+//! ;; expect-exit: 1          期望的退出码：0为正常结束(默认)，1为编译或运行时错误
+//! ```
+//!
+//! 同时有`.expected`文件与`expect-output`时两者都要满足。
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::cancel::CancelToken;
+use crate::diagnostic::{Diagnostic, stdout_color};
+use crate::test_io::TestIo;
+use crate::{DerstandInterpreter, runtime_error};
+
+/// 每个测试的默认步数上限
+pub const DEFAULT_MAX_STEPS: u64 = 100_000_000;
+
+/// 每个测试的默认时间上限(毫秒)
+pub const DEFAULT_TIMEOUT_MS: u64 = 5_000;
+
+/// 失败时最多显示的差异行数
+const MAX_DIFF_LINES: usize = 10;
+
+/// 期望注释的前缀
+const EXPECT_PREFIX: &str = "expect-";
+
+/// 一个测试：程序、可选的期望输出文件与输入
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCase {
+    pub program: PathBuf,
+    pub expected: Option<PathBuf>,
+    pub input: Option<PathBuf>,
+}
+
+/// 源码中期望注释给出的检查
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Expectations {
+    pub output: Option<Vec<u8>>,
+    pub exit: i32,
+}
+
+/// 期望注释的内容：`;; expect-KEY: VALUE`中的KEY与VALUE
+fn expectation(line: &str) -> Option<(&str, &str)> {
+    let rest = line.trim_start().strip_prefix(";;")?.trim_start().strip_prefix(EXPECT_PREFIX)?;
+    let (key, value) = rest.split_once(':')?;
+    Some((key.trim(), value.strip_prefix(' ').unwrap_or(value).trim_end_matches('\r')))
+}
+
+/// 解析源码中的期望注释
+pub fn parse_expectations(source: &str) -> Result<Expectations, String> {
+    let mut expectations = Expectations::default();
+    for (number, line) in source.lines().enumerate() {
+        let Some((key, value)) = expectation(line) else {
+            continue;
+        };
+        match key {
+            "output" => {
+                let output = expectations.output.get_or_insert_with(Vec::new);
+                if !output.is_empty() {
+                    output.push(b'\n');
+                }
+                output.extend_from_slice(value.as_bytes());
+            },
+            "exit" => {
+                expectations.exit = match value.trim() {
+                    "0" => 0,
+                    "1" => 1,
+                    other => return Err(format!("line {}: expect-exit must be 0 or 1, found '{}'", number + 1, other)),
+                };
+            },
+            other => return Err(format!("line {}: unknown expectation 'expect-{}' (expected expect-output or expect-exit)", number + 1, other)),
+        }
+    }
+    Ok(expectations)
+}
+
+/// 运行选项
+#[derive(Debug, Clone, Copy)]
+pub struct TestOptions {
+    pub max_steps: u64,
+    pub timeout_ms: u64,
+}
+
+impl Default for TestOptions {
+    fn default() -> Self {
+        TestOptions { max_steps: DEFAULT_MAX_STEPS, timeout_ms: DEFAULT_TIMEOUT_MS }
+    }
+}
+
+/// 递归查找path下的测试，按路径排序；path也可以直接是一个`.dr`文件
+pub fn discover(path: &Path) -> Result<Vec<TestCase>, String> {
+    if !path.is_dir() {
+        return case_for(path)
+            .map(|case| vec![case])
+            .ok_or_else(|| format!("{} is not a directory or a .dr file with a .expected file or expect- comments", path.display()));
+    }
+    Ok(programs(path)?.iter().filter_map(|program| case_for(program)).collect())
+}
+
+/// 递归查找directory下的`.dr`文件，按路径排序
+pub(crate) fn programs(directory: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut found = Vec::new();
+    visit(directory, &mut found)?;
+    found.sort();
+    Ok(found)
+}
+
+fn visit(directory: &Path, found: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = std::fs::read_dir(directory).map_err(|e| format!("Error reading directory {}: {}", directory.display(), e))?;
+    for entry in entries {
+        let path = entry.map_err(|e| format!("Error reading directory {}: {}", directory.display(), e))?.path();
+        if path.is_dir() {
+            // 隐藏目录与构建输出里没有要找的程序
+            let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            if !name.starts_with('.') && name != "target" {
+                visit(&path, found)?;
+            }
+        } else if path.extension().is_some_and(|e| e == "dr") {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// 有`.expected`文件或期望注释的`.dr`文件是一个测试
+fn case_for(program: &Path) -> Option<TestCase> {
+    if program.extension()? != "dr" {
+        return None;
+    }
+    let expected = Some(program.with_extension("expected")).filter(|p| p.is_file());
+    if expected.is_none() && !std::fs::read_to_string(program).is_ok_and(|source| source.lines().any(|l| expectation(l).is_some())) {
+        return None;
+    }
+    let input = Some(program.with_extension("in")).filter(|p| p.is_file());
+    Some(TestCase { program: program.to_path_buf(), expected, input })
+}
+
+/// 比较用的输出：`\r\n`换成`\n`并去掉末尾的一个换行
+fn normalize(bytes: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(bytes.len());
+    for (i, &b) in bytes.iter().enumerate() {
+        if !(b == b'\r' && bytes.get(i + 1) == Some(&b'\n')) {
+            normalized.push(b);
+        }
+    }
+    if normalized.last() == Some(&b'\n') {
+        normalized.pop();
+    }
+    normalized
+}
+
+/// 逐行对比，列出不同的行
+pub(crate) fn diff(expected: &[u8], actual: &[u8]) -> String {
+    let expected = String::from_utf8_lossy(expected);
+    let actual = String::from_utf8_lossy(actual);
+    let (expected, actual): (Vec<&str>, Vec<&str>) = (expected.split('\n').collect(), actual.split('\n').collect());
+    let mut out = String::new();
+    let mut shown = 0;
+    for line in 0..expected.len().max(actual.len()) {
+        let (e, a) = (expected.get(line), actual.get(line));
+        if e == a {
+            continue;
+        }
+        if shown == MAX_DIFF_LINES {
+            out.push_str("    ...\n");
+            break;
+        }
+        out.push_str(&format!("    line {}:\n", line + 1));
+        match e {
+            Some(e) => out.push_str(&format!("      - {:?}\n", e)),
+            None => out.push_str("      - (no line)\n"),
+        }
+        match a {
+            Some(a) => out.push_str(&format!("      + {:?}\n", a)),
+            None => out.push_str("      + (no line)\n"),
+        }
+        shown += 1;
+    }
+    out
+}
+
+/// 运行一个测试；失败时返回要显示的说明
+pub fn run_case(case: &TestCase, options: TestOptions) -> Result<(), String> {
+    let read = |path: &Path| std::fs::read(path).map_err(|e| format!("Error reading {}: {}", path.display(), e));
+    let source = String::from_utf8(read(&case.program)?).map_err(|_| format!("{} is not valid UTF-8", case.program.display()))?;
+    let expectations = parse_expectations(&source)?;
+    let input = match &case.input {
+        Some(path) => read(path)?,
+        None => Vec::new(),
+    };
+
+    let file = case.program.display().to_string();
+    let io = TestIo::new().input(&input);
+    let mut interpreter = DerstandInterpreter::new().with_io(&io);
+    interpreter.set_source_path(&case.program);
+    interpreter.set_assertions(true);
+    // 出错的程序退出码为1，与命令行一致
+    let error = match interpreter.compile(&source) {
+        Ok(()) => execute(&mut interpreter, options)?.map(|e| runtime_error(&interpreter, &source, e)),
+        Err(e) => Some(e),
+    };
+    match (&error, expectations.exit) {
+        (Some(e), 0) => return Err(e.render(&source, Some(&file), stdout_color())),
+        (None, 1) => return Err("expected the program to fail (expect-exit: 1), but it finished normally".to_string()),
+        _ => {},
+    }
+
+    let actual = normalize(&io.output());
+    if let Some(path) = &case.expected {
+        let expected = normalize(&read(path)?);
+        if expected != actual {
+            return Err(format!("output differs from {}\n{}", path.display(), diff(&expected, &actual).trim_end()));
+        }
+    }
+    if let Some(expected) = &expectations.output {
+        let expected = normalize(expected);
+        if expected != actual {
+            return Err(format!("output differs from the expect-output comments\n{}", diff(&expected, &actual).trim_end()));
+        }
+    }
+    Ok(())
+}
+
+/// 在上限内运行已编译的程序；超限时返回Err，程序自身的运行时错误放在Ok中
+pub(crate) fn execute(interpreter: &mut DerstandInterpreter, options: TestOptions) -> Result<Option<Diagnostic>, String> {
+    let token = CancelToken::new();
+    token.cancel_after(Duration::from_millis(options.timeout_ms));
+    interpreter.set_cancel_token(Some(token.clone()));
+    interpreter.reset();
+    let mut steps = 0u64;
+    loop {
+        if steps == options.max_steps {
+            return Err(format!("step limit of {} exceeded", options.max_steps));
+        }
+        if token.is_cancelled() {
+            return Err(format!("time limit of {} ms exceeded", options.timeout_ms));
+        }
+        match interpreter.step() {
+            Ok(true) => steps += 1,
+            Ok(false) => return Ok(None),
+            Err(e) if e.code == "E0114" => return Err(format!("time limit of {} ms exceeded", options.timeout_ms)),
+            Err(e) => return Ok(Some(e)),
+        }
+    }
+}
+
+/// `derstand test`子命令
+pub fn command(args: &[String]) -> i32 {
+    let mut options = TestOptions::default();
+    let mut paths = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let positive = |value: Option<&String>| value.and_then(|v| v.parse::<u64>().ok()).filter(|&n| n > 0);
+        let parsed = match arg.as_str() {
+            "--max-steps" => positive(iter.next()).map(|n| options.max_steps = n).ok_or("--max-steps expects a positive number".to_string()),
+            "--timeout" => positive(iter.next())
+                .map(|n| options.timeout_ms = n)
+                .ok_or("--timeout expects a positive number of milliseconds".to_string()),
+            _ if arg.starts_with("--") => Err(format!("Unknown option: {}", arg)),
+            _ => {
+                paths.push(PathBuf::from(arg));
+                Ok(())
+            },
+        };
+        if let Err(e) = parsed {
+            eprintln!("{}", e);
+            eprintln!("Usage: derstand test [--max-steps N] [--timeout MS] [path...]");
+            return 2;
+        }
+    }
+    if paths.is_empty() {
+        paths.push(PathBuf::from("."));
+    }
+
+    let mut cases = Vec::new();
+    for path in &paths {
+        match discover(path) {
+            Ok(found) => cases.extend(found),
+            Err(e) => {
+                eprintln!("{}", e);
+                return 2;
+            },
+        }
+    }
+    if cases.is_empty() {
+        eprintln!("No tests found: add a .expected file or `;; expect-output:` comments to each .dr program to test");
+        return 1;
+    }
+
+    println!("running {} test(s)", cases.len());
+    let started = Instant::now();
+    let mut failures = Vec::new();
+    for case in &cases {
+        match run_case(case, options) {
+            Ok(()) => println!("test {} ... ok", case.program.display()),
+            Err(reason) => {
+                println!("test {} ... FAILED", case.program.display());
+                failures.push((case, reason));
+            },
+        }
+    }
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for (case, reason) in &failures {
+            println!("\n---- {} ----\n{}", case.program.display(), reason);
+        }
+    }
+    println!(
+        "\ntest result: {}. {} passed; {} failed; finished in {:.2}s",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        cases.len() - failures.len(),
+        failures.len(),
+        started.elapsed().as_secs_f64()
+    );
+    if failures.is_empty() { 0 } else { 1 }
+}
@@ -0,0 +1,121 @@
+//! 访问热图 - `--heatmap out.png|csv`统计每个单元格被读写的次数，看出程序的内存局部性
+//!
+//! 只统计第一条纸带，按调试器观察点的规则计数(一条`+`既读又写当前单元格，扫描`«`/`»`读取经过的每个单元格)；
+//! 自定义指令访问的其他单元格不计。输出按扩展名选择：
+//!
+//! ```text
+//! $ derstand run --heatmap heat.png prog.dr   # 每行256格，从黑经红、黄到白，按对数着色
+//! $ derstand run --heatmap heat.csv prog.dr   # cell,reads,writes，只列出被访问过的单元格
+//! ```
+//!
+//! 图像格式还可以是`.ppm`或`.pgm`。
+
+use std::fmt::Write;
+
+use crate::access::{Access, accesses};
+use crate::image::{self, Format};
+use crate::{DerstandInterpreter, Instruction};
+
+/// 热图图像每行的单元格数
+pub const WIDTH: usize = 256;
+
+/// 热图的输出格式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Export {
+    Csv,
+    Image(Format),
+}
+
+impl Export {
+    pub fn from_path(path: &str) -> Result<Self, String> {
+        if path.to_ascii_lowercase().ends_with(".csv") {
+            return Ok(Export::Csv);
+        }
+        Format::from_path(path).map(Export::Image).map_err(|_| format!("Cannot tell the heatmap format of {} (expected .csv, .png, .ppm or .pgm)", path))
+    }
+}
+
+/// 每个单元格的读写次数
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Heatmap {
+    reads: Vec<u64>,
+    writes: Vec<u64>,
+}
+
+impl Heatmap {
+    /// 记下instruction在pointer处将访问的单元格
+    pub(crate) fn record(&mut self, instruction: Instruction, pointer: usize, memory: &[u8]) {
+        if self.reads.len() < memory.len() {
+            self.reads.resize(memory.len(), 0);
+            self.writes.resize(memory.len(), 0);
+        }
+        for (cell, access) in accesses(instruction, pointer, memory) {
+            match access {
+                Access::Read => self.reads[cell] += 1,
+                Access::Write => self.writes[cell] += 1,
+            }
+        }
+    }
+
+    pub fn reads(&self) -> &[u64] {
+        &self.reads
+    }
+
+    pub fn writes(&self) -> &[u64] {
+        &self.writes
+    }
+
+    /// `cell,reads,writes`，跳过从未访问的单元格
+    pub fn csv(&self) -> String {
+        let mut csv = String::from("cell,reads,writes\n");
+        for (cell, (&reads, &writes)) in self.reads.iter().zip(&self.writes).enumerate() {
+            if reads + writes > 0 {
+                let _ = writeln!(csv, "{},{},{}", cell, reads, writes);
+            }
+        }
+        csv
+    }
+
+    /// 每行WIDTH格的RGB图像；返回(宽, 高, 像素)
+    pub fn pixels(&self) -> (usize, usize, Vec<u8>) {
+        let totals: Vec<u64> = self.reads.iter().zip(&self.writes).map(|(r, w)| r + w).collect();
+        let scale = (totals.iter().copied().max().unwrap_or(0) as f64).ln_1p();
+        let width = WIDTH.min(totals.len()).max(1);
+        let height = totals.len().div_ceil(width).max(1);
+        let mut pixels = vec![0; width * height * 3];
+        for (pixel, &total) in pixels.chunks_mut(3).zip(&totals) {
+            if total > 0 {
+                pixel.copy_from_slice(&heat((total as f64).ln_1p() / scale));
+            }
+        }
+        (width, height, pixels)
+    }
+
+    pub fn encode(&self, export: Export) -> Vec<u8> {
+        match export {
+            Export::Csv => self.csv().into_bytes(),
+            Export::Image(format) => {
+                let (width, height, pixels) = self.pixels();
+                image::encode_rgb(format, width, height, &pixels)
+            },
+        }
+    }
+}
+
+/// 0..=1映射到黑-红-黄-白
+fn heat(t: f64) -> [u8; 3] {
+    let channel = |offset: f64| ((t * 3.0 - offset).clamp(0.0, 1.0) * 255.0).round() as u8;
+    [channel(0.0), channel(1.0), channel(2.0)]
+}
+
+impl DerstandInterpreter {
+    /// 开启或关闭读写计数；开启后每次运行前清零
+    pub fn set_heatmap(&mut self, enabled: bool) {
+        self.heatmap = enabled.then(Heatmap::default);
+    }
+
+    /// 本次运行的读写计数，未开启时为None
+    pub fn heatmap(&self) -> Option<&Heatmap> {
+        self.heatmap.as_ref()
+    }
+}
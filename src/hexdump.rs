@@ -0,0 +1,78 @@
+//! hexdump风格的内存查看器 - 偏移、十六进制、ASCII列，重复行压缩并标出指针
+
+const BYTES_PER_LINE: usize = 16;
+
+/// 渲染memory[start..start+len]；与上一行相同的行折叠为`*`，指针所在字节用方括号标出
+pub fn hexdump(memory: &[u8], start: usize, len: usize, pointer: usize) -> String {
+    let end = start.saturating_add(len).min(memory.len());
+    let start = start.min(end);
+    let mut out = String::new();
+    let mut previous: Option<&[u8]> = None;
+    let mut folded = false;
+
+    for offset in (start..end).step_by(BYTES_PER_LINE) {
+        let line = &memory[offset..(offset + BYTES_PER_LINE).min(end)];
+        let has_pointer = (offset..offset + line.len()).contains(&pointer);
+        // 指针所在行总是显示
+        if !has_pointer && previous == Some(line) {
+            if !folded {
+                out.push_str("*\n");
+                folded = true;
+            }
+            continue;
+        }
+        previous = Some(line);
+        folded = false;
+
+        out.push_str(&format!("{:08x} ", offset));
+        for column in 0..BYTES_PER_LINE {
+            let index = offset + column;
+            // 每个字节前的分隔位兼作指针标记: `[`开、`]`闭
+            let separator = if index == pointer {
+                '['
+            } else if column > 0 && index == pointer.wrapping_add(1) {
+                ']'
+            } else {
+                ' '
+            };
+            if column == BYTES_PER_LINE / 2 && separator == ']' {
+                out.push_str("] ");
+            } else if column == BYTES_PER_LINE / 2 {
+                out.push(' ');
+                out.push(separator);
+            } else {
+                out.push(separator);
+            }
+            match line.get(column) {
+                Some(b) => out.push_str(&format!("{:02x}", b)),
+                None => out.push_str("  "),
+            }
+        }
+        out.push(if pointer == offset + BYTES_PER_LINE - 1 { ']' } else { ' ' });
+        out.push(' ');
+        out.push('|');
+        out.extend(line.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }));
+        out.push_str("|\n");
+    }
+    out.push_str(&format!("{:08x}", end));
+    if (start..end).contains(&pointer) {
+        out.push_str(&format!("  (pointer at {} = 0x{:04x})", pointer, pointer));
+    }
+    out
+}
+
+/// 解析 `[start [len]]` 形式的查看范围
+pub fn parse_range<'a>(mut args: impl Iterator<Item = &'a str>, memory_size: usize) -> Result<(usize, usize), String> {
+    let start = match args.next() {
+        Some(text) => text.parse::<usize>().map_err(|_| format!("Invalid start offset: {}", text))?,
+        None => 0,
+    };
+    let len = match args.next() {
+        Some(text) => text.parse::<usize>().map_err(|_| format!("Invalid length: {}", text))?,
+        None => memory_size.saturating_sub(start),
+    };
+    if start >= memory_size {
+        return Err(format!("Start offset {} is beyond the tape ({} cells)", start, memory_size));
+    }
+    Ok((start, len))
+}
@@ -0,0 +1,376 @@
+//! 语法高亮 - `derstand highlight`，按类别为指令着色并输出ANSI或HTML
+//!
+//! 循环括号按嵌套深度轮换颜色，注释淡化显示；未配对的括号标为错误。
+//! HTML输出使用`dr-`前缀的类名，`--standalone`时附带默认样式表，生成完整页面。
+
+use std::io::IsTerminal;
+
+use crate::preprocess::{DIRECTIVE_MARKER, is_ident, is_ident_start};
+use crate::pragma::{self, PRAGMA_MARKER};
+use crate::{COMMENT_MARKER, Dialect, Instruction, SET_MARKER, STRING_MARKER};
+
+/// 循环括号轮换使用的颜色数
+const DEPTH_COLORS: usize = 6;
+
+/// 输出格式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HighlightFormat {
+    Ansi,
+    Html,
+}
+
+impl HighlightFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "ansi" => Ok(HighlightFormat::Ansi),
+            "html" => Ok(HighlightFormat::Html),
+            _ => Err(format!("Unknown highlight format '{}' (expected: html, ansi)", value)),
+        }
+    }
+}
+
+/// 源码片段的类别
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Class {
+    Plain,     // 空白
+    Comment,   // 注释与其他非指令文字
+    Directive, // 预处理指令行与编译指示
+    Macro,     // 宏调用
+    Str,       // 字符串字面量
+    Pointer,   // 指针移动
+    Arithmetic, // 单元格运算
+    Io,        // 输入输出
+    Loop(usize), // 循环括号，附嵌套深度
+    Procedure, // 过程定义与调用
+    Stack,     // 辅助栈与双纸带
+    System,    // 随机数、时间、睡眠与调试
+    Error,     // 未配对的括号
+}
+
+impl Class {
+    /// 指令所属的类别
+    fn of(instruction: Instruction) -> Class {
+        match instruction {
+            Instruction::Right
+            | Instruction::Left
+            | Instruction::MoveRight(_)
+            | Instruction::MoveLeft(_)
+            | Instruction::ScanLeft
+            | Instruction::ScanRight
+            | Instruction::MoveHigh
+            | Instruction::MoveLow => Class::Pointer,
+            Instruction::Increment
+            | Instruction::Decrement
+            | Instruction::Add(_)
+            | Instruction::Sub(_)
+            | Instruction::Set(_)
+            | Instruction::Zero
+            | Instruction::Copy
+            | Instruction::CopyLeft
+            | Instruction::AddNext => Class::Arithmetic,
+            Instruction::Output | Instruction::OutputDecimal | Instruction::Input | Instruction::InputDecimal => Class::Io,
+            Instruction::JumpIfZero | Instruction::JumpIfNotZero => Class::Loop(0),
+            Instruction::ProcStart | Instruction::ProcEnd | Instruction::Call(_) | Instruction::CallCell => Class::Procedure,
+            Instruction::Push | Instruction::Pop | Instruction::SwitchTape | Instruction::Exchange => Class::Stack,
+            Instruction::Random | Instruction::Time | Instruction::Sleep | Instruction::Debug | Instruction::Assert(_) | Instruction::Custom(_) | Instruction::HostCall => {
+                Class::System
+            },
+        }
+    }
+
+    /// HTML类名
+    fn css(self) -> String {
+        match self {
+            Class::Plain => String::new(),
+            Class::Comment => "dr-comment".to_string(),
+            Class::Directive => "dr-directive".to_string(),
+            Class::Macro => "dr-macro".to_string(),
+            Class::Str => "dr-string".to_string(),
+            Class::Pointer => "dr-pointer".to_string(),
+            Class::Arithmetic => "dr-arith".to_string(),
+            Class::Io => "dr-io".to_string(),
+            Class::Loop(depth) => format!("dr-loop dr-depth-{}", depth % DEPTH_COLORS),
+            Class::Procedure => "dr-proc".to_string(),
+            Class::Stack => "dr-stack".to_string(),
+            Class::System => "dr-system".to_string(),
+            Class::Error => "dr-error".to_string(),
+        }
+    }
+
+    /// ANSI SGR参数
+    fn sgr(self) -> &'static str {
+        const DEPTHS: [&str; DEPTH_COLORS] = ["1;35", "1;36", "1;33", "1;32", "1;34", "1;91"];
+        match self {
+            Class::Plain => "",
+            Class::Comment => "2",
+            Class::Directive => "95",
+            Class::Macro => "96",
+            Class::Str => "92",
+            Class::Pointer => "34",
+            Class::Arithmetic => "32",
+            Class::Io => "33",
+            Class::Loop(depth) => DEPTHS[depth % DEPTH_COLORS],
+            Class::Procedure => "35",
+            Class::Stack => "36",
+            Class::System => "31",
+            Class::Error => "1;97;41",
+        }
+    }
+}
+
+/// 把源码切分为带类别的片段，拼接后与原文相同
+pub fn classify(source: &str) -> Vec<(Class, String)> {
+    let mut segments: Vec<(Class, String)> = Vec::new();
+    let mut push = |class: Class, text: &str| match segments.last_mut() {
+        Some((last, current)) if *last == class && !matches!(class, Class::Loop(_)) => current.push_str(text),
+        _ => segments.push((class, text.to_string())),
+    };
+    let mut macros: Vec<String> = Vec::new();
+    let mut depth = 0usize;
+    let mut in_pragmas = true; // 文件开头连续的`;!`行是编译指示
+    let dialect = pragma::dialect(source);
+    for line in source.split_inclusive('\n') {
+        let body = line.trim_end_matches('\n');
+        let trimmed = body.trim_start();
+        if in_pragmas && !trimmed.is_empty() && !trimmed.starts_with(PRAGMA_MARKER) {
+            in_pragmas = false;
+        }
+        if trimmed.starts_with(DIRECTIVE_MARKER) || (in_pragmas && trimmed.starts_with(PRAGMA_MARKER)) {
+            if let Some(definition) = trimmed.strip_prefix("!define") {
+                macros.push(definition.trim_start().chars().take_while(|&c| is_ident(c)).collect());
+            }
+            let indent = &body[..body.len() - trimmed.len()];
+            push(Class::Plain, indent);
+            push(Class::Directive, trimmed);
+            push(Class::Plain, &line[body.len()..]);
+            continue;
+        }
+
+        let chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let text = |end: usize| chars[i..end].iter().collect::<String>();
+            if c == COMMENT_MARKER {
+                let end = chars.iter().position(|&c| c == '\n').unwrap_or(chars.len());
+                push(Class::Comment, &text(end));
+                i = end;
+                continue;
+            }
+            if c == STRING_MARKER && dialect == Dialect::Extended {
+                let mut end = i + 1;
+                while end < chars.len() && chars[end] != STRING_MARKER && chars[end] != '\n' {
+                    end += if chars[end] == '\\' { 2 } else { 1 };
+                }
+                let end = (end + 1).min(chars.len());
+                push(Class::Str, &text(end));
+                i = end;
+                continue;
+            }
+            if is_ident_start(c) {
+                let mut end = (i..chars.len()).find(|&e| !is_ident(chars[e])).unwrap_or(chars.len());
+                // 带参数的调用(宏可能定义在被包含文件中)连同参数表作为一个整体
+                let call = chars.get(end) == Some(&'(');
+                let class = if call || macros.contains(&text(end)) { Class::Macro } else { Class::Comment };
+                if call {
+                    let mut nesting = 0usize;
+                    while end < chars.len() && chars[end] != '\n' {
+                        match chars[end] {
+                            '(' => nesting += 1,
+                            ')' => nesting -= 1,
+                            _ => {},
+                        }
+                        end += 1;
+                        if nesting == 0 {
+                            break;
+                        }
+                    }
+                }
+                push(class, &text(end));
+                i = end;
+                continue;
+            }
+            // =?{n} 断言连同'?'一起着色
+            let assert = c == SET_MARKER && chars.get(i + 1) == Some(&'?') && chars.get(i + 2) == Some(&'{');
+            let set = if assert {
+                Some(Instruction::Assert(0))
+            } else {
+                (c == SET_MARKER && chars.get(i + 1) == Some(&'{')).then_some(Instruction::Set(0))
+            };
+            let Some(instruction) = set.or_else(|| Instruction::from_char_in(c, dialect)) else {
+                push(if c.is_whitespace() { Class::Plain } else { Class::Comment }, &c.to_string());
+                i += 1;
+                continue;
+            };
+            // 重复计数、赋值与调用的花括号数值与指令一同着色
+            let braced = set.is_some() || instruction == Instruction::CallCell || instruction.repeated(2).is_some();
+            let mut end = i + 1 + usize::from(assert);
+            if braced && chars.get(end) == Some(&'{') {
+                end = (end..chars.len()).find(|&e| chars[e] == '}' || chars[e] == '\n').map_or(chars.len(), |e| e + usize::from(chars[e] == '}'));
+            }
+            let class = match instruction {
+                Instruction::JumpIfZero => {
+                    depth += 1;
+                    Class::Loop(depth - 1)
+                },
+                Instruction::JumpIfNotZero if depth == 0 => Class::Error,
+                Instruction::JumpIfNotZero => {
+                    depth -= 1;
+                    Class::Loop(depth)
+                },
+                _ => Class::of(instruction),
+            };
+            push(class, &text(end));
+            i = end;
+        }
+    }
+    segments
+}
+
+/// 转义HTML特殊字符
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// `--standalone`页面的默认样式
+const STYLESHEET: &str = "\
+pre.derstand { background: #1e1e2e; color: #cdd6f4; padding: 1em; border-radius: 6px; line-height: 1.4; }
+.dr-comment { color: #7f849c; font-style: italic; }
+.dr-directive { color: #f5c2e7; }
+.dr-macro { color: #94e2d5; font-weight: bold; }
+.dr-string { color: #a6e3a1; }
+.dr-pointer { color: #89b4fa; }
+.dr-arith { color: #a6e3a1; font-weight: bold; }
+.dr-io { color: #f9e2af; }
+.dr-proc { color: #cba6f7; }
+.dr-stack { color: #89dceb; }
+.dr-system { color: #f38ba8; }
+.dr-loop { font-weight: bold; }
+.dr-depth-0 { color: #cba6f7; }
+.dr-depth-1 { color: #89dceb; }
+.dr-depth-2 { color: #f9e2af; }
+.dr-depth-3 { color: #a6e3a1; }
+.dr-depth-4 { color: #89b4fa; }
+.dr-depth-5 { color: #fab387; }
+.dr-error { color: #1e1e2e; background: #f38ba8; }
+";
+
+/// 渲染为HTML；standalone时输出带样式表的完整页面
+pub fn render_html(source: &str, standalone: bool, title: &str) -> String {
+    let mut body = String::from("<pre class=\"derstand\"><code>");
+    for (class, text) in classify(source) {
+        match class {
+            Class::Plain => body.push_str(&escape_html(&text)),
+            Class::Loop(depth) => {
+                body.push_str(&format!("<span class=\"{}\" title=\"loop depth {}\">{}</span>", class.css(), depth + 1, escape_html(&text)));
+            },
+            _ => body.push_str(&format!("<span class=\"{}\">{}</span>", class.css(), escape_html(&text))),
+        }
+    }
+    body.push_str("</code></pre>\n");
+    if !standalone {
+        return body;
+    }
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(title),
+        STYLESHEET,
+        body
+    )
+}
+
+/// 渲染为带ANSI颜色的终端文本；颜色不跨行，分页器中也能正确显示
+pub fn render_ansi(source: &str) -> String {
+    let mut out = String::with_capacity(source.len() * 2);
+    for (class, text) in classify(source) {
+        if class == Class::Plain {
+            out.push_str(&text);
+            continue;
+        }
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            if !line.is_empty() {
+                out.push_str(&format!("\x1b[{}m{}\x1b[0m", class.sgr(), line));
+            }
+        }
+    }
+    out
+}
+
+/// `derstand highlight [--format html|ansi] [--standalone] [-o out] <file>`
+pub fn command(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: derstand highlight [--format html|ansi] [--standalone] [-o out] <file>";
+    let mut format = HighlightFormat::Ansi;
+    let mut standalone = false;
+    let mut output = None;
+    let mut file = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let parsed = match arg.as_str() {
+            "--format" => iter.next().ok_or("Missing value for --format".to_string())
+                .and_then(|v| HighlightFormat::parse(v)).map(|f| format = f),
+            "--standalone" => {
+                standalone = true;
+                Ok(())
+            },
+            "-o" | "--output" => iter.next().ok_or(format!("Missing value for {}", arg)).map(|path| output = Some(path.clone())),
+            _ if arg.starts_with('-') => Err(format!("Unknown option: {}", arg)),
+            _ => {
+                file = Some(arg.clone());
+                Ok(())
+            },
+        };
+        if let Err(e) = parsed {
+            eprintln!("{}", e);
+            return 2;
+        }
+    }
+    let Some(file) = file else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    if standalone && format != HighlightFormat::Html {
+        eprintln!("--standalone requires --format html");
+        return 2;
+    }
+
+    let source = match std::fs::read_to_string(&file) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error reading file {}: {}", file, e);
+            return 1;
+        },
+    };
+    let rendered = match format {
+        HighlightFormat::Html => render_html(&source, standalone, &file),
+        // 输出到非终端(管道、文件)时仍然着色：这正是用户要求的格式
+        HighlightFormat::Ansi => render_ansi(&source),
+    };
+    match output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, rendered) {
+                eprintln!("Error writing file {}: {}", path, e);
+                return 1;
+            }
+        },
+        None => {
+            print!("{}", rendered);
+            if format == HighlightFormat::Ansi && !rendered.ends_with('\n') && std::io::stdout().is_terminal() {
+                println!();
+            }
+        },
+    }
+    0
+}
@@ -0,0 +1,41 @@
+//! 宿主输入输出 - 解释器读写字节的抽象，no_std环境由调用者实现
+//!
+//! 开启`std`特性时，任何`std::io::Read`/`Write`都自动实现这两个特征。
+
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::string::ToString;
+
+/// 程序的输入来源
+pub trait Input {
+    /// 读取一个字节，Ok(None)表示输入结束
+    fn read_byte(&mut self) -> Result<Option<u8>, String>;
+}
+
+/// 程序的输出目标
+pub trait Output {
+    /// 写出全部字节
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), String>;
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> Input for R {
+    fn read_byte(&mut self) -> Result<Option<u8>, String> {
+        let mut byte = [0u8];
+        loop {
+            match self.read(&mut byte) {
+                Ok(0) => return Ok(None),
+                Ok(_) => return Ok(Some(byte[0])),
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> Output for W {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.write_all(bytes).and_then(|()| self.flush()).map_err(|e| e.to_string())
+    }
+}
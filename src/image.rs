@@ -0,0 +1,236 @@
+//! 图像编码 - 不依赖外部库写出PNG、PPM/PGM与GIF动画，供帧缓冲等输出使用
+//!
+//! PNG的图像数据用未压缩的deflate块保存：文件较大，但任何查看器都能打开，编码也足够简单。
+//! GIF用标准的LZW压缩，所有帧共用一个256色的全局调色板。
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// 图像文件格式，按扩展名选择
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Png,
+    Ppm, // 二进制P6，灰度复制到三个通道
+    Pgm, // 二进制P5
+}
+
+impl Format {
+    pub fn from_path(path: &str) -> Result<Self, String> {
+        let extension = path.rsplit_once('.').map(|(_, e)| e.to_ascii_lowercase()).unwrap_or_default();
+        match extension.as_str() {
+            "png" => Ok(Format::Png),
+            "ppm" => Ok(Format::Ppm),
+            "pgm" => Ok(Format::Pgm),
+            _ => Err(format!("Cannot tell the image format of {} (expected .png, .ppm or .pgm)", path)),
+        }
+    }
+}
+
+/// 按format编码width×height的灰度图，每字节一个像素，逐行排列
+pub fn encode_gray(format: Format, width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    match format {
+        Format::Png => png(width, height, pixels, 0),
+        Format::Ppm => {
+            let rgb: Vec<u8> = pixels.iter().flat_map(|&p| [p, p, p]).collect();
+            netpbm("P6", width, height, &rgb)
+        },
+        Format::Pgm => netpbm("P5", width, height, pixels),
+    }
+}
+
+/// 按format编码width×height的RGB图，每像素三字节；PGM按亮度转为灰度
+pub fn encode_rgb(format: Format, width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    match format {
+        Format::Png => png(width, height, pixels, 2),
+        Format::Ppm => netpbm("P6", width, height, pixels),
+        Format::Pgm => {
+            let gray: Vec<u8> = pixels.chunks(3).map(|p| ((p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 1000) as u8).collect();
+            netpbm("P5", width, height, &gray)
+        },
+    }
+}
+
+/// 纸带图像：每个单元格一个像素，每行width格，最后一行不足时补0；返回(高度, 像素)
+pub fn tape_pixels(memory: &[u8], width: usize) -> (usize, Vec<u8>) {
+    let height = memory.len().div_ceil(width);
+    let mut pixels = memory.to_vec();
+    pixels.resize(width * height, 0);
+    (height, pixels)
+}
+
+fn netpbm(magic: &str, width: usize, height: usize, data: &[u8]) -> Vec<u8> {
+    let mut out = format!("{}\n{} {}\n255\n", magic, width, height).into_bytes();
+    out.extend_from_slice(data);
+    out
+}
+
+/// CRC-32(PNG块校验)
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Adler-32(zlib流校验)
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+/// 只含未压缩块的zlib流
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const MAX_BLOCK: usize = 65535;
+    let mut out = Vec::with_capacity(data.len() + data.len() / MAX_BLOCK * 5 + 11);
+    out.extend_from_slice(&[0x78, 0x01]);
+    let mut blocks = data.chunks(MAX_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let len = block.len() as u16;
+        out.push(blocks.peek().is_none() as u8);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(&(!len).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+fn chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// color_type为0(灰度)或2(RGB)，每通道8位
+fn png(width: usize, height: usize, pixels: &[u8], color_type: u8) -> Vec<u8> {
+    let row = width * if color_type == 2 { 3 } else { 1 };
+    // 每行前加过滤类型0
+    let mut raw = Vec::with_capacity((row + 1) * height);
+    for line in pixels.chunks(row.max(1)).take(height) {
+        raw.push(0);
+        raw.extend_from_slice(line);
+    }
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, color_type, 0, 0, 0]);
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut out, b"IHDR", &header);
+    chunk(&mut out, b"IDAT", &zlib_stored(&raw));
+    chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+/// 按LSB优先顺序拼接变长编码
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+/// GIF的LZW压缩，最小码长8
+fn lzw(pixels: &[u8]) -> Vec<u8> {
+    const CLEAR: u16 = 256;
+    const END: u16 = 257;
+    const MAX_CODE: u16 = 4096;
+    let mut out = BitWriter { bytes: Vec::new(), buffer: 0, bits: 0 };
+    let mut table: BTreeMap<(u16, u8), u16> = BTreeMap::new();
+    let (mut next, mut size) = (END + 1, 9);
+    out.write(CLEAR, size);
+    let Some((&first, rest)) = pixels.split_first() else {
+        out.write(END, size);
+        return out.finish();
+    };
+    let mut prefix = first as u16;
+    for &pixel in rest {
+        if let Some(&code) = table.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+        out.write(prefix, size);
+        if next == MAX_CODE {
+            // 码表已满，重新开始
+            out.write(CLEAR, size);
+            table.clear();
+            (next, size) = (END + 1, 9);
+        } else {
+            table.insert((prefix, pixel), next);
+            if next == 1 << size {
+                size += 1;
+            }
+            next += 1;
+        }
+        prefix = pixel as u16;
+    }
+    out.write(prefix, size);
+    out.write(END, size);
+    out.finish()
+}
+
+/// 循环播放的GIF动画：每帧width×height个调色板下标，帧间隔delay(百分之一秒)
+pub fn gif(width: u16, height: u16, palette: &[[u8; 3]; 256], frames: &[Vec<u8>], delay: u16) -> Vec<u8> {
+    let mut out = b"GIF89a".to_vec();
+    out.extend_from_slice(&width.to_le_bytes());
+    out.extend_from_slice(&height.to_le_bytes());
+    // 全局调色板，2^(7+1)=256色
+    out.extend_from_slice(&[0xF7, 0, 0]);
+    for color in palette {
+        out.extend_from_slice(color);
+    }
+    // NETSCAPE2.0扩展：无限循环
+    out.extend_from_slice(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00");
+    for frame in frames {
+        out.extend_from_slice(&[0x21, 0xF9, 4, 0]);
+        out.extend_from_slice(&delay.to_le_bytes());
+        out.extend_from_slice(&[0, 0]);
+        out.push(0x2C);
+        out.extend_from_slice(&[0, 0, 0, 0]);
+        out.extend_from_slice(&width.to_le_bytes());
+        out.extend_from_slice(&height.to_le_bytes());
+        out.extend_from_slice(&[0, 8]);
+        // 数据分成不超过255字节的子块
+        for block in lzw(frame).chunks(255) {
+            out.push(block.len() as u8);
+            out.extend_from_slice(block);
+        }
+        out.push(0);
+    }
+    out.push(0x3B);
+    out
+}
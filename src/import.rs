@@ -0,0 +1,72 @@
+//! 导入 - 把标准Brainfuck程序改写为使用Derstand扩展指令的等价程序
+//!
+//! 只有`+ - < > . , [ ]`是Brainfuck指令，其余字符都是注释；它们在Derstand中可能是
+//! 指令(`#`、`$`、`"`等)，因此先替换为空格再编译，诊断中的行列号仍对应原文件。
+//! 改写用到`~`等扩展指令时，结果以`;! dialect=extended`开头。
+
+use std::path::Path;
+
+use crate::formatter::{self, FormatOptions};
+use crate::{DerstandInterpreter, minify};
+
+/// 只保留Brainfuck指令与换行，其余字符替换为空格
+pub fn brainfuck_source(source: &str) -> String {
+    source
+        .chars()
+        .map(|c| if matches!(c, '+' | '-' | '<' | '>' | '.' | ',' | '[' | ']' | '\n') { c } else { ' ' })
+        .collect()
+}
+
+/// `derstand import [-o out] <file.bf>`，默认写到同名的 .dr 文件
+pub fn command(args: &[String]) -> i32 {
+    let mut output = None;
+    let mut file = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-o" | "--output" => match iter.next() {
+                Some(path) => output = Some(path.clone()),
+                None => {
+                    eprintln!("Missing value for {}", arg);
+                    return 2;
+                },
+            },
+            _ if arg.starts_with('-') => {
+                eprintln!("Unknown option: {}", arg);
+                return 2;
+            },
+            _ => file = Some(arg.clone()),
+        }
+    }
+    let Some(file) = file else {
+        eprintln!("Usage: derstand import [-o out] <file.bf>");
+        return 2;
+    };
+    let output = output.unwrap_or_else(|| Path::new(&file).with_extension("dr").to_string_lossy().into_owned());
+    if Path::new(&output) == Path::new(&file) {
+        eprintln!("Refusing to overwrite {}; choose another path with -o", file);
+        return 2;
+    }
+
+    let source = match std::fs::read_to_string(&file) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error reading file {}: {}", file, e);
+            return 1;
+        },
+    };
+    let mut interpreter = DerstandInterpreter::new();
+    if let Err(e) = interpreter.compile(&brainfuck_source(&source)) {
+        e.report(Default::default(), &source, Some(&file));
+        return 1;
+    }
+
+    let instructions = minify::minify(&interpreter, true);
+    let code = formatter::format_source(&minify::render(&instructions), FormatOptions::default());
+    if let Err(e) = std::fs::write(&output, &code) {
+        eprintln!("Error writing file {}: {}", output, e);
+        return 1;
+    }
+    println!("{} -> {}: {} -> {} instructions", file, output, interpreter.instructions().len(), instructions.len());
+    0
+}
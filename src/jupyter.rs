@@ -0,0 +1,611 @@
+//! Jupyter内核 - `derstand jupyter-kernel`，每个单元格在同一个解释器上编译执行
+//!
+//! 实现Jupyter消息协议5.3：shell与control通道处理请求，程序输出作为stdout流发到iopub，
+//! 编译与运行错误渲染为带源码片段的诊断。纸带在单元格之间保留，与交互模式一样；
+//! 单元格`:mem [start [len]]`显示纸带。程序读取输入时经stdin通道向前端请求一行。
+//!
+//! `derstand jupyter-kernel --install`把内核规格写入Jupyter的数据目录。
+
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::cancel::CancelToken;
+use crate::digest::{hex, hmac_sha256};
+use crate::host::{Input, Output};
+use crate::json::Json;
+use crate::random::Rng;
+use crate::zmq::{Message, Socket, SocketType};
+use crate::{DerstandInterpreter, hexdump, runtime_error};
+
+/// 协议版本
+const PROTOCOL_VERSION: &str = "5.3";
+/// 标识帧与消息正文的分隔帧
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+
+/// 连接文件中的端口与密钥
+struct Connection {
+    transport: String,
+    ip: String,
+    key: String,
+    shell_port: i64,
+    iopub_port: i64,
+    stdin_port: i64,
+    control_port: i64,
+    hb_port: i64,
+}
+
+impl Connection {
+    fn parse(text: &str) -> Result<Self, String> {
+        let json = Json::parse(text)?;
+        let string = |name: &str| json.get(name).and_then(Json::as_str).map(str::to_string);
+        let port = |name: &str| json.get(name).and_then(Json::as_i64).ok_or_else(|| format!("Missing {} in connection file", name));
+        if let Some(scheme) = string("signature_scheme")
+            && scheme != "hmac-sha256"
+        {
+            return Err(format!("Unsupported signature scheme '{}' (expected: hmac-sha256)", scheme));
+        }
+        Ok(Connection {
+            transport: string("transport").unwrap_or_else(|| "tcp".to_string()),
+            ip: string("ip").unwrap_or_else(|| "127.0.0.1".to_string()),
+            key: string("key").unwrap_or_default(),
+            shell_port: port("shell_port")?,
+            iopub_port: port("iopub_port")?,
+            stdin_port: port("stdin_port")?,
+            control_port: port("control_port")?,
+            hb_port: port("hb_port")?,
+        })
+    }
+
+    fn address(&self, port: i64) -> String {
+        format!("{}:{}", self.ip, port)
+    }
+}
+
+/// 毫秒时间戳格式化为ISO 8601(UTC)
+fn iso_date(ms: u64) -> String {
+    let secs = ms / 1000;
+    let days = (secs / 86400) as i64;
+    // 公历由天数推算年月日(Howard Hinnant的civil_from_days)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60,
+        ms % 1000
+    )
+}
+
+/// 会话 - 签名密钥与消息头
+struct Session {
+    key: Vec<u8>,
+    id: String,
+    counter: AtomicU64,
+}
+
+/// 收到的请求
+struct Request {
+    identities: Vec<Vec<u8>>,
+    header: Json,
+    content: Json,
+}
+
+impl Request {
+    fn msg_type(&self) -> &str {
+        self.header.get("msg_type").and_then(Json::as_str).unwrap_or("")
+    }
+}
+
+impl Session {
+    fn new(key: &str) -> Self {
+        let mut rng = Rng::new(Rng::entropy_seed());
+        let id: String = (0..16).map(|_| format!("{:02x}", rng.next_u64() as u8)).collect();
+        Session { key: key.as_bytes().to_vec(), id, counter: AtomicU64::new(0) }
+    }
+
+    fn sign(&self, parts: &[&[u8]]) -> String {
+        if self.key.is_empty() {
+            return String::new();
+        }
+        hex(&hmac_sha256(&self.key, parts))
+    }
+
+    /// 解析消息；签名不符或格式错误时返回None
+    fn parse(&self, message: Message) -> Option<Request> {
+        let split = message.iter().position(|frame| frame == DELIMITER)?;
+        let (identities, rest) = message.split_at(split);
+        let [_, signature, header, parent, metadata, content, ..] = rest else {
+            return None;
+        };
+        if self.sign(&[header, parent, metadata, content]).as_bytes() != signature.as_slice() {
+            return None;
+        }
+        let json = |frame: &[u8]| std::str::from_utf8(frame).ok().and_then(|text| Json::parse(text).ok());
+        Some(Request { identities: identities.to_vec(), header: json(header)?, content: json(content)? })
+    }
+
+    /// 组装并签名一条消息
+    fn message(&self, identities: &[Vec<u8>], msg_type: &str, parent: &Json, content: Json) -> Message {
+        let n = self.counter.fetch_add(1, Ordering::Relaxed);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis() as u64);
+        let header = Json::object([
+            ("msg_id", format!("{}_{}", self.id, n).into()),
+            ("session", self.id.as_str().into()),
+            ("username", "derstand".into()),
+            ("date", iso_date(now).into()),
+            ("msg_type", msg_type.into()),
+            ("version", PROTOCOL_VERSION.into()),
+        ]);
+        let [header, parent, metadata, content] =
+            [header, parent.clone(), Json::Object(Vec::new()), content].map(|part| part.to_string().into_bytes());
+        let signature = self.sign(&[&header, &parent, &metadata, &content]);
+        let mut message = identities.to_vec();
+        message.extend([DELIMITER.to_vec(), signature.into_bytes(), header, parent, metadata, content]);
+        message
+    }
+}
+
+/// iopub通道及其会话，输出与状态消息都经它发送
+#[derive(Clone)]
+struct Publisher {
+    socket: Socket,
+    session: Arc<Session>,
+}
+
+impl Publisher {
+    fn publish(&self, msg_type: &str, parent: &Json, content: Json) {
+        let topic = format!("kernel.{}.{}", self.session.id, msg_type).into_bytes();
+        self.socket.publish(&self.session.message(&[topic], msg_type, parent, content));
+    }
+
+    fn status(&self, parent: &Json, state: &str) {
+        self.publish("status", parent, Json::object([("execution_state", state.into())]));
+    }
+}
+
+/// 程序输出 - 按行缓冲为stdout流消息
+struct Stream {
+    publisher: Publisher,
+    parent: Json,
+    silent: bool,
+    buffer: Vec<u8>,
+}
+
+impl Stream {
+    fn flush(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        let text = String::from_utf8_lossy(&self.buffer).into_owned();
+        self.buffer.clear();
+        if !self.silent {
+            self.publisher.publish("stream", &self.parent, Json::object([("name", "stdout".into()), ("text", text.into())]));
+        }
+    }
+}
+
+/// 交给解释器的输出端
+struct StreamOutput(Rc<RefCell<Stream>>);
+
+impl Output for StreamOutput {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let mut stream = self.0.borrow_mut();
+        stream.buffer.extend_from_slice(bytes);
+        if bytes.contains(&b'\n') {
+            stream.flush();
+        }
+        Ok(())
+    }
+}
+
+/// 程序输入 - 向前端请求一行
+struct Prompt {
+    socket: Socket,
+    replies: Receiver<Message>,
+    session: Arc<Session>,
+    stream: Rc<RefCell<Stream>>, // 请求输入前先送出已有输出
+    identities: Vec<Vec<u8>>,
+    parent: Json,
+    allowed: bool,
+    cancel: CancelToken,
+    pending: Vec<u8>,
+}
+
+impl Prompt {
+    /// 发送input_request并等待回复；前端不允许输入或运行被中断时返回None
+    fn request_line(&mut self) -> Option<String> {
+        if !self.allowed {
+            return None;
+        }
+        self.stream.borrow_mut().flush();
+        let content = Json::object([("prompt", "".into()), ("password", false.into())]);
+        self.socket.send(&self.session.message(&self.identities, "input_request", &self.parent, content)).ok()?;
+        loop {
+            match self.replies.recv_timeout(Duration::from_millis(100)) {
+                Ok(message) => {
+                    if let Some(reply) = self.session.parse(message)
+                        && reply.msg_type() == "input_reply"
+                    {
+                        return reply.content.get("value").and_then(Json::as_str).map(str::to_string);
+                    }
+                },
+                Err(RecvTimeoutError::Timeout) if !self.cancel.is_cancelled() => {},
+                Err(_) => return None,
+            }
+        }
+    }
+}
+
+/// 交给解释器的输入端
+struct PromptInput(Rc<RefCell<Prompt>>);
+
+impl Input for PromptInput {
+    fn read_byte(&mut self) -> Result<Option<u8>, String> {
+        let mut prompt = self.0.borrow_mut();
+        if prompt.pending.is_empty() {
+            let Some(line) = prompt.request_line() else {
+                return Ok(None);
+            };
+            prompt.pending = line.into_bytes();
+            prompt.pending.push(b'\n');
+            prompt.pending.reverse();
+        }
+        Ok(prompt.pending.pop())
+    }
+}
+
+struct Kernel {
+    session: Arc<Session>,
+    shell: Socket,
+    control: Socket,
+    publisher: Publisher,
+    interpreter: DerstandInterpreter,
+    stream: Rc<RefCell<Stream>>,
+    prompt: Rc<RefCell<Prompt>>,
+    cancel: Arc<Mutex<CancelToken>>, // 当前单元格的取消令牌，control线程中断时取消它
+    execution_count: i64,
+}
+
+/// 请求的处理结果
+enum Flow {
+    Continue,
+    Shutdown,
+}
+
+impl Kernel {
+    fn reply(&self, socket: &Socket, request: &Request, msg_type: &str, content: Json) {
+        let _ = socket.send(&self.session.message(&request.identities, msg_type, &request.header, content));
+    }
+
+    fn kernel_info() -> Json {
+        Json::object([
+            ("status", "ok".into()),
+            ("protocol_version", PROTOCOL_VERSION.into()),
+            ("implementation", "derstand".into()),
+            ("implementation_version", env!("CARGO_PKG_VERSION").into()),
+            (
+                "language_info",
+                Json::object([
+                    ("name", "derstand".into()),
+                    ("version", env!("CARGO_PKG_VERSION").into()),
+                    ("mimetype", "text/x-derstand".into()),
+                    ("file_extension", ".dr".into()),
+                ]),
+            ),
+            ("banner", format!("Derstand {}", env!("CARGO_PKG_VERSION")).into()),
+            ("help_links", Json::Array(Vec::new())),
+        ])
+    }
+
+    fn handle(&mut self, request: Request, control: bool) -> Flow {
+        let socket = if control { self.control.clone() } else { self.shell.clone() };
+        self.publisher.status(&request.header, "busy");
+        let mut flow = Flow::Continue;
+        let empty = || Json::Object(Vec::new());
+        match request.msg_type() {
+            "kernel_info_request" => self.reply(&socket, &request, "kernel_info_reply", Self::kernel_info()),
+            "execute_request" => {
+                let content = self.execute(&request);
+                self.reply(&socket, &request, "execute_reply", content);
+            },
+            "is_complete_request" => {
+                let code = request.content.get("code").and_then(Json::as_str).unwrap_or("");
+                let status = match DerstandInterpreter::new().compile(code) {
+                    Ok(()) => "complete",
+                    Err(e) if e.code == "E0002" => "incomplete", // 循环尚未闭合
+                    Err(_) => "invalid",
+                };
+                let mut content = vec![("status", status.into())];
+                if status == "incomplete" {
+                    content.push(("indent", "".into()));
+                }
+                self.reply(&socket, &request, "is_complete_reply", Json::object(content));
+            },
+            "complete_request" => {
+                let cursor = request.content.get("cursor_pos").and_then(Json::as_i64).unwrap_or(0);
+                self.reply(&socket, &request, "complete_reply", Json::object([
+                    ("status", "ok".into()),
+                    ("matches", Json::Array(Vec::new())),
+                    ("cursor_start", cursor.into()),
+                    ("cursor_end", cursor.into()),
+                    ("metadata", empty()),
+                ]));
+            },
+            "inspect_request" => self.reply(&socket, &request, "inspect_reply", Json::object([
+                ("status", "ok".into()),
+                ("found", false.into()),
+                ("data", empty()),
+                ("metadata", empty()),
+            ])),
+            "comm_info_request" => self.reply(&socket, &request, "comm_info_reply", Json::object([("status", "ok".into()), ("comms", empty())])),
+            "history_request" => self.reply(&socket, &request, "history_reply", Json::object([("status", "ok".into()), ("history", Json::Array(Vec::new()))])),
+            // control线程已处理运行中的中断，这里只需回复
+            "interrupt_request" => self.reply(&socket, &request, "interrupt_reply", Json::object([("status", "ok".into())])),
+            "shutdown_request" => {
+                let restart = request.content.get("restart").and_then(Json::as_bool).unwrap_or(false);
+                self.reply(&socket, &request, "shutdown_reply", Json::object([("status", "ok".into()), ("restart", restart.into())]));
+                flow = Flow::Shutdown;
+            },
+            _ => {},
+        }
+        self.publisher.status(&request.header, "idle");
+        flow
+    }
+
+    /// 编译并执行一个单元格，返回execute_reply的内容
+    fn execute(&mut self, request: &Request) -> Json {
+        let code = request.content.get("code").and_then(Json::as_str).unwrap_or("").to_string();
+        let silent = request.content.get("silent").and_then(Json::as_bool).unwrap_or(false);
+        if !silent {
+            self.execution_count += 1;
+            self.publisher.publish("execute_input", &request.header, Json::object([
+                ("code", code.as_str().into()),
+                ("execution_count", self.execution_count.into()),
+            ]));
+        }
+        {
+            let mut stream = self.stream.borrow_mut();
+            stream.parent = request.header.clone();
+            stream.silent = silent;
+        }
+        let token = CancelToken::new();
+        *self.cancel.lock().unwrap_or_else(|e| e.into_inner()) = token.clone();
+        {
+            let mut prompt = self.prompt.borrow_mut();
+            prompt.identities = request.identities.clone();
+            prompt.parent = request.header.clone();
+            prompt.allowed = request.content.get("allow_stdin").and_then(Json::as_bool).unwrap_or(false);
+            prompt.cancel = token.clone();
+            prompt.pending.clear();
+        }
+        self.interpreter.set_cancel_token(Some(token));
+
+        let result = if let Some(args) = code.trim().strip_prefix(":mem") {
+            // 查看内存 - 与交互模式一样显示上次运行后的纸带
+            let text = match hexdump::parse_range(args.split_whitespace(), self.interpreter.memory_size()) {
+                Ok((start, len)) => hexdump::hexdump(self.interpreter.memory(), start, len, self.interpreter.pointer()),
+                Err(e) => e,
+            };
+            self.stream.borrow_mut().buffer.extend_from_slice(format!("{}\n", text).as_bytes());
+            Ok(())
+        } else {
+            self.interpreter.compile(&code).and_then(|()| {
+                self.interpreter.execute().map_err(|e| runtime_error(&self.interpreter, &code, e))
+            })
+        };
+        self.stream.borrow_mut().flush();
+
+        match result {
+            Ok(()) => Json::object([
+                ("status", "ok".into()),
+                ("execution_count", self.execution_count.into()),
+                ("user_expressions", Json::Object(Vec::new())),
+                ("payload", Json::Array(Vec::new())),
+            ]),
+            Err(e) => {
+                let traceback = e.render(&code, None, true);
+                let (ename, evalue) = (e.code.to_string(), e.message.clone());
+                if !silent {
+                    self.publisher.publish("error", &request.header, Json::object([
+                        ("ename", ename.as_str().into()),
+                        ("evalue", evalue.as_str().into()),
+                        ("traceback", vec![traceback.as_str()].into()),
+                    ]));
+                }
+                Json::object([
+                    ("status", "error".into()),
+                    ("execution_count", self.execution_count.into()),
+                    ("ename", ename.into()),
+                    ("evalue", evalue.into()),
+                    ("traceback", vec![traceback].into()),
+                ])
+            },
+        }
+    }
+}
+
+/// 接收control消息：中断立即生效，其余交给主循环
+fn watch_control(session: Arc<Session>, control: Socket, messages: Receiver<Message>, forward: mpsc::Sender<(bool, Message)>, cancel: Arc<Mutex<CancelToken>>) {
+    for message in messages {
+        let interrupt = session.parse(message.clone()).is_some_and(|request| request.msg_type() == "interrupt_request");
+        if interrupt {
+            cancel.lock().unwrap_or_else(|e| e.into_inner()).cancel();
+            // 主循环正忙于执行时也要及时回复
+            if let Some(request) = session.parse(message) {
+                let _ = control.send(&session.message(&request.identities, "interrupt_reply", &request.header, Json::object([("status", "ok".into())])));
+            }
+            continue;
+        }
+        if forward.send((true, message)).is_err() {
+            break;
+        }
+    }
+}
+
+/// 启动内核并处理请求直到收到shutdown_request
+fn serve(connection: &Connection) -> Result<(), String> {
+    if connection.transport != "tcp" {
+        return Err(format!("Unsupported transport '{}' (expected: tcp)", connection.transport));
+    }
+    let session = Arc::new(Session::new(&connection.key));
+    let bind = |port: i64, kind: SocketType, inbox| {
+        Socket::bind(&connection.address(port), kind, inbox).map_err(|e| format!("Cannot bind {}: {}", connection.address(port), e))
+    };
+
+    let (requests_tx, requests) = mpsc::channel();
+    let (shell_tx, shell_rx) = mpsc::channel();
+    let (control_tx, control_rx) = mpsc::channel();
+    let (stdin_tx, stdin_rx) = mpsc::channel();
+    let shell = bind(connection.shell_port, SocketType::Router, Some(shell_tx))?;
+    let control = bind(connection.control_port, SocketType::Router, Some(control_tx))?;
+    let stdin = bind(connection.stdin_port, SocketType::Router, Some(stdin_tx))?;
+    let iopub = bind(connection.iopub_port, SocketType::Pub, None)?;
+    bind(connection.hb_port, SocketType::Rep, None)?;
+
+    {
+        let forward = requests_tx.clone();
+        thread::spawn(move || {
+            for message in shell_rx {
+                if forward.send((false, message)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    let cancel = Arc::new(Mutex::new(CancelToken::new()));
+    {
+        let (session, control, cancel) = (Arc::clone(&session), control.clone(), Arc::clone(&cancel));
+        thread::spawn(move || watch_control(session, control, control_rx, requests_tx, cancel));
+    }
+
+    let publisher = Publisher { socket: iopub, session: Arc::clone(&session) };
+    let stream = Rc::new(RefCell::new(Stream { publisher: publisher.clone(), parent: Json::Object(Vec::new()), silent: false, buffer: Vec::new() }));
+    let prompt = Rc::new(RefCell::new(Prompt {
+        socket: stdin,
+        replies: stdin_rx,
+        session: Arc::clone(&session),
+        stream: Rc::clone(&stream),
+        identities: Vec::new(),
+        parent: Json::Object(Vec::new()),
+        allowed: false,
+        cancel: CancelToken::new(),
+        pending: Vec::new(),
+    }));
+    let mut interpreter = DerstandInterpreter::new();
+    interpreter.set_output(Box::new(StreamOutput(Rc::clone(&stream))));
+    interpreter.set_input(Box::new(PromptInput(Rc::clone(&prompt))));
+    let mut kernel = Kernel { session, shell, control, publisher, interpreter, stream, prompt, cancel, execution_count: 0 };
+    kernel.publisher.status(&Json::Object(Vec::new()), "starting");
+
+    for (control, message) in requests {
+        let Some(request) = kernel.session.parse(message) else {
+            continue; // 签名不符的消息直接丢弃
+        };
+        if let Flow::Shutdown = kernel.handle(request, control) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Jupyter的用户数据目录
+fn data_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("JUPYTER_DATA_DIR") {
+        return Some(dir.into());
+    }
+    if cfg!(windows) {
+        return std::env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("jupyter"));
+    }
+    let home = PathBuf::from(std::env::var_os("HOME")?);
+    Some(if cfg!(target_os = "macos") { home.join("Library/Jupyter") } else { home.join(".local/share/jupyter") })
+}
+
+/// 写出内核规格kernel.json
+fn install(dir: Option<PathBuf>) -> Result<PathBuf, String> {
+    let dir = match dir {
+        Some(dir) => dir,
+        None => data_dir().ok_or("Cannot locate the Jupyter data directory; pass one to --install")?.join("kernels").join("derstand"),
+    };
+    let exe = std::env::current_exe().map_err(|e| format!("Cannot locate the derstand executable: {}", e))?;
+    let spec = Json::object([
+        ("argv", vec![exe.display().to_string(), "jupyter-kernel".to_string(), "-f".to_string(), "{connection_file}".to_string()].into()),
+        ("display_name", "Derstand".into()),
+        ("language", "derstand".into()),
+        ("interrupt_mode", "message".into()),
+    ]);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+    let path = dir.join("kernel.json");
+    std::fs::write(&path, format!("{}\n", spec)).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// `derstand jupyter-kernel [-f] <connection.json>` 或 `derstand jupyter-kernel --install [dir]`
+pub fn command(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: derstand jupyter-kernel [-f] <connection.json>\n       derstand jupyter-kernel --install [dir]";
+    let mut file = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--install" => {
+                return match install(iter.next().map(PathBuf::from)) {
+                    Ok(path) => {
+                        println!("Installed kernel spec {}", path.display());
+                        0
+                    },
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        1
+                    },
+                };
+            },
+            "-f" => match iter.next() {
+                Some(path) => file = Some(path.clone()),
+                None => {
+                    eprintln!("Missing value for -f");
+                    return 2;
+                },
+            },
+            _ if arg.starts_with('-') => {
+                eprintln!("Unknown option: {}", arg);
+                return 2;
+            },
+            _ => file = Some(arg.clone()),
+        }
+    }
+    let Some(file) = file else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+
+    let connection = match std::fs::read_to_string(&file).map_err(|e| format!("Error reading connection file {}: {}", file, e)).and_then(|text| Connection::parse(&text)) {
+        Ok(connection) => connection,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        },
+    };
+    match serve(&connection) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        },
+    }
+}
@@ -0,0 +1,168 @@
+//! 交互式教程 - `derstand learn`逐课讲解指令，学习者写出程序，沙箱中运行并检查输出
+//!
+//! 每课给出一个目标和若干组输入/期望输出，全部通过才进入下一课。提示符下还可以输入
+//! `hint`、`skip`或`quit`。`derstand learn 5`从第5课开始。
+
+use std::io::{self, BufRead, Write};
+
+use crate::diagnostic::stderr_color;
+use crate::sandbox::SandboxProfile;
+use crate::{DerstandInterpreter, Dialect, runtime_error};
+
+/// 一课：讲解、目标、提示与检查用例(输入, 期望输出)
+struct Lesson {
+    title: &'static str,
+    text: &'static str,
+    goal: &'static str,
+    hint: &'static str,
+    cases: &'static [(&'static [u8], &'static [u8])],
+}
+
+const LESSONS: &[Lesson] = &[
+    Lesson {
+        title: "Printing a byte",
+        text: "The tape is a row of byte cells, all starting at 0. '+' adds 1 to the current cell and '.' prints it as a byte.\n\
+               '+{n}' adds n at once.",
+        goal: "Print the letter A (byte 65).",
+        hint: "+{65}.",
+        cases: &[(b"", b"A")],
+    },
+    Lesson {
+        title: "Printing numbers",
+        text: "':' prints the current cell as a decimal number instead of a byte. It belongs to the extended dialect, like most\n\
+               lessons from here on; files turn it on with a first line ';! dialect=extended'.",
+        goal: "Print 42.",
+        hint: "+{42}:",
+        cases: &[(b"", b"42")],
+    },
+    Lesson {
+        title: "Loops",
+        text: "'>' and '<' move to the next and previous cell. '[' skips past the matching ']' when the cell is 0,\n\
+               and ']' jumps back while it is not, so a counter cell that counts down repeats the loop body.",
+        goal: "Print ten exclamation marks (byte 33): !!!!!!!!!!",
+        hint: "Keep a counter of 10 in the first cell and the 33 in the second: +{10}[>...<-]",
+        cases: &[(b"", b"!!!!!!!!!!")],
+    },
+    Lesson {
+        title: "Reading input",
+        text: "',' reads one byte of input into the current cell.",
+        goal: "Read one byte and print it twice.",
+        hint: ",..",
+        cases: &[(b"x", b"xx"), (b"7", b"77")],
+    },
+    Lesson {
+        title: "Cat",
+        text: "At the end of the input ',' reads 0, so a loop can run until the input is used up.",
+        goal: "Copy all of the input to the output.",
+        hint: ",[.,]",
+        cases: &[(b"hello", b"hello"), (b"Derstand!", b"Derstand!"), (b"", b"")],
+    },
+    Lesson {
+        title: "Copying and adding",
+        text: "'?' reads a decimal number. '$' copies the current cell into the next one, and '~' adds the current\n\
+               cell to the next one and clears it, without writing a loop.",
+        goal: "Read a number and print twice its value.",
+        hint: "?$~>:",
+        cases: &[(b"21\n", b"42"), (b"0\n", b"0"), (b"100\n", b"200")],
+    },
+    Lesson {
+        title: "Clearing",
+        text: "'#' sets the current cell to 0 in one step, however large it was.",
+        goal: "Read a byte, print it, then clear the cell and print it as a number.",
+        hint: ",.#:",
+        cases: &[(b"Q", b"Q0"), (b"~", b"~0")],
+    },
+    Lesson {
+        title: "The stack",
+        text: "'(' pushes the current cell onto a stack and ')' pops the top of the stack into the current cell.",
+        goal: "Read two bytes and print them in reverse order.",
+        hint: ",(,.).",
+        cases: &[(b"ab", b"ba"), (b"xy", b"yx")],
+    },
+    Lesson {
+        title: "Procedures",
+        text: "'{...}' defines a procedure, numbered from 0 in order; running past it skips the body.\n\
+               '^{n}' calls procedure n, which works on whatever cell is current.",
+        goal: "Define a procedure that prints the current cell twice, and use it to print AABB.",
+        hint: "{..}+{65}^{0}+^{0}",
+        cases: &[(b"", b"AABB")],
+    },
+    Lesson {
+        title: "Two tapes",
+        text: "'|' switches to a second tape with its own pointer, and '\\' swaps the current cells of the two tapes.",
+        goal: "Read a byte, move it to the second tape and print it from there.",
+        hint: ",\\|.",
+        cases: &[(b"x", b"x"), (b"!", b"!")],
+    },
+];
+
+/// 沙箱中按用例逐一运行；返回第一个失败的说明
+fn check(lesson: &Lesson, source: &str) -> Result<(), String> {
+    for (input, expected) in lesson.cases {
+        let mut interpreter = DerstandInterpreter::new();
+        // 教程讲解全部指令
+        interpreter.set_dialect(Dialect::Extended);
+        interpreter.set_sandbox(Some(SandboxProfile { input: input.to_vec(), max_steps: 1_000_000, max_time_ms: 2_000, ..Default::default() }));
+        interpreter.compile(source).map_err(|e| e.render(source, None, stderr_color()))?;
+        if let Err(e) = interpreter.execute() {
+            return Err(runtime_error(&interpreter, source, e).render(source, None, stderr_color()));
+        }
+        let output = interpreter.captured_output();
+        if output != *expected {
+            let show = |bytes: &[u8]| format!("{:?}", String::from_utf8_lossy(bytes));
+            let context = if input.is_empty() { String::new() } else { format!("With input {}, ", show(input)) };
+            return Err(format!("{}expected {} but the program printed {}", context, show(expected), show(output)));
+        }
+    }
+    Ok(())
+}
+
+/// `derstand learn [LESSON]`
+pub fn command(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: derstand learn [LESSON]";
+    let start = match args {
+        [] => 0,
+        [lesson] => match lesson.parse::<usize>() {
+            Ok(n) if (1..=LESSONS.len()).contains(&n) => n - 1,
+            _ => {
+                eprintln!("There are {} lessons; pick one from 1 to {}", LESSONS.len(), LESSONS.len());
+                return 2;
+            },
+        },
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        },
+    };
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    for (number, lesson) in LESSONS.iter().enumerate().skip(start) {
+        println!("\nLesson {}/{}: {}\n", number + 1, LESSONS.len(), lesson.title);
+        println!("{}\n", lesson.text);
+        println!("Goal: {}", lesson.goal);
+        println!("Type your program on one line, or 'hint', 'skip' or 'quit'.");
+        loop {
+            print!("> ");
+            let _ = io::stdout().flush();
+            let Some(Ok(line)) = lines.next() else {
+                println!();
+                return 0;
+            };
+            match line.trim() {
+                "" => continue,
+                "quit" => return 0,
+                "skip" => break,
+                "hint" => println!("Hint: {}", lesson.hint),
+                source => match check(lesson, source) {
+                    Ok(()) => {
+                        println!("Correct!");
+                        break;
+                    },
+                    Err(e) => println!("{}\nNot quite; try again.", e),
+                },
+            }
+        }
+    }
+    println!("\nYou finished all {} lessons. Run your own programs with 'derstand program.dr'.", LESSONS.len());
+    0
+}
@@ -0,0 +1,2053 @@
+//! Derstand解释器 - 编译、执行与各种工具；命令行程序见main.rs
+//!
+//! 默认的`std`特性提供文件、标准输入输出与命令行工具。关闭后只剩`no_std` + `alloc`的核心：
+//! 编译、执行与加载字节码照常可用，程序的输入输出经`host`模块的特征由调用者提供，
+//! '`'读到0、'/'不睡眠(没有时钟)，`!include`、检查点、死循环检测与取消不可用。
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+
+use diagnostic::Diagnostic;
+use host::{Input, Output};
+#[cfg(feature = "std")]
+use loop_detector::LoopDetector;
+use preprocess::Origin;
+
+pub mod abi;
+mod access;
+#[cfg(feature = "std")]
+mod analysis;
+#[cfg(feature = "std")]
+pub mod animate;
+pub mod args;
+#[cfg(feature = "async")]
+pub mod async_io;
+pub mod audio;
+#[cfg(feature = "std")]
+pub mod background;
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod bundle;
+pub mod bytecode;
+#[cfg(feature = "std")]
+pub mod cancel;
+#[cfg(feature = "std")]
+pub mod channel;
+#[cfg(feature = "std")]
+pub mod checkpoint;
+mod clock;
+#[cfg(feature = "std")]
+pub mod compile;
+pub mod corpus;
+pub mod custom;
+#[cfg(feature = "std")]
+pub mod debugger;
+pub mod device;
+pub mod diagnostic;
+#[cfg(feature = "std")]
+pub mod diff_run;
+#[cfg(feature = "std")]
+mod digest;
+#[cfg(feature = "std")]
+pub mod emit;
+#[cfg(feature = "std")]
+pub mod equiv;
+pub mod examples;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod formatter;
+#[cfg(feature = "std")]
+pub mod framebuffer;
+#[cfg(feature = "std")]
+pub mod fs;
+#[cfg(feature = "std")]
+pub mod generate;
+#[cfg(feature = "std")]
+pub mod golden;
+#[cfg(feature = "std")]
+pub mod heatmap;
+#[cfg(feature = "std")]
+pub mod hexdump;
+#[cfg(feature = "std")]
+pub mod highlight;
+pub mod host;
+pub mod image;
+#[cfg(feature = "std")]
+pub mod import;
+mod json;
+#[cfg(feature = "std")]
+pub mod jupyter;
+#[cfg(feature = "std")]
+pub mod learn;
+#[cfg(feature = "std")]
+pub mod lint;
+#[cfg(feature = "std")]
+pub mod loop_detector;
+#[cfg(feature = "std")]
+pub mod lsp;
+#[cfg(feature = "std")]
+mod metrics;
+#[cfg(feature = "std")]
+pub mod minify;
+#[cfg(feature = "std")]
+pub mod obfuscate;
+#[cfg(feature = "wasm")]
+pub mod playground;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+pub mod pragma;
+mod preprocess;
+pub mod program;
+mod random;
+#[cfg(feature = "std")]
+mod repl;
+#[cfg(feature = "std")]
+pub mod rpc;
+pub mod sandbox;
+#[cfg(feature = "std")]
+pub mod serve;
+#[cfg(feature = "std")]
+pub mod shared;
+mod state;
+#[cfg(feature = "std")]
+pub mod step;
+#[cfg(feature = "std")]
+pub mod synth;
+#[cfg(feature = "std")]
+pub mod tape_file;
+pub mod test_io;
+#[cfg(feature = "std")]
+pub mod turtle;
+#[cfg(feature = "std")]
+pub mod verify;
+#[cfg(feature = "std")]
+pub mod watch;
+#[cfg(feature = "std")]
+mod websocket;
+#[cfg(feature = "std")]
+mod zmq;
+mod zstd;
+
+// 内存大小常量 - 优化的内存使用
+const MEMORY_SIZE: usize = 30000; // 默认纸带大小，可由--memory或编译指示修改
+
+/// 辅助栈的最大深度
+pub const STACK_LIMIT: usize = 1 << 20;
+
+/// 过程调用的最大嵌套深度
+pub const CALL_DEPTH_LIMIT: usize = 10_000;
+
+/// '`'写入的时间戳字节数
+pub const TIME_BYTES: usize = 4;
+
+/// '/'睡眠时检查取消令牌的间隔(毫秒)
+const SLEEP_SLICE_MS: u64 = 10;
+
+/// 行注释标记 - 从它到行尾的字符都不是指令
+pub const COMMENT_MARKER: char = ';';
+
+/// 赋值标记 - `={65}`把当前单元格设为65；不跟花括号时是普通注释字符
+pub const SET_MARKER: char = '=';
+
+/// 字符串字面量标记(扩展方言) - `"Hi"`把各字节写入从指针开始的连续单元格，指针停在最后一个字节之后
+pub const STRING_MARKER: char = '"';
+
+/// Derstand指令枚举 - 13个基本指令，以及由重复计数语法(`+{65}`)生成的合并指令
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    Right,    // > 指针右移
+    Left,     // < 指针左移  
+    Increment, // + 值加1
+    Decrement, // - 值减1
+    Output,   // . 输出
+    OutputDecimal, // : 以十进制数字输出
+    Input,    // , 输入
+    InputDecimal, // ? 读入十进制数
+    Random,   // * 写入伪随机字节
+    Time,     // ` 写入时间戳
+    Sleep,    // / 睡眠当前单元格值的毫秒数
+    ScanLeft,  // « 左移到最近的0单元格
+    ScanRight, // » 右移到最近的0单元格
+    JumpIfZero, // [ 跳到对应的]
+    JumpIfNotZero, // ] 跳回对应的[
+    Zero,     // # 快速清零
+    Copy,     // $ 复制到下一单元格
+    CopyLeft, // £ 复制到上一单元格
+    AddNext,  // ~ 加到下一单元格并清零，等价于[->+<]
+    Push,     // ( 当前值压入辅助栈
+    Pop,      // ) 弹出栈顶写入当前单元格
+    SwitchTape, // | 切换到另一条纸带
+    Exchange, // \ 交换两条纸带当前单元格的值
+    ProcStart, // { 过程定义开始；顺序执行时跳过整个定义
+    ProcEnd,  // } 过程结束，返回调用处
+    Call(u32), // ^{n} 调用第n个过程
+    CallCell, // ^ 调用编号为当前单元格值的过程
+    MoveHigh, // % 移动到高端边界
+    MoveLow,  // & 移动到低端边界
+    Debug,    // @ 向stderr输出调试信息
+    Add(u32),       // +{n} 值加n
+    Sub(u32),       // -{n} 值减n
+    MoveRight(u32), // >{n} 指针右移n
+    MoveLeft(u32),  // <{n} 指针左移n
+    Set(u8),        // ={n} 单元格设为n
+    Custom(char),   // 宿主或插件注册的自定义指令，见custom模块
+    HostCall,       // § 调用编号为当前单元格值的宿主函数
+    Assert(u8),     // =?{n} 当前单元格不等于n时报错；只在开启断言时生成
+}
+
+impl Instruction {
+    /// 从源码字符解析指令(扩展方言)
+    pub fn from_char(c: char) -> Option<Self> {
+        match c {
+            '>' => Some(Instruction::Right),
+            '<' => Some(Instruction::Left),
+            '+' => Some(Instruction::Increment),
+            '-' => Some(Instruction::Decrement),
+            '.' => Some(Instruction::Output),
+            ':' => Some(Instruction::OutputDecimal),
+            ',' => Some(Instruction::Input),
+            '?' => Some(Instruction::InputDecimal),
+            '*' => Some(Instruction::Random),
+            '`' => Some(Instruction::Time),
+            '/' => Some(Instruction::Sleep),
+            '«' => Some(Instruction::ScanLeft),
+            '»' => Some(Instruction::ScanRight),
+            '[' => Some(Instruction::JumpIfZero),
+            ']' => Some(Instruction::JumpIfNotZero),
+            '#' => Some(Instruction::Zero),
+            '$' => Some(Instruction::Copy),
+            '£' => Some(Instruction::CopyLeft),
+            '~' => Some(Instruction::AddNext),
+            '(' => Some(Instruction::Push),
+            ')' => Some(Instruction::Pop),
+            '|' => Some(Instruction::SwitchTape),
+            '\\' => Some(Instruction::Exchange),
+            '{' => Some(Instruction::ProcStart),
+            '}' => Some(Instruction::ProcEnd),
+            '^' => Some(Instruction::CallCell),
+            '%' => Some(Instruction::MoveHigh),
+            '&' => Some(Instruction::MoveLow),
+            '@' => Some(Instruction::Debug),
+            '§' => Some(Instruction::HostCall),
+            _ => None,
+        }
+    }
+
+    /// 按方言解析源码字符：经典方言中扩展指令的字符是注释
+    pub fn from_char_in(c: char, dialect: Dialect) -> Option<Self> {
+        Instruction::from_char(c).filter(|instruction| dialect == Dialect::Extended || !instruction.is_extended())
+    }
+
+    /// 只在扩展方言中是指令 - 这些字符在Brainfuck程序的注释文字里很常见
+    pub fn is_extended(self) -> bool {
+        matches!(
+            self,
+            Instruction::OutputDecimal
+                | Instruction::InputDecimal
+                | Instruction::Random
+                | Instruction::Time
+                | Instruction::Sleep
+                | Instruction::AddNext
+                | Instruction::Push
+                | Instruction::Pop
+                | Instruction::SwitchTape
+                | Instruction::Exchange
+                | Instruction::ProcStart
+                | Instruction::ProcEnd
+                | Instruction::Call(_)
+                | Instruction::CallCell
+                | Instruction::Debug
+                | Instruction::HostCall
+        )
+    }
+
+    /// 指令对应的源码字符
+    pub fn symbol(self) -> char {
+        match self {
+            Instruction::Right => '>',
+            Instruction::Left => '<',
+            Instruction::Increment => '+',
+            Instruction::Decrement => '-',
+            Instruction::Output => '.',
+            Instruction::OutputDecimal => ':',
+            Instruction::Input => ',',
+            Instruction::InputDecimal => '?',
+            Instruction::Random => '*',
+            Instruction::Time => '`',
+            Instruction::Sleep => '/',
+            Instruction::ScanLeft => '«',
+            Instruction::ScanRight => '»',
+            Instruction::JumpIfZero => '[',
+            Instruction::JumpIfNotZero => ']',
+            Instruction::Zero => '#',
+            Instruction::Copy => '$',
+            Instruction::CopyLeft => '£',
+            Instruction::AddNext => '~',
+            Instruction::Push => '(',
+            Instruction::Pop => ')',
+            Instruction::SwitchTape => '|',
+            Instruction::Exchange => '\\',
+            Instruction::ProcStart => '{',
+            Instruction::ProcEnd => '}',
+            Instruction::Call(_) | Instruction::CallCell => '^',
+            Instruction::MoveHigh => '%',
+            Instruction::MoveLow => '&',
+            Instruction::Debug => '@',
+            Instruction::Custom(symbol) => symbol,
+            Instruction::HostCall => '§',
+            Instruction::Assert(_) => SET_MARKER,
+            Instruction::Add(_) => '+',
+            Instruction::Sub(_) => '-',
+            Instruction::MoveRight(_) => '>',
+            Instruction::MoveLeft(_) => '<',
+            Instruction::Set(_) => SET_MARKER,
+        }
+    }
+
+    /// 把指令重复count次 - 只有加减与左右移动可以合并
+    pub fn repeated(self, count: u32) -> Option<Self> {
+        match (self, count) {
+            (_, 1) => Some(self),
+            (Instruction::Increment, _) => Some(Instruction::Add(count)),
+            (Instruction::Decrement, _) => Some(Instruction::Sub(count)),
+            (Instruction::Right, _) => Some(Instruction::MoveRight(count)),
+            (Instruction::Left, _) => Some(Instruction::MoveLeft(count)),
+            _ => None,
+        }
+    }
+
+    /// 合并的次数(普通指令为1)
+    pub fn count(self) -> u32 {
+        match self {
+            Instruction::Add(n) | Instruction::Sub(n) | Instruction::MoveRight(n) | Instruction::MoveLeft(n) => n,
+            _ => 1,
+        }
+    }
+
+    /// 指令名称 - 用于IR导出等工具输出
+    pub fn name(self) -> &'static str {
+        match self {
+            Instruction::Right => "Right",
+            Instruction::Left => "Left",
+            Instruction::Increment => "Increment",
+            Instruction::Decrement => "Decrement",
+            Instruction::Output => "Output",
+            Instruction::OutputDecimal => "OutputDecimal",
+            Instruction::Input => "Input",
+            Instruction::InputDecimal => "InputDecimal",
+            Instruction::Random => "Random",
+            Instruction::Time => "Time",
+            Instruction::Sleep => "Sleep",
+            Instruction::ScanLeft => "ScanLeft",
+            Instruction::ScanRight => "ScanRight",
+            Instruction::JumpIfZero => "JumpIfZero",
+            Instruction::JumpIfNotZero => "JumpIfNotZero",
+            Instruction::Zero => "Zero",
+            Instruction::Copy => "Copy",
+            Instruction::CopyLeft => "CopyLeft",
+            Instruction::AddNext => "AddNext",
+            Instruction::Push => "Push",
+            Instruction::Pop => "Pop",
+            Instruction::SwitchTape => "SwitchTape",
+            Instruction::Exchange => "Exchange",
+            Instruction::ProcStart => "ProcStart",
+            Instruction::ProcEnd => "ProcEnd",
+            Instruction::Call(_) => "Call",
+            Instruction::CallCell => "CallCell",
+            Instruction::MoveHigh => "MoveHigh",
+            Instruction::MoveLow => "MoveLow",
+            Instruction::Debug => "Debug",
+            Instruction::Add(_) => "Add",
+            Instruction::Sub(_) => "Sub",
+            Instruction::MoveRight(_) => "MoveRight",
+            Instruction::MoveLeft(_) => "MoveLeft",
+            Instruction::Set(_) => "Set",
+            Instruction::Custom(_) => "Custom",
+            Instruction::HostCall => "HostCall",
+            Instruction::Assert(_) => "Assert",
+        }
+    }
+
+    /// 指令作用的一句话说明 - 用于编辑器悬停提示等
+    pub fn description(self) -> &'static str {
+        match self {
+            Instruction::Right => "Move the pointer one cell to the right",
+            Instruction::Left => "Move the pointer one cell to the left",
+            Instruction::Increment => "Add 1 to the current cell",
+            Instruction::Decrement => "Subtract 1 from the current cell",
+            Instruction::Output => "Write the current cell as a byte",
+            Instruction::OutputDecimal => "Write the current cell as a decimal number",
+            Instruction::Input => "Read a byte into the current cell",
+            Instruction::InputDecimal => "Read a decimal number into the current cell",
+            Instruction::Random => "Store a pseudo-random byte in the current cell",
+            Instruction::Time => "Store a millisecond timestamp in the current cell and the next three, little-endian",
+            Instruction::Sleep => "Sleep for as many milliseconds as the current cell holds",
+            Instruction::ScanLeft => "Move left to the nearest cell holding 0",
+            Instruction::ScanRight => "Move right to the nearest cell holding 0",
+            Instruction::JumpIfZero => "Start a loop: skip past the matching ']' if the current cell is 0",
+            Instruction::JumpIfNotZero => "End a loop: jump back to the matching '[' unless the current cell is 0",
+            Instruction::Zero => "Set the current cell to 0",
+            Instruction::Copy => "Copy the current cell into the next cell",
+            Instruction::CopyLeft => "Copy the current cell into the previous cell",
+            Instruction::AddNext => "Add the current cell to the next cell and clear it, like [->+<]",
+            Instruction::Push => "Push the current cell onto the stack",
+            Instruction::Pop => "Pop the top of the stack into the current cell",
+            Instruction::SwitchTape => "Switch to the other tape",
+            Instruction::Exchange => "Swap the current cells of the two tapes",
+            Instruction::ProcStart => "Start a procedure definition; skipped when reached in sequence",
+            Instruction::ProcEnd => "End a procedure and return to the caller",
+            Instruction::Call(_) => "Call procedure n",
+            Instruction::CallCell => "Call the procedure numbered by the current cell",
+            Instruction::MoveHigh => "Move the pointer to the last cell",
+            Instruction::MoveLow => "Move the pointer to the first cell",
+            Instruction::Debug => "Print the tape position and current cell to stderr",
+            Instruction::Add(_) => "Add n to the current cell",
+            Instruction::Sub(_) => "Subtract n from the current cell",
+            Instruction::MoveRight(_) => "Move the pointer n cells to the right",
+            Instruction::MoveLeft(_) => "Move the pointer n cells to the left",
+            Instruction::Set(_) => "Set the current cell to n",
+            Instruction::Custom(_) => "Run an instruction registered by the host or a plugin",
+            Instruction::HostCall => "Call the host function numbered by the current cell, passing the cells after it",
+            Instruction::Assert(_) => "Stop with an error unless the current cell holds n (only with assertions enabled)",
+        }
+    }
+}
+
+impl core::fmt::Display for Instruction {
+    /// 源码形式 - 合并指令写作`+{65}`，赋值写作`={65}`，调用写作`^{2}`
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Instruction::Set(value) => return write!(f, "{}{{{}}}", SET_MARKER, value),
+            Instruction::Assert(value) => return write!(f, "{}?{{{}}}", SET_MARKER, value),
+            Instruction::Call(n) => return write!(f, "{}{{{}}}", self.symbol(), n),
+            _ => {},
+        }
+        match self.count() {
+            1 => write!(f, "{}", self.symbol()),
+            n => write!(f, "{}{{{}}}", self.symbol(), n),
+        }
+    }
+}
+
+/// 单元格加减越界时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum OverflowPolicy {
+    #[default]
+    Wrap,     // 按字节回绕(255+1=0)
+    Saturate, // 停在0或255
+    Trap,     // 报告运行时错误
+}
+
+impl OverflowPolicy {
+    /// 解析命令行参数值
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "wrap" => Ok(OverflowPolicy::Wrap),
+            "saturate" => Ok(OverflowPolicy::Saturate),
+            "trap" => Ok(OverflowPolicy::Trap),
+            _ => Err(format!("Unknown overflow policy '{}' (expected: wrap, saturate, trap)", value)),
+        }
+    }
+
+    /// 命令行参数值，与parse互逆
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OverflowPolicy::Wrap => "wrap",
+            OverflowPolicy::Saturate => "saturate",
+            OverflowPolicy::Trap => "trap",
+        }
+    }
+}
+
+/// 源码的方言 - 决定哪些字符是指令
+///
+/// 经典方言是Brainfuck的八条指令，另外只保留下列写法，其余字符(包括`@`与`"`)都是注释：
+///
+/// ```text
+/// # $ £ % & « »     清零、复制、移到两端与扫描
+/// ;                 行注释，到行尾为止
+/// +{n} >{n} ...     重复计数
+/// ={n} =?{n}        赋值与断言；不跟花括号的'='是注释
+/// ```
+///
+/// 扩展方言另有`:`、`?`、`*`、`` ` ``、`/`、`~`、`(`、`)`、`|`、`\`、`{`、`}`、`^`、`@`、`§`与`"`字符串字面量，
+/// 以`;! dialect=extended`或`--dialect extended`开启。
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Dialect {
+    #[default]
+    Classic,
+    Extended,
+}
+
+impl Dialect {
+    /// 解析命令行参数值
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "classic" => Ok(Dialect::Classic),
+            "extended" => Ok(Dialect::Extended),
+            _ => Err(format!("Unknown dialect '{}' (expected: classic, extended)", value)),
+        }
+    }
+
+    /// 命令行参数值，与parse互逆
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Dialect::Classic => "classic",
+            Dialect::Extended => "extended",
+        }
+    }
+}
+
+/// 输入耗尽时','的行为
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EofBehavior {
+    Zero,      // 单元格设为0(交互式模式的默认行为)
+    Unchanged, // 保持单元格不变
+    Max,       // 单元格设为255，即-1
+    Error,     // 报告运行时错误(文件模式的默认行为)
+}
+
+impl EofBehavior {
+    /// 解析命令行参数值
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "zero" => Ok(EofBehavior::Zero),
+            "unchanged" => Ok(EofBehavior::Unchanged),
+            "max" => Ok(EofBehavior::Max),
+            "error" => Ok(EofBehavior::Error),
+            _ => Err(format!("Unknown EOF behavior '{}' (expected: zero, unchanged, max, error)", value)),
+        }
+    }
+
+    /// 命令行参数值，与parse互逆
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EofBehavior::Zero => "zero",
+            EofBehavior::Unchanged => "unchanged",
+            EofBehavior::Max => "max",
+            EofBehavior::Error => "error",
+        }
+    }
+}
+
+/// 源码位置 - 字符偏移与行列号(行列从1开始)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// 回溯中的一层循环
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoopFrame {
+    pub open: usize, // '['的指令下标
+    pub span: Span,
+    pub iteration: u64, // 当前轮次(从1开始)
+}
+
+/// 高效的跳转表结构 - 避免运行时计算跳转位置
+#[derive(Debug, Clone)]
+struct JumpTable {
+    // 跳转到对应右括号的位置: '[' -> 位置
+    to_close: Vec<usize>,
+    // 跳转到对应左括号的位置: ']' -> 位置  
+    to_open: Vec<usize>,
+}
+
+impl JumpTable {
+    /// 查询括号指令的配对位置
+    fn target(&self, pc: usize, instruction: Instruction) -> Option<usize> {
+        match instruction {
+            Instruction::JumpIfZero | Instruction::ProcStart => self.to_close.get(pc).copied(),
+            Instruction::JumpIfNotZero | Instruction::ProcEnd => self.to_open.get(pc).copied(),
+            _ => None,
+        }
+    }
+}
+
+/// 未激活的纸带及其指针
+#[derive(Debug, Clone, Hash)]
+struct Tape {
+    memory: Vec<u8>,
+    pointer: usize,
+}
+
+/// Derstand解释器 - 优化版本
+pub struct DerstandInterpreter {
+    memory: Vec<u8>, // 零拷贝内存访问
+    stack: Vec<u8>, // 辅助栈
+    other_tape: Option<Tape>, // 第二条纸带，首次使用时分配；激活的纸带总在memory中
+    active_tape: usize, // 当前纸带编号(0或1)
+    procedures: Vec<usize>, // 每个过程'{'的指令下标，按源码顺序编号
+    call_stack: Vec<(usize, usize)>, // (返回地址, 过程编号)
+    pointer: usize,
+    pc: usize, // 程序计数器 - 支持单步执行
+    steps: u64, // 本次运行已执行的指令数
+    instructions: Vec<Instruction>,
+    spans: Vec<Span>, // 每条指令对应的源码位置
+    expansions: Vec<Option<usize>>, // 每条指令所属的宏展开(macros的下标)
+    expansion_notes: Vec<String>, // 宏展开与被包含文件的说明
+    #[cfg(feature = "std")]
+    source_path: Option<PathBuf>, // 源码文件路径，用于解析!include
+    #[cfg(feature = "std")]
+    allow_include: bool, // 关闭后!include不读取文件
+    jump_table: JumpTable, // 优化后的跳转表
+    loop_counts: Vec<u64>, // 以'['下标索引的当前循环轮次
+    input_buffer: Vec<u8>,
+    output_buffer: Vec<u8>,
+    is_interactive_mode: bool, // 标记是否处于交互式模式
+    #[cfg(feature = "std")]
+    loop_detector: Option<LoopDetector>, // 可选的死循环检测
+    input_recorder: Option<Box<dyn Output>>, // 输入录制目标
+    input_source: Option<Box<dyn Input>>, // 调用者提供的输入，取代标准输入
+    output: Option<Box<dyn Output>>, // 调用者提供的输出，取代标准输出
+    #[cfg(feature = "std")]
+    checkpoint: Option<checkpoint::CheckpointConfig>, // 周期性检查点
+    overflow: Option<OverflowPolicy>, // 单元格加减越界的处理方式；None时由编译指示决定
+    dialect: Option<Dialect>, // 调用者指定的方言，优先于编译指示
+    strict_bounds: bool, // 指针越界时报错而非钳制
+    memory_override: Option<usize>, // 调用者指定的纸带大小，优先于编译指示
+    start_pointer: Option<usize>, // 每次运行开始时第一条纸带的指针位置，默认为0
+    eof: Option<EofBehavior>, // 调用者指定的输入耗尽行为，优先于编译指示
+    pragma: pragma::Pragma, // 源码开头声明的执行选项
+    rng: random::Rng, // '*'使用的伪随机数生成器
+    deterministic: bool, // 确定性模式：'`'读取按执行步数计的虚拟时钟
+    started: u64, // 本次运行开始时的clock::now_ms()
+    #[cfg(feature = "std")]
+    cancel: Option<cancel::CancelToken>, // 调用者持有的取消令牌
+    custom: Vec<custom::CustomInstruction>, // 已注册的自定义指令
+    host_functions: Vec<Option<custom::HostEntry>>, // 以编号索引的宿主函数
+    sandbox: Option<sandbox::Sandbox>, // 启用时切断外部输入输出并限制资源
+    assertions: bool, // 编译时是否生成'=?{n}'断言
+    args: Option<Vec<u8>>, // 编码后的程序参数，每次运行前写入纸带开头
+    abi: bool, // 调用者开启的纸带ABI；编译指示abi=1同样开启
+    devices: Vec<(usize, device::Device)>, // 映射到设备的单元格
+    #[cfg(feature = "std")]
+    shared: Vec<shared::Mapping>, // 映射到纸带上的共享区域
+    #[cfg(feature = "std")]
+    heatmap: Option<heatmap::Heatmap>, // 开启时统计每个单元格的读写次数
+}
+
+impl Default for DerstandInterpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DerstandInterpreter {
+    /// 创建新的解释器实例 - 最小化内存分配
+    pub fn new() -> Self {
+        DerstandInterpreter {
+            memory: vec![0; MEMORY_SIZE],
+            stack: Vec::new(),
+            other_tape: None,
+            active_tape: 0,
+            procedures: Vec::new(),
+            call_stack: Vec::new(),
+            pointer: 0,
+            pc: 0,
+            steps: 0,
+            instructions: Vec::with_capacity(1024), // 预分配指令空间
+            spans: Vec::with_capacity(1024),
+            expansions: Vec::with_capacity(1024),
+            expansion_notes: Vec::new(),
+            #[cfg(feature = "std")]
+            source_path: None,
+            #[cfg(feature = "std")]
+            allow_include: true,
+            jump_table: JumpTable {
+                to_close: Vec::with_capacity(512),
+                to_open: Vec::with_capacity(512),
+            },
+            loop_counts: Vec::new(),
+            input_buffer: Vec::with_capacity(256),
+            output_buffer: Vec::with_capacity(256),
+            is_interactive_mode: false,
+            #[cfg(feature = "std")]
+            loop_detector: None,
+            input_recorder: None,
+            input_source: None,
+            output: None,
+            #[cfg(feature = "std")]
+            checkpoint: None,
+            overflow: None,
+            dialect: None,
+            strict_bounds: false,
+            memory_override: None,
+            start_pointer: None,
+            eof: None,
+            pragma: pragma::Pragma::default(),
+            rng: random::Rng::new(random::Rng::entropy_seed()),
+            deterministic: false,
+            started: clock::now_ms(),
+            #[cfg(feature = "std")]
+            cancel: None,
+            custom: Vec::new(),
+            host_functions: Vec::new(),
+            sandbox: None,
+            assertions: false,
+            args: None,
+            abi: false,
+            devices: Vec::new(),
+            #[cfg(feature = "std")]
+            shared: Vec::new(),
+            #[cfg(feature = "std")]
+            heatmap: None,
+        }
+    }
+
+    /// 以构造器设置选项、输入输出与自定义指令
+    pub fn builder() -> builder::Builder {
+        builder::Builder::new()
+    }
+
+    /// 编译源代码 - 优化版本
+    pub fn compile(&mut self, source: &str) -> Result<(), Diagnostic> {
+        self.instructions.clear();
+        self.spans.clear();
+        self.expansions.clear();
+        self.expansion_notes.clear();
+        self.jump_table.to_close.clear();
+        self.jump_table.to_open.clear();
+        self.procedures.clear();
+        self.instructions.reserve(source.len()); // 预分配空间
+        
+        // 编译指示决定纸带大小；大小改变时重新分配纸带
+        self.pragma = pragma::parse(source)?;
+        let size = self.memory_override.or(self.pragma.memory).unwrap_or(MEMORY_SIZE);
+        self.check_memory_limit(size)?;
+        if self.memory.len() != size {
+            self.memory = vec![0; size];
+            self.pointer = self.pointer.min(size - 1);
+            self.other_tape = None;
+            self.active_tape = 0;
+        }
+        let reserved = if self.abi_enabled() { abi::header_len(self.args.as_deref()) } else { self.args.as_ref().map_or(0, Vec::len) };
+        let region = if self.abi_enabled() { "the tape ABI header" } else { "the program arguments" };
+        if reserved > size {
+            let what = if self.abi_enabled() { "The tape ABI header needs" } else { "Program arguments need" };
+            return Err(Diagnostic::error("E0017", format!("{} {} cells, but the tape has only {}", what, reserved, size))
+                .with_hint("shorten the arguments, or enlarge the tape with --memory or a memory pragma"));
+        }
+        if let Some(start) = self.start_pointer {
+            if start >= size {
+                return Err(Diagnostic::error("E0018", format!("Start pointer {} is outside the tape of {} cells", start, size))
+                    .with_hint("pick a cell below the tape size, or enlarge the tape with --memory or a memory pragma"));
+            }
+            // 保留区是程序的输入，从中间开始会让程序把它当作工作区
+            if start < reserved {
+                return Err(Diagnostic::error("E0018", format!("Start pointer {} is inside the first {} cells reserved for {}", start, reserved, region))
+                    .with_hint(format!("start at cell {} or later", reserved)));
+            }
+        }
+        
+        // 含指令行时先展开宏；否则直接逐字符解析，避免额外的位置表
+        if preprocess::has_directives(source) {
+            #[cfg(feature = "std")]
+            let expanded = preprocess::expand(source, self.source_path.as_deref(), self.allow_include && self.sandbox.is_none())?;
+            #[cfg(not(feature = "std"))]
+            let expanded = preprocess::expand(source)?;
+            self.expansion_notes = expanded.notes;
+            self.parse(expanded.text.chars().zip(expanded.origins))
+        } else {
+            self.parse(preprocess::spanned(source).map(|(c, span)| (c, Origin { span, expansion: None })))
+        }
+    }
+
+    /// 沙箱中纸带不能超过max_memory；在分配纸带之前检查
+    pub(crate) fn check_memory_limit(&self, size: usize) -> Result<(), Diagnostic> {
+        match &self.sandbox {
+            Some(sandbox) if size > sandbox.max_memory => {
+                Err(Diagnostic::error("E0120", format!("Program asks for {} cells, more than the sandbox limit of {}", size, sandbox.max_memory))
+                    .with_hint("lower the memory pragma, or raise max_memory in the sandbox profile"))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// 解析(已展开的)源码字符并建立跳转表
+    fn parse(&mut self, chars: impl Iterator<Item = (char, Origin)>) -> Result<(), Diagnostic> {
+        let mut bracket_stack = Vec::with_capacity(128);
+        let mut procedure = None; // 正在定义的过程的'{'下标
+        let mut in_comment = false; // ';'到行尾为注释
+        let dialect = self.dialect();
+        let mut chars = chars.peekable();
+        
+        while let Some((c, origin)) = chars.next() {
+            let mut span = origin.span;
+            if c == '\n' {
+                in_comment = false;
+            }
+            if in_comment {
+                continue;
+            }
+            if c == STRING_MARKER && dialect == Dialect::Extended {
+                self.string(&mut chars, origin)?;
+                continue;
+            }
+            if c == COMMENT_MARKER {
+                in_comment = true;
+                continue;
+            }
+            // ={n} 赋值，=?{n} 断言，其余的'='是注释
+            let question = if c == SET_MARKER { chars.next_if(|&(c, _)| c == '?') } else { None };
+            let assert = question.is_some();
+            let set = c == SET_MARKER && !assert && chars.peek().is_some_and(|&(c, _)| c == '{');
+            let instruction = if assert {
+                Some(Instruction::Assert(0))
+            } else if set {
+                Some(Instruction::Set(0))
+            } else {
+                Instruction::from_char_in(c, dialect).or_else(|| self.custom.iter().any(|i| i.symbol == c).then_some(Instruction::Custom(c)))
+            };
+            let Some(mut instruction) = instruction else {
+                continue; // 忽略非指令字符
+            };
+            // 重复计数(+{65})、赋值(={65})、断言(=?{65})与调用(^{2})的花括号数值
+            let call = instruction == Instruction::CallCell;
+            let braced = set || assert || call || instruction.repeated(2).is_some();
+            let opened = braced && chars.next_if(|&(c, _)| c == '{').is_some();
+            if let Some((_, question)) = question.filter(|_| !opened) {
+                // 没有花括号时'='只是注释，'?'在经典方言中也是
+                if dialect == Dialect::Classic {
+                    continue;
+                }
+                instruction = Instruction::InputDecimal;
+                span = question.span;
+            } else if opened {
+                let mut digits = String::new();
+                while let Some((c, _)) = chars.next_if(|&(c, _)| c != '}' && c != '\n') {
+                    digits.push(c);
+                }
+                let closed = chars.next_if(|&(c, _)| c == '}').is_some();
+                let value = digits.trim().parse::<u32>().ok().filter(|_| closed);
+                let what = if set || assert { "cell value" } else if call { "procedure number" } else { "repeat count" };
+                instruction = match value {
+                    None => {
+                        let example = if assert { "=?".to_string() } else if set { SET_MARKER.to_string() } else { c.to_string() };
+                        let error = Diagnostic::error("E0008", format!("Invalid {} '{{{}'", what, digits))
+                            .with_span(span)
+                            .with_label("expected a number followed by '}'")
+                            .with_hint(format!("write the {} in braces, e.g. '{}{{{}}}'", what, example, if call { 0 } else { 65 }));
+                        return Err(self.with_note_for(origin.expansion, error));
+                    },
+                    Some(value) if set || assert => match u8::try_from(value) {
+                        Ok(_) if assert && !self.assertions => continue, // 未开启断言时不生成指令
+                        Ok(value) if assert => Instruction::Assert(value),
+                        Ok(value) => Instruction::Set(value),
+                        Err(_) => {
+                            let error = Diagnostic::error("E0009", format!("Cell value {} is out of range", value))
+                                .with_span(span)
+                                .with_label("cells hold values 0..=255")
+                                .with_hint("use a value between 0 and 255");
+                            return Err(self.with_note_for(origin.expansion, error));
+                        },
+                    },
+                    Some(n) if call => Instruction::Call(n),
+                    Some(0) => continue, // 重复0次等于没有指令
+                    Some(count) => instruction.repeated(count).unwrap_or(instruction),
+                };
+            }
+            // 跳转表按指令下标索引，与源码中的注释字符无关
+            let index = self.instructions.len();
+            self.instructions.push(instruction);
+            self.spans.push(span);
+            self.expansions.push(origin.expansion);
+            
+            match instruction {
+                Instruction::JumpIfZero => bracket_stack.push(index),
+                Instruction::JumpIfNotZero => {
+                    if let Some(open_pos) = bracket_stack.pop() {
+                        // 确保跳转表足够大
+                        while self.jump_table.to_close.len() <= open_pos {
+                            self.jump_table.to_close.push(0);
+                        }
+                        while self.jump_table.to_open.len() <= index {
+                            self.jump_table.to_open.push(0);
+                        }
+                        self.jump_table.to_close[open_pos] = index;
+                        self.jump_table.to_open[index] = open_pos;
+                    } else {
+                        let error = Diagnostic::error("E0001", format!("Unmatched closing bracket at position {}", span.offset))
+                            .with_span(span)
+                            .with_label("no matching '['")
+                            .with_hint("remove this ']' or add a matching '[' before it");
+                        return Err(self.with_expansion_note(index, error));
+                    }
+                },
+                Instruction::ProcStart => {
+                    // 过程只能在顶层定义，便于静态编号与分析
+                    if procedure.is_some() || !bracket_stack.is_empty() {
+                        let error = Diagnostic::error("E0013", "Procedure defined inside a loop or another procedure")
+                            .with_span(span)
+                            .with_label("procedures can only be defined at the top level")
+                            .with_hint("move this '{ ... }' block out of the enclosing loop or procedure");
+                        return Err(self.with_expansion_note(index, error));
+                    }
+                    procedure = Some(index);
+                    self.procedures.push(index);
+                },
+                Instruction::ProcEnd => {
+                    let Some(open_pos) = procedure.take() else {
+                        let error = Diagnostic::error("E0013", format!("Unmatched closing brace at position {}", span.offset))
+                            .with_span(span)
+                            .with_label("no matching '{'")
+                            .with_hint("remove this '}' or start the procedure with '{'");
+                        return Err(self.with_expansion_note(index, error));
+                    };
+                    if let Some(&open) = bracket_stack.first() {
+                        let error = Diagnostic::error("E0002", format!("Unmatched opening bracket at position {}", self.spans[open].offset))
+                            .with_span(self.spans[open])
+                            .with_label("this loop is not closed before the procedure ends")
+                            .with_hint("add a matching ']' before the closing '}'");
+                        return Err(self.with_expansion_note(open, error));
+                    }
+                    self.jump_table.to_close.resize(self.jump_table.to_close.len().max(open_pos + 1), 0);
+                    self.jump_table.to_open.resize(self.jump_table.to_open.len().max(index + 1), 0);
+                    self.jump_table.to_close[open_pos] = index;
+                    self.jump_table.to_open[index] = open_pos;
+                },
+                _ => {},
+            }
+        }
+        
+        if let Some(open_pos) = procedure {
+            let error = Diagnostic::error("E0013", format!("Unclosed procedure at position {}", self.spans[open_pos].offset))
+                .with_span(self.spans[open_pos])
+                .with_label("this procedure is never closed")
+                .with_hint("add a matching '}' after the procedure body");
+            return Err(self.with_expansion_note(open_pos, error));
+        }
+        
+        // 检查未匹配的左括号
+        if let Some(&open_pos) = bracket_stack.first() {
+            let error = Diagnostic::error("E0002", format!("Unmatched opening bracket at position {}", self.spans[open_pos].offset))
+                .with_span(self.spans[open_pos])
+                .with_label("this loop is never closed")
+                .with_hint("add a matching ']' after this '['");
+            return Err(self.with_expansion_note(open_pos, error));
+        }
+        
+        // 静态调用的过程必须存在
+        for (pc, &instruction) in self.instructions.iter().enumerate() {
+            if let Instruction::Call(n) = instruction
+                && n as usize >= self.procedures.len()
+            {
+                let error = Diagnostic::error("E0014", format!("Call to undefined procedure {}", n))
+                    .with_span(self.spans[pc])
+                    .with_label(format!("the program defines {} procedure(s)", self.procedures.len()))
+                    .with_hint("procedures are numbered from 0 in the order their '{ ... }' blocks appear");
+                return Err(self.with_expansion_note(pc, error));
+            }
+        }
+        
+        // 编译后可以不经reset直接从第一条指令run或step
+        self.pc = 0;
+        self.loop_counts.clear();
+        self.loop_counts.resize(self.instructions.len(), 0);
+        Ok(())
+    }
+
+    /// 解析字符串字面量(开头的'"'已读入)：每个字节依次写入单元格并右移
+    fn string(&mut self, chars: &mut core::iter::Peekable<impl Iterator<Item = (char, Origin)>>, start: Origin) -> Result<(), Diagnostic> {
+        let error = |code: &'static str, message: String, span: Span| Diagnostic::error(code, message).with_span(span);
+        loop {
+            let Some((c, origin)) = chars.next_if(|&(c, _)| c != '\n') else {
+                let error = error("E0010", "Unterminated string literal".to_string(), start.span)
+                    .with_label("this string is never closed")
+                    .with_hint("close the string with '\"' on the same line; write line breaks as \\n");
+                return Err(self.with_note_for(start.expansion, error));
+            };
+            let bytes = match c {
+                STRING_MARKER => return Ok(()),
+                '\\' => {
+                    let escape = chars.next_if(|&(c, _)| c != '\n').map(|(c, _)| c);
+                    let byte = match escape {
+                        Some('n') => Some(b'\n'),
+                        Some('t') => Some(b'\t'),
+                        Some('r') => Some(b'\r'),
+                        Some('0') => Some(0),
+                        Some('\\') => Some(b'\\'),
+                        Some('"') => Some(b'"'),
+                        Some('x') => {
+                            let mut digits = String::new();
+                            while digits.len() < 2
+                                && let Some((c, _)) = chars.next_if(|&(c, _)| c.is_ascii_hexdigit())
+                            {
+                                digits.push(c);
+                            }
+                            u8::from_str_radix(&digits, 16).ok().filter(|_| digits.len() == 2)
+                        },
+                        _ => None,
+                    };
+                    let Some(byte) = byte else {
+                        let shown = escape.map(String::from).unwrap_or_default();
+                        let error = error("E0011", format!("Invalid escape sequence '\\{}'", shown), origin.span)
+                            .with_label("unknown escape")
+                            .with_hint("supported escapes: \\n \\t \\r \\0 \\\\ \\\" \\xNN");
+                        return Err(self.with_note_for(origin.expansion, error));
+                    };
+                    vec![byte]
+                },
+                _ => c.to_string().into_bytes(), // 非ASCII字符按UTF-8写入多个单元格
+            };
+            for byte in bytes {
+                // 字符串生成的右移用MoveRight(1)，与源码中的'>'区分(例如lint不把它与之后的'<'视为抵消)
+                for instruction in [Instruction::Set(byte), Instruction::MoveRight(1)] {
+                    self.instructions.push(instruction);
+                    self.spans.push(origin.span);
+                    self.expansions.push(origin.expansion);
+                }
+            }
+        }
+    }
+
+    /// 指令来自宏展开或被包含文件时，在诊断中注明来源
+    pub fn with_expansion_note(&self, pc: usize, error: Diagnostic) -> Diagnostic {
+        self.with_note_for(self.expansions.get(pc).copied().flatten(), error)
+    }
+
+    fn with_note_for(&self, expansion: Option<usize>, error: Diagnostic) -> Diagnostic {
+        match expansion.and_then(|e| self.expansion_notes.get(e)) {
+            Some(note) => error.with_note(note.clone()),
+            None => error,
+        }
+    }
+
+    /// 设置源码文件路径 - !include的相对路径以它所在的目录为基准
+    #[cfg(feature = "std")]
+    pub fn set_source_path(&mut self, path: impl Into<PathBuf>) {
+        self.source_path = Some(path.into());
+    }
+
+    /// 关闭后`!include`报E0007而不读取文件，用于运行不可信的源码
+    #[cfg(feature = "std")]
+    pub fn set_allow_include(&mut self, allow: bool) {
+        self.allow_include = allow;
+    }
+
+    /// 注册自定义指令，之后编译的源码中symbol字符调用handler；name用于错误信息
+    pub fn register_instruction(&mut self, symbol: char, name: &str, handler: custom::Handler) -> Result<(), String> {
+        custom::check_symbol(symbol)?;
+        if let Some(existing) = self.custom.iter().find(|i| i.symbol == symbol) {
+            return Err(format!("'{}' is already registered as '{}'", symbol, existing.name));
+        }
+        self.custom.push(custom::CustomInstruction { symbol, name: name.to_string(), handler });
+        Ok(())
+    }
+
+    /// 注册编号为number的宿主函数，替换已有的同号函数；`§`调用时它读写当前单元格之后的window个单元格
+    pub fn register_host_function(&mut self, number: u8, window: usize, function: impl FnMut(&mut [u8]) -> Result<(), String> + 'static) {
+        let index = number as usize;
+        if self.host_functions.len() <= index {
+            self.host_functions.resize_with(index + 1, || None);
+        }
+        self.host_functions[index] = Some(custom::HostEntry { window, function: Box::new(function) });
+    }
+
+    /// 把第一条纸带的cell单元格映射到device，替换该单元格已有的设备，见device模块
+    pub fn map_device(&mut self, cell: usize, device: device::Device) {
+        self.devices.retain(|(c, _)| *c != cell);
+        self.devices.push((cell, device));
+    }
+
+    /// 设置程序参数，每次运行前按args模块的布局写入纸带开头
+    pub fn set_args<S: AsRef<[u8]>>(&mut self, args: &[S]) -> Result<(), String> {
+        self.args = Some(args::encode(args)?);
+        Ok(())
+    }
+
+    /// 开启纸带ABI：每次运行前按abi模块的布局写入头部，指针从头部之后开始
+    pub fn set_abi(&mut self, enabled: bool) {
+        self.abi = enabled;
+    }
+
+    /// 是否按纸带ABI运行(调用者开启或源码声明了`;! abi=1`)
+    pub fn abi_enabled(&self) -> bool {
+        self.abi || self.pragma.abi
+    }
+
+    /// 按纸带ABI运行时程序留下的退出状态(第一条纸带的单元格0)；未开启时为None
+    pub fn abi_status(&self) -> Option<u8> {
+        if !self.abi_enabled() {
+            return None;
+        }
+        let first = match &self.other_tape {
+            Some(other) if self.active_tape == 1 => &other.memory,
+            _ => &self.memory,
+        };
+        first.get(abi::STATUS_CELL).copied()
+    }
+
+    /// 开启后编译的源码中'=?{n}'生成断言，否则被忽略；`derstand test`与`--assertions`开启
+    pub fn set_assertions(&mut self, enabled: bool) {
+        self.assertions = enabled;
+    }
+
+    /// 启用或关闭沙箱；启用时以配置中的input取代已预置的输入，见sandbox模块
+    pub fn set_sandbox(&mut self, profile: Option<sandbox::SandboxProfile>) {
+        self.input_buffer.clear();
+        self.sandbox = profile.map(|profile| {
+            let (sandbox, input) = sandbox::Sandbox::new(profile);
+            self.preload_input(&input);
+            sandbox
+        });
+    }
+
+    /// 沙箱中本次运行捕获的输出；未启用沙箱时为空
+    pub fn captured_output(&self) -> &[u8] {
+        self.sandbox.as_ref().map_or(&[], |sandbox| &sandbox.output)
+    }
+
+    /// 已编译的指令序列
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    /// 指令对应的源码位置
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// 括号指令的配对指令下标
+    pub fn jump_target(&self, pc: usize) -> Option<usize> {
+        self.jump_table.target(pc, *self.instructions.get(pc)?)
+    }
+
+    /// 执行编译后的指令 - 高度优化的执行循环
+    pub fn execute(&mut self) -> Result<(), Diagnostic> {
+        self.reset();
+        self.run()
+    }
+
+    /// 在上一次运行留下的纸带与指针上从头执行(热重载)：与execute相同，但两条纸带的指针都保留
+    pub fn execute_in_place(&mut self) -> Result<(), Diagnostic> {
+        if self.active_tape == 1 {
+            self.switch_tape();
+        }
+        let pointers = (self.pointer, self.other_tape.as_ref().map(|other| other.pointer));
+        self.reset();
+        self.pointer = pointers.0;
+        if let (Some(other), Some(pointer)) = (&mut self.other_tape, pointers.1) {
+            other.pointer = pointer;
+        }
+        self.run()
+    }
+
+    /// 从当前状态继续执行直到程序结束(用于从检查点恢复)
+    pub fn run(&mut self) -> Result<(), Diagnostic> {
+        #[cfg(feature = "std")]
+        if self.loop_detector.is_some() || self.checkpoint.is_some() || self.cancel.is_some() {
+            // 带监控(死循环检测、检查点、取消)的执行循环
+            let mut detector = self.loop_detector.take();
+            let result = self.run_monitored(detector.as_mut());
+            self.loop_detector = detector;
+            return result;
+        }
+        
+        // 优化的执行循环
+        while self.step()? {}
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    fn run_monitored(&mut self, mut detector: Option<&mut LoopDetector>) -> Result<(), Diagnostic> {
+        if let Some(detector) = detector.as_deref_mut() {
+            detector.forget();
+        }
+        loop {
+            self.check_cancelled(self.pc)?;
+            let external = detector.is_some() && self.reads_outside(self.pc);
+            if !self.step()? {
+                return Ok(());
+            }
+            if let Some(detector) = detector.as_deref_mut() {
+                let key = (self.pc, self.pointer, self.memory.get(self.pointer), self.stack.len(), self.call_stack.len());
+                if external {
+                    detector.forget();
+                } else if detector.observe(key, || (&self.memory, &self.stack, &self.other_tape, &self.call_stack, self.rng)) {
+                    let frame = self.loop_backtrace().last().copied();
+                    let span = frame.map_or(self.spans[self.pc.min(self.spans.len() - 1)], |f| f.span);
+                    return Err(Diagnostic::error("E0104", format!(
+                        "Non-terminating loop detected at line {}, column {} (machine state repeated after {} steps)",
+                        span.line, span.column, self.steps
+                    ))
+                    .with_span(span)
+                    .with_label("this loop never terminates")
+                    .with_hint("the whole machine state repeated; make sure the loop changes the cell it tests"));
+                }
+            }
+            if let Some(config) = &self.checkpoint
+                && self.sandbox.is_none()
+                && self.steps.is_multiple_of(config.every)
+            {
+                checkpoint::save(self, &config.path).map_err(|e| Diagnostic::error("E0106", e))?;
+            }
+        }
+    }
+
+    /// pc处的指令的结果是否依赖解释器之外的状态：输入、时钟、宿主函数、自定义指令(包括通道)与设备
+    #[cfg(feature = "std")]
+    fn reads_outside(&self, pc: usize) -> bool {
+        let Some(&instruction) = self.instructions.get(pc) else {
+            return false;
+        };
+        match instruction {
+            Instruction::Input | Instruction::InputDecimal | Instruction::Time | Instruction::HostCall | Instruction::Custom(_) => true,
+            _ => !self.devices.is_empty() && self.reads_device(pc),
+        }
+    }
+
+    /// 预置输入字节 - 排在已缓冲的输入之后，先于标准输入被消费
+    pub fn preload_input(&mut self, bytes: &[u8]) {
+        // 输入缓冲区从尾部弹出，因此逆序插入到头部
+        self.input_buffer.splice(0..0, bytes.iter().rev().copied());
+    }
+
+    /// 将每个被`,`消费的字节写入recorder
+    pub fn record_input(&mut self, recorder: Box<dyn Output>) {
+        self.input_recorder = Some(recorder);
+    }
+
+    /// 设置输入源 - 预置输入耗尽后从source读取，与交互式模式一样按流处理
+    pub fn set_input(&mut self, source: Box<dyn Input>) {
+        self.input_source = Some(source);
+    }
+
+    /// 设置输出目标，取代标准输出
+    pub fn set_output(&mut self, sink: Box<dyn Output>) {
+        self.output = Some(sink);
+    }
+
+    /// 输入输出都改用io，便于测试时不碰标准输入输出
+    pub fn with_io(mut self, io: &test_io::TestIo) -> Self {
+        self.set_input(Box::new(io.clone()));
+        self.set_output(Box::new(io.clone()));
+        self
+    }
+
+    /// 交互式模式：预置输入耗尽后读取标准输入，输入耗尽时默认读到0
+    pub fn set_interactive(&mut self, interactive: bool) {
+        self.is_interactive_mode = interactive;
+    }
+
+    /// 每执行every步将状态写入path，None表示关闭
+    #[cfg(feature = "std")]
+    pub fn set_checkpointing(&mut self, config: Option<checkpoint::CheckpointConfig>) {
+        self.checkpoint = config;
+    }
+
+    /// 开启或关闭死循环检测
+    #[cfg(feature = "std")]
+    pub fn set_loop_detection(&mut self, enabled: bool) {
+        self.loop_detector = enabled.then(LoopDetector::new);
+    }
+
+    /// 设置单元格加减越界的处理方式
+    pub fn set_overflow_policy(&mut self, policy: OverflowPolicy) {
+        self.overflow = Some(policy);
+    }
+
+    /// 设置源码的方言，在下次编译时生效
+    pub fn set_dialect(&mut self, dialect: Dialect) {
+        self.dialect = Some(dialect);
+    }
+
+    /// 设置纸带大小(单元格数)，在下次编译时生效
+    pub fn set_memory_size(&mut self, size: usize) {
+        self.memory_override = Some(size);
+    }
+
+    /// 设置输入耗尽时','的行为
+    pub fn set_eof_behavior(&mut self, eof: EofBehavior) {
+        self.eof = Some(eof);
+    }
+
+    /// 设置'*'的随机数种子，使运行可重现
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = random::Rng::new(seed);
+    }
+
+    /// 单元格cell加减amount越界时按策略得到新值
+    #[cold]
+    fn overflowed(&self, pc: usize, cell: usize, value: u8, amount: u32, increment: bool) -> Result<u8, Diagnostic> {
+        match self.overflow_policy() {
+            OverflowPolicy::Wrap => {
+                let amount = (amount % 256) as u8;
+                Ok(if increment { value.wrapping_add(amount) } else { value.wrapping_sub(amount) })
+            },
+            OverflowPolicy::Saturate => Ok(if increment { u8::MAX } else { 0 }),
+            OverflowPolicy::Trap => {
+                let (what, operator) = if increment { ("overflow", '+') } else { ("underflow", '-') };
+                Err(Diagnostic::error("E0107", format!(
+                    "Cell {}: {} {} {} at cell {}",
+                    what, value, operator, amount, cell
+                ))
+                .with_span(self.spans[pc])
+                .with_label(format!("this '{}' leaves the range 0..=255", self.instructions[pc]))
+                .with_hint("check the arithmetic, or run with --overflow wrap to allow wrap-around"))
+            },
+        }
+    }
+
+    /// 当前单元格加减amount
+    #[inline]
+    fn add(&mut self, pc: usize, amount: u32, increment: bool) -> Result<(), Diagnostic> {
+        let value = self.memory[self.pointer];
+        let result = u8::try_from(amount).ok().and_then(|amount| {
+            if increment { value.checked_add(amount) } else { value.checked_sub(amount) }
+        });
+        self.memory[self.pointer] = match result {
+            Some(result) => result,
+            None => self.overflowed(pc, self.pointer, value, amount, increment)?,
+        };
+        Ok(())
+    }
+
+    /// 指针移动amount格 - 越界时钳制，或在严格模式下报错
+    #[inline]
+    fn shift(&mut self, pc: usize, amount: u32, right: bool) -> Result<(), Diagnostic> {
+        let amount = amount as usize;
+        let target = if right { self.pointer.checked_add(amount).filter(|&p| p < self.memory.len()) } else { self.pointer.checked_sub(amount) };
+        match target {
+            Some(target) => self.pointer = target,
+            None if self.strict_bounds => return Err(self.out_of_bounds(pc)),
+            None => self.pointer = if right { self.memory.len() - 1 } else { 0 },
+        }
+        Ok(())
+    }
+
+    /// 每次运行从第一条纸带的cell单元格开始，供需要向左留出空间的程序使用；第二条纸带仍从0开始
+    pub fn set_start_pointer(&mut self, cell: usize) {
+        self.start_pointer = Some(cell);
+    }
+
+    /// 开启后指针移出纸带两端时报告运行时错误，而非静默钳制
+    pub fn set_strict_bounds(&mut self, strict: bool) {
+        self.strict_bounds = strict;
+    }
+
+    /// 开启后'`'读取虚拟时钟(已执行的指令数)而非真实时间，使运行可重现
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    /// 设置取消令牌：令牌被取消后，运行在下一条指令或睡眠中途以E0114停止
+    #[cfg(feature = "std")]
+    pub fn set_cancel_token(&mut self, token: Option<cancel::CancelToken>) {
+        self.cancel = token;
+    }
+
+    /// 令牌已被取消时返回错误
+    #[cfg(not(feature = "std"))]
+    fn check_cancelled(&self, _pc: usize) -> Result<(), Diagnostic> {
+        Ok(())
+    }
+
+    /// 令牌已被取消时返回错误
+    #[cfg(feature = "std")]
+    fn check_cancelled(&self, pc: usize) -> Result<(), Diagnostic> {
+        if !self.cancel.as_ref().is_some_and(|token| token.is_cancelled()) {
+            return Ok(());
+        }
+        let error = Diagnostic::error("E0114", format!("Execution cancelled after {} steps", self.steps))
+            .with_hint("the run was stopped by --timeout or by the host; raise the limit if the program needs more time");
+        Err(match self.spans.get(pc) {
+            Some(&span) => error.with_span(span).with_label("cancelled while executing this instruction"),
+            None => error,
+        })
+    }
+
+    /// 睡眠ms毫秒；分段睡眠以便及时响应取消
+    fn sleep(&self, pc: usize, ms: u64) -> Result<(), Diagnostic> {
+        let deadline = clock::now_ms().saturating_add(ms);
+        if let Some(sandbox) = &self.sandbox
+            && deadline - self.started > sandbox.max_time_ms
+        {
+            return Err(self.sandbox_limit(pc, format!("Sandbox time limit of {} ms exceeded", sandbox.max_time_ms)));
+        }
+        loop {
+            self.check_cancelled(pc)?;
+            let now = clock::now_ms();
+            if now >= deadline {
+                return Ok(());
+            }
+            clock::sleep_ms((deadline - now).min(SLEEP_SLICE_MS));
+        }
+    }
+
+    /// '`'读到的时间：本次运行开始以来的毫秒数，确定性模式下为已执行的指令数
+    fn clock(&self) -> u64 {
+        if self.deterministic { self.steps } else { clock::now_ms() - self.started }
+    }
+
+    /// 沙箱中步数或时间超限时返回错误
+    fn check_sandbox(&self, pc: usize, sandbox: &sandbox::Sandbox) -> Result<(), Diagnostic> {
+        if self.steps >= sandbox.max_steps {
+            return Err(self.sandbox_limit(pc, format!("Sandbox step limit of {} exceeded", sandbox.max_steps)));
+        }
+        if self.steps.is_multiple_of(sandbox::TIME_CHECK_INTERVAL) && clock::now_ms() - self.started > sandbox.max_time_ms {
+            return Err(self.sandbox_limit(pc, format!("Sandbox time limit of {} ms exceeded", sandbox.max_time_ms)));
+        }
+        Ok(())
+    }
+
+    #[cold]
+    fn sandbox_limit(&self, pc: usize, message: String) -> Diagnostic {
+        Diagnostic::error("E0120", message)
+            .with_span(self.spans[pc])
+            .with_label("stopped before this instruction")
+            .with_hint("the program may not terminate; raise the limit in the sandbox profile")
+    }
+
+    /// 沙箱中被禁用的指令
+    #[cold]
+    fn sandbox_disabled(&self, pc: usize) -> Diagnostic {
+        Diagnostic::error("E0121", format!("'{}' is disabled in the sandbox", self.instructions[pc].symbol()))
+            .with_span(self.spans[pc])
+            .with_label("this instruction would reach the host")
+            .with_hint("sandboxed programs cannot call host functions, run custom instructions or print '@' debug lines")
+    }
+
+    /// 严格模式下的指针越界错误
+    #[cold]
+    fn out_of_bounds(&self, pc: usize) -> Diagnostic {
+        let instruction = self.instructions[pc];
+        let amount = instruction.count() as i64;
+        let (target, edge) = match instruction {
+            Instruction::Left | Instruction::MoveLeft(_) | Instruction::CopyLeft => (self.pointer as i64 - amount, "low"),
+            Instruction::Time => ((self.pointer + TIME_BYTES - 1) as i64, "high"),
+            Instruction::ScanLeft => (-1, "low"),
+            Instruction::ScanRight => (self.memory.len() as i64, "high"),
+            _ => (self.pointer as i64 + amount, "high"),
+        };
+        Diagnostic::error("E0108", format!(
+            "Pointer out of bounds: '{}' at cell {} targets cell {}",
+            instruction, self.pointer, target
+        ))
+        .with_span(self.spans[pc])
+        .with_label(format!(
+            "this '{}' {} past the {} edge of the tape",
+            instruction,
+            match instruction {
+                Instruction::Copy | Instruction::CopyLeft => "copies",
+                Instruction::AddNext => "adds",
+                Instruction::Time => "writes",
+                Instruction::ScanLeft | Instruction::ScanRight => "scans",
+                _ => "moves",
+            },
+            edge
+        ))
+        .with_hint(format!("valid cells are 0..={}; run without --strict-bounds to clamp at the edges", self.memory.len() - 1))
+    }
+
+    /// 读取一个输入字节 - 根据模式不同处理方式不同；输入耗尽时返回None
+    fn read_byte(&mut self, pc: usize) -> Result<Option<u8>, Diagnostic> {
+        let byte = match self.input_buffer.pop() {
+            Some(b) => b,
+            // 沙箱：只有预置的输入
+            None if self.sandbox.is_some() => return Ok(None),
+            None if self.streaming_input() => {
+                // 交互式模式或调用者提供的输入源：逐字节读取
+                let read = match &mut self.input_source {
+                    Some(source) => source.read_byte(),
+                    #[cfg(feature = "std")]
+                    None => io::stdin().read_byte(),
+                    #[cfg(not(feature = "std"))]
+                    None => Ok(None),
+                };
+                match read {
+                    Ok(Some(byte)) => byte,
+                    Ok(None) => return Ok(None),
+                    Err(e) => return Err(Diagnostic::error("E0102", format!("Input error: {}", e)).with_span(self.spans[pc])),
+                }
+            },
+            // 文件模式：只有预先载入的输入
+            None => return Ok(None),
+        };
+        if let Some(recorder) = &mut self.input_recorder
+            && self.sandbox.is_none()
+        {
+            // 记录每个被消费的字节，便于之后重放
+            recorder.write_bytes(&[byte])
+                .map_err(|e| Diagnostic::error("E0105", format!("Input recording error: {}", e)))?;
+        }
+        Ok(Some(byte))
+    }
+
+    /// 读取十进制数：跳过前导空白，读到第一个非数字字符为止(该字符也被消费)
+    fn read_number(&mut self, pc: usize) -> Result<u8, Diagnostic> {
+        let mut byte = self.read_byte(pc)?;
+        while byte.is_some_and(|b| b.is_ascii_whitespace()) {
+            byte = self.read_byte(pc)?;
+        }
+        let mut number = None;
+        while let Some(digit) = byte.filter(u8::is_ascii_digit) {
+            number = Some(number.unwrap_or(0u32).saturating_mul(10).saturating_add((digit - b'0') as u32));
+            byte = self.read_byte(pc)?;
+        }
+        match (number, byte) {
+            (Some(n), _) => match u8::try_from(n) {
+                Ok(value) => Ok(value),
+                Err(_) => self.overflowed(pc, self.pointer, 0, n, true),
+            },
+            (None, None) => self.end_of_input(pc),
+            (None, Some(found)) => Err(Diagnostic::error("E0113", format!("Expected a decimal number, found {:?}", found as char))
+                .with_span(self.spans[pc])
+                .with_label("this '?' reads a number")
+                .with_hint("enter digits such as 42, followed by a newline or space")),
+        }
+    }
+
+    /// 写出程序输出并立即刷新；写入失败(如管道关闭)不中断程序，沙箱中输出超限时停止
+    fn write_output(&mut self, pc: usize, bytes: &[u8]) -> Result<(), Diagnostic> {
+        if let Some(sandbox) = &mut self.sandbox {
+            if sandbox.output.len() + bytes.len() > sandbox.max_output {
+                let limit = sandbox.max_output;
+                return Err(self.sandbox_limit(pc, format!("Sandbox output limit of {} bytes exceeded", limit)));
+            }
+            sandbox.output.extend_from_slice(bytes);
+            return Ok(());
+        }
+        let _ = match &mut self.output {
+            Some(sink) => sink.write_bytes(bytes),
+            #[cfg(feature = "std")]
+            None => io::stdout().write_bytes(bytes),
+            #[cfg(not(feature = "std"))]
+            None => Ok(()),
+        };
+        Ok(())
+    }
+
+    /// 输入是否是可读到末尾的流(标准输入、调用者的输入源或沙箱的输入)，而不只是预置的字节
+    fn streaming_input(&self) -> bool {
+        self.sandbox.is_some() || self.is_interactive_mode || self.input_source.is_some()
+    }
+
+    /// 输入耗尽时','读到的值
+    #[cold]
+    fn end_of_input(&self, pc: usize) -> Result<u8, Diagnostic> {
+        let default = if self.streaming_input() { EofBehavior::Zero } else { EofBehavior::Error };
+        match self.eof_behavior().unwrap_or(default) {
+            EofBehavior::Zero => Ok(0),
+            EofBehavior::Unchanged => Ok(self.memory[self.pointer]),
+            EofBehavior::Max => Ok(u8::MAX),
+            EofBehavior::Error if self.streaming_input() => Err(Diagnostic::error("E0101", "Input instruction reached end of input")
+                .with_span(self.spans[pc])
+                .with_label("no more input available")
+                .with_hint("run with --eof zero, or declare `;! eof=zero` at the top of the file")),
+            EofBehavior::Error => Err(Diagnostic::error("E0101", "Input instruction found in file mode")
+                .with_span(self.spans[pc])
+                .with_label("no input available")
+                .with_hint("File execution cannot handle input instructions. Please use interactive mode, --replay-input, --eof, or modify your program to remove input instructions.")),
+        }
+    }
+
+    /// 重置执行状态(指针与程序计数器)，保留内存内容
+    pub fn reset(&mut self) {
+        if self.active_tape == 1 {
+            self.switch_tape();
+        }
+        if let Some(other) = &mut self.other_tape {
+            other.pointer = 0;
+        }
+        // 编译时已核对起点在纸带内；字节码载入不经编译，仍然钳制
+        self.pointer = self.start_pointer.unwrap_or(0).min(self.memory.len() - 1);
+        self.pc = 0;
+        self.steps = 0;
+        self.started = clock::now_ms();
+        self.stack.clear();
+        self.call_stack.clear();
+        self.loop_counts.clear();
+        self.loop_counts.resize(self.instructions.len(), 0);
+        self.output_buffer.clear();
+        if let Some(sandbox) = &mut self.sandbox {
+            sandbox.output.clear();
+        }
+        #[cfg(feature = "std")]
+        if let Some(heatmap) = &mut self.heatmap {
+            *heatmap = heatmap::Heatmap::default();
+        }
+        if self.abi_enabled() {
+            // 交互式或调用者提供的输入在运行前不知道长度
+            let known = !self.is_interactive_mode && self.input_source.is_none();
+            let header = abi::header(self.args.as_deref(), known.then_some(self.input_buffer.len()));
+            let len = header.len().min(self.memory.len());
+            self.memory[..len].copy_from_slice(&header[..len]);
+            if self.start_pointer.is_none() {
+                self.pointer = len.min(self.memory.len() - 1);
+            }
+        } else if let Some(region) = &self.args {
+            let len = region.len().min(self.memory.len());
+            self.memory[..len].copy_from_slice(&region[..len]);
+        }
+    }
+
+    /// 调用第n个过程，返回过程体的第一条指令
+    fn call(&mut self, pc: usize, n: usize) -> Result<usize, Diagnostic> {
+        let Some(&start) = self.procedures.get(n) else {
+            return Err(Diagnostic::error("E0112", format!("Call to undefined procedure {}", n))
+                .with_span(self.spans[pc])
+                .with_label(format!("the program defines {} procedure(s)", self.procedures.len()))
+                .with_hint("'^' calls the procedure numbered by the current cell; procedures are numbered from 0"));
+        };
+        if self.call_stack.len() == CALL_DEPTH_LIMIT {
+            return Err(Diagnostic::error("E0111", format!("Call stack overflow: more than {} nested calls", CALL_DEPTH_LIMIT))
+                .with_span(self.spans[pc])
+                .with_label("this call exceeds the depth limit")
+                .with_hint("check for unbounded recursion"));
+        }
+        self.call_stack.push((pc + 1, n));
+        Ok(start + 1)
+    }
+
+    /// 切换激活的纸带；第二条纸带在首次使用时分配
+    /// 第一条纸带；切换到第二条时它保存在other_tape里
+    #[cfg(feature = "std")]
+    fn first_tape(&mut self) -> &mut Vec<u8> {
+        match &mut self.other_tape {
+            Some(other) if self.active_tape == 1 => &mut other.memory,
+            _ => &mut self.memory,
+        }
+    }
+
+    fn switch_tape(&mut self) {
+        let size = self.memory.len();
+        let other = self.other_tape.get_or_insert_with(|| Tape { memory: vec![0; size], pointer: 0 });
+        core::mem::swap(&mut self.memory, &mut other.memory);
+        core::mem::swap(&mut self.pointer, &mut other.pointer);
+        self.active_tape ^= 1;
+    }
+
+    /// 单步执行一条指令 - 程序结束时返回false
+    #[inline]
+    pub fn step(&mut self) -> Result<bool, Diagnostic> {
+        let mut pc = self.pc; // 程序计数器
+        if pc >= self.instructions.len() {
+            return Ok(false);
+        }
+        if let Some(sandbox) = &self.sandbox {
+            self.check_sandbox(pc, sandbox)?;
+        }
+        // 映射到设备的单元格：执行前从设备取值，执行后把写入的值交给设备
+        let device_writes = if self.devices.is_empty() { Vec::new() } else { self.read_devices(pc)? };
+        #[cfg(feature = "std")]
+        if let Some(heatmap) = &mut self.heatmap
+            && self.active_tape == 0
+        {
+            heatmap.record(self.instructions[pc], self.pointer, &self.memory);
+        }
+        
+        match self.instructions[pc] {
+            Instruction::Right => {
+                // 优化的边界检查
+                if self.pointer + 1 < self.memory.len() {
+                    self.pointer += 1;
+                } else if self.strict_bounds {
+                    return Err(self.out_of_bounds(pc));
+                }
+                pc += 1;
+            },
+            Instruction::Left => {
+                // 优化的边界检查
+                if self.pointer > 0 {
+                    self.pointer -= 1;
+                } else if self.strict_bounds {
+                    return Err(self.out_of_bounds(pc));
+                }
+                pc += 1;
+            },
+            Instruction::Increment => {
+                // 单字节操作，越界时按策略处理
+                self.memory[self.pointer] = match self.memory[self.pointer].checked_add(1) {
+                    Some(value) => value,
+                    None => self.overflowed(pc, self.pointer, u8::MAX, 1, true)?,
+                };
+                pc += 1;
+            },
+            Instruction::Decrement => {
+                // 单字节操作，越界时按策略处理
+                self.memory[self.pointer] = match self.memory[self.pointer].checked_sub(1) {
+                    Some(value) => value,
+                    None => self.overflowed(pc, self.pointer, 0, 1, false)?,
+                };
+                pc += 1;
+            },
+            Instruction::Add(n) | Instruction::Sub(n) => {
+                // 合并的加减 - 一次完成n次操作
+                self.add(pc, n, matches!(self.instructions[pc], Instruction::Add(_)))?;
+                pc += 1;
+            },
+            Instruction::MoveRight(n) | Instruction::MoveLeft(n) => {
+                // 合并的移动 - 一次移动n格
+                self.shift(pc, n, matches!(self.instructions[pc], Instruction::MoveRight(_)))?;
+                pc += 1;
+            },
+            Instruction::Output => {
+                // 直接输出字节，避免UTF-8转换问题
+                self.write_output(pc, &[self.memory[self.pointer]])?;
+                pc += 1;
+            },
+            Instruction::OutputDecimal => {
+                // 十进制ASCII数字，如255输出"255"
+                self.write_output(pc, self.memory[self.pointer].to_string().as_bytes())?;
+                pc += 1;
+            },
+            Instruction::Input => {
+                let value = match self.read_byte(pc)? {
+                    Some(byte) => byte,
+                    None => self.end_of_input(pc)?,
+                };
+                self.memory[self.pointer] = value;
+                pc += 1;
+            },
+            Instruction::InputDecimal => {
+                self.memory[self.pointer] = self.read_number(pc)?;
+                pc += 1;
+            },
+            Instruction::Random => {
+                self.memory[self.pointer] = self.rng.next_byte();
+                pc += 1;
+            },
+            Instruction::Time => {
+                // 从当前单元格起按小端序写入时间戳的低位字节；在高端边界放不下的字节被丢弃
+                let fit = (self.memory.len() - self.pointer).min(TIME_BYTES);
+                if self.strict_bounds && fit < TIME_BYTES {
+                    return Err(self.out_of_bounds(pc));
+                }
+                let bytes = self.clock().to_le_bytes();
+                self.memory[self.pointer..self.pointer + fit].copy_from_slice(&bytes[..fit]);
+                pc += 1;
+            },
+            Instruction::Sleep => {
+                self.sleep(pc, self.memory[self.pointer] as u64)?;
+                pc += 1;
+            },
+            Instruction::JumpIfZero => {
+                // 高效跳转 - 使用预计算的跳转表
+                if self.memory[self.pointer] == 0 {
+                    if pc < self.jump_table.to_close.len() {
+                        pc = self.jump_table.to_close[pc] + 1;
+                    } else {
+                        return Err(Diagnostic::error("E0103", "Jump table out of bounds").with_span(self.spans[pc]));
+                    }
+                } else {
+                    self.loop_counts[pc] = 1; // 进入循环，开始第一轮
+                    pc += 1;
+                }
+            },
+            Instruction::JumpIfNotZero => {
+                // 高效跳转 - 使用预计算的跳转表
+                if self.memory[self.pointer] != 0 {
+                    if pc < self.jump_table.to_open.len() {
+                        let open = self.jump_table.to_open[pc];
+                        self.loop_counts[open] += 1;
+                        pc = open + 1;
+                    } else {
+                        return Err(Diagnostic::error("E0103", "Jump table out of bounds").with_span(self.spans[pc]));
+                    }
+                } else {
+                    pc += 1;
+                }
+            },
+            Instruction::Zero => {
+                // 快速清零 - 比多次减操作更高效
+                self.memory[self.pointer] = 0;
+                pc += 1;
+            },
+            Instruction::Set(value) => {
+                // 直接赋值
+                self.memory[self.pointer] = value;
+                pc += 1;
+            },
+            Instruction::Copy => {
+                // 复制当前值到下一单元格
+                if self.pointer + 1 < self.memory.len() {
+                    self.memory[self.pointer + 1] = self.memory[self.pointer];
+                } else if self.strict_bounds {
+                    return Err(self.out_of_bounds(pc));
+                }
+                pc += 1;
+            },
+            Instruction::CopyLeft => {
+                // 复制当前值到上一单元格
+                if self.pointer > 0 {
+                    self.memory[self.pointer - 1] = self.memory[self.pointer];
+                } else if self.strict_bounds {
+                    return Err(self.out_of_bounds(pc));
+                }
+                pc += 1;
+            },
+            Instruction::AddNext => {
+                // 加到下一单元格并清零；在高端边界时值被丢弃
+                let value = self.memory[self.pointer];
+                if self.pointer + 1 < self.memory.len() {
+                    let next = self.memory[self.pointer + 1];
+                    self.memory[self.pointer + 1] = match next.checked_add(value) {
+                        Some(sum) => sum,
+                        None => self.overflowed(pc, self.pointer + 1, next, value as u32, true)?,
+                    };
+                } else if self.strict_bounds && value != 0 {
+                    return Err(self.out_of_bounds(pc));
+                }
+                self.memory[self.pointer] = 0;
+                pc += 1;
+            },
+            Instruction::Push => {
+                // 压栈 - 深度受限，避免失控的程序耗尽内存
+                if self.stack.len() == STACK_LIMIT {
+                    return Err(Diagnostic::error("E0110", format!("Stack overflow: more than {} values pushed", STACK_LIMIT))
+                        .with_span(self.spans[pc])
+                        .with_label("this '(' exceeds the stack limit")
+                        .with_hint("make sure every '(' is matched by a ')' when the loop repeats"));
+                }
+                self.stack.push(self.memory[self.pointer]);
+                pc += 1;
+            },
+            Instruction::Pop => {
+                // 弹栈
+                let Some(value) = self.stack.pop() else {
+                    return Err(Diagnostic::error("E0109", "Pop from an empty stack")
+                        .with_span(self.spans[pc])
+                        .with_label("nothing was pushed before this ')'")
+                        .with_hint("push a value with '(' first"));
+                };
+                self.memory[self.pointer] = value;
+                pc += 1;
+            },
+            Instruction::SwitchTape => {
+                self.switch_tape();
+                pc += 1;
+            },
+            Instruction::Exchange => {
+                // 与另一条纸带指针处的单元格交换
+                let size = self.memory.len();
+                let other = self.other_tape.get_or_insert_with(|| Tape { memory: vec![0; size], pointer: 0 });
+                core::mem::swap(&mut self.memory[self.pointer], &mut other.memory[other.pointer]);
+                pc += 1;
+            },
+            Instruction::ProcStart => {
+                // 顺序执行到过程定义时跳过
+                pc = self.jump_table.to_close[pc] + 1;
+            },
+            Instruction::ProcEnd => {
+                // 返回调用处；过程只能通过调用进入，调用栈不会为空
+                pc = self.call_stack.pop().map_or(pc + 1, |(ret, _)| ret);
+            },
+            Instruction::Call(n) => pc = self.call(pc, n as usize)?,
+            Instruction::CallCell => pc = self.call(pc, self.memory[self.pointer] as usize)?,
+            Instruction::MoveHigh => {
+                // 移动到高端边界
+                self.pointer = self.memory.len() - 1;
+                pc += 1;
+            },
+            Instruction::MoveLow => {
+                // 移动到低端边界
+                self.pointer = 0;
+                pc += 1;
+            },
+            Instruction::ScanLeft => {
+                // 与[<]相同，但没有0单元格时停在低端边界而不是永远循环
+                match self.memory[..=self.pointer].iter().rposition(|&cell| cell == 0) {
+                    Some(p) => self.pointer = p,
+                    None if self.strict_bounds => return Err(self.out_of_bounds(pc)),
+                    None => self.pointer = 0,
+                }
+                pc += 1;
+            },
+            Instruction::ScanRight => {
+                // 与[>]相同，但没有0单元格时停在高端边界而不是永远循环
+                match self.memory[self.pointer..].iter().position(|&cell| cell == 0) {
+                    Some(offset) => self.pointer += offset,
+                    None if self.strict_bounds => return Err(self.out_of_bounds(pc)),
+                    None => self.pointer = self.memory.len() - 1,
+                }
+                pc += 1;
+            },
+            // 调试输出会写到宿主的stderr
+            Instruction::Debug if self.sandbox.is_some() => return Err(self.sandbox_disabled(pc)),
+            Instruction::Debug => {
+                // 调试输出 - 写入stderr，不影响程序输出；no_std下没有stderr，忽略
+                #[cfg(feature = "std")]
+                {
+                    let value = self.memory[self.pointer];
+                    let shown = if value.is_ascii_graphic() || value == b' ' { format!("'{}'", value as char) } else { "-".to_string() };
+                    eprintln!("[@] tape={} pointer={} value={} hex=0x{:02x} char={}", self.active_tape, self.pointer, value, value, shown);
+                }
+                pc += 1;
+            },
+            Instruction::Assert(expected) => {
+                let value = self.memory[self.pointer];
+                if value != expected {
+                    return Err(Diagnostic::error("E0122", format!("Assertion failed: expected {} but cell {} holds {}", expected, self.pointer, value))
+                        .with_span(self.spans[pc])
+                        .with_label(format!("expected {}", expected)));
+                }
+                pc += 1;
+            },
+            Instruction::Custom(_) | Instruction::HostCall if self.sandbox.is_some() => return Err(self.sandbox_disabled(pc)),
+            Instruction::Custom(symbol) => {
+                // 字节码可能带有本解释器没有注册的指令
+                let Some(custom) = self.custom.iter_mut().find(|i| i.symbol == symbol) else {
+                    return Err(Diagnostic::error("E0116", format!("Custom instruction '{}' is not registered", symbol))
+                        .with_span(self.spans[pc])
+                        .with_label("no handler for this instruction")
+                        .with_hint("load the plugin that provides it, or register it before running the program"));
+                };
+                let mut machine = custom::Machine { memory: &mut self.memory, pointer: &mut self.pointer };
+                if let Err(e) = (custom.handler)(&mut machine) {
+                    return Err(Diagnostic::error("E0117", format!("Custom instruction '{}' ({}) failed: {}", symbol, custom.name, e))
+                        .with_span(self.spans[pc])
+                        .with_label("this instruction reported an error"));
+                }
+                pc += 1;
+            },
+            Instruction::HostCall => {
+                let number = self.memory[self.pointer];
+                let Some(host) = self.host_functions.get_mut(number as usize).and_then(Option::as_mut) else {
+                    return Err(Diagnostic::error("E0118", format!("Call to undefined host function {}", number))
+                        .with_span(self.spans[pc])
+                        .with_label("the current cell selects the host function")
+                        .with_hint("'§' calls functions the embedding program registers with register_host_function"));
+                };
+                let start = self.pointer + 1;
+                let Some(window) = start.checked_add(host.window).and_then(|end| self.memory.get_mut(start..end)) else {
+                    return Err(Diagnostic::error("E0119", format!("Host function {} failed: its {}-cell window runs past the end of the tape", number, host.window))
+                        .with_span(self.spans[pc])
+                        .with_label("the window starts after the current cell")
+                        .with_hint("move the pointer left before the call, or enlarge the tape with --memory"));
+                };
+                if let Err(e) = (host.function)(window) {
+                    return Err(Diagnostic::error("E0119", format!("Host function {} failed: {}", number, e))
+                        .with_span(self.spans[pc])
+                        .with_label("this call reported an error"));
+                }
+                pc += 1;
+            },
+        }
+        if !device_writes.is_empty() {
+            self.write_devices(pc, &device_writes)?;
+        }
+        
+        self.pc = pc;
+        self.steps += 1;
+        Ok(true)
+    }
+
+    /// 当前程序计数器(下一条待执行指令的下标)
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// 当前数据指针
+    pub fn pointer(&self) -> usize {
+        self.pointer
+    }
+
+    /// 当前处于执行中的循环(由外到内)，用于运行时错误的回溯
+    pub fn loop_backtrace(&self) -> Vec<LoopFrame> {
+        (0..self.pc.min(self.instructions.len()))
+            .filter(|&open| self.instructions[open] == Instruction::JumpIfZero)
+            .filter(|&open| self.jump_table.to_close.get(open).is_some_and(|&close| close >= self.pc))
+            .map(|open| LoopFrame {
+                open,
+                span: self.spans[open],
+                iteration: self.loop_counts.get(open).copied().unwrap_or(0),
+            })
+            .collect()
+    }
+
+    /// 纸带大小(单元格数)
+    pub fn memory_size(&self) -> usize {
+        self.memory.len()
+    }
+
+    /// 生效的单元格加减越界处理方式(调用者设置优先于编译指示)
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow.or(self.pragma.overflow).unwrap_or_default()
+    }
+
+    /// 生效的方言(调用者设置优先于编译指示)
+    pub fn dialect(&self) -> Dialect {
+        self.dialect.or(self.pragma.dialect).unwrap_or_default()
+    }
+
+    /// 生效的输入耗尽行为；未设置时为None，由运行模式决定
+    pub fn eof_behavior(&self) -> Option<EofBehavior> {
+        self.eof.or(self.pragma.eof)
+    }
+
+    /// 纸带内容
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// 每个过程'{'的指令下标，下标即过程编号
+    pub fn procedures(&self) -> &[usize] {
+        &self.procedures
+    }
+
+    /// 当前的过程调用(由外到内)：过程编号与调用处
+    pub fn call_backtrace(&self) -> Vec<(usize, Span)> {
+        self.call_stack.iter().map(|&(ret, n)| (n, self.spans[ret - 1])).collect()
+    }
+
+    /// 当前纸带编号(0或1)
+    pub fn active_tape(&self) -> usize {
+        self.active_tape
+    }
+
+    /// 辅助栈内容(栈底在前)
+    pub fn stack(&self) -> &[u8] {
+        &self.stack
+    }
+
+    /// 读取单元格的值
+    pub fn cell(&self, index: usize) -> u8 {
+        self.memory[index]
+    }
+}
+
+/// 运行时错误中列出的过程调用层数上限
+const CALL_NOTE_LIMIT: usize = 8;
+
+/// 补全运行时错误 - 附带出错位置与当前打开循环的回溯(最内层在前)
+pub fn runtime_error(interpreter: &DerstandInterpreter, source: &str, error: Diagnostic) -> Diagnostic {
+    let mut error = interpreter.with_expansion_note(interpreter.pc(), error);
+    if error.span.is_none()
+        && let Some(&span) = interpreter.spans().get(interpreter.pc())
+    {
+        error.span = Some(span);
+    }
+    for frame in interpreter.loop_backtrace().iter().rev() {
+        error.notes.push(format!(
+            "in loop #{} at {}:{} (iteration {}): {}",
+            frame.open,
+            frame.span.line,
+            frame.span.column,
+            frame.iteration,
+            diagnostic::source_line(source, frame.span.line).trim()
+        ));
+    }
+    // 深层递归只显示最内层的几次调用
+    let calls = interpreter.call_backtrace();
+    for (procedure, span) in calls.iter().rev().take(CALL_NOTE_LIMIT) {
+        error.notes.push(format!("in procedure {} called at {}:{}", procedure, span.line, span.column));
+    }
+    if calls.len() > CALL_NOTE_LIMIT {
+        error.notes.push(format!("... and {} more call(s)", calls.len() - CALL_NOTE_LIMIT));
+    }
+    error
+}
@@ -0,0 +1,289 @@
+//! 静态检查 - 发现可疑写法并给出可操作的警告
+
+use crate::analysis;
+use crate::diagnostic::{Diagnostic, DiagnosticFormat};
+use crate::{DerstandInterpreter, Instruction, TIME_BYTES};
+
+/// 默认的最大循环嵌套深度
+pub const DEFAULT_MAX_DEPTH: usize = 8;
+
+/// lint选项
+#[derive(Debug, Clone, Copy)]
+pub struct LintOptions {
+    pub max_depth: usize,
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        LintOptions { max_depth: DEFAULT_MAX_DEPTH }
+    }
+}
+
+/// 互相抵消的相邻指令
+fn cancels(a: Instruction, b: Instruction) -> bool {
+    matches!(
+        (a, b),
+        (Instruction::Increment, Instruction::Decrement)
+            | (Instruction::Decrement, Instruction::Increment)
+            | (Instruction::Right, Instruction::Left)
+            | (Instruction::Left, Instruction::Right)
+    )
+}
+
+/// 当前单元格必为0、因而永远不会执行的循环，返回原因
+pub fn dead_loop_reason(instructions: &[Instruction], pc: usize) -> Option<&'static str> {
+    match pc.checked_sub(1).map(|p| instructions[p]) {
+        None => Some("all cells are zero when the program starts"),
+        Some(Instruction::Zero) => Some("the preceding '#' clears the cell"),
+        Some(Instruction::Set(0)) => Some("the preceding '={0}' clears the cell"),
+        Some(Instruction::AddNext) => Some("the preceding '~' clears the cell"),
+        Some(Instruction::JumpIfNotZero) => Some("the preceding loop only exits when the cell is zero"),
+        _ => None,
+    }
+}
+
+/// 循环体是否可证明不会改变被测试的单元格：循环一旦进入就永不结束
+///
+/// 只处理不含嵌套循环、输入与绝对定位，且指针净移动为0的循环体；
+/// 按相对偏移跟踪，忽略边界钳制的影响(由指针范围分析单独报告)。
+fn never_terminates(body: &[Instruction]) -> bool {
+    let mut offset = 0isize;
+    for &instruction in body {
+        match instruction {
+            Instruction::Right | Instruction::MoveRight(_) => offset += instruction.count() as isize,
+            Instruction::Left | Instruction::MoveLeft(_) => offset -= instruction.count() as isize,
+            Instruction::Increment
+            | Instruction::Decrement
+            | Instruction::Add(_)
+            | Instruction::Sub(_)
+            | Instruction::Zero
+            | Instruction::Set(0)
+            | Instruction::AddNext
+            | Instruction::Pop
+            | Instruction::Exchange
+            | Instruction::Random
+                if offset == 0 =>
+            {
+                return false;
+            },
+            Instruction::Copy | Instruction::AddNext if offset == -1 => return false,
+            Instruction::CopyLeft if offset == 1 => return false,
+            Instruction::Input
+            | Instruction::InputDecimal
+            | Instruction::Time
+            | Instruction::SwitchTape
+            | Instruction::Call(_)
+            | Instruction::CallCell
+            | Instruction::MoveHigh
+            | Instruction::MoveLow
+            | Instruction::ScanLeft
+            | Instruction::ScanRight
+            | Instruction::JumpIfZero
+            | Instruction::JumpIfNotZero => return false,
+            _ => {},
+        }
+    }
+    offset == 0
+}
+
+/// 检查已编译程序，返回警告列表(按源码顺序)
+pub fn lint(interpreter: &DerstandInterpreter, options: LintOptions) -> Vec<Diagnostic> {
+    let instructions = interpreter.instructions();
+    let spans = interpreter.spans();
+    let high = interpreter.memory_size() - 1;
+    let mut warnings = Vec::new();
+
+    // 从程序开头的直线代码中跟踪确定的指针位置与已写入的单元格；
+    // 进入第一个循环后状态不再确定，停止跟踪
+    let mut pointer = Some(0usize);
+    let mut written = vec![false; interpreter.memory_size()];
+    let mut depth = 0usize;
+    // 永不执行的循环(常用作注释块)到此下标为止，其内部不做检查
+    let mut dead_until = None;
+    // 过程体不在定义处执行：进入定义时保存跟踪状态，定义结束后恢复
+    let mut outside_procedure = None;
+
+    for (pc, &instruction) in instructions.iter().enumerate() {
+        if let Some(end) = dead_until {
+            if pc <= end {
+                continue;
+            }
+            dead_until = None;
+        }
+        let span = spans[pc];
+        let previous = pc.checked_sub(1).map(|p| instructions[p]);
+
+        match instruction {
+            Instruction::JumpIfZero => {
+                if let Some(reason) = dead_loop_reason(instructions, pc) {
+                    warnings.push(
+                        Diagnostic::warning("W0001", "Loop can never execute")
+                            .with_span(span)
+                            .with_label(reason)
+                            .with_hint("remove the loop, or if it is a comment block, write it as a ';' line comment instead"),
+                    );
+                    // 跳过循环体，之前跟踪的状态依然有效
+                    dead_until = interpreter.jump_target(pc);
+                    continue;
+                }
+                if let Some(close) = interpreter.jump_target(pc)
+                    && never_terminates(&instructions[pc + 1..close])
+                {
+                    warnings.push(
+                        Diagnostic::warning("W0008", "Loop never terminates once entered")
+                            .with_span(span)
+                            .with_label("the loop body never changes the tested cell")
+                            .with_hint("modify the tested cell inside the loop, e.g. with '-'"),
+                    );
+                }
+                depth += 1;
+                if depth == options.max_depth + 1 {
+                    warnings.push(
+                        Diagnostic::warning("W0005", format!("Loops nested more than {} levels deep", options.max_depth))
+                            .with_span(span)
+                            .with_label(format!("nesting depth {}", depth))
+                            .with_hint("split the inner logic into a flatter sequence, or raise --max-depth"),
+                    );
+                }
+                pointer = None;
+            },
+            Instruction::JumpIfNotZero => {
+                depth = depth.saturating_sub(1);
+                pointer = None;
+            },
+            Instruction::ProcStart => {
+                outside_procedure = Some(pointer);
+                pointer = None;
+                continue;
+            },
+            Instruction::ProcEnd => {
+                pointer = outside_procedure.take().flatten();
+                continue;
+            },
+            Instruction::Output => {
+                if let Some(p) = pointer
+                    && !written[p]
+                {
+                    warnings.push(
+                        Diagnostic::warning("W0004", format!("Output of cell {} before any value is set", p))
+                            .with_span(span)
+                            .with_label("this always prints a zero byte")
+                            .with_hint("set the cell with '+' or ',' before printing it"),
+                    );
+                }
+            },
+            _ => {},
+        }
+
+        if let Some(previous) = previous
+            && cancels(previous, instruction)
+        {
+            warnings.push(
+                Diagnostic::warning("W0003", format!("'{}{}' cancels out", previous.symbol(), instruction.symbol()))
+                    .with_span(spans[pc - 1])
+                    .with_label("these two instructions undo each other")
+                    .with_hint("remove both instructions"),
+            );
+        }
+
+        // 更新确定的指针与写入状态
+        if let Some(p) = pointer {
+            pointer = match instruction {
+                Instruction::Right | Instruction::MoveRight(_) => Some((p + instruction.count() as usize).min(high)),
+                Instruction::Left | Instruction::MoveLeft(_) => Some(p.saturating_sub(instruction.count() as usize)),
+                Instruction::MoveHigh => Some(high),
+                Instruction::MoveLow => Some(0),
+                Instruction::SwitchTape => None, // 另一条纸带的状态不跟踪
+                Instruction::Call(_) | Instruction::CallCell => None, // 过程的效果不跟踪
+                Instruction::ScanLeft | Instruction::ScanRight => None, // 停在哪里取决于纸带内容
+                _ => Some(p),
+            };
+            match instruction {
+                Instruction::Increment
+                | Instruction::Decrement
+                | Instruction::Add(_)
+                | Instruction::Sub(_)
+                | Instruction::Input
+                | Instruction::InputDecimal
+                | Instruction::Random
+                | Instruction::Zero
+                | Instruction::Set(_)
+                | Instruction::Pop
+                | Instruction::Exchange => written[p] = true,
+                Instruction::Copy if p < high => written[p + 1] = true,
+                Instruction::AddNext => {
+                    written[p] = true;
+                    if p < high {
+                        written[p + 1] = true;
+                    }
+                },
+                Instruction::CopyLeft if p > 0 => written[p - 1] = true,
+                Instruction::Time => written[p..(p + TIME_BYTES).min(high + 1)].fill(true),
+                _ => {},
+            }
+        }
+    }
+
+    // 边界钳制由指针范围分析报告
+    warnings.extend(analysis::pointer_bounds(interpreter));
+    warnings.sort_by_key(|w| w.span.map(|s| s.offset));
+    warnings
+}
+
+/// `derstand lint [--diagnostics json] [--max-depth N] <file>...`
+pub fn command(args: &[String]) -> i32 {
+    let mut format = DiagnosticFormat::Human;
+    let mut options = LintOptions::default();
+    let mut files = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let parsed = match arg.as_str() {
+            "--diagnostics" => iter.next().ok_or("Missing value for --diagnostics".to_string())
+                .and_then(|v| DiagnosticFormat::parse(v)).map(|f| format = f),
+            "--max-depth" => iter.next().and_then(|v| v.parse().ok())
+                .ok_or("--max-depth expects a number".to_string()).map(|d| options.max_depth = d),
+            _ if arg.starts_with("--") => Err(format!("Unknown option: {}", arg)),
+            _ => {
+                files.push(arg.clone());
+                Ok(())
+            },
+        };
+        if let Err(e) = parsed {
+            eprintln!("{}", e);
+            return 2;
+        }
+    }
+    if files.is_empty() {
+        eprintln!("Usage: derstand lint [--diagnostics json] [--max-depth N] <file>...");
+        return 2;
+    }
+
+    let mut errors = 0;
+    let mut warning_count = 0;
+    for file in &files {
+        let source = match std::fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("Error reading file {}: {}", file, e);
+                errors += 1;
+                continue;
+            },
+        };
+        let mut interpreter = DerstandInterpreter::new();
+        interpreter.set_source_path(file);
+        if let Err(e) = interpreter.compile(&source) {
+            e.report(format, &source, Some(file));
+            errors += 1;
+            continue;
+        }
+        for warning in lint(&interpreter, options) {
+            warning.report(format, &source, Some(file));
+            warning_count += 1;
+        }
+    }
+
+    if format == DiagnosticFormat::Human && (errors > 0 || warning_count > 0) {
+        eprintln!("{} error(s), {} warning(s)", errors, warning_count);
+    }
+    if errors > 0 { 1 } else { 0 }
+}
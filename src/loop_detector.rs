@@ -0,0 +1,60 @@
+//! 运行时死循环检测 - 按Brent算法比较机器状态指纹，状态重复即判定不终止
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// 死循环检测器
+///
+/// 指纹覆盖程序计数器、指针、纸带与辅助栈等完整的机器状态。每一步都与保存的状态比较，
+/// 步数到2的幂时换成当前状态，周期为λ、前导为μ的循环在约2(μ+λ)步内发现。
+/// 程序只在依赖外部状态(输入、时钟、设备、宿主函数、自定义指令)的指令之间是确定性的，
+/// 执行这些指令后须调用`forget`。指纹是64位哈希，碰撞导致误报的概率可以忽略但不为零。
+#[derive(Debug, Clone)]
+pub struct LoopDetector {
+    saved: Option<(u64, u64)>, // 保存的(摘要, 指纹)
+    power: u64,
+    length: u64,
+}
+
+impl Default for LoopDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn fingerprint(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl LoopDetector {
+    pub fn new() -> Self {
+        LoopDetector { saved: None, power: 1, length: 0 }
+    }
+
+    /// 状态不再可重现，从头开始检测
+    pub fn forget(&mut self) {
+        *self = Self::new();
+    }
+
+    /// 每步调用一次；状态与保存的状态相同时返回true
+    ///
+    /// key是廉价的部分状态(如程序计数器与指针)，不同时不必计算state的完整指纹
+    pub fn observe<S: Hash>(&mut self, key: impl Hash, state: impl Fn() -> S) -> bool {
+        let key = fingerprint(key);
+        if let Some((saved_key, saved)) = self.saved
+            && saved_key == key
+            && fingerprint(state()) == saved
+        {
+            return true;
+        }
+        self.length += 1;
+        if self.length == self.power {
+            self.saved = Some((key, fingerprint(state())));
+            self.power = self.power.saturating_mul(2);
+            self.length = 0;
+        }
+        false
+    }
+}
@@ -0,0 +1,419 @@
+//! 语言服务器 - `derstand lsp`，经标准输入输出与编辑器通信(Language Server Protocol)
+//!
+//! 文档整体同步。打开或修改时发布诊断：编译错误，或编译成功后的lint警告；
+//! 初始化选项`{"strictBounds": true}`时必然越界的移动报告为错误，与`--strict-bounds`一致。
+//! 另外提供指令的悬停说明、配对括号高亮与跳转、折叠与整篇格式化。
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use crate::diagnostic::{Diagnostic, Severity, source_line};
+use crate::formatter::{self, FormatOptions};
+use crate::json::Json;
+use crate::lint::{self, LintOptions};
+use crate::{DerstandInterpreter, Instruction, Span};
+
+/// JSON-RPC错误码
+const INVALID_REQUEST: i64 = -32600;
+pub(crate) const METHOD_NOT_FOUND: i64 = -32601;
+pub(crate) const INVALID_PARAMS: i64 = -32602;
+
+/// 读取一条消息(`Content-Length`头与正文)；输入结束时返回None
+pub(crate) fn read_message(input: &mut impl BufRead) -> io::Result<Option<Json>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length")
+        {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let Some(length) = length else {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "message without Content-Length"));
+    };
+    let mut body = vec![0u8; length];
+    input.read_exact(&mut body)?;
+    let text = String::from_utf8(body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Json::parse(&text).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub(crate) fn write_message(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// 一行中前column个字符对应的UTF-16长度(LSP的列单位)
+fn utf16_column(line: &str, column: usize) -> usize {
+    line.chars().take(column).map(char::len_utf16).sum()
+}
+
+/// LSP位置转换为源码的行与列(均从1开始，列按字符计)
+fn char_position(source: &str, line: usize, character: usize) -> (usize, usize) {
+    let mut units = 0;
+    let mut column = 1;
+    for c in source_line(source, line + 1).chars() {
+        if units >= character {
+            break;
+        }
+        units += c.len_utf16();
+        column += 1;
+    }
+    (line + 1, column)
+}
+
+fn position(line: usize, character: usize) -> Json {
+    Json::object([("line", line.into()), ("character", character.into())])
+}
+
+/// 源码位置处一个长为width个字符的范围
+fn range(source: &str, span: Span, width: usize) -> Json {
+    let text = source_line(source, span.line);
+    let start = span.column.saturating_sub(1);
+    Json::object([
+        ("start", position(span.line - 1, utf16_column(text, start))),
+        ("end", position(span.line - 1, utf16_column(text, start + width))),
+    ])
+}
+
+/// 整篇文档的范围，用于格式化的替换
+fn whole_range(source: &str) -> Json {
+    let lines = source.split('\n').count();
+    let last = source.rsplit('\n').next().unwrap_or("");
+    Json::object([("start", position(0, 0)), ("end", position(lines - 1, last.encode_utf16().count()))])
+}
+
+/// 诊断转换为LSP格式
+fn lsp_diagnostic(source: &str, diagnostic: &Diagnostic, severity: Severity) -> Json {
+    let span = diagnostic.span.unwrap_or(Span { offset: 0, line: 1, column: 1 });
+    let mut message = diagnostic.message.clone();
+    if let Some(label) = &diagnostic.label {
+        message.push_str(&format!(": {}", label));
+    }
+    for note in &diagnostic.notes {
+        message.push_str(&format!("\nnote: {}", note));
+    }
+    if let Some(hint) = &diagnostic.hint {
+        message.push_str(&format!("\nhint: {}", hint));
+    }
+    Json::object([
+        ("range", range(source, span, 1)),
+        ("severity", (if severity == Severity::Error { 1i64 } else { 2 }).into()),
+        ("code", diagnostic.code.into()),
+        ("source", "derstand".into()),
+        ("message", message.into()),
+    ])
+}
+
+/// file:// URI转换为本地路径，用于解析!include
+fn uri_path(uri: &str) -> Option<String> {
+    let path = uri.strip_prefix("file://")?;
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%').then(|| path.get(i + 1..i + 3)).flatten().and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            },
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            },
+        }
+    }
+    let path = String::from_utf8(decoded).ok()?;
+    // Windows的URI形如file:///C:/dir
+    Some(match path.strip_prefix('/') {
+        Some(rest) if rest.as_bytes().get(1) == Some(&b':') => rest.to_string(),
+        _ => path,
+    })
+}
+
+/// 指令在源码中占的字符数 - `+{65}`、`^{2}`写在一起时按其规范写法计
+fn width(instruction: Instruction) -> usize {
+    instruction.to_string().chars().count()
+}
+
+/// 打开的文档及其编译结果
+struct Document {
+    uri: String,
+    text: String,
+    interpreter: DerstandInterpreter,
+    compiled: Result<(), Diagnostic>,
+}
+
+impl Document {
+    fn new(uri: String, text: String) -> Self {
+        let mut interpreter = DerstandInterpreter::new();
+        if let Some(path) = uri_path(&uri) {
+            interpreter.set_source_path(path);
+        }
+        let compiled = interpreter.compile(&text);
+        Document { uri, text, interpreter, compiled }
+    }
+
+    fn diagnostics(&self, strict_bounds: bool) -> Vec<Json> {
+        match &self.compiled {
+            Err(e) => vec![lsp_diagnostic(&self.text, e, Severity::Error)],
+            Ok(()) => lint::lint(&self.interpreter, LintOptions::default())
+                .iter()
+                .map(|warning| {
+                    // W0002：指针必然越界，严格模式下运行时一定报错
+                    let severity = if strict_bounds && warning.code == "W0002" { Severity::Error } else { warning.severity };
+                    lsp_diagnostic(&self.text, warning, severity)
+                })
+                .collect(),
+        }
+    }
+
+    /// 写在这个文件中(而非来自宏或被包含文件)的指令在源码中的位置
+    fn is_literal(&self, pc: usize) -> bool {
+        let instruction = self.interpreter.instructions()[pc];
+        let span = self.interpreter.spans()[pc];
+        source_line(&self.text, span.line).chars().nth(span.column - 1) == Some(instruction.symbol())
+    }
+
+    /// LSP位置处的指令
+    fn instruction_at(&self, params: &Json) -> Option<usize> {
+        let at = params.get("position")?;
+        let line = at.get("line")?.as_i64()? as usize;
+        let character = at.get("character")?.as_i64()? as usize;
+        let (line, column) = char_position(&self.text, line, character);
+        let spans = self.interpreter.spans();
+        let instructions = self.interpreter.instructions();
+        // 光标在指令之后时也算(编辑器的光标位于两个字符之间)
+        let find = |column: usize| {
+            (0..spans.len()).find(|&pc| spans[pc].line == line && (spans[pc].column..spans[pc].column + width(instructions[pc])).contains(&column))
+        };
+        find(column).or_else(|| find(column.checked_sub(1)?)).filter(|&pc| self.is_literal(pc))
+    }
+
+    fn location(&self, pc: usize) -> Json {
+        Json::object([("uri", self.uri.as_str().into()), ("range", range(&self.text, self.interpreter.spans()[pc], 1))])
+    }
+
+    fn hover(&self, params: &Json) -> Json {
+        let Some(pc) = self.instruction_at(params) else {
+            return Json::Null;
+        };
+        let instruction = self.interpreter.instructions()[pc];
+        let mut text = format!("**`{}`** {}\n\n{}", instruction, instruction.name(), instruction.description());
+        if let Some(partner) = self.interpreter.jump_target(pc) {
+            let span = self.interpreter.spans()[partner];
+            text.push_str(&format!("\n\nMatches `{}` at {}:{}", self.interpreter.instructions()[partner].symbol(), span.line, span.column));
+        }
+        if let Instruction::ProcStart = instruction {
+            let number = self.interpreter.procedures().iter().position(|&start| start == pc).unwrap_or(0);
+            text.push_str(&format!("\n\nProcedure {}, called with `^{{{}}}`", number, number));
+        }
+        Json::object([
+            ("contents", Json::object([("kind", "markdown".into()), ("value", text.into())])),
+            ("range", range(&self.text, self.interpreter.spans()[pc], width(instruction))),
+        ])
+    }
+
+    /// 光标处的括号与其配对括号
+    fn highlights(&self, params: &Json) -> Json {
+        let Some(pc) = self.instruction_at(params) else {
+            return Json::Null;
+        };
+        let Some(partner) = self.interpreter.jump_target(pc).filter(|&partner| self.is_literal(partner)) else {
+            return Json::Null;
+        };
+        Json::Array(
+            [pc, partner]
+                .iter()
+                .map(|&pc| Json::object([("range", range(&self.text, self.interpreter.spans()[pc], 1)), ("kind", 1i64.into())]))
+                .collect(),
+        )
+    }
+
+    /// 跳转：括号到配对括号，`^{n}`到第n个过程的定义
+    fn definition(&self, params: &Json) -> Json {
+        let Some(pc) = self.instruction_at(params) else {
+            return Json::Null;
+        };
+        let target = match self.interpreter.instructions()[pc] {
+            Instruction::Call(n) => self.interpreter.procedures().get(n as usize).copied(),
+            _ => self.interpreter.jump_target(pc),
+        };
+        target.filter(|&target| self.is_literal(target)).map_or(Json::Null, |target| self.location(target))
+    }
+
+    /// 跨越多行的循环与过程可以折叠
+    fn folding_ranges(&self) -> Json {
+        let spans = self.interpreter.spans();
+        let ranges = self
+            .interpreter
+            .instructions()
+            .iter()
+            .enumerate()
+            .filter(|&(pc, instruction)| matches!(instruction, Instruction::JumpIfZero | Instruction::ProcStart) && self.is_literal(pc))
+            .filter_map(|(pc, _)| {
+                let close = self.interpreter.jump_target(pc)?;
+                let (start, end) = (spans[pc].line, spans[close].line);
+                (end > start).then(|| Json::object([("startLine", (start - 1).into()), ("endLine", (end - 1).into())]))
+            })
+            .collect();
+        Json::Array(ranges)
+    }
+
+    fn formatting(&self, params: &Json) -> Json {
+        let mut options = FormatOptions::default();
+        if let Some(size) = params.get("options").and_then(|o| o.get("tabSize")).and_then(Json::as_i64) {
+            options.indent = size.clamp(1, 16) as usize;
+        }
+        let formatted = formatter::format_source(&self.text, options);
+        if formatted == self.text {
+            return Json::Array(Vec::new());
+        }
+        Json::Array(vec![Json::object([("range", whole_range(&self.text)), ("newText", formatted.into())])])
+    }
+}
+
+struct Server {
+    documents: HashMap<String, Document>,
+    strict_bounds: bool,
+    shutdown: bool,
+}
+
+/// 处理结果：请求的结果或错误，通知没有回复
+enum Reply {
+    Result(Json),
+    Error(i64, String),
+    None,
+}
+
+impl Server {
+    fn capabilities() -> Json {
+        Json::object([
+            ("textDocumentSync", 1i64.into()), // 整篇同步
+            ("hoverProvider", true.into()),
+            ("documentHighlightProvider", true.into()),
+            ("definitionProvider", true.into()),
+            ("foldingRangeProvider", true.into()),
+            ("documentFormattingProvider", true.into()),
+        ])
+    }
+
+    fn publish(&self, uri: &str, output: &mut impl Write) -> io::Result<()> {
+        let diagnostics = self.documents.get(uri).map_or(Vec::new(), |document| document.diagnostics(self.strict_bounds));
+        write_message(output, &Json::object([
+            ("jsonrpc", "2.0".into()),
+            ("method", "textDocument/publishDiagnostics".into()),
+            ("params", Json::object([("uri", uri.into()), ("diagnostics", Json::Array(diagnostics))])),
+        ]))
+    }
+
+    fn handle(&mut self, method: &str, params: &Json, output: &mut impl Write) -> io::Result<Reply> {
+        let uri = params.get("textDocument").and_then(|d| d.get("uri")).and_then(Json::as_str).map(str::to_string);
+        if self.shutdown && method != "exit" {
+            return Ok(Reply::Error(INVALID_REQUEST, "the server is shutting down".to_string()));
+        }
+        let document = uri.as_ref().and_then(|uri| self.documents.get(uri));
+        Ok(match method {
+            "initialize" => {
+                self.strict_bounds = params.get("initializationOptions").and_then(|o| o.get("strictBounds")).and_then(Json::as_bool).unwrap_or(false);
+                Reply::Result(Json::object([
+                    ("capabilities", Self::capabilities()),
+                    ("serverInfo", Json::object([("name", "derstand".into()), ("version", env!("CARGO_PKG_VERSION").into())])),
+                ]))
+            },
+            "shutdown" => {
+                self.shutdown = true;
+                Reply::Result(Json::Null)
+            },
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                let text = match method {
+                    "textDocument/didOpen" => params.get("textDocument").and_then(|d| d.get("text")),
+                    // 整篇同步：最后一项变更就是全文
+                    _ => params.get("contentChanges").and_then(Json::as_array).and_then(<[Json]>::last).and_then(|c| c.get("text")),
+                };
+                if let (Some(uri), Some(text)) = (uri, text.and_then(Json::as_str)) {
+                    self.documents.insert(uri.clone(), Document::new(uri.clone(), text.to_string()));
+                    self.publish(&uri, output)?;
+                }
+                Reply::None
+            },
+            "textDocument/didClose" => {
+                if let Some(uri) = uri {
+                    self.documents.remove(&uri);
+                    self.publish(&uri, output)?;
+                }
+                Reply::None
+            },
+            "textDocument/hover" | "textDocument/documentHighlight" | "textDocument/definition" | "textDocument/foldingRange" | "textDocument/formatting" => {
+                let Some(document) = document else {
+                    return Ok(Reply::Error(INVALID_PARAMS, "unknown document".to_string()));
+                };
+                Reply::Result(match method {
+                    "textDocument/hover" => document.hover(params),
+                    "textDocument/documentHighlight" => document.highlights(params),
+                    "textDocument/definition" => document.definition(params),
+                    "textDocument/foldingRange" => document.folding_ranges(),
+                    _ => document.formatting(params),
+                })
+            },
+            // 通知无需回复；未知的请求报告方法不存在
+            _ if method.starts_with("$/") || method == "initialized" || method == "textDocument/didSave" => Reply::None,
+            _ => Reply::Error(METHOD_NOT_FOUND, format!("unsupported method '{}'", method)),
+        })
+    }
+}
+
+/// `derstand lsp [--stdio]`
+pub fn command(args: &[String]) -> i32 {
+    if let Some(arg) = args.iter().find(|arg| *arg != "--stdio") {
+        eprintln!("Unknown option: {}\nUsage: derstand lsp [--stdio]", arg);
+        return 2;
+    }
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    let mut server = Server { documents: HashMap::new(), strict_bounds: false, shutdown: false };
+    loop {
+        let message = match read_message(&mut input) {
+            Ok(Some(message)) => message,
+            Ok(None) => return 1, // 未收到exit就断开
+            Err(e) => {
+                eprintln!("lsp: {}", e);
+                return 1;
+            },
+        };
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        if method == "exit" {
+            return if server.shutdown { 0 } else { 1 };
+        }
+        let params = message.get("params").cloned().unwrap_or(Json::Null);
+        let reply = match server.handle(method, &params, &mut output) {
+            Ok(reply) => reply,
+            Err(e) => {
+                eprintln!("lsp: {}", e);
+                return 1;
+            },
+        };
+        // 只有带id的请求需要回复
+        let Some(id) = message.get("id").cloned() else {
+            continue;
+        };
+        let body = match reply {
+            Reply::Result(result) => ("result", result),
+            Reply::Error(code, message) => ("error", Json::object([("code", code.into()), ("message", message.into())])),
+            Reply::None => ("result", Json::Null),
+        };
+        if let Err(e) = write_message(&mut output, &Json::object([("jsonrpc", "2.0".into()), ("id", id), body])) {
+            eprintln!("lsp: {}", e);
+            return 1;
+        }
+    }
+}
@@ -1,289 +1,711 @@
-use std::io::{self, Read, Write};
+use std::io::{self, Write};
 use std::path::Path;
 use std::process;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-// 内存大小常量 - 优化的内存使用
-const MEMORY_SIZE: usize = 30000;
+use derstand::diagnostic::{self, Diagnostic};
+use derstand::{
+    DerstandInterpreter, Dialect, EofBehavior, Instruction, OverflowPolicy, animate, audio, bundle, bytecode, cancel, channel, checkpoint, compile, debugger, diff_run, emit, equiv, examples, formatter, framebuffer, generate,
+    golden, heatmap, hexdump, highlight, image, import, jupyter, learn, lint, lsp, minify, obfuscate, pragma, rpc, runtime_error, serve, step, synth, tape_file, turtle, verify, watch,
+};
 
-/// Derstand指令枚举 - 12个基本指令
-#[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Instruction {
-    Right,    // > 指针右移
-    Left,     // < 指针左移  
-    Increment, // + 值加1
-    Decrement, // - 值减1
-    Output,   // . 输出
-    Input,    // , 输入
-    JumpIfZero, // [ 跳到对应的]
-    JumpIfNotZero, // ] 跳回对应的[
-    Zero,     // # 快速清零
-    Copy,     // $ 复制到下一单元格
-    MoveHigh, // % 移动到高端边界
-    MoveLow,  // & 移动到低端边界
-}
-
-/// 高效的跳转表结构 - 避免运行时计算跳转位置
-#[derive(Debug, Clone)]
-struct JumpTable {
-    // 跳转到对应右括号的位置: '[' -> 位置
-    to_close: Vec<usize>,
-    // 跳转到对应左括号的位置: ']' -> 位置  
-    to_open: Vec<usize>,
+/// 报告文件模式下的运行时错误
+fn report_runtime_error(
+    interpreter: &DerstandInterpreter,
+    source: &str,
+    file: &str,
+    error: Diagnostic,
+    format: diagnostic::DiagnosticFormat,
+) {
+    if format == diagnostic::DiagnosticFormat::Human {
+        eprintln!(); // 程序输出可能停在行中
+    }
+    runtime_error(interpreter, source, error).report(format, source, Some(file));
 }
 
-/// Derstand解释器 - 优化版本
-pub struct DerstandInterpreter {
-    memory: [u8; MEMORY_SIZE], // 零拷贝内存访问
-    pointer: usize,
-    instructions: Vec<Instruction>,
-    jump_table: JumpTable, // 优化后的跳转表
-    input_buffer: Vec<u8>,
-    output_buffer: Vec<u8>,
-    is_interactive_mode: bool, // 标记是否处于交互式模式
+/// 命令行选项
+#[derive(Debug, Default)]
+struct CliOptions {
+    file: Option<String>,
+    emit: Option<emit::EmitKind>,
+    step_through: bool,
+    explain: Option<u64>,
+    debug: bool,
+    detect_loops: bool,
+    record_input: Option<String>,
+    replay_input: Option<String>,
+    checkpoint_every: Option<u64>,
+    checkpoint_file: Option<String>,
+    resume: Option<String>,
+    dump_memory: bool,
+    diagnostics: diagnostic::DiagnosticFormat,
+    overflow: Option<OverflowPolicy>,
+    dialect: Option<Dialect>,
+    strict_bounds: bool,
+    memory: Option<usize>,
+    start_pointer: Option<usize>,
+    eof: Option<EofBehavior>,
+    seed: Option<u64>,
+    deterministic: bool,
+    timeout: Option<u64>,
+    sandbox: bool,
+    assertions: bool,
+    bundle: Option<String>,
+    args: Option<Vec<String>>,
+    abi: bool,
+    allow_fs: Option<String>,
+    tape_file: Option<String>,
+    framebuffer: Option<framebuffer::Framebuffer>,
+    tape_image: Option<(String, image::Format)>,
+    tape_image_width: Option<usize>,
+    turtle: Option<String>,
+    wav: Option<String>,
+    heatmap: Option<(String, heatmap::Export)>,
+    wav_rate: Option<u32>,
+    wav_tape: Option<(usize, usize)>,
+    #[cfg(feature = "plugins")]
+    plugins: Vec<String>,
 }
 
-impl DerstandInterpreter {
-    /// 创建新的解释器实例 - 最小化内存分配
-    pub fn new() -> Self {
-        DerstandInterpreter {
-            memory: [0; MEMORY_SIZE],
-            pointer: 0,
-            instructions: Vec::with_capacity(1024), // 预分配指令空间
-            jump_table: JumpTable {
-                to_close: Vec::with_capacity(512),
-                to_open: Vec::with_capacity(512),
-            },
-            input_buffer: Vec::with_capacity(256),
-            output_buffer: Vec::with_capacity(256),
-            is_interactive_mode: false,
-        }
+/// 运行结束后写回持久纸带(运行出错时也写回)
+fn store_tape(interpreter: &mut DerstandInterpreter, tape: Option<&mut tape_file::TapeFile>) {
+    if let Some(tape) = tape
+        && let Err(e) = interpreter.store_tape(tape)
+    {
+        eprintln!("{}", e);
+        process::exit(1);
     }
+}
 
-    /// 编译源代码 - 优化版本
-    pub fn compile(&mut self, source: &str) -> Result<(), String> {
-        self.instructions.clear();
-        self.jump_table.to_close.clear();
-        self.jump_table.to_open.clear();
-        self.instructions.reserve(source.len()); // 预分配空间
-        
-        // 第一遍：解析指令
-        let mut bracket_stack = Vec::with_capacity(128);
-        
-        for (pos, c) in source.chars().enumerate() {
-            match c {
-                '>' => self.instructions.push(Instruction::Right),
-                '<' => self.instructions.push(Instruction::Left),
-                '+' => self.instructions.push(Instruction::Increment),
-                '-' => self.instructions.push(Instruction::Decrement),
-                '.' => self.instructions.push(Instruction::Output),
-                ',' => self.instructions.push(Instruction::Input),
-                '[' => {
-                    self.instructions.push(Instruction::JumpIfZero);
-                    bracket_stack.push(pos);
-                },
-                ']' => {
-                    self.instructions.push(Instruction::JumpIfNotZero);
-                    if let Some(open_pos) = bracket_stack.pop() {
-                        // 确保跳转表足够大
-                        while self.jump_table.to_close.len() <= open_pos {
-                            self.jump_table.to_close.push(0);
-                        }
-                        while self.jump_table.to_open.len() <= pos {
-                            self.jump_table.to_open.push(0);
-                        }
-                        self.jump_table.to_close[open_pos] = pos;
-                        self.jump_table.to_open[pos] = open_pos;
-                    } else {
-                        return Err(format!("Unmatched closing bracket at position {}", pos));
-                    }
-                },
-                '#' => self.instructions.push(Instruction::Zero),
-                '$' => self.instructions.push(Instruction::Copy),
-                '%' => self.instructions.push(Instruction::MoveHigh),
-                '&' => self.instructions.push(Instruction::MoveLow),
-                _ => { /* 忽略非指令字符 */ },
-            }
-        }
-        
-        // 检查未匹配的左括号
-        if !bracket_stack.is_empty() {
-            return Err(format!("Unmatched opening bracket at position {}", bracket_stack[0]));
-        }
-        
-        Ok(())
-    }
+/// 读取选项的参数值
+fn option_value(iter: &mut std::slice::Iter<'_, String>, flag: &str) -> Result<String, String> {
+    iter.next().cloned().ok_or_else(|| format!("Missing value for {}", flag))
+}
 
-    /// 执行编译后的指令 - 高度优化的执行循环
-    pub fn execute(&mut self) -> Result<(), String> {
-        self.pointer = 0;
-        self.output_buffer.clear();
-        
-        let mut pc = 0; // 程序计数器
-        
-        // 优化的执行循环
-        while pc < self.instructions.len() {
-            match self.instructions[pc] {
-                Instruction::Right => {
-                    // 优化的边界检查
-                    if self.pointer < MEMORY_SIZE - 1 {
-                        self.pointer += 1;
+impl CliOptions {
+    /// 解析命令行参数(不含程序名)
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = CliOptions::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--emit" => options.emit = Some(emit::EmitKind::parse(&option_value(&mut iter, arg)?)?),
+                "--step-through" => options.step_through = true,
+                "--explain" => {
+                    // 步数可以省略：下一个参数是数字时才作为它的值
+                    let budget = iter.as_slice().first().and_then(|next| next.parse::<u64>().ok());
+                    if budget.is_some() {
+                        iter.next();
                     }
-                    pc += 1;
+                    options.explain = Some(budget.unwrap_or(step::EXPLAIN_BUDGET));
                 },
-                Instruction::Left => {
-                    // 优化的边界检查
-                    if self.pointer > 0 {
-                        self.pointer -= 1;
-                    }
-                    pc += 1;
+                "--debug" => options.debug = true,
+                "--record-input" => options.record_input = Some(option_value(&mut iter, arg)?),
+                "--replay-input" => options.replay_input = Some(option_value(&mut iter, arg)?),
+                "--checkpoint-every" => {
+                    // 以百万步为单位
+                    let millions = option_value(&mut iter, arg)?.parse::<u64>()
+                        .ok().filter(|&n| n > 0)
+                        .ok_or("--checkpoint-every expects a positive number (millions of steps)")?;
+                    options.checkpoint_every = Some(millions.saturating_mul(1_000_000));
                 },
-                Instruction::Increment => {
-                    // 无分支的单字节操作
-                    self.memory[self.pointer] = self.memory[self.pointer].wrapping_add(1);
-                    pc += 1;
+                "--checkpoint-file" => options.checkpoint_file = Some(option_value(&mut iter, arg)?),
+                "--resume" => options.resume = Some(option_value(&mut iter, arg)?),
+                "--dump-memory" => options.dump_memory = true,
+                "--diagnostics" => options.diagnostics = diagnostic::DiagnosticFormat::parse(&option_value(&mut iter, arg)?)?,
+                "--overflow" => options.overflow = Some(OverflowPolicy::parse(&option_value(&mut iter, arg)?)?),
+                "--dialect" => options.dialect = Some(Dialect::parse(&option_value(&mut iter, arg)?)?),
+                "--memory" => {
+                    let size = option_value(&mut iter, arg)?.parse::<usize>()
+                        .ok().filter(|n| (1..=pragma::MAX_MEMORY_SIZE).contains(n))
+                        .ok_or(format!("--memory expects a number of cells between 1 and {}", pragma::MAX_MEMORY_SIZE))?;
+                    options.memory = Some(size);
                 },
-                Instruction::Decrement => {
-                    // 无分支的单字节操作
-                    self.memory[self.pointer] = self.memory[self.pointer].wrapping_sub(1);
-                    pc += 1;
+                "--eof" => options.eof = Some(EofBehavior::parse(&option_value(&mut iter, arg)?)?),
+                "--start-pointer" => {
+                    let cell = option_value(&mut iter, arg)?.parse::<usize>()
+                        .map_err(|_| "--start-pointer expects a cell number".to_string())?;
+                    options.start_pointer = Some(cell);
                 },
-                Instruction::Output => {
-                    // 直接输出字节，避免UTF-8转换问题
-                    let _ = io::stdout().write_all(&[self.memory[self.pointer]]);
-                    let _ = io::stdout().flush();
-                    pc += 1;
+                "--seed" => {
+                    let seed = option_value(&mut iter, arg)?.parse::<u64>()
+                        .map_err(|_| "--seed expects a non-negative integer".to_string())?;
+                    options.seed = Some(seed);
                 },
-                Instruction::Input => {
-                    // 处理输入 - 根据模式不同处理方式不同
-                    let byte = match self.input_buffer.pop() {
-                        Some(b) => b,
-                        None => {
-                            if self.is_interactive_mode {
-                                // 交互式模式：从标准输入读取
-                                let mut input = [0u8];
-                                match io::stdin().read(&mut input) {
-                                    Ok(1) => input[0],
-                                    Ok(_) => 0,
-                                    Err(e) => return Err(format!("Input error: {}", e)),
-                                }
-                            } else {
-                                // 文件模式：遇到输入指令时提示错误并退出
-                                return Err("\nInput instruction found in file mode. File execution cannot handle input instructions. Please use interactive mode or modify your program to remove input instructions.".to_string());
-                            }
-                        },
-                    };
-                    self.memory[self.pointer] = byte;
-                    pc += 1;
+                "--strict-bounds" => options.strict_bounds = true,
+                "--deterministic" => options.deterministic = true,
+                "--timeout" => {
+                    let ms = option_value(&mut iter, arg)?.parse::<u64>()
+                        .ok().filter(|&n| n > 0)
+                        .ok_or("--timeout expects a positive number of milliseconds")?;
+                    options.timeout = Some(ms);
                 },
-                Instruction::JumpIfZero => {
-                    // 高效跳转 - 使用预计算的跳转表
-                    if self.memory[self.pointer] == 0 {
-                        if pc < self.jump_table.to_close.len() {
-                            pc = self.jump_table.to_close[pc] + 1;
-                        } else {
-                            return Err("Jump table out of bounds".to_string());
-                        }
-                    } else {
-                        pc += 1;
+                "--detect-loops" => options.detect_loops = true,
+                "--sandbox" => options.sandbox = true,
+                "--assertions" => options.assertions = true,
+                "--abi" => options.abi = true,
+                "--bundle" => options.bundle = Some(option_value(&mut iter, arg)?),
+                "--allow-fs" => {
+                    // 目录可以省略：下一个参数是目录时才作为它的值
+                    let directory = iter.as_slice().first().filter(|next| Path::new(next).is_dir()).cloned();
+                    if directory.is_some() {
+                        iter.next();
                     }
+                    options.allow_fs = Some(directory.unwrap_or_else(|| ".".to_string()));
                 },
-                Instruction::JumpIfNotZero => {
-                    // 高效跳转 - 使用预计算的跳转表
-                    if self.memory[self.pointer] != 0 {
-                        if pc < self.jump_table.to_open.len() {
-                            pc = self.jump_table.to_open[pc] + 1;
-                        } else {
-                            return Err("Jump table out of bounds".to_string());
-                        }
-                    } else {
-                        pc += 1;
-                    }
+                "--tape-file" => options.tape_file = Some(option_value(&mut iter, arg)?),
+                "--emit-tape-image" => {
+                    let path = option_value(&mut iter, arg)?;
+                    let format = image::Format::from_path(&path)?;
+                    options.tape_image = Some((path, format));
                 },
-                Instruction::Zero => {
-                    // 快速清零 - 比多次减操作更高效
-                    self.memory[self.pointer] = 0;
-                    pc += 1;
+                "--tape-image-width" => {
+                    let width = option_value(&mut iter, arg)?.parse::<usize>()
+                        .ok().filter(|&n| n > 0)
+                        .ok_or("--tape-image-width expects a positive number of cells")?;
+                    options.tape_image_width = Some(width);
                 },
-                Instruction::Copy => {
-                    // 复制当前值到下一单元格
-                    if self.pointer < MEMORY_SIZE - 1 {
-                        self.memory[self.pointer + 1] = self.memory[self.pointer];
-                    }
-                    pc += 1;
+                "--heatmap" => {
+                    let path = option_value(&mut iter, arg)?;
+                    let export = heatmap::Export::from_path(&path)?;
+                    options.heatmap = Some((path, export));
                 },
-                Instruction::MoveHigh => {
-                    // 移动到高端边界
-                    self.pointer = MEMORY_SIZE - 1;
-                    pc += 1;
+                "--emit-wav" => options.wav = Some(option_value(&mut iter, arg)?),
+                "--wav-rate" => {
+                    let rate = option_value(&mut iter, arg)?.parse::<u32>()
+                        .ok().filter(|&n| n > 0)
+                        .ok_or("--wav-rate expects a positive sample rate in Hz")?;
+                    options.wav_rate = Some(rate);
                 },
-                Instruction::MoveLow => {
-                    // 移动到低端边界
-                    self.pointer = 0;
-                    pc += 1;
+                "--wav-tape" => options.wav_tape = Some(audio::parse_region(&option_value(&mut iter, arg)?)?),
+                "--turtle" => options.turtle = Some(option_value(&mut iter, arg)?),
+                "--framebuffer" => {
+                    let geometry = option_value(&mut iter, arg)?;
+                    options.framebuffer = Some(framebuffer::Framebuffer::parse(&geometry, &option_value(&mut iter, arg)?)?);
                 },
+                // 之后的所有参数都交给程序
+                "--args" => options.args = Some(iter.by_ref().cloned().collect()),
+                #[cfg(feature = "plugins")]
+                "--plugin" => options.plugins.push(option_value(&mut iter, arg)?),
+                #[cfg(not(feature = "plugins"))]
+                "--plugin" => return Err("--plugin requires building derstand with `--features plugins`".to_string()),
+                _ if arg.starts_with("--") => return Err(format!("Unknown option: {}", arg)),
+                _ if options.file.is_none() => options.file = Some(arg.clone()),
+                _ => return Err(format!("Unexpected argument: {}", arg)),
             }
         }
-        
-        Ok(())
+        if options.file.is_none() && options.bundle.is_some() {
+            // 重放运行包：配置与输入都来自包
+            let recorded = [
+                ("--replay-input", options.replay_input.is_some()),
+                ("--record-input", options.record_input.is_some()),
+                ("--seed", options.seed.is_some()),
+                ("--memory", options.memory.is_some()),
+                ("--start-pointer", options.start_pointer.is_some()),
+                ("--overflow", options.overflow.is_some()),
+                ("--dialect", options.dialect.is_some()),
+                ("--eof", options.eof.is_some()),
+                ("--timeout", options.timeout.is_some()),
+                ("--strict-bounds", options.strict_bounds),
+                ("--deterministic", options.deterministic),
+                ("--assertions", options.assertions),
+                ("--sandbox", options.sandbox),
+                ("--detect-loops", options.detect_loops),
+                ("--resume", options.resume.is_some()),
+                ("--args", options.args.is_some()),
+                ("--abi", options.abi),
+            ];
+            if let Some((flag, _)) = recorded.iter().find(|(_, set)| *set) {
+                return Err(format!("{} cannot be used when replaying a bundle; the bundle records its own configuration", flag));
+            }
+        } else if options.file.is_none() {
+            let file_only = [
+                ("--emit", options.emit.is_some()),
+                ("--step-through", options.step_through),
+                ("--explain", options.explain.is_some()),
+                ("--debug", options.debug),
+                ("--resume", options.resume.is_some()),
+                ("--dump-memory", options.dump_memory),
+                ("--checkpoint-every", options.checkpoint_every.is_some()),
+                ("--timeout", options.timeout.is_some()),
+                ("--sandbox", options.sandbox),
+                ("--args", options.args.is_some()),
+                ("--abi", options.abi),
+                ("--tape-file", options.tape_file.is_some()),
+                ("--framebuffer", options.framebuffer.is_some()),
+                ("--emit-tape-image", options.tape_image.is_some()),
+                ("--turtle", options.turtle.is_some()),
+                ("--emit-wav", options.wav.is_some()),
+                ("--heatmap", options.heatmap.is_some()),
+            ];
+            if let Some((flag, _)) = file_only.iter().find(|(_, set)| *set) {
+                return Err(format!("{} requires a source file", flag));
+            }
+        }
+        if options.tape_image_width.is_some() && options.tape_image.is_none() {
+            return Err("--tape-image-width requires --emit-tape-image".to_string());
+        }
+        let wav_only = [("--wav-rate", options.wav_rate.is_some()), ("--wav-tape", options.wav_tape.is_some())];
+        if options.wav.is_none()
+            && let Some((flag, _)) = wav_only.iter().find(|(_, set)| *set)
+        {
+            return Err(format!("{} requires --emit-wav", flag));
+        }
+        if options.checkpoint_every.is_some() != options.checkpoint_file.is_some() {
+            return Err("--checkpoint-every and --checkpoint-file must be used together".to_string());
+        }
+        // 检查点与运行包各自决定纸带的内容
+        let conflicting = [("--resume", options.resume.is_some()), ("--bundle", options.bundle.is_some())];
+        if options.tape_file.is_some()
+            && let Some((flag, _)) = conflicting.iter().find(|(_, set)| *set)
+        {
+            return Err(format!("{} cannot be used with --tape-file", flag));
+        }
+        if options.sandbox {
+            // 这些选项会读写文件、终端或加载代码
+            #[cfg(feature = "plugins")]
+            let plugins = !options.plugins.is_empty();
+            #[cfg(not(feature = "plugins"))]
+            let plugins = false;
+            let unsandboxed = [
+                ("--debug", options.debug),
+                ("--step-through", options.step_through),
+                ("--explain", options.explain.is_some()),
+                ("--record-input", options.record_input.is_some()),
+                ("--checkpoint-every", options.checkpoint_every.is_some()),
+                ("--resume", options.resume.is_some()),
+                ("--plugin", plugins),
+                ("--allow-fs", options.allow_fs.is_some()),
+                ("--tape-file", options.tape_file.is_some()),
+                ("--framebuffer", options.framebuffer.is_some()),
+                ("--emit-tape-image", options.tape_image.is_some()),
+                ("--turtle", options.turtle.is_some()),
+                ("--emit-wav", options.wav.is_some()),
+                ("--heatmap", options.heatmap.is_some()),
+            ];
+            if let Some((flag, _)) = unsandboxed.iter().find(|(_, set)| *set) {
+                return Err(format!("{} cannot be used with --sandbox", flag));
+            }
+        }
+        Ok(options)
+    }
+
+    /// 不给源文件时按`--bundle`重放：载入包并以其中的配置取代命令行选项
+    fn replay_bundle(&mut self) -> Result<Option<bundle::Bundle>, String> {
+        let (None, Some(path)) = (&self.file, &self.bundle) else {
+            return Ok(None);
+        };
+        let bundle = bundle::Bundle::load(Path::new(path))?;
+        if bundle.version != env!("CARGO_PKG_VERSION") {
+            eprintln!("warning: the bundle was recorded by derstand {}, this is {}", bundle.version, env!("CARGO_PKG_VERSION"));
+        }
+        let config = &bundle.config;
+        if config.sandbox && (self.debug || self.step_through || self.explain.is_some()) {
+            let flag = if self.debug {
+                "--debug"
+            } else if self.step_through {
+                "--step-through"
+            } else {
+                "--explain"
+            };
+            return Err(format!("{} cannot be used with a sandboxed bundle", flag));
+        }
+        self.file = Some(bundle.program_name.clone());
+        self.seed = Some(config.seed);
+        self.memory = config.memory;
+        self.start_pointer = config.start_pointer;
+        self.overflow = config.overflow;
+        self.dialect = config.dialect;
+        self.eof = config.eof;
+        self.timeout = config.timeout_ms;
+        self.strict_bounds = config.strict_bounds;
+        self.deterministic = config.deterministic;
+        self.assertions = config.assertions;
+        self.sandbox = config.sandbox;
+        self.detect_loops = config.detect_loops;
+        self.args = config.args.clone();
+        self.abi = config.abi;
+        Ok(Some(bundle))
+    }
+
+    /// 录制运行包时的配置
+    fn bundle_config(&self) -> bundle::BundleConfig {
+        bundle::BundleConfig {
+            seed: self.seed.unwrap_or(0),
+            memory: self.memory,
+            start_pointer: self.start_pointer,
+            overflow: self.overflow,
+            dialect: self.dialect,
+            eof: self.eof,
+            timeout_ms: self.timeout,
+            strict_bounds: self.strict_bounds,
+            deterministic: self.deterministic,
+            assertions: self.assertions,
+            sandbox: self.sandbox,
+            detect_loops: self.detect_loops,
+            args: self.args.clone(),
+            abi: self.abi,
+        }
     }
 }
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
+    
+    // 子命令；`run`只是文件模式的显式写法
+    let skip = if args.get(1).is_some_and(|command| command == "run") { 2 } else { 1 };
+    if let Some(command) = args.get(1) {
+        let code = match command.as_str() {
+            "lint" => Some(lint::command(&args[2..])),
+            "fmt" => Some(formatter::command(&args[2..])),
+            "minify" => Some(minify::command(&args[2..])),
+            "compile" => Some(compile::command(&args[2..])),
+            "build" => Some(compile::build_command(&args[2..])),
+            "import" => Some(import::command(&args[2..])),
+            "highlight" => Some(highlight::command(&args[2..])),
+            "jupyter-kernel" => Some(jupyter::command(&args[2..])),
+            "lsp" => Some(lsp::command(&args[2..])),
+            "serve" => Some(serve::command(&args[2..])),
+            "test" => Some(golden::command(&args[2..])),
+            "verify" => Some(verify::command(&args[2..])),
+            "equiv" => Some(equiv::command(&args[2..])),
+            "diff-run" => Some(diff_run::command(&args[2..])),
+            "run-set" => Some(channel::command(&args[2..])),
+            "animate" => Some(animate::command(&args[2..])),
+            "learn" => Some(learn::command(&args[2..])),
+            "gen" => Some(generate::command(&args[2..])),
+            "synth" => Some(synth::command(&args[2..])),
+            "obfuscate" => Some(obfuscate::command(&args[2..])),
+            "examples" => Some(examples::command(&args[2..])),
+            "watch" => Some(watch::command(&args[2..])),
+            "--rpc" => Some(rpc::command(&args[2..])),
+            _ => None,
+        };
+        if let Some(code) = code {
+            process::exit(code);
+        }
+    }
+    
+    let mut options = CliOptions::parse(&args[skip..]).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+    let replayed = options.replay_bundle().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    let replaying = replayed.is_some();
+    // 录制运行包时种子必须确定，才能写进包里
+    if !replaying && options.bundle.is_some() && options.seed.is_none() && !options.deterministic {
+        options.seed = Some(bundle::fresh_seed());
+    }
     let mut interpreter = DerstandInterpreter::new();
+    interpreter.set_loop_detection(options.detect_loops);
+    // 命令行选项优先于源码中的编译指示
+    if let Some(policy) = options.overflow {
+        interpreter.set_overflow_policy(policy);
+    }
+    if let Some(dialect) = options.dialect {
+        interpreter.set_dialect(dialect);
+    }
+    if let Some(size) = options.memory {
+        interpreter.set_memory_size(size);
+    }
+    if let Some(cell) = options.start_pointer {
+        interpreter.set_start_pointer(cell);
+    }
+    // 持久纸带的大小取代编译指示
+    let mut tape = options.tape_file.as_ref().map(|path| {
+        let tape = tape_file::TapeFile::open(Path::new(path), options.memory).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+        interpreter.set_memory_size(tape.len());
+        tape
+    });
+    if let Some(eof) = options.eof {
+        interpreter.set_eof_behavior(eof);
+    }
+    // 确定性模式下未指定种子时使用固定种子
+    if let Some(seed) = options.seed.or(options.deterministic.then_some(0)) {
+        interpreter.set_seed(seed);
+    }
+    interpreter.set_deterministic(options.deterministic);
+    if let Some(ms) = options.timeout {
+        let token = cancel::CancelToken::new();
+        token.cancel_after(Duration::from_millis(ms));
+        interpreter.set_cancel_token(Some(token));
+    }
+    interpreter.set_strict_bounds(options.strict_bounds);
+    interpreter.set_assertions(options.assertions);
+    interpreter.set_abi(options.abi);
+    if let Some(args) = &options.args
+        && let Err(e) = interpreter.set_args(args)
+    {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+    if let Some(directory) = &options.allow_fs
+        && let Err(e) = derstand::fs::enable(&mut interpreter, Path::new(directory))
+    {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+    if let Some(framebuffer) = &options.framebuffer
+        && let Err(e) = framebuffer.attach(&mut interpreter)
+    {
+        eprintln!("{}", e);
+        process::exit(2);
+    }
+    let turtle = options.turtle.as_ref().map(|_| {
+        turtle::attach(&mut interpreter).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(2);
+        })
+    });
+    interpreter.set_heatmap(options.heatmap.is_some());
+    // 采样取自输出时边输出边记录
+    let recorder = (options.wav.is_some() && options.wav_tape.is_none()).then(|| {
+        let recorder = audio::Recorder::default();
+        interpreter.set_output(Box::new(recorder.clone()));
+        recorder
+    });
+    // 插件指令要在编译之前注册
+    #[cfg(feature = "plugins")]
+    for path in &options.plugins {
+        if let Err(e) = derstand::plugin::load(&mut interpreter, Path::new(path)) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+    let replay = match &replayed {
+        Some(bundle) => Some(bundle.input.clone()),
+        None => options.replay_input.as_ref().map(|path| {
+            std::fs::read(path).unwrap_or_else(|e| {
+                eprintln!("Error reading replay file: {}", e);
+                process::exit(1);
+            })
+        }),
+    };
+    let bundle_input = options.bundle.is_some().then(|| replay.clone().unwrap_or_default());
+    if options.sandbox {
+        // 沙箱只能读到--replay-input的字节，输出在运行结束后写出
+        let input = replay.unwrap_or_default();
+        interpreter.set_sandbox(Some(derstand::sandbox::SandboxProfile { input, ..Default::default() }));
+    } else if let Some(bytes) = replay {
+        interpreter.preload_input(&bytes);
+    }
+    if let Some(path) = &options.record_input {
+        let file = std::fs::File::create(path).unwrap_or_else(|e| {
+            eprintln!("Error creating record file: {}", e);
+            process::exit(1);
+        });
+        interpreter.record_input(Box::new(file));
+    }
+    if let (Some(every), Some(path)) = (options.checkpoint_every, &options.checkpoint_file) {
+        interpreter.set_checkpointing(Some(checkpoint::CheckpointConfig { every, path: path.into() }));
+    }
     
-    if args.len() > 1 {
+    if let Some(file_path) = &options.file {
         // 文件模式 - 设置为非交互式
-        let file_path = &args[1];
-        if !Path::new(file_path).exists() {
-            eprintln!("File not found: {}", file_path);
-            process::exit(1);
+        let data = match replayed {
+            Some(bundle) => bundle.program,
+            None => {
+                if !Path::new(file_path).exists() {
+                    eprintln!("File not found: {}", file_path);
+                    process::exit(1);
+                }
+                std::fs::read(file_path)
+                    .unwrap_or_else(|e| {
+                        eprintln!("Error reading file: {}", e);
+                        process::exit(1);
+                    })
+            },
+        };
+        
+        // 录制运行包后照常运行
+        if let (Some(path), Some(input)) = (&options.bundle, bundle_input)
+            && !replaying
+        {
+            let recorded = bundle::Bundle::new(file_path, data.clone(), input, options.bundle_config());
+            if let Err(e) = recorded.save(Path::new(path)) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
         }
         
-        let source = std::fs::read_to_string(file_path)
-            .unwrap_or_else(|e| {
-                eprintln!("Error reading file: {}", e);
+        // 字节码文件跳过解析与优化，诊断使用其中保存的源码
+        let (source, source_name, compiled) = if bytecode::is_bytecode(&data) {
+            match bytecode::load(&mut interpreter, &data) {
+                Ok(map) => (map.source, map.name, Ok(())),
+                Err(e) => {
+                    eprintln!("Error loading {}: {}", file_path, e);
+                    process::exit(1);
+                },
+            }
+        } else {
+            let source = String::from_utf8(data).unwrap_or_else(|_| {
+                eprintln!("Error reading file: stream did not contain valid UTF-8");
                 process::exit(1);
             });
+            interpreter.set_source_path(file_path);
+            let compiled = interpreter.compile(&source);
+            (source, file_path.clone(), compiled)
+        };
+        let file_path = &source_name;
+        
+        // 导出模式 - 只输出编译结果，不执行
+        if let Some(kind) = options.emit {
+            if let Err(e) = compiled {
+                e.report(options.diagnostics, &source, Some(file_path));
+                process::exit(1);
+            }
+            println!("{}", emit::render(kind, &interpreter));
+            return;
+        }
+        
+        if compiled.is_ok() && let Some(tape) = &tape {
+            interpreter.load_tape(tape);
+        }
         
         // 编译和执行
-        match interpreter.compile(&source) {
+        match compiled {
+            Ok(_) if options.debug => {
+                let result = debugger::debug(&mut interpreter, &source);
+                store_tape(&mut interpreter, tape.as_mut());
+                if let Err(e) = result {
+                    report_runtime_error(&interpreter, &source, file_path, e, options.diagnostics);
+                    process::exit(1);
+                }
+            },
+            Ok(_) if options.step_through => {
+                let result = step::step_through(&mut interpreter, &source);
+                store_tape(&mut interpreter, tape.as_mut());
+                if let Err(e) = result {
+                    report_runtime_error(&interpreter, &source, file_path, e, options.diagnostics);
+                    process::exit(1);
+                }
+            },
+            Ok(_) if let Some(budget) = options.explain => {
+                let result = step::explain(&mut interpreter, budget, step::EXPLAIN_DELAY);
+                store_tape(&mut interpreter, tape.as_mut());
+                if let Err(e) = result {
+                    report_runtime_error(&interpreter, &source, file_path, e, options.diagnostics);
+                    process::exit(1);
+                }
+            },
             Ok(_) => {
+                // 从检查点恢复时跳过重置，继续之前的运行
+                if let Some(path) = &options.resume
+                    && let Err(e) = checkpoint::load(&mut interpreter, Path::new(path))
+                {
+                    eprintln!("Resume error: {}", e);
+                    process::exit(1);
+                }
+                
                 // 开始计时
                 let start_time = Instant::now();
                 
-                match interpreter.execute() {
+                let result = if options.resume.is_some() { interpreter.run() } else { interpreter.execute() };
+                store_tape(&mut interpreter, tape.as_mut());
+                if options.sandbox {
+                    let mut stdout = io::stdout();
+                    let _ = stdout.write_all(interpreter.captured_output()).and_then(|()| stdout.flush());
+                }
+                match result {
                     Ok(_) => {
                         // 结束计时并计算时间
                         let elapsed = start_time.elapsed();
-                        println!("\nExecution time: {}.{} ms", elapsed.as_millis(), elapsed.subsec_micros() / 1000);
+                        println!("\nExecution time: {}.{} ms", elapsed.as_millis(), elapsed.subsec_millis());
+                        if options.dump_memory {
+                            let memory = interpreter.memory();
+                            eprintln!("{}", hexdump::hexdump(memory, 0, memory.len(), interpreter.pointer()));
+                        }
+                        if let Some(framebuffer) = &options.framebuffer
+                            && let Err(e) = framebuffer.present(interpreter.memory())
+                        {
+                            eprintln!("{}", e);
+                            process::exit(1);
+                        }
+                        if let Some((path, format)) = &options.tape_image {
+                            // 默认每行256格
+                            let width = options.tape_image_width.unwrap_or(256);
+                            let (height, pixels) = image::tape_pixels(interpreter.memory(), width);
+                            if let Err(e) = std::fs::write(path, image::encode_gray(*format, width, height, &pixels)) {
+                                eprintln!("Error writing tape image {}: {}", path, e);
+                                process::exit(1);
+                            }
+                        }
+                        if let (Some(path), Some(turtle)) = (&options.turtle, &turtle)
+                            && let Err(e) = std::fs::write(path, turtle.borrow().svg())
+                        {
+                            eprintln!("Error writing turtle drawing {}: {}", path, e);
+                            process::exit(1);
+                        }
+                        if let Some(path) = &options.wav {
+                            let samples = match (options.wav_tape, &recorder) {
+                                (Some(region), _) => audio::tape_samples(interpreter.memory(), region).map(<[u8]>::to_vec),
+                                (None, Some(recorder)) => Ok(recorder.samples()),
+                                (None, None) => Ok(Vec::new()),
+                            };
+                            let written = samples.and_then(|samples| {
+                                let rate = options.wav_rate.unwrap_or(audio::DEFAULT_RATE);
+                                std::fs::write(path, audio::wav(rate, &samples)).map_err(|e| format!("Error writing {}: {}", path, e))
+                            });
+                            if let Err(e) = written {
+                                eprintln!("{}", e);
+                                process::exit(1);
+                            }
+                        }
+                        if let (Some((path, export)), Some(heatmap)) = (&options.heatmap, interpreter.heatmap())
+                            && let Err(e) = std::fs::write(path, heatmap.encode(*export))
+                        {
+                            eprintln!("Error writing heatmap {}: {}", path, e);
+                            process::exit(1);
+                        }
                     },
                     Err(e) => {
-                        eprintln!("Execution error: {}", e);
+                        report_runtime_error(&interpreter, &source, file_path, e, options.diagnostics);
                         process::exit(1);
                     },
                 }
             },
             Err(e) => {
-                eprintln!("Compilation error: {}", e);
+                e.report(options.diagnostics, &source, Some(file_path));
                 process::exit(1);
             },
         }
+        // 纸带ABI：程序留在单元格0的值是退出码
+        if let Some(status) = interpreter.abi_status()
+            && status != 0
+        {
+            process::exit(status as i32);
+        }
     } else {
         // 交互式模式 - 设置为交互式
-        interpreter.is_interactive_mode = true;
+        interpreter.set_interactive(true);
         
         println!("Derstand Interpreter v0.1.0");
-        println!("Instructions: > < + - . , [ ] # $ % &");
-        println!("Type 'quit' to exit.");
+        let dialect = interpreter.dialect();
+        let symbols: Vec<String> = "><+-.:,?*`/«»[]#$£~()|\\{}^%&@§"
+            .chars()
+            .filter(|&c| Instruction::from_char_in(c, dialect).is_some())
+            .map(String::from)
+            .collect();
+        println!("Instructions ({} dialect): {}", dialect.as_str(), symbols.join(" "));
+        if dialect == Dialect::Classic {
+            println!("Start with --dialect extended for : ? * ` / ~ ( ) | \\ {{ }} ^ @ § and \"strings\".");
+        }
+        println!("Type ':mem [start [len]]' to view memory, ':load FILE' to run a file on the current tape, 'quit' to exit.");
         
         loop {
             print!("\n> ");
             io::stdout().flush().unwrap();
             
             let mut input = String::new();
-            io::stdin().read_line(&mut input).unwrap();
+            if io::stdin().read_line(&mut input).unwrap() == 0 {
+                break; // 标准输入结束
+            }
             
             let input = input.trim();
             if input == "quit" || input == "exit" {
@@ -292,6 +714,35 @@ fn main() {
             if input.is_empty() {
                 continue;
             }
+            if let Some(args) = input.strip_prefix(":mem") {
+                // 查看内存 - 显示上次运行后的纸带
+                match hexdump::parse_range(args.split_whitespace(), interpreter.memory_size()) {
+                    Ok((start, len)) => println!("{}", hexdump::hexdump(interpreter.memory(), start, len, interpreter.pointer())),
+                    Err(e) => println!("{}", e),
+                }
+                continue;
+            }
+            if let Some(path) = input.strip_prefix(":load") {
+                // 热重载 - 文件在当前纸带与指针上运行，改了文件再:load一次即可
+                let path = path.trim();
+                let source = match std::fs::read_to_string(path) {
+                    Ok(source) => source,
+                    Err(e) => {
+                        println!("Error reading file {}: {}", path, e);
+                        continue;
+                    },
+                };
+                interpreter.set_source_path(path);
+                if let Err(e) = interpreter.compile(&source) {
+                    println!("{}", e.render(&source, Some(path), diagnostic::stdout_color()));
+                    continue;
+                }
+                match interpreter.execute_in_place() {
+                    Ok(()) => println!("\nLoaded {}; the pointer is at {}", path, interpreter.pointer()),
+                    Err(e) => println!("\n{}", runtime_error(&interpreter, &source, e).render(&source, Some(path), diagnostic::stdout_color())),
+                }
+                continue;
+            }
             
             // 编译和执行
             match interpreter.compile(input) {
@@ -303,12 +754,12 @@ fn main() {
                         Ok(_) => {
                             // 结束计时并计算时间
                             let elapsed = start_time.elapsed();
-                            println!("\nExecution time: {}.{} ms", elapsed.as_millis(), elapsed.subsec_micros() / 1000);
+                            println!("\nExecution time: {}.{} ms", elapsed.as_millis(), elapsed.subsec_millis());
                         },
-                        Err(e) => println!("Execution error: {}", e),
+                        Err(e) => println!("\n{}", runtime_error(&interpreter, input, e).render(input, None, diagnostic::stdout_color())),
                     }
                 },
-                Err(e) => println!("Compilation error: {}", e),
+                Err(e) => println!("{}", e.render(input, None, diagnostic::stdout_color())),
             }
         }
     }
@@ -0,0 +1,125 @@
+//! 服务指标 - `derstand serve`的`/metrics`端点，Prometheus文本格式(0.0.4)
+//!
+//! 计数只增不减，由各连接线程并发更新；进程重启后从零开始。
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// 运行结束的状态，与`POST /run`返回的status相同
+pub const STATUSES: [&str; 7] = ["ok", "compile_error", "runtime_error", "step_limit", "time_limit", "memory_limit", "output_limit"];
+
+/// 超限状态与limits_hit的limit标签
+const LIMITS: [(&str, &str); 4] = [("step_limit", "steps"), ("time_limit", "time"), ("memory_limit", "memory"), ("output_limit", "output")];
+
+/// 运行耗时的桶上界(秒)
+const LATENCY_BUCKETS: [f64; 11] = [0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// 每次运行执行指令数的桶上界
+const STEP_BUCKETS: [f64; 9] = [1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10];
+
+/// 累积直方图
+struct Histogram {
+    bounds: &'static [f64],
+    buckets: Vec<AtomicU64>, // 落在各桶(不累积)的次数，最后一个是+Inf
+    sum: AtomicU64, // 以micro为单位的和，避免浮点原子操作
+    micro: f64, // 观测值乘以它后计入sum
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64], micro: f64) -> Self {
+        Histogram { bounds, buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(), sum: AtomicU64::new(0), micro }
+    }
+
+    fn observe(&self, value: f64) {
+        let bucket = self.bounds.iter().position(|&bound| value <= bound).unwrap_or(self.bounds.len());
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add((value * self.micro) as u64, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} histogram", name, help, name);
+        let mut count = 0;
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            count += bucket.load(Ordering::Relaxed);
+            let le = self.bounds.get(i).map_or("+Inf".to_string(), |bound| bound.to_string());
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, count);
+        }
+        let _ = writeln!(out, "{}_sum {}", name, self.sum.load(Ordering::Relaxed) as f64 / self.micro);
+        let _ = writeln!(out, "{}_count {}", name, count);
+    }
+}
+
+/// 服务的全部指标
+pub struct Metrics {
+    started: AtomicU64,
+    completed: [AtomicU64; STATUSES.len()],
+    instructions: AtomicU64,
+    latency: Histogram,
+    steps: Histogram,
+    sessions_opened: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            started: AtomicU64::new(0),
+            completed: Default::default(),
+            instructions: AtomicU64::new(0),
+            latency: Histogram::new(&LATENCY_BUCKETS, 1e6),
+            steps: Histogram::new(&STEP_BUCKETS, 1.0),
+            sessions_opened: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Metrics {
+    /// 一次运行开始(HTTP提交或REPL中的一行)
+    pub fn run_started(&self) {
+        self.started.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 一次运行结束；status必须是STATUSES之一
+    pub fn run_completed(&self, status: &str, steps: u64, elapsed: Duration) {
+        if let Some(i) = STATUSES.iter().position(|&s| s == status) {
+            self.completed[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.instructions.fetch_add(steps, Ordering::Relaxed);
+        self.latency.observe(elapsed.as_secs_f64());
+        self.steps.observe(steps as f64);
+    }
+
+    pub fn session_opened(&self) {
+        self.sessions_opened.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 文本格式；running与sessions是调用者持有的当前值
+    pub fn render(&self, running: usize, sessions: usize) -> String {
+        let mut out = String::new();
+        let counter = |out: &mut String, name: &str, help: &str, value: u64| {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} counter\n{} {}", name, help, name, name, value);
+        };
+        let gauge = |out: &mut String, name: &str, help: &str, value: usize| {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} gauge\n{} {}", name, help, name, name, value);
+        };
+        counter(&mut out, "derstand_runs_started_total", "Runs started (HTTP submissions and REPL lines).", self.started.load(Ordering::Relaxed));
+
+        let _ = writeln!(out, "# HELP derstand_runs_completed_total Runs completed, by status.\n# TYPE derstand_runs_completed_total counter");
+        for (status, count) in STATUSES.iter().zip(&self.completed) {
+            let _ = writeln!(out, "derstand_runs_completed_total{{status=\"{}\"}} {}", status, count.load(Ordering::Relaxed));
+        }
+        let _ = writeln!(out, "# HELP derstand_limits_hit_total Runs stopped by a resource limit.\n# TYPE derstand_limits_hit_total counter");
+        for (status, limit) in LIMITS {
+            let count = STATUSES.iter().position(|&s| s == status).map_or(0, |i| self.completed[i].load(Ordering::Relaxed));
+            let _ = writeln!(out, "derstand_limits_hit_total{{limit=\"{}\"}} {}", limit, count);
+        }
+
+        counter(&mut out, "derstand_instructions_executed_total", "Instructions executed across all runs.", self.instructions.load(Ordering::Relaxed));
+        self.latency.render(&mut out, "derstand_run_duration_seconds", "Wall-clock time per run, including compilation.");
+        self.steps.render(&mut out, "derstand_run_instructions", "Instructions executed per run.");
+        gauge(&mut out, "derstand_runs_in_progress", "HTTP submissions currently running.", running);
+        counter(&mut out, "derstand_repl_sessions_opened_total", "WebSocket REPL sessions opened.", self.sessions_opened.load(Ordering::Relaxed));
+        gauge(&mut out, "derstand_repl_sessions", "WebSocket REPL sessions currently open.", sessions);
+        out
+    }
+}
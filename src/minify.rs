@@ -0,0 +1,340 @@
+//! 最小化 - 去掉注释与可证明无效的指令，可选地改写为Derstand扩展指令
+
+use std::ops::Deref;
+
+use crate::pragma::EXTENDED_DIALECT;
+use crate::{DerstandInterpreter, Instruction, STRING_MARKER, Span};
+
+/// 最小化中的指令序列与各指令的源码位置；合并或改写得到的指令取被替换的第一条指令的位置
+#[derive(Default)]
+struct Code {
+    instructions: Vec<Instruction>,
+    spans: Vec<Span>,
+}
+
+impl Code {
+    fn push(&mut self, instruction: Instruction, span: Span) {
+        self.instructions.push(instruction);
+        self.spans.push(span);
+    }
+
+    fn pop(&mut self) -> Option<Span> {
+        self.instructions.pop();
+        self.spans.pop()
+    }
+
+    /// 删除末尾n条指令，返回其中第一条的位置
+    fn remove_tail(&mut self, n: usize) -> Span {
+        let start = self.instructions.len() - n;
+        self.instructions.truncate(start);
+        self.spans.drain(start..).next().expect("the tail is not empty")
+    }
+}
+
+impl Deref for Code {
+    type Target = [Instruction];
+
+    fn deref(&self) -> &[Instruction] {
+        &self.instructions
+    }
+}
+
+/// 末尾可被当前指令覆盖而删除的指令
+fn overwritten_by(instruction: Instruction, previous: Instruction) -> bool {
+    match instruction {
+        // 清零、赋值、读入、取随机数、读时钟或弹栈会覆盖之前对当前单元格的加减与赋值
+        Instruction::Zero
+        | Instruction::Set(_)
+        | Instruction::Input
+        | Instruction::InputDecimal
+        | Instruction::Random
+        | Instruction::Time
+        | Instruction::Pop => matches!(
+            previous,
+            Instruction::Increment
+                | Instruction::Decrement
+                | Instruction::Add(_)
+                | Instruction::Sub(_)
+                | Instruction::Zero
+                | Instruction::Set(_)
+        ),
+        // 绝对定位会覆盖之前的指针移动
+        Instruction::MoveHigh | Instruction::MoveLow => matches!(
+            previous,
+            Instruction::Right
+                | Instruction::Left
+                | Instruction::MoveRight(_)
+                | Instruction::MoveLeft(_)
+                | Instruction::MoveHigh
+                | Instruction::MoveLow
+                | Instruction::ScanLeft
+                | Instruction::ScanRight
+        ),
+        _ => false,
+    }
+}
+
+/// 追加一条指令并做窥孔化简
+fn push(out: &mut Code, instruction: Instruction, span: Span, idioms: bool) {
+    // ={0} 与 # 等价且更长
+    let instruction = if instruction == Instruction::Set(0) { Instruction::Zero } else { instruction };
+    match (out.last(), instruction) {
+        // 单元格按字节回绕，+- 与 -+ 总是抵消；指针移动受边界钳制，不能同样处理
+        // 连续两次切换纸带或交换单元格也互相抵消
+        (Some(Instruction::Increment), Instruction::Decrement)
+        | (Some(Instruction::Decrement), Instruction::Increment)
+        | (Some(Instruction::SwitchTape), Instruction::SwitchTape)
+        | (Some(Instruction::Exchange), Instruction::Exchange) => {
+            out.pop();
+            return;
+        },
+        _ => {},
+    }
+    while out.last().is_some_and(|&previous| overwritten_by(instruction, previous)) {
+        out.pop();
+    }
+    if instruction == Instruction::Zero && cell_is_zero(out) {
+        return; // 单元格已经是0
+    }
+    out.push(instruction, span);
+
+    // [-] 与 [+] 等价于 #
+    if idioms && instruction == Instruction::JumpIfNotZero && out.len() >= 3 {
+        let tail = &out[out.len() - 3..];
+        if tail[0] == Instruction::JumpIfZero && matches!(tail[1], Instruction::Increment | Instruction::Decrement) {
+            let span = out.remove_tail(3);
+            push(out, Instruction::Zero, span, idioms);
+            return;
+        }
+    }
+    // [->+<] 与 [>+<-] 等价于 ~ (只有指针在高端边界时不同)
+    if idioms && instruction == Instruction::JumpIfNotZero && out.len() >= 6 {
+        use Instruction::{Decrement, Increment, JumpIfZero, Left, Right};
+        let tail = &out[out.len() - 6..];
+        if tail[0] == JumpIfZero && matches!(tail[1..5], [Decrement, Right, Increment, Left] | [Right, Increment, Left, Decrement]) {
+            let span = out.remove_tail(6);
+            push(out, Instruction::AddNext, span, idioms);
+        }
+    }
+    // [<] 与 [>] 等价于 « 与 » (只有找不到0单元格时不同：扫描停在边界而不是永远循环)
+    if idioms && instruction == Instruction::JumpIfNotZero && out.len() >= 3 {
+        use Instruction::{JumpIfNotZero, JumpIfZero, Left, Right};
+        let scan = match out[out.len() - 3..] {
+            [JumpIfZero, Left, JumpIfNotZero] => Some(Instruction::ScanLeft),
+            [JumpIfZero, Right, JumpIfNotZero] => Some(Instruction::ScanRight),
+            _ => None,
+        };
+        if let Some(scan) = scan {
+            let span = out.remove_tail(3);
+            push(out, scan, span, idioms);
+        }
+    }
+    if idioms {
+        rewrite_moves(out);
+    }
+}
+
+/// 把当前单元格移动或复制到清零的相邻单元格的写法改写为 $ 与 £
+fn rewrite_moves(out: &mut Code) {
+    use Instruction::{AddNext, Copy, CopyLeft, Decrement, Increment, JumpIfNotZero, JumpIfZero, Left, Right, Zero};
+    // >#<~ 与 <#>[-<+>] 把当前单元格移到清零的相邻单元格
+    let (rewritten, span): (&[Instruction], Span) = if out.ends_with(&[Right, Zero, Left, AddNext]) {
+        (&[Copy, Zero], out.remove_tail(4))
+    } else if out.ends_with(&[Left, Zero, Right, JumpIfZero, Decrement, Left, Increment, Right, JumpIfNotZero])
+        || out.ends_with(&[Left, Zero, Right, JumpIfZero, Left, Increment, Right, Decrement, JumpIfNotZero])
+    {
+        (&[CopyLeft, Zero], out.remove_tail(9))
+    } else if out.ends_with(&[
+        Right, Zero, Right, Zero, Left, Left,
+        JumpIfZero, Decrement, Right, Increment, Right, Increment, Left, Left, JumpIfNotZero,
+        Right, Right,
+        JumpIfZero, Decrement, Left, Left, Increment, Right, Right, JumpIfNotZero,
+    ]) {
+        // 借清零的第二格复制到清零的下一格，结束时指针停在第二格
+        (&[Copy, Right, Right, Zero], out.remove_tail(25))
+    } else {
+        return;
+    };
+    for &instruction in rewritten {
+        out.push(instruction, span);
+    }
+}
+
+/// 当前单元格是否必为0(此时循环不会执行)
+fn cell_is_zero(out: &[Instruction]) -> bool {
+    // 输出为空时与初始状态等价：被删除的指令都没有效果
+    matches!(out.last(), None | Some(Instruction::Zero) | Some(Instruction::JumpIfNotZero) | Some(Instruction::AddNext))
+}
+
+/// 把同类的加减或移动合并为重复计数形式(`+{65}`)，清零后的加法合并为赋值(`={65}`)，只在更短时合并
+fn fuse_runs(code: Code) -> Code {
+    let base = |i: Instruction| match i {
+        Instruction::Add(_) => Instruction::Increment,
+        Instruction::Sub(_) => Instruction::Decrement,
+        Instruction::MoveRight(_) => Instruction::Right,
+        Instruction::MoveLeft(_) => Instruction::Left,
+        _ => i,
+    };
+    let instructions = &code.instructions;
+    let mut out = Code::default();
+    let mut i = 0;
+    while i < instructions.len() {
+        let kind = base(instructions[i]);
+        let mut end = i;
+        let mut count = 0u32;
+        while end < instructions.len() && base(instructions[end]) == kind && kind.repeated(2).is_some() {
+            let Some(total) = count.checked_add(instructions[end].count()) else {
+                break;
+            };
+            count = total;
+            end += 1;
+        }
+        let span = code.spans[i];
+        if end == i {
+            out.push(instructions[i], span);
+            i += 1;
+            continue;
+        }
+        // 不合并时逐条保留原来的位置
+        let run = match kind.repeated(count) {
+            Some(fused) if fused.to_string().len() < count as usize => vec![(fused, span)],
+            _ => (i..end).flat_map(|j| std::iter::repeat_n((kind, code.spans[j]), instructions[j].count() as usize)).collect(),
+        };
+        let length: usize = run.iter().map(|(i, _)| i.to_string().len()).sum();
+        // # 后紧跟加法可写成赋值 ={n}
+        if kind == Instruction::Increment
+            && out.last() == Some(&Instruction::Zero)
+            && let Ok(value) = u8::try_from(count)
+            && Instruction::Set(value).to_string().len() < 1 + length
+        {
+            let span = out.pop().expect("a zero precedes the run");
+            out.push(Instruction::Set(value), span);
+        } else {
+            for (instruction, span) in run {
+                out.push(instruction, span);
+            }
+        }
+        i = end;
+    }
+    out
+}
+
+/// 计算等价的最小指令序列
+pub fn minify(interpreter: &DerstandInterpreter, idioms: bool) -> Vec<Instruction> {
+    minify_spanned(interpreter, idioms).0
+}
+
+/// 同minify，另外返回每条指令对应的源码位置
+pub fn minify_spanned(interpreter: &DerstandInterpreter, idioms: bool) -> (Vec<Instruction>, Vec<Span>) {
+    let instructions = interpreter.instructions();
+    let spans = interpreter.spans();
+    let mut out = Code::default();
+    let mut pc = 0;
+    while pc < instructions.len() {
+        let instruction = instructions[pc];
+        if instruction == Instruction::JumpIfZero && cell_is_zero(&out) {
+            // 永不执行的循环(包括注释块)整体删除
+            pc = interpreter.jump_target(pc).unwrap_or(pc) + 1;
+            continue;
+        }
+        push(&mut out, instruction, spans[pc], idioms);
+        pc += 1;
+    }
+    let out = if idioms { fuse_runs(out) } else { out };
+    (out.instructions, out.spans)
+}
+
+/// 字符串字面量中字节的写法；不可打印的字节写作赋值更短
+fn string_escape(byte: u8) -> Option<String> {
+    match byte {
+        b'"' | b'\\' => Some(format!("\\{}", byte as char)),
+        b'\n' => Some("\\n".to_string()),
+        b'\t' => Some("\\t".to_string()),
+        b' '..=b'~' => Some((byte as char).to_string()),
+        _ => None,
+    }
+}
+
+/// 输出指令序列；连续的"赋值后右移一格"写成字符串字面量，用到扩展指令时开头声明扩展方言
+pub fn render(instructions: &[Instruction]) -> String {
+    let close = |out: &mut String, literal: &mut String| {
+        if !literal.is_empty() {
+            *out += &format!("{}{}{}", STRING_MARKER, std::mem::take(literal), STRING_MARKER);
+        }
+    };
+    let mut out = String::new();
+    let mut literal = String::new();
+    let mut i = 0;
+    while i < instructions.len() {
+        if let Instruction::Set(byte) = instructions[i]
+            && matches!(instructions.get(i + 1), Some(Instruction::Right | Instruction::MoveRight(1)))
+            && let Some(text) = string_escape(byte)
+        {
+            literal.push_str(&text);
+            i += 2;
+            continue;
+        }
+        close(&mut out, &mut literal);
+        out.push_str(&instructions[i].to_string());
+        i += 1;
+    }
+    close(&mut out, &mut literal);
+    // 字符串字面量也只在扩展方言中有效
+    let extended = instructions.iter().any(|instruction| instruction.is_extended()) || out.contains(STRING_MARKER);
+    if extended { format!("{}{}", EXTENDED_DIALECT, out) } else { out }
+}
+
+/// `derstand minify [--idioms] [-o out] <file>`
+pub fn command(args: &[String]) -> i32 {
+    let mut idioms = false;
+    let mut output = None;
+    let mut file = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--idioms" => idioms = true,
+            "-o" | "--output" => match iter.next() {
+                Some(path) => output = Some(path.clone()),
+                None => {
+                    eprintln!("Missing value for {}", arg);
+                    return 2;
+                },
+            },
+            _ if arg.starts_with('-') => {
+                eprintln!("Unknown option: {}", arg);
+                return 2;
+            },
+            _ => file = Some(arg.clone()),
+        }
+    }
+    let Some(file) = file else {
+        eprintln!("Usage: derstand minify [--idioms] [-o out] <file>");
+        return 2;
+    };
+
+    let source = match std::fs::read_to_string(&file) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error reading file {}: {}", file, e);
+            return 1;
+        },
+    };
+    let mut interpreter = DerstandInterpreter::new();
+    interpreter.set_source_path(&file);
+    if let Err(e) = interpreter.compile(&source) {
+        e.report(Default::default(), &source, Some(&file));
+        return 1;
+    }
+
+    let minified = render(&minify(&interpreter, idioms));
+    match output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, &minified) {
+                eprintln!("Error writing file {}: {}", path, e);
+                return 1;
+            }
+        },
+        None => println!("{}", minified),
+    }
+    0
+}
@@ -0,0 +1,229 @@
+//! 混淆 - `derstand obfuscate prog.dr`把程序改写成行为相同但难以阅读的形式，用于谜题与CTF
+//!
+//! 改写只使用在任何溢出策略与边界模式下都成立的等价：
+//!
+//! ```text
+//! +{5}  →  ++{3}+     合并的加减与移动拆成几段
+//! #     →  [-]        ={n}写成清零再相加
+//! ||  \\              插入成对抵消的指令(只在扩展方言中)
+//! ]  →  ][>.,+<]     单元格必为0处插入永不执行的循环
+//! ```
+//!
+//! 原来的注释被丢弃，换成随机的注释行与换行；开头的`;!`编译指示原样保留。改写后以参考运行核对：
+//! 在同一输入(`--input`，默认为空)上运行两个程序，输出、出错与否和最终纸带都一致才写出结果。
+
+use std::path::Path;
+
+use crate::diagnostic::stderr_color;
+use crate::equiv::{self, divergences};
+use crate::golden::TestOptions;
+use crate::pragma::{self, EXTENDED_DIALECT, PRAGMA_MARKER};
+use crate::random::Rng;
+use crate::{DerstandInterpreter, Dialect, Instruction};
+
+/// 成对抵消的指令；`()`在辅助栈将满时会提前溢出，不能使用
+const NEUTRAL: &[&str] = &["||", "\\\\"];
+
+/// 永不执行的循环体所用的指令
+const JUNK: &[char] = &['+', '-', '>', '<', '.', ',', '#', '$'];
+
+/// 扩展方言中永不执行的循环体所用的指令
+const EXTENDED_JUNK: &[char] = &['+', '-', '>', '<', '.', ',', '#', '$', '~', '(', ')'];
+
+/// 注释行的词
+const WORDS: &[&str] = &[
+    "tape", "cell", "carry", "loop", "stack", "pointer", "shift", "mask", "seed", "byte", "left", "right", "copy", "swap", "flag", "state",
+    "init", "done", "scan", "clear", "todo", "magic", "fixme", "again", "twice", "keep", "ignore", "next",
+];
+
+struct Obfuscator {
+    rng: Rng,
+    tokens: Vec<String>,
+    extended: bool, // 可以插入扩展指令
+}
+
+impl Obfuscator {
+    /// 以1/n的概率返回true
+    fn chance(&mut self, n: u64) -> bool {
+        self.rng.next_u64().is_multiple_of(n)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.rng.next_u64() % n as u64) as usize
+    }
+
+    /// 把count次instruction拆成随机的几段
+    fn split(&mut self, instruction: Instruction, count: u32) {
+        let mut left = count;
+        while left > 0 {
+            let piece = if left == 1 || self.chance(3) { left } else { 1 + self.below(left as usize) as u32 };
+            let token = instruction.repeated(piece).expect("only fusable instructions are split");
+            self.tokens.push(token.to_string());
+            left -= piece;
+        }
+    }
+
+    fn junk(&mut self) -> String {
+        let length = 3 + self.below(8);
+        let junk = if self.extended { EXTENDED_JUNK } else { JUNK };
+        let body: String = (0..length).map(|_| junk[self.below(junk.len())]).collect();
+        format!("[{}]", body)
+    }
+
+    fn push(&mut self, instruction: Instruction) {
+        match instruction {
+            Instruction::Add(n) => self.split(Instruction::Increment, n),
+            Instruction::Sub(n) => self.split(Instruction::Decrement, n),
+            Instruction::MoveRight(n) => self.split(Instruction::Right, n),
+            Instruction::MoveLeft(n) => self.split(Instruction::Left, n),
+            Instruction::Zero if self.chance(2) => self.tokens.push("[-]".to_string()),
+            Instruction::Set(n) => {
+                let zero = if self.chance(2) { "#" } else { "[-]" };
+                self.tokens.push(zero.to_string());
+                if n > 0 {
+                    self.split(Instruction::Increment, n as u32);
+                }
+            },
+            _ => self.tokens.push(instruction.to_string()),
+        }
+        // 这些指令之后当前单元格必为0
+        if matches!(instruction, Instruction::JumpIfNotZero | Instruction::Zero | Instruction::Set(0)) && self.chance(4) {
+            let junk = self.junk();
+            self.tokens.push(junk);
+        }
+        if self.extended && self.chance(8) {
+            let neutral = NEUTRAL[self.below(NEUTRAL.len())];
+            self.tokens.push(neutral.to_string());
+        }
+    }
+
+    /// 随机断行，行间夹杂注释
+    fn layout(mut self) -> String {
+        let tokens = std::mem::take(&mut self.tokens);
+        let mut out = String::new();
+        let mut line = String::new();
+        let mut width = 24 + self.below(48);
+        for token in tokens {
+            line.push_str(&token);
+            if line.chars().count() >= width {
+                out.push_str(&line);
+                out.push('\n');
+                line.clear();
+                width = 24 + self.below(48);
+                if self.chance(3) {
+                    let words: Vec<&str> = (0..2 + self.below(6)).map(|_| WORDS[self.below(WORDS.len())]).collect();
+                    out.push_str(&format!("; {}\n", words.join(" ")));
+                }
+            }
+        }
+        if !line.is_empty() {
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// 开头的编译指示行
+fn pragmas(source: &str) -> String {
+    let lines = source.lines().map(str::trim).skip_while(|line| line.is_empty());
+    lines.take_while(|line| line.starts_with(PRAGMA_MARKER)).map(|line| format!("{}\n", line)).collect()
+}
+
+/// 改写interpreter中已编译的程序；source用于保留编译指示
+pub fn obfuscate(interpreter: &DerstandInterpreter, source: &str, seed: u64) -> String {
+    let extended = interpreter.dialect() == Dialect::Extended;
+    let mut obfuscator = Obfuscator { rng: Rng::new(seed), tokens: Vec::new(), extended };
+    for &instruction in interpreter.instructions() {
+        obfuscator.push(instruction);
+    }
+    // 方言由调用者设置而非编译指示时补上声明
+    let dialect = if extended && pragma::dialect(source) != Dialect::Extended { EXTENDED_DIALECT } else { "" };
+    format!("{}{}{}", pragmas(source), dialect, obfuscator.layout())
+}
+
+/// `derstand obfuscate [--seed S] [--input FILE] [-o out] <file>`
+pub fn command(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: derstand obfuscate [--seed S] [--input FILE] [-o out] <file>";
+    let mut seed = Rng::entropy_seed();
+    let (mut input, mut output, mut file) = (None, None, None);
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let parsed = match arg.as_str() {
+            "--seed" => iter.next().and_then(|v| v.parse().ok()).map(|n| seed = n).ok_or("--seed expects a number".to_string()),
+            "--input" => iter.next().map(|path| input = Some(path.clone())).ok_or("Missing value for --input".to_string()),
+            "-o" | "--output" => iter.next().map(|path| output = Some(path.clone())).ok_or(format!("Missing value for {}", arg)),
+            _ if arg.starts_with('-') => Err(format!("Unknown option: {}", arg)),
+            _ => {
+                file = Some(arg.clone());
+                Ok(())
+            },
+        };
+        if let Err(e) = parsed {
+            eprintln!("{}", e);
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    }
+    let Some(file) = file else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let source = match std::fs::read_to_string(&file) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error reading file {}: {}", file, e);
+            return 1;
+        },
+    };
+    let input = match input.as_ref().map(std::fs::read).transpose() {
+        Ok(input) => input.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("Error reading input: {}", e);
+            return 1;
+        },
+    };
+    let mut interpreter = DerstandInterpreter::new();
+    interpreter.set_source_path(&file);
+    // 保留断言，使它们在混淆后的程序中依然可用
+    interpreter.set_assertions(true);
+    if let Err(e) = interpreter.compile(&source) {
+        e.report(Default::default(), &source, Some(&file));
+        return 1;
+    }
+    let obfuscated = obfuscate(&interpreter, &source, seed);
+
+    // 参考运行
+    let budget = TestOptions::default();
+    let runs = equiv::run(&source, Path::new(&file), &input, budget).and_then(|reference| Ok((reference, equiv::run(&obfuscated, Path::new(&file), &input, budget)?)));
+    let (reference, rewritten) = match runs {
+        Ok(runs) => runs,
+        Err(e) => {
+            eprintln!("{}", e.render(&source, Some(&file), stderr_color()));
+            return 1;
+        },
+    };
+    // 改写后的程序执行的步数更多，只有它超出预算时同样无法判断
+    let verdict = if rewritten.exhausted.is_some() { None } else { divergences(&reference, &rewritten, ("the original", "the obfuscated program"), true) };
+    match verdict {
+        None => eprintln!("warning: the run exceeded the verification budget, so the obfuscated version could not be verified"),
+        Some(found) if found.is_empty() => {},
+        Some(found) => {
+            eprintln!("Internal error: the obfuscated program behaves differently (seed {}):", seed);
+            for line in found {
+                eprintln!("  {}", line);
+            }
+            return 1;
+        },
+    }
+    match output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, &obfuscated) {
+                eprintln!("Error writing file {}: {}", path, e);
+                return 1;
+            }
+        },
+        None => print!("{}", obfuscated),
+    }
+    0
+}
@@ -0,0 +1,272 @@
+//! 浏览器演练场接口 - 启用`wasm`特性并编译到wasm32-unknown-unknown后，由JavaScript直接调用
+//!
+//! 不依赖wasm-bindgen：导出的都是普通的C ABI函数，`WebAssembly.instantiate`即可使用。
+//! 没有标准输入输出，程序的输入输出与时钟通过宿主函数进行(导入模块`derstand`)：
+//!
+//! ```text
+//! input() -> i32                  下一个输入字节，输入结束时返回-1
+//! output(ptr: i32, len: i32)      程序输出，ptr指向线性内存
+//! now_ms() -> f64                 单调时钟(毫秒)
+//! sleep_ms(ms: f64)               '/'的睡眠，宿主可以直接返回
+//! random_seed() -> f64            '*'的初始种子
+//! ```
+//!
+//! 字符串以(指针, 长度)传递：源码先用`playground_alloc`分配再写入线性内存，
+//! 错误信息用`playground_error_ptr`与`playground_error_len`读取。
+//! 会话像交互式模式一样在多次编译之间保留纸带。
+//!
+//! ```text
+//! $ cargo rustc --release --lib --features wasm --target wasm32-unknown-unknown --crate-type cdylib
+//! ```
+
+use std::io::{self, Read, Write};
+
+use crate::{DerstandInterpreter, runtime_error};
+
+#[link(wasm_import_module = "derstand")]
+unsafe extern "C" {
+    fn input() -> i32;
+    fn output(data: *const u8, len: usize);
+}
+
+/// 宿主的输入函数
+struct HostInput;
+
+impl Read for HostInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // SAFETY: 宿主函数没有前置条件
+        match unsafe { input() } {
+            byte @ 0..=255 => {
+                buf[0] = byte as u8;
+                Ok(1)
+            },
+            _ => Ok(0),
+        }
+    }
+}
+
+/// 宿主的输出函数
+struct HostOutput;
+
+impl Write for HostOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // SAFETY: buf在调用期间有效
+        unsafe { output(buf.as_ptr(), buf.len()) };
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 演练场会话
+pub struct Session {
+    interpreter: DerstandInterpreter,
+    source: String,
+    error: String,
+}
+
+/// 单步执行的结果
+const PAUSED: i32 = 0;
+const FINISHED: i32 = 1;
+const FAILED: i32 = 2;
+
+/// 在线性内存中分配len字节，供宿主写入源码
+#[unsafe(no_mangle)]
+pub extern "C" fn playground_alloc(len: usize) -> *mut u8 {
+    let mut buffer = Vec::<u8>::with_capacity(len);
+    let ptr = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+    ptr
+}
+
+/// 释放`playground_alloc`分配的内存
+///
+/// # Safety
+///
+/// ptr与len必须来自同一次`playground_alloc`调用，且只释放一次
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn playground_dealloc(ptr: *mut u8, len: usize) {
+    // SAFETY: 见函数说明
+    drop(unsafe { Vec::from_raw_parts(ptr, 0, len) });
+}
+
+/// 创建会话；输入输出接到宿主函数，输入结束时读到0
+#[unsafe(no_mangle)]
+pub extern "C" fn playground_new() -> *mut Session {
+    let mut interpreter = DerstandInterpreter::new();
+    interpreter.set_input(Box::new(HostInput));
+    interpreter.set_output(Box::new(HostOutput));
+    Box::into_raw(Box::new(Session { interpreter, source: String::new(), error: String::new() }))
+}
+
+/// 会话句柄转为引用
+///
+/// # Safety
+///
+/// session必须是`playground_new`返回且尚未释放的句柄
+unsafe fn session<'a>(session: *mut Session) -> &'a mut Session {
+    // SAFETY: 见函数说明
+    unsafe { session.as_mut() }.expect("playground session handle is null")
+}
+
+/// 编译len字节的UTF-8源码并准备从头执行；返回0表示成功，1表示编译错误
+///
+/// # Safety
+///
+/// session必须是有效句柄，ptr指向len个可读字节
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn playground_compile(session: *mut Session, ptr: *const u8, len: usize) -> i32 {
+    // SAFETY: 见函数说明
+    let (session, bytes) = unsafe { (self::session(session), std::slice::from_raw_parts(ptr, len)) };
+    session.error.clear();
+    session.source = String::from_utf8_lossy(bytes).into_owned();
+    match session.interpreter.compile(&session.source) {
+        Ok(()) => {
+            session.interpreter.reset();
+            0
+        },
+        Err(e) => {
+            session.error = e.render(&session.source, None, false);
+            1
+        },
+    }
+}
+
+/// 回到程序开头(保留纸带内容)，用于重新单步执行
+///
+/// # Safety
+///
+/// session必须是有效句柄
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn playground_reset(session: *mut Session) {
+    // SAFETY: 见函数说明
+    unsafe { self::session(session) }.interpreter.reset();
+}
+
+/// 记录运行时错误
+fn fail(session: &mut Session, error: crate::diagnostic::Diagnostic) -> i32 {
+    session.error = runtime_error(&session.interpreter, &session.source, error).render(&session.source, None, false);
+    FAILED
+}
+
+/// 从头运行到结束；返回1表示完成，2表示运行时错误
+///
+/// # Safety
+///
+/// session必须是有效句柄
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn playground_run(session: *mut Session) -> i32 {
+    // SAFETY: 见函数说明
+    let session = unsafe { self::session(session) };
+    session.error.clear();
+    match session.interpreter.execute() {
+        Ok(()) => FINISHED,
+        Err(e) => fail(session, e),
+    }
+}
+
+/// 从当前位置最多执行steps条指令；返回0表示还没结束，1表示完成，2表示运行时错误
+///
+/// # Safety
+///
+/// session必须是有效句柄
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn playground_step(session: *mut Session, steps: u32) -> i32 {
+    // SAFETY: 见函数说明
+    let session = unsafe { self::session(session) };
+    session.error.clear();
+    for _ in 0..steps {
+        match session.interpreter.step() {
+            Ok(true) => {},
+            Ok(false) => return FINISHED,
+            Err(e) => return fail(session, e),
+        }
+    }
+    PAUSED
+}
+
+/// 最近一次错误信息的地址；没有错误时长度为0
+///
+/// # Safety
+///
+/// session必须是有效句柄
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn playground_error_ptr(session: *mut Session) -> *const u8 {
+    // SAFETY: 见函数说明
+    unsafe { self::session(session) }.error.as_ptr()
+}
+
+/// 最近一次错误信息的字节数
+///
+/// # Safety
+///
+/// session必须是有效句柄
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn playground_error_len(session: *mut Session) -> usize {
+    // SAFETY: 见函数说明
+    unsafe { self::session(session) }.error.len()
+}
+
+/// 当前纸带的地址，在下一次编译或执行前有效
+///
+/// # Safety
+///
+/// session必须是有效句柄
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn playground_tape_ptr(session: *mut Session) -> *const u8 {
+    // SAFETY: 见函数说明
+    unsafe { self::session(session) }.interpreter.memory().as_ptr()
+}
+
+/// 当前纸带的单元格数
+///
+/// # Safety
+///
+/// session必须是有效句柄
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn playground_tape_len(session: *mut Session) -> usize {
+    // SAFETY: 见函数说明
+    unsafe { self::session(session) }.interpreter.memory_size()
+}
+
+/// 数据指针位置
+///
+/// # Safety
+///
+/// session必须是有效句柄
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn playground_pointer(session: *mut Session) -> usize {
+    // SAFETY: 见函数说明
+    unsafe { self::session(session) }.interpreter.pointer()
+}
+
+/// 下一条要执行的指令在源码中的字节偏移，程序结束时为源码长度；编辑器据此高亮
+///
+/// # Safety
+///
+/// session必须是有效句柄
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn playground_offset(session: *mut Session) -> usize {
+    // SAFETY: 见函数说明
+    let session = unsafe { self::session(session) };
+    let interpreter = &session.interpreter;
+    interpreter.spans().get(interpreter.pc()).map_or(session.source.len(), |span| span.offset)
+}
+
+/// 释放会话
+///
+/// # Safety
+///
+/// session必须为NULL或有效句柄，释放后不能再使用
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn playground_free(session: *mut Session) {
+    if !session.is_null() {
+        // SAFETY: 句柄由playground_new中的Box::into_raw创建
+        drop(unsafe { Box::from_raw(session) });
+    }
+}
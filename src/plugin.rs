@@ -0,0 +1,169 @@
+//! 动态库插件 - 启用`plugins`特性后，从共享库加载自定义指令，接口见`include/derstand_plugin.h`
+//!
+//! 插件导出`derstand_plugin_register`，加载时调用一次，经传入的回调登记指令字符与处理函数；
+//! 之后编译的源码中这些字符不再是注释。库加载后不卸载，处理函数在进程结束前都可能被调用。
+
+use std::ffi::{CStr, c_char, c_int, c_void};
+use std::path::Path;
+
+use crate::DerstandInterpreter;
+
+/// 插件接口版本，作为第一个参数传给入口函数；不兼容的插件应返回非0
+pub const API_VERSION: u32 = 1;
+
+/// 入口函数的符号名
+const ENTRY_POINT: &str = "derstand_plugin_register";
+
+/// 处理函数看到的状态；返回后pointer必须仍在纸带内
+#[repr(C)]
+pub struct PluginMachine {
+    pub memory: *mut u8,
+    pub length: usize,
+    pub pointer: usize,
+}
+
+/// 执行一条指令：成功时返回0，其他值成为运行时错误E0117
+pub type OpcodeCallback = unsafe extern "C" fn(user: *mut c_void, machine: *mut PluginMachine) -> c_int;
+/// 登记一条指令：symbol是Unicode码点，name是UTF-8；成功时返回0
+pub type RegisterCallback =
+    unsafe extern "C" fn(registry: *mut c_void, symbol: u32, name: *const c_char, run: OpcodeCallback, user: *mut c_void) -> c_int;
+/// 插件入口
+pub type EntryPoint = unsafe extern "C" fn(api_version: u32, registry: *mut c_void, register: RegisterCallback) -> c_int;
+
+/// 入口函数登记的指令，先收集再交给解释器
+struct Registry {
+    entries: Vec<(char, String, OpcodeCallback, *mut c_void)>,
+    error: Option<String>,
+}
+
+unsafe extern "C" fn register(registry: *mut c_void, symbol: u32, name: *const c_char, run: OpcodeCallback, user: *mut c_void) -> c_int {
+    // SAFETY: registry是load()传给入口函数的指针，只在入口函数返回前使用
+    let registry = unsafe { &mut *(registry as *mut Registry) };
+    let Some(symbol) = char::from_u32(symbol) else {
+        registry.error = Some(format!("invalid instruction character U+{:04X}", symbol));
+        return -1;
+    };
+    let name = if name.is_null() {
+        symbol.to_string()
+    } else {
+        // SAFETY: 插件保证name是以NUL结尾的字符串
+        unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned()
+    };
+    if let Err(e) = crate::custom::check_symbol(symbol) {
+        registry.error = Some(e);
+        return -1;
+    }
+    registry.entries.push((symbol, name, run, user));
+    0
+}
+
+/// 加载插件并注册它提供的指令，返回指令字符
+pub fn load(interpreter: &mut DerstandInterpreter, path: &Path) -> Result<Vec<char>, String> {
+    let describe = |e: String| format!("Cannot load plugin {}: {}", path.display(), e);
+    // 不含目录的名字会被当作系统库名查找
+    let path = std::fs::canonicalize(path).map_err(|e| describe(e.to_string()))?;
+    let library = sys::open(&path).map_err(describe)?;
+    let entry = sys::symbol(library, ENTRY_POINT).map_err(describe)?;
+    // SAFETY: 按插件接口约定，这个符号是EntryPoint类型的函数
+    let entry: EntryPoint = unsafe { std::mem::transmute::<*mut c_void, EntryPoint>(entry) };
+
+    let mut registry = Registry { entries: Vec::new(), error: None };
+    // SAFETY: registry在调用期间有效，register只在调用期间使用它
+    let status = unsafe { entry(API_VERSION, &mut registry as *mut Registry as *mut c_void, register) };
+    if let Some(e) = registry.error {
+        return Err(describe(e));
+    }
+    if status != 0 {
+        return Err(describe(format!("{} returned {}", ENTRY_POINT, status)));
+    }
+
+    let mut symbols = Vec::new();
+    for (symbol, name, run, user) in registry.entries {
+        interpreter
+            .register_instruction(
+                symbol,
+                &name,
+                Box::new(move |machine| {
+                    let length = machine.memory().len();
+                    let mut state = PluginMachine { memory: machine.memory_mut().as_mut_ptr(), length, pointer: machine.pointer() };
+                    // SAFETY: 纸带在调用期间有效且长度为length；插件保证只访问其中的字节
+                    let code = unsafe { run(user, &mut state) };
+                    if code != 0 {
+                        return Err(format!("the plugin returned {}", code));
+                    }
+                    machine.set_pointer(state.pointer)
+                }),
+            )
+            .map_err(describe)?;
+        symbols.push(symbol);
+    }
+    Ok(symbols)
+}
+
+#[cfg(unix)]
+mod sys {
+    use std::ffi::{CStr, CString, c_char, c_int, c_void};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    const RTLD_NOW: c_int = 2;
+
+    #[cfg_attr(target_os = "linux", link(name = "dl"))]
+    unsafe extern "C" {
+        fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+        fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+        fn dlerror() -> *mut c_char;
+    }
+
+    fn last_error() -> String {
+        // SAFETY: dlerror返回NULL或线程局部的错误字符串
+        let message = unsafe { dlerror() };
+        if message.is_null() {
+            "unknown error".to_string()
+        } else {
+            // SAFETY: 非NULL时是以NUL结尾的字符串
+            unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned()
+        }
+    }
+
+    pub fn open(path: &Path) -> Result<*mut c_void, String> {
+        let path = CString::new(path.as_os_str().as_bytes()).map_err(|_| "path contains a NUL byte".to_string())?;
+        // SAFETY: path是有效的C字符串
+        let handle = unsafe { dlopen(path.as_ptr(), RTLD_NOW) };
+        if handle.is_null() { Err(last_error()) } else { Ok(handle) }
+    }
+
+    pub fn symbol(handle: *mut c_void, name: &str) -> Result<*mut c_void, String> {
+        let name = CString::new(name).expect("symbol names have no NUL");
+        // SAFETY: handle来自dlopen且从不关闭
+        let symbol = unsafe { dlsym(handle, name.as_ptr()) };
+        if symbol.is_null() { Err(last_error()) } else { Ok(symbol) }
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use std::ffi::{CString, c_char, c_void};
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Path;
+
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn LoadLibraryW(name: *const u16) -> *mut c_void;
+        fn GetProcAddress(module: *mut c_void, name: *const c_char) -> *mut c_void;
+    }
+
+    pub fn open(path: &Path) -> Result<*mut c_void, String> {
+        let wide: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+        // SAFETY: wide是以0结尾的UTF-16字符串
+        let handle = unsafe { LoadLibraryW(wide.as_ptr()) };
+        if handle.is_null() { Err(std::io::Error::last_os_error().to_string()) } else { Ok(handle) }
+    }
+
+    pub fn symbol(handle: *mut c_void, name: &str) -> Result<*mut c_void, String> {
+        let name = CString::new(name).expect("symbol names have no NUL");
+        // SAFETY: handle来自LoadLibraryW且从不释放
+        let symbol = unsafe { GetProcAddress(handle, name.as_ptr()) };
+        if symbol.is_null() { Err(std::io::Error::last_os_error().to_string()) } else { Ok(symbol) }
+    }
+}
@@ -0,0 +1,109 @@
+//! 编译指示 - 文件开头的`;!`行声明程序所需的执行选项，使文件无需额外的命令行参数即可正确运行
+//!
+//! ```text
+//! ;! memory=65536 eof=zero
+//! ;! overflow=trap abi=1
+//! ;! dialect=extended
+//! ```
+//!
+//! `abi=1`声明程序按纸带ABI启动与退出，见abi模块。`dialect=extended`开启扩展指令，见`Dialect`。
+//!
+//! 单元格宽度不能声明：解释器与各后端的单元格总是8位，`cells=16`与`cells=32`这类声明未实现，报告E0012；
+//! 只接受与之相符的`cells=8`。
+//!
+//! 只有文件开头(允许前置空行)连续的`;!`行是编译指示，之后的`;!`只是普通注释。
+//! 命令行选项优先于编译指示。
+
+use alloc::format;
+use alloc::string::String;
+
+use crate::abi;
+use crate::diagnostic::Diagnostic;
+use crate::{Dialect, EofBehavior, OverflowPolicy, Span};
+
+/// 编译指示行标记
+pub const PRAGMA_MARKER: &str = ";!";
+
+/// 声明扩展方言的编译指示行；生成源码的工具在用到扩展指令时写在开头
+pub const EXTENDED_DIALECT: &str = ";! dialect=extended\n";
+
+/// 纸带大小的上限(单元格数)
+pub const MAX_MEMORY_SIZE: usize = 1 << 28;
+
+/// 文件声明的执行选项；未声明的项为None
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Pragma {
+    pub memory: Option<usize>,
+    pub eof: Option<EofBehavior>,
+    pub overflow: Option<OverflowPolicy>,
+    pub abi: bool,
+    pub dialect: Option<Dialect>,
+}
+
+impl Pragma {
+    /// 设置一项；未知的项返回false，值无效时返回说明
+    fn set(&mut self, key: &str, value: &str) -> Result<bool, String> {
+        match key {
+            "memory" => {
+                let size = value.parse().ok().filter(|n| (1..=MAX_MEMORY_SIZE).contains(n));
+                self.memory = Some(size.ok_or(format!("expected a number of cells between 1 and {}", MAX_MEMORY_SIZE))?);
+            },
+            // 其他实现的文件可能写有cells=8，与本实现一致，接受但不作为可配置项
+            "cells" if value == "8" => {},
+            "cells" => return Err(format!("cell width {} is not supported; cells are always 8 bits wide", value)),
+            "eof" => self.eof = Some(EofBehavior::parse(value)?),
+            "overflow" => self.overflow = Some(OverflowPolicy::parse(value)?),
+            "dialect" => self.dialect = Some(Dialect::parse(value)?),
+            "abi" if value.parse() == Ok(abi::VERSION) => self.abi = true,
+            "abi" => return Err(format!("tape ABI version '{}' is not supported; only abi={} is", value, abi::VERSION)),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// 源码声明的方言；供不编译源码的工具(格式化、着色)使用，编译指示无效时按经典方言
+pub fn dialect(source: &str) -> Dialect {
+    parse(source).ok().and_then(|pragma| pragma.dialect).unwrap_or_default()
+}
+
+/// 解析文件开头的编译指示
+pub fn parse(source: &str) -> Result<Pragma, Diagnostic> {
+    let mut pragma = Pragma::default();
+    let mut offset = 0;
+    for (index, line) in source.split('\n').enumerate() {
+        let line_offset = offset;
+        offset += line.chars().count() + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some(rest) = line.trim_start().strip_prefix(PRAGMA_MARKER) else {
+            break;
+        };
+        // 逐个`key=value`，记录每项所在的列
+        let mut column = line.chars().count() - rest.chars().count();
+        for entry in rest.split(char::is_whitespace) {
+            let span = Span { offset: line_offset + column, line: index + 1, column: column + 1 };
+            column += entry.chars().count() + 1;
+            if entry.is_empty() {
+                continue;
+            }
+            let (key, value) = entry.split_once('=').unwrap_or((entry, ""));
+            match pragma.set(key, value) {
+                Ok(true) => {},
+                Ok(false) => {
+                    return Err(Diagnostic::error("E0012", format!("Unknown pragma '{}'", key))
+                        .with_span(span)
+                        .with_label("expected key=value")
+                        .with_hint("supported pragmas: memory=N eof=zero|unchanged|max|error overflow=wrap|saturate|trap abi=1 dialect=classic|extended"));
+                },
+                Err(reason) => {
+                    return Err(Diagnostic::error("E0012", format!("Invalid value '{}' for pragma '{}'", value, key))
+                        .with_span(span)
+                        .with_label(reason));
+                },
+            }
+        }
+    }
+    Ok(pragma)
+}
@@ -0,0 +1,445 @@
+//! 预处理 - 在编译前展开宏，并记录展开后每个字符在原始源码中的位置
+//!
+//! 以`!`开头的行是指令行：
+//!
+//! ```text
+//! !define add5 +++++             ; 无参数宏
+//! !define move(n) [-n+]          ; 参数按名称替换
+//! add5 move(>)                   ; 调用：完整的标识符匹配宏名时展开
+//! ```
+//!
+//! 调用时参数以空白分隔(`,`是输入指令，不能作为分隔符)。
+//! 宏体中出现的其他宏在调用时展开；`;`注释中的文字不会展开。
+//!
+//! `!include "lib.dr"` 在此处插入另一个文件(路径相对于当前文件)，其中定义的宏此后可用。
+//! 被包含文件中的指令在诊断中指向`!include`所在位置，并注明原始文件与行号。
+//! 读取文件需要`std`特性，no_std下`!include`报E0007。
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+
+use crate::diagnostic::Diagnostic;
+use crate::{COMMENT_MARKER, STRING_MARKER, Span};
+
+/// 指令行标记
+pub const DIRECTIVE_MARKER: char = '!';
+
+/// 宏定义
+#[derive(Debug, Clone)]
+struct Macro {
+    name: String,
+    params: Vec<String>,
+    body: String,
+    location: String, // 定义所在位置(被包含文件中的宏带文件名)
+}
+
+/// 展开后字符的来源
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Origin {
+    pub span: Span, // 原始源码中的位置；宏展开的字符指向调用处
+    pub expansion: Option<usize>, // 来自宏展开或被包含文件时的说明(Expanded::notes的下标)
+}
+
+/// 预处理结果
+#[derive(Debug, Clone, Default)]
+pub struct Expanded {
+    pub text: String,
+    pub origins: Vec<Origin>, // 与text的字符一一对应
+    pub notes: Vec<String>, // 展开说明，如"in expansion of macro 'add5' (defined at 1:1)"
+}
+
+/// 源码字符及其位置
+pub fn spanned(source: &str) -> impl Iterator<Item = (char, Span)> + '_ {
+    let (mut line, mut column) = (1, 1);
+    source.chars().enumerate().map(move |(offset, c)| {
+        let span = Span { offset, line, column };
+        if c == '\n' {
+            line += 1;
+            column = 1;
+        } else {
+            column += 1;
+        }
+        (c, span)
+    })
+}
+
+/// 源码是否含有需要预处理的指令行
+pub fn has_directives(source: &str) -> bool {
+    source.lines().any(|line| line.trim_start().starts_with(DIRECTIVE_MARKER))
+}
+
+/// 标识符(宏名、参数名)的首字符
+pub fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+/// 标识符的后续字符
+pub fn is_ident(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// 从chars[i]开始读取标识符，返回结束下标
+fn ident_end(chars: &[char], i: usize) -> usize {
+    let mut end = i;
+    while end < chars.len() && is_ident(chars[end]) {
+        end += 1;
+    }
+    end
+}
+
+/// 正在处理的被包含文件
+#[derive(Debug, Clone)]
+struct Included {
+    name: String, // 用于诊断的显示名称
+    site: Span, // 主文件中最外层`!include`的位置
+    note: usize, // 当前行的说明(Expanded::notes的下标)
+}
+
+/// chars[i]是'"'时返回字符串字面量之后的下标 - 字符串中的文字不展开
+fn string_end(chars: &[char], i: usize) -> usize {
+    let mut end = i + 1;
+    while end < chars.len() && chars[end] != STRING_MARKER && chars[end] != '\n' {
+        end += if chars[end] == '\\' { 2 } else { 1 };
+    }
+    (end + 1).min(chars.len())
+}
+
+/// chars[i]开始的一个词：标识符、字符串字面量或单个字符
+fn word_end(chars: &[char], i: usize) -> usize {
+    match chars[i] {
+        c if is_ident_start(c) => ident_end(chars, i),
+        STRING_MARKER => string_end(chars, i),
+        _ => i + 1,
+    }
+}
+
+struct Preprocessor {
+    macros: Vec<Macro>,
+    out: Expanded,
+    #[cfg(feature = "std")]
+    files: Vec<PathBuf>, // 包含链，用于检测循环包含
+    #[cfg(feature = "std")]
+    dir: PathBuf, // 当前文件所在目录
+    #[cfg(feature = "std")]
+    allow_include: bool,
+    included: Option<Included>,
+}
+
+impl Preprocessor {
+    fn lookup(&self, name: &str) -> Option<usize> {
+        // 后定义的同名宏覆盖之前的定义
+        self.macros.iter().rposition(|m| m.name == name)
+    }
+
+    /// 当前文件中位置的显示形式
+    fn location(&self, span: Span) -> String {
+        match &self.included {
+            Some(included) => format!("{}:{}:{}", included.name, span.line, span.column),
+            None => format!("{}:{}", span.line, span.column),
+        }
+    }
+
+    /// 解析一行指令
+    fn directive(&mut self, line: &[(char, Span)]) -> Result<(), Diagnostic> {
+        let span = line[0].1;
+        // 去掉行注释(字符串中的';'不算)
+        let chars: Vec<char> = line.iter().map(|&(c, _)| c).collect();
+        let mut end = 0;
+        while end < chars.len() && chars[end] != COMMENT_MARKER {
+            end = word_end(&chars, end);
+        }
+        let text: String = chars[..end.min(chars.len())].iter().collect();
+        let text = text.as_str();
+        let malformed = |message: String| {
+            Diagnostic::error("E0003", message)
+                .with_span(span)
+                .with_label("malformed directive")
+                .with_hint("directives look like '!define name body', '!define name(a b) body' or '!include \"file\"'")
+        };
+
+        let rest = text.trim_start().trim_start_matches(DIRECTIVE_MARKER);
+        let directive: String = rest.chars().take_while(|&c| is_ident(c)).collect();
+        let rest = rest[directive.len()..].trim_start();
+        match directive.as_str() {
+            "define" => {},
+            "include" => {
+                let path = rest.trim().trim_matches('"');
+                if path.is_empty() {
+                    return Err(malformed("Include directive without a file name".to_string()));
+                }
+                return self.include(path, span);
+            },
+            _ => return Err(malformed(format!("Unknown directive '{}{}'", DIRECTIVE_MARKER, directive))),
+        }
+        let name: String = rest.chars().take_while(|&c| is_ident(c)).collect();
+        if !name.starts_with(is_ident_start) {
+            return Err(malformed("Macro definition without a name".to_string()));
+        }
+        let mut rest = &rest[name.len()..];
+        let mut params = Vec::new();
+        if let Some(list) = rest.strip_prefix('(') {
+            let Some(close) = list.find(')') else {
+                return Err(malformed(format!("Unclosed parameter list in definition of '{}'", name)));
+            };
+            for param in list[..close].split(|c: char| c == ',' || c.is_whitespace()).filter(|p| !p.is_empty()) {
+                if !param.starts_with(is_ident_start) || !param.chars().all(is_ident) {
+                    return Err(malformed(format!("Invalid parameter name '{}' in definition of '{}'", param, name)));
+                }
+                params.push(param.to_string());
+            }
+            rest = &list[close + 1..];
+        }
+        let location = self.location(span);
+        self.macros.push(Macro { name, params, body: rest.trim().to_string(), location });
+        Ok(())
+    }
+
+    /// 没有文件系统时`!include`总是失败
+    #[cfg(not(feature = "std"))]
+    fn include(&mut self, path: &str, span: Span) -> Result<(), Diagnostic> {
+        Err(Diagnostic::error("E0007", format!("Cannot include '{}': file access requires the std feature", path))
+            .with_span(span)
+            .with_label("included here"))
+    }
+
+    /// 处理`!include`：读取并展开另一个文件
+    #[cfg(feature = "std")]
+    fn include(&mut self, path: &str, span: Span) -> Result<(), Diagnostic> {
+        let resolved = self.dir.join(path);
+        let name = resolved.display().to_string();
+        let site = self.included.as_ref().map_or(span, |i| i.site);
+        let error = |code: &'static str, message: String| {
+            Diagnostic::error(code, message).with_span(span).with_label("included here")
+        };
+        if !self.allow_include {
+            return Err(error("E0007", format!("Cannot include '{}': file access is disabled", path))
+                .with_hint("this host runs untrusted sources; inline the included file instead"));
+        }
+
+        let canonical = resolved.canonicalize().unwrap_or_else(|_| resolved.clone());
+        if let Some(start) = self.files.iter().position(|f| *f == canonical) {
+            let chain: Vec<String> = self.files[start..].iter().chain([&canonical]).map(|f| f.display().to_string()).collect();
+            return Err(error("E0006", format!("Include cycle: {}", chain.join(" -> ")))
+                .with_hint("remove one of the !include directives in the cycle"));
+        }
+        let source = std::fs::read_to_string(&resolved).map_err(|e| {
+            error("E0007", format!("Cannot include '{}': {}", name, e))
+                .with_hint("include paths are resolved relative to the including file")
+        })?;
+
+        // 在被包含文件的上下文中展开，之后恢复
+        let dir = resolved.parent().map(Path::to_path_buf).unwrap_or_default();
+        let outer_dir = std::mem::replace(&mut self.dir, dir);
+        let outer = self.included.replace(Included { name: name.clone(), site, note: 0 });
+        self.files.push(canonical);
+        let result = self.source(&source);
+        self.files.pop();
+        self.dir = outer_dir;
+        self.included = outer;
+
+        // 被包含文件中的错误改为指向本文件的!include，并注明原始位置
+        result.map_err(|mut e| {
+            if let Some(inner) = e.span {
+                e.notes.insert(0, format!("in {}:{}:{}", name, inner.line, inner.column));
+            }
+            e.span = Some(span);
+            e.label = Some("error in this included file".to_string());
+            e
+        })
+    }
+
+    /// 展开宏调用，返回展开后的文本
+    fn expand_call(&self, index: usize, args: Vec<String>, active: &mut Vec<usize>, site: Span) -> Result<String, Diagnostic> {
+        let definition = &self.macros[index];
+        let defined_at = format!("'{}' is defined at {}", definition.name, definition.location);
+        if active.contains(&index) {
+            return Err(Diagnostic::error("E0005", format!("Recursive expansion of macro '{}'", definition.name))
+                .with_span(site)
+                .with_label("expanded here")
+                .with_note(defined_at)
+                .with_hint("a macro cannot invoke itself, directly or through other macros"));
+        }
+        if args.len() != definition.params.len() {
+            return Err(Diagnostic::error("E0004", format!(
+                "Macro '{}' expects {} argument(s), found {}",
+                definition.name, definition.params.len(), args.len()
+            ))
+            .with_span(site)
+            .with_label("in this invocation")
+            .with_note(defined_at)
+            .with_hint("pass arguments in parentheses, separated by whitespace, e.g. 'name(>> +)'"));
+        }
+
+        // 先替换参数，再展开结果中的其他宏
+        let chars: Vec<char> = definition.body.chars().collect();
+        let mut substituted = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let end = word_end(&chars, i);
+            let word: String = chars[i..end].iter().collect();
+            match definition.params.iter().position(|p| *p == word) {
+                Some(p) => substituted.push_str(&args[p]),
+                None => substituted.push_str(&word),
+            }
+            i = end;
+        }
+
+        active.push(index);
+        let chars: Vec<char> = substituted.chars().collect();
+        let mut text = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let end = word_end(&chars, i);
+            let word: String = chars[i..end].iter().collect();
+            match self.lookup(&word) {
+                Some(inner) => {
+                    let (args, next) = self.arguments(&chars, end, inner, site)?;
+                    text.push_str(&self.expand_call(inner, args, active, site)?);
+                    i = next;
+                },
+                None => {
+                    text.push_str(&word);
+                    i = end;
+                },
+            }
+        }
+        active.pop();
+        Ok(text)
+    }
+
+    /// 读取调用参数 `(a b)`，返回参数与之后的下标；无参数的宏不读取括号
+    fn arguments(&self, chars: &[char], i: usize, index: usize, site: Span) -> Result<(Vec<String>, usize), Diagnostic> {
+        if self.macros[index].params.is_empty() || chars.get(i) != Some(&'(') {
+            return Ok((Vec::new(), i));
+        }
+        // 参数中可以含有成对的'('')'(辅助栈指令)
+        let mut depth = 0usize;
+        let close = chars[i..].iter().position(|&c| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {},
+            }
+            depth == 0
+        });
+        let Some(close) = close.map(|p| i + p) else {
+            return Err(Diagnostic::error("E0004", format!("Unclosed argument list for macro '{}'", self.macros[index].name))
+                .with_span(site)
+                .with_label("in this invocation")
+                .with_hint("close the argument list with ')' on the same line"));
+        };
+        let list: String = chars[i + 1..close].iter().collect();
+        Ok((list.split_whitespace().map(str::to_string).collect(), close + 1))
+    }
+
+    /// 追加展开后的字符；被包含文件中的字符指向主文件中的!include
+    fn emit(&mut self, c: char, span: Span, expansion: Option<usize>) {
+        let origin = match &self.included {
+            Some(included) => {
+                Origin { span: included.site, expansion: Some(expansion.unwrap_or(included.note)) }
+            },
+            None => Origin { span, expansion },
+        };
+        self.out.text.push(c);
+        self.out.origins.push(origin);
+    }
+
+    fn note(&mut self, note: String) -> usize {
+        self.out.notes.push(note);
+        self.out.notes.len() - 1
+    }
+
+    /// 处理普通源码行；行注释原样保留
+    fn line(&mut self, line: &[(char, Span)]) -> Result<(), Diagnostic> {
+        let chars: Vec<char> = line.iter().map(|&(c, _)| c).collect();
+        let mut i = 0;
+        while i < line.len() {
+            let (c, span) = line[i];
+            if c == COMMENT_MARKER {
+                for &(c, span) in &line[i..] {
+                    self.emit(c, span, None);
+                }
+                return Ok(());
+            }
+            let end = word_end(&chars, i);
+            let word: String = chars[i..end].iter().collect();
+            let Some(index) = self.lookup(&word) else {
+                // 非宏名的文字原样保留(作为注释)
+                for &(c, span) in &line[i..end] {
+                    self.emit(c, span, None);
+                }
+                i = end;
+                continue;
+            };
+            let (args, next) = self.arguments(&chars, end, index, span)?;
+            let text = self.expand_call(index, args, &mut Vec::new(), span)?;
+            let definition = &self.macros[index];
+            let mut note = format!("in expansion of macro '{}' (defined at {})", definition.name, definition.location);
+            if self.included.is_some() {
+                note = format!("{}, used at {}", note, self.location(span));
+            }
+            let expansion = self.note(note);
+            for c in text.chars() {
+                self.emit(c, span, Some(expansion));
+            }
+            i = next;
+        }
+        Ok(())
+    }
+
+    /// 逐行处理一个文件
+    fn source(&mut self, source: &str) -> Result<(), Diagnostic> {
+        let chars: Vec<(char, Span)> = spanned(source).collect();
+        for line in chars.split_inclusive(|&(c, _)| c == '\n') {
+            let (body, newline) = match line.last() {
+                Some(&('\n', span)) => (&line[..line.len() - 1], Some(span)),
+                _ => (line, None),
+            };
+            if let Some(number) = line.first().map(|(_, span)| span.line)
+                && let Some(name) = self.included.as_ref().map(|i| i.name.clone())
+            {
+                // 被包含文件的每一行对应一条说明
+                let note = self.note(format!("in {}:{}", name, number));
+                if let Some(included) = &mut self.included {
+                    included.note = note;
+                }
+            }
+            let directive = body.iter().find(|(c, _)| !c.is_whitespace()).is_some_and(|&(c, _)| c == DIRECTIVE_MARKER);
+            if directive {
+                let start = body.iter().position(|&(c, _)| c == DIRECTIVE_MARKER).unwrap_or(0);
+                self.directive(&body[start..])?;
+            } else {
+                self.line(body)?;
+            }
+            // 保留换行，使行号不变
+            if let Some(span) = newline {
+                self.emit('\n', span, None);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 展开源码中的宏与包含文件；path为源码文件路径，用于解析相对的包含路径
+pub fn expand(
+    source: &str,
+    #[cfg(feature = "std")] path: Option<&Path>,
+    #[cfg(feature = "std")] allow_include: bool,
+) -> Result<Expanded, Diagnostic> {
+    let mut preprocessor = Preprocessor {
+        macros: Vec::new(),
+        out: Expanded::default(),
+        #[cfg(feature = "std")]
+        files: path.map(|p| p.canonicalize().unwrap_or_else(|_| p.to_path_buf())).into_iter().collect(),
+        #[cfg(feature = "std")]
+        dir: path.and_then(Path::parent).map(Path::to_path_buf).unwrap_or_default(),
+        #[cfg(feature = "std")]
+        allow_include,
+        included: None,
+    };
+    preprocessor.source(source)?;
+    Ok(preprocessor.out)
+}
@@ -0,0 +1,201 @@
+//! 已编译的程序 - 从解释器取出、拼接并装回，供代码生成器用编译好的片段组装大程序
+//!
+//! `Program`保存指令、源码位置与每条括号指令的配对下标。拼接时后一段的配对下标加上前一段的长度，
+//! `^{n}`加上前一段定义的过程数，因此各段的循环与过程调用保持原意。
+//!
+//! ```text
+//! use derstand::program::{Program, link};
+//!
+//! let greet = Program::compile(">\"hi\"<[<]>[.>]")?;
+//! let newline = Program::compile("[-]++++++++++.")?;
+//! let program = link(&[greet.repeat(2), newline]);
+//! let mut interpreter = DerstandInterpreter::new();
+//! interpreter.load_program(&program);
+//! interpreter.execute()?;
+//! ```
+//!
+//! `Display`与`Debug`输出结构化的清单：每行一条(已融合的)指令，循环与过程体缩进，括号与`^{n}`注明跳转目标。
+//!
+//! 限制：`^`按运行时的单元格值调用过程，不随拼接改编号；`;!`编译指示只取第一段的；
+//! 源码位置仍指向各段自己的源码；自定义指令按注册顺序编号，各段应来自注册相同指令的解释器。
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::diagnostic::Diagnostic;
+use crate::pragma::Pragma;
+use crate::{DerstandInterpreter, Instruction, MEMORY_SIZE, Span};
+
+/// 见模块说明
+#[derive(Clone, Default, PartialEq)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+    spans: Vec<Span>,
+    targets: Vec<usize>, // 括号指令的配对下标；其他指令为0
+    pragma: Pragma,
+}
+
+impl Program {
+    /// 用默认配置的解释器编译source
+    pub fn compile(source: &str) -> Result<Program, Diagnostic> {
+        let mut interpreter = DerstandInterpreter::new();
+        interpreter.compile(source)?;
+        Ok(interpreter.program())
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    /// 括号或过程边界指令的配对下标
+    pub fn jump_target(&self, pc: usize) -> Option<usize> {
+        match self.instructions.get(pc)? {
+            Instruction::JumpIfZero | Instruction::JumpIfNotZero | Instruction::ProcStart | Instruction::ProcEnd => Some(self.targets[pc]),
+            _ => None,
+        }
+    }
+
+    /// 定义的过程数
+    pub fn procedure_count(&self) -> usize {
+        self.instructions.iter().filter(|&&i| i == Instruction::ProcStart).count()
+    }
+
+    /// 在self之后接上other
+    pub fn concat(&self, other: &Program) -> Program {
+        let mut program = self.clone();
+        program.append(other);
+        program
+    }
+
+    /// self重复n次；n为0时得到空程序
+    pub fn repeat(&self, n: usize) -> Program {
+        let mut program = Program { pragma: self.pragma, ..Program::default() };
+        for _ in 0..n {
+            program.append(self);
+        }
+        program
+    }
+
+    /// 追加other，配对下标与过程编号按已有部分重新计算
+    fn append(&mut self, other: &Program) {
+        let (offset, procedures) = (self.len(), self.procedure_count() as u32);
+        for (pc, &instruction) in other.instructions.iter().enumerate() {
+            let (instruction, target) = match instruction {
+                Instruction::Call(n) => (Instruction::Call(n + procedures), 0),
+                _ => (instruction, other.jump_target(pc).map_or(0, |target| target + offset)),
+            };
+            self.instructions.push(instruction);
+            self.spans.push(other.spans[pc]);
+            self.targets.push(target);
+        }
+    }
+}
+
+/// 清单示例：
+///
+/// ```text
+/// Program: 6 instruction(s), 0 procedure(s)
+/// 0  +{3}
+/// 1  [  -> 4
+/// 2    .
+/// 3    -
+/// 4  ]  -> 1
+/// 5  >
+/// ```
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Program: {} instruction(s), {} procedure(s)", self.len(), self.procedure_count())?;
+        let width = self.len().saturating_sub(1).checked_ilog10().map_or(1, |digits| digits as usize + 1);
+        let procedures: Vec<usize> = (0..self.len()).filter(|&pc| self.instructions[pc] == Instruction::ProcStart).collect();
+        let mut depth = 0usize;
+        for (pc, &instruction) in self.instructions.iter().enumerate() {
+            // 闭括号与它的开括号对齐
+            if matches!(instruction, Instruction::JumpIfNotZero | Instruction::ProcEnd) {
+                depth = depth.saturating_sub(1);
+            }
+            write!(f, "\n{:>width$}  {}{}", pc, "  ".repeat(depth), instruction, width = width)?;
+            let target = match instruction {
+                Instruction::Call(n) => procedures.get(n as usize).copied(),
+                _ => self.jump_target(pc),
+            };
+            if let Some(target) = target {
+                write!(f, "  -> {}", target)?;
+            }
+            if matches!(instruction, Instruction::JumpIfZero | Instruction::ProcStart) {
+                depth += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// 依次拼接所有片段
+pub fn link(programs: &[Program]) -> Program {
+    let mut linked = Program { pragma: programs.first().map(|p| p.pragma).unwrap_or_default(), ..Program::default() };
+    for program in programs {
+        linked.append(program);
+    }
+    linked
+}
+
+impl DerstandInterpreter {
+    /// 当前编译结果的副本
+    pub fn program(&self) -> Program {
+        let targets = (0..self.instructions.len()).map(|pc| self.jump_target(pc).unwrap_or(0)).collect();
+        Program { instructions: self.instructions.clone(), spans: self.spans.clone(), targets, pragma: self.pragma }
+    }
+
+    /// 装入已编译的程序，取代当前的编译结果；之后用`execute`运行
+    pub fn load_program(&mut self, program: &Program) {
+        let count = program.len();
+        let mut to_close = vec![0; count];
+        let mut to_open = vec![0; count];
+        let mut procedures = Vec::new();
+        for (pc, &instruction) in program.instructions.iter().enumerate() {
+            match instruction {
+                Instruction::JumpIfZero => to_close[pc] = program.targets[pc],
+                Instruction::ProcStart => {
+                    to_close[pc] = program.targets[pc];
+                    procedures.push(pc);
+                },
+                Instruction::JumpIfNotZero | Instruction::ProcEnd => to_open[pc] = program.targets[pc],
+                _ => {},
+            }
+        }
+
+        self.pragma = program.pragma;
+        let size = self.memory_override.or(self.pragma.memory).unwrap_or(MEMORY_SIZE);
+        if self.memory.len() != size {
+            self.memory = vec![0; size];
+            self.pointer = self.pointer.min(size - 1);
+            self.other_tape = None;
+            self.active_tape = 0;
+        }
+        self.instructions = program.instructions.clone();
+        self.spans = program.spans.clone();
+        self.expansions = vec![None; count];
+        self.expansion_notes.clear();
+        self.jump_table.to_close = to_close;
+        self.jump_table.to_open = to_open;
+        self.procedures = procedures;
+    }
+}
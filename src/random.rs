@@ -0,0 +1,40 @@
+//! 伪随机数 - `*`指令使用的SplitMix64生成器
+//!
+//! 算法固定，相同种子在任何平台上产生相同的序列；未指定种子时从系统时间取种。
+
+use crate::clock;
+
+/// SplitMix64生成器 - 任何种子(包括0)都可用
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    /// 每次运行都不同的种子
+    pub fn entropy_seed() -> u64 {
+        clock::entropy()
+    }
+
+    /// 生成器内部状态，用于检查点与状态JSON
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// 下一个随机字节(取高位，统计质量更好)
+    pub fn next_byte(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+}
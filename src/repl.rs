@@ -0,0 +1,276 @@
+//! 远程REPL - `derstand serve`的`/repl`端点，经WebSocket提供与交互式模式相同的会话
+//!
+//! 每个连接有独立的解释器，纸带在各行之间保留。客户端发来的文本或二进制消息按字节流处理：
+//! 每行是一段代码，运行中的`,`读取其后的字节，连接关闭即输入结束。程序输出与提示以文本消息发回，
+//! 按行(或在等待输入前)发送，多字节字符不会被拆开。
+//!
+//! 会话与`POST /run`一样受限：`!include`不读取文件，每行的运行受服务端的步数、时间(等待输入的时间也计入)、
+//! 纸带与输出上限约束，超限只结束这一行的运行。
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::BufReader;
+use std::net::{Shutdown, TcpStream};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cancel::CancelToken;
+use crate::host::{Input, Output};
+use crate::metrics::Metrics;
+use crate::serve::{Limits, run_limited, tape_size};
+use crate::websocket::{self, BINARY, CLOSE, CONTINUATION, MESSAGE_TOO_BIG, NORMAL_CLOSURE, PING, PONG, PROTOCOL_ERROR, TEXT};
+use crate::{DerstandInterpreter, hexdump};
+
+/// 连接空闲超过这个时间即关闭
+const IDLE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// 等待输入时检查取消的间隔
+const INPUT_POLL: Duration = Duration::from_millis(50);
+
+/// 输出缓冲超过这个大小时即使没有换行也发送
+const FLUSH_SIZE: usize = 4096;
+
+/// 发往客户端的写端，读取线程回复心跳时也要用
+type Writer = Arc<Mutex<TcpStream>>;
+
+/// 客户端发来的字节；读取线程经通道送来
+struct Inbox {
+    receiver: Receiver<Vec<u8>>,
+    pending: VecDeque<u8>,
+    closed: bool,
+}
+
+impl Inbox {
+    /// 下一个字节；连接关闭时为None，token被取消时为Err
+    fn next_byte(&mut self, token: Option<&CancelToken>) -> Result<Option<u8>, String> {
+        loop {
+            if let Some(byte) = self.pending.pop_front() {
+                return Ok(Some(byte));
+            }
+            if self.closed {
+                return Ok(None);
+            }
+            if token.is_some_and(CancelToken::is_cancelled) {
+                return Err("time limit exceeded while waiting for input".to_string());
+            }
+            match self.receiver.recv_timeout(INPUT_POLL) {
+                Ok(bytes) => self.pending.extend(bytes),
+                Err(RecvTimeoutError::Timeout) => {},
+                Err(RecvTimeoutError::Disconnected) => self.closed = true,
+            }
+        }
+    }
+
+    /// 读取一行(去掉行尾的"\r\n"或"\n")；连接关闭且没有剩余字节时为None
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = Vec::new();
+        loop {
+            match self.next_byte(None) {
+                Ok(Some(b'\n')) => break,
+                Ok(Some(byte)) => line.push(byte),
+                _ if line.is_empty() => return None,
+                _ => break,
+            }
+        }
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        Some(String::from_utf8_lossy(&line).into_owned())
+    }
+}
+
+/// 发往客户端的文本，按UTF-8字符边界缓冲
+struct Terminal {
+    writer: Writer,
+    pending: Vec<u8>,
+    written: usize, // 本次运行的输出字节数
+    limit: usize,
+    overflowed: bool,
+}
+
+impl Terminal {
+    fn send(&mut self, text: &str) {
+        if !text.is_empty() {
+            let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+            let _ = websocket::write_frame(&mut *writer, TEXT, text.as_bytes());
+        }
+    }
+
+    /// 发送缓冲的输出；除非程序已停止，末尾不完整的多字节字符留到下次
+    fn flush(&mut self, stopped: bool) {
+        let keep = match std::str::from_utf8(&self.pending) {
+            Err(e) if e.error_len().is_none() && !stopped => self.pending.len() - e.valid_up_to(),
+            _ => 0,
+        };
+        let ready: Vec<u8> = self.pending.drain(..self.pending.len() - keep).collect();
+        self.send(&String::from_utf8_lossy(&ready));
+    }
+
+    /// 发送提示等服务端文本，先送出程序的输出
+    fn print(&mut self, text: &str) {
+        self.flush(true);
+        self.send(text);
+    }
+}
+
+/// 解释器的输出目标
+struct TerminalOutput(Rc<RefCell<Terminal>>);
+
+impl Output for TerminalOutput {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let mut terminal = self.0.borrow_mut();
+        let room = terminal.limit.saturating_sub(terminal.written);
+        let bytes = if bytes.len() > room {
+            terminal.overflowed = true;
+            &bytes[..room]
+        } else {
+            bytes
+        };
+        terminal.written += bytes.len();
+        terminal.pending.extend_from_slice(bytes);
+        if bytes.contains(&b'\n') || terminal.pending.len() >= FLUSH_SIZE {
+            terminal.flush(false);
+        }
+        if terminal.overflowed { Err("output limit exceeded".to_string()) } else { Ok(()) }
+    }
+}
+
+/// 解释器的输入来源；等待前先把已有的输出发给客户端
+struct TerminalInput {
+    inbox: Rc<RefCell<Inbox>>,
+    terminal: Rc<RefCell<Terminal>>,
+    token: Rc<RefCell<Option<CancelToken>>>, // 当前运行的计时令牌
+}
+
+impl Input for TerminalInput {
+    fn read_byte(&mut self) -> Result<Option<u8>, String> {
+        self.terminal.borrow_mut().flush(false);
+        self.inbox.borrow_mut().next_byte(self.token.borrow().as_ref())
+    }
+}
+
+/// 读取客户端的帧直到连接关闭：数据交给inbox，回复心跳与关闭
+fn read_frames(mut reader: BufReader<TcpStream>, writer: Writer, inbox: mpsc::Sender<Vec<u8>>) {
+    let close = |code: u16, reason: &str| {
+        let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = websocket::write_close(&mut *writer, code, reason);
+        let _ = writer.shutdown(Shutdown::Both);
+    };
+    let mut fragmented = false; // 正在接收分片消息
+    loop {
+        let frame = match websocket::read_frame(&mut reader) {
+            Ok(frame) => frame,
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => return close(PROTOCOL_ERROR, "malformed frame"),
+            Err(e) if e.kind() == std::io::ErrorKind::Other => return close(MESSAGE_TOO_BIG, "frame too large"),
+            Err(_) => return close(NORMAL_CLOSURE, "idle timeout"),
+        };
+        match frame.opcode {
+            TEXT | BINARY | CONTINUATION => {
+                if fragmented != (frame.opcode == CONTINUATION) {
+                    return close(PROTOCOL_ERROR, "unexpected continuation frame");
+                }
+                fragmented = !frame.fin;
+                if inbox.send(frame.payload).is_err() {
+                    return;
+                }
+            },
+            PING => {
+                let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+                let _ = websocket::write_frame(&mut *writer, PONG, &frame.payload);
+            },
+            PONG => {},
+            CLOSE => return close(NORMAL_CLOSURE, ""),
+            _ => return close(PROTOCOL_ERROR, "unknown opcode"),
+        }
+    }
+}
+
+/// 在已完成握手的连接上运行REPL会话，直到客户端退出或断开
+pub(crate) fn session(reader: BufReader<TcpStream>, writer: TcpStream, limits: Limits, metrics: &Metrics) {
+    let _ = reader.get_ref().set_read_timeout(Some(IDLE_TIMEOUT));
+    let writer = Arc::new(Mutex::new(writer));
+    let (sender, receiver) = mpsc::channel();
+    let frames = {
+        let writer = Arc::clone(&writer);
+        thread::spawn(move || read_frames(reader, writer, sender))
+    };
+
+    let inbox = Rc::new(RefCell::new(Inbox { receiver, pending: VecDeque::new(), closed: false }));
+    let terminal = Rc::new(RefCell::new(Terminal {
+        writer: Arc::clone(&writer),
+        pending: Vec::new(),
+        written: 0,
+        limit: limits.output,
+        overflowed: false,
+    }));
+    let token = Rc::new(RefCell::new(None));
+    let mut interpreter = DerstandInterpreter::new();
+    interpreter.set_allow_include(false);
+    interpreter.set_input(Box::new(TerminalInput { inbox: Rc::clone(&inbox), terminal: Rc::clone(&terminal), token: Rc::clone(&token) }));
+    interpreter.set_output(Box::new(TerminalOutput(Rc::clone(&terminal))));
+
+    terminal.borrow_mut().print(&format!(
+        "Derstand Interpreter v{} (remote)\nType ':mem [start [len]]' to view memory, 'quit' to exit.\n",
+        env!("CARGO_PKG_VERSION")
+    ));
+    loop {
+        terminal.borrow_mut().print("\n> ");
+        let Some(line) = inbox.borrow_mut().read_line() else {
+            break; // 连接已关闭
+        };
+        let line = line.trim();
+        if line == "quit" || line == "exit" {
+            break;
+        }
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(args) = line.strip_prefix(":mem") {
+            // 查看内存 - 显示上次运行后的纸带
+            let text = match hexdump::parse_range(args.split_whitespace(), interpreter.memory_size()) {
+                Ok((start, len)) => hexdump::hexdump(interpreter.memory(), start, len, interpreter.pointer()),
+                Err(e) => e,
+            };
+            terminal.borrow_mut().print(&format!("{}\n", text));
+            continue;
+        }
+
+        metrics.run_started();
+        let started = Instant::now();
+        let compiled = tape_size(line, limits).and_then(|memory| {
+            interpreter.set_memory_size(memory);
+            interpreter.compile(line).map_err(|e| ("compile_error", e))
+        });
+        if let Err((status, e)) = compiled {
+            metrics.run_completed(status, 0, started.elapsed());
+            terminal.borrow_mut().print(&format!("{}\n", e.render(line, None, false)));
+            continue;
+        }
+        {
+            let mut terminal = terminal.borrow_mut();
+            terminal.written = 0;
+            terminal.overflowed = false;
+        }
+        let run_token = CancelToken::new();
+        run_token.cancel_after(Duration::from_millis(limits.time_ms));
+        *token.borrow_mut() = Some(run_token.clone());
+        let (steps, stopped) = run_limited(&mut interpreter, line, limits, &run_token, &|| terminal.borrow().overflowed);
+        *token.borrow_mut() = None;
+        metrics.run_completed(stopped.as_ref().map_or("ok", |(status, _)| status), steps, started.elapsed());
+        let report = match stopped {
+            None => format!("\nExecution time: {:.3} ms ({} steps)", started.elapsed().as_secs_f64() * 1000.0, steps),
+            Some((_, e)) => format!("\n{}", e.render(line, None, false)),
+        };
+        terminal.borrow_mut().print(&report);
+    }
+
+    {
+        let mut writer = writer.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = websocket::write_close(&mut *writer, NORMAL_CLOSURE, "");
+        let _ = writer.shutdown(Shutdown::Both);
+    }
+    let _ = frames.join();
+}
@@ -0,0 +1,341 @@
+//! JSON-RPC服务 - `derstand --rpc`，经标准输入输出供编辑器以外的工具驱动解释器
+//!
+//! 消息格式与语言服务器相同(`Content-Length`头加JSON正文)。会话在多次调用之间保留，
+//! 以`compile`返回的编号指定：
+//!
+//! ```text
+//! compile       {source, session?, path?, memory?, overflow?, eof?, strictBounds?, seed?}
+//! run           {session, input?, maxSteps?, timeoutMs?}   运行到结束、断点或上限
+//! step          {session, count?, input?}                   执行count条指令(默认1)
+//! setBreakpoint {session, line, column?, enabled?}          断点设在该位置起的第一条指令
+//! readMemory    {session, start?, length?}
+//! reset         {session}                                   回到程序开头，保留纸带
+//! close         {session}
+//! ```
+//!
+//! 编译错误与运行时错误不是RPC错误，而是以`status`与`diagnostics`返回；
+//! `run`与`step`的结果带上本次调用产生的输出。程序只读到请求中给出的输入，读完后按EOF处理。
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::cancel::CancelToken;
+use crate::diagnostic::Diagnostic;
+use crate::json::Json;
+use crate::lint::{self, LintOptions};
+use crate::lsp::{INVALID_PARAMS, METHOD_NOT_FOUND, read_message, write_message};
+use crate::{DerstandInterpreter, EofBehavior, OverflowPolicy, runtime_error};
+
+/// 一次readMemory最多返回的单元格数
+const MAX_READ: usize = 1 << 16;
+
+/// 收集会话输出的缓冲区(解释器持有输出目标的所有权)
+struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl crate::host::Output for SharedOutput {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// 一个持久的解释器会话
+struct Session {
+    interpreter: DerstandInterpreter,
+    source: String,
+    path: Option<String>,
+    output: Rc<RefCell<Vec<u8>>>,
+    breakpoints: BTreeSet<usize>, // 指令下标
+}
+
+/// 参数错误
+type Failure = (i64, String);
+
+fn invalid(message: impl Into<String>) -> Failure {
+    (INVALID_PARAMS, message.into())
+}
+
+/// 可选的非负整数参数
+fn count(params: &Json, name: &str) -> Result<Option<u64>, Failure> {
+    match params.get(name) {
+        None | Some(Json::Null) => Ok(None),
+        Some(value) => value.as_i64().filter(|&n| n >= 0).map(|n| Some(n as u64)).ok_or_else(|| invalid(format!("'{}' must be a non-negative integer", name))),
+    }
+}
+
+/// 可选的字符串参数
+fn string<'a>(params: &'a Json, name: &str) -> Result<Option<&'a str>, Failure> {
+    match params.get(name) {
+        None | Some(Json::Null) => Ok(None),
+        Some(value) => value.as_str().map(Some).ok_or_else(|| invalid(format!("'{}' must be a string", name))),
+    }
+}
+
+/// 运行停止的原因
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stop {
+    Finished,
+    Paused, // 达到maxSteps或count
+    Breakpoint,
+    Timeout,
+}
+
+impl Stop {
+    fn as_str(self) -> &'static str {
+        match self {
+            Stop::Finished => "finished",
+            Stop::Paused => "paused",
+            Stop::Breakpoint => "breakpoint",
+            Stop::Timeout => "timeout",
+        }
+    }
+}
+
+impl Session {
+    fn diagnostics(&self, diagnostics: &[Diagnostic]) -> Json {
+        Json::Array(diagnostics.iter().map(|d| d.to_json(self.path.as_deref())).collect())
+    }
+
+    /// 下一条待执行指令的位置，程序结束时为null
+    fn location(&self) -> Json {
+        let interpreter = &self.interpreter;
+        interpreter.spans().get(interpreter.pc()).map_or(Json::Null, |span| {
+            Json::object([("line", span.line.into()), ("column", span.column.into()), ("offset", span.offset.into())])
+        })
+    }
+
+    /// 执行最多limit条指令；在断点处(第一条指令除外)停下
+    fn execute(&mut self, limit: Option<u64>, breakpoints: bool, timeout: Option<u64>) -> Json {
+        let token = timeout.map(|ms| {
+            let token = CancelToken::new();
+            token.cancel_after(Duration::from_millis(ms));
+            token
+        });
+        self.interpreter.set_cancel_token(token.clone());
+        let mut steps = 0u64;
+        let result = loop {
+            if limit == Some(steps) {
+                break Ok(Stop::Paused);
+            }
+            if steps > 0 && breakpoints && self.breakpoints.contains(&self.interpreter.pc()) {
+                break Ok(Stop::Breakpoint);
+            }
+            if token.as_ref().is_some_and(CancelToken::is_cancelled) {
+                break Ok(Stop::Timeout);
+            }
+            match self.interpreter.step() {
+                Ok(true) => steps += 1,
+                Ok(false) => break Ok(Stop::Finished),
+                // 睡眠中途超时
+                Err(e) if e.code == "E0114" => break Ok(Stop::Timeout),
+                Err(e) => break Err(e),
+            }
+        };
+        self.interpreter.set_cancel_token(None);
+
+        let output = std::mem::take(&mut *self.output.borrow_mut());
+        let (status, diagnostics) = match result {
+            Ok(stop) => (stop.as_str(), Vec::new()),
+            Err(e) => ("runtime_error", vec![runtime_error(&self.interpreter, &self.source, e)]),
+        };
+        Json::object([
+            ("status", status.into()),
+            ("output", String::from_utf8_lossy(&output).into_owned().into()),
+            ("steps", steps.into()),
+            ("pc", self.interpreter.pc().into()),
+            ("pointer", self.interpreter.pointer().into()),
+            ("location", self.location()),
+            ("diagnostics", self.diagnostics(&diagnostics)),
+        ])
+    }
+
+    /// line(及column)处或之后的第一条指令
+    fn instruction_at(&self, line: usize, column: usize) -> Option<usize> {
+        let spans = self.interpreter.spans();
+        (0..spans.len()).find(|&pc| spans[pc].line == line && spans[pc].column >= column)
+    }
+}
+
+struct Server {
+    sessions: HashMap<u64, Session>,
+    next_session: u64,
+}
+
+impl Server {
+    fn session(&mut self, params: &Json) -> Result<&mut Session, Failure> {
+        let id = count(params, "session")?.ok_or_else(|| invalid("missing 'session'"))?;
+        self.sessions.get_mut(&id).ok_or_else(|| invalid(format!("unknown session {}", id)))
+    }
+
+    /// 编译到已有会话或新会话
+    fn compile(&mut self, params: &Json) -> Result<Json, Failure> {
+        let source = string(params, "source")?.ok_or_else(|| invalid("missing 'source'"))?.to_string();
+        // 先检查全部选项，出错时不创建会话
+        let memory = count(params, "memory")?
+            .map(|size| usize::try_from(size).ok().filter(|&n| n > 0).ok_or_else(|| invalid("'memory' must be positive")))
+            .transpose()?;
+        let overflow = string(params, "overflow")?.map(OverflowPolicy::parse).transpose().map_err(invalid)?;
+        let eof = string(params, "eof")?.map(EofBehavior::parse).transpose().map_err(invalid)?;
+        let seed = count(params, "seed")?;
+        let strict = params.get("strictBounds").map(|strict| strict.as_bool().ok_or_else(|| invalid("'strictBounds' must be a boolean"))).transpose()?;
+        let path = string(params, "path")?.map(str::to_string);
+
+        let id = match count(params, "session")? {
+            Some(id) if self.sessions.contains_key(&id) => id,
+            Some(id) => return Err(invalid(format!("unknown session {}", id))),
+            None => {
+                let output = Rc::new(RefCell::new(Vec::new()));
+                let mut interpreter = DerstandInterpreter::new();
+                interpreter.set_output(Box::new(SharedOutput(Rc::clone(&output))));
+                // 标准输入是RPC通道，程序不能读取
+                interpreter.set_input(Box::new(io::empty()));
+                self.next_session += 1;
+                let session = Session { interpreter, source: String::new(), path: None, output, breakpoints: BTreeSet::new() };
+                self.sessions.insert(self.next_session, session);
+                self.next_session
+            },
+        };
+        let session = self.sessions.get_mut(&id).expect("session was just looked up");
+
+        let interpreter = &mut session.interpreter;
+        if let Some(size) = memory {
+            interpreter.set_memory_size(size);
+        }
+        if let Some(policy) = overflow {
+            interpreter.set_overflow_policy(policy);
+        }
+        if let Some(eof) = eof {
+            interpreter.set_eof_behavior(eof);
+        }
+        if let Some(seed) = seed {
+            interpreter.set_seed(seed);
+        }
+        if let Some(strict) = strict {
+            interpreter.set_strict_bounds(strict);
+        }
+        if let Some(path) = &path {
+            interpreter.set_source_path(path);
+        }
+        session.path = path;
+
+        // 源码变了，旧断点的下标不再有意义
+        session.breakpoints.clear();
+        let compiled = interpreter.compile(&source);
+        interpreter.reset();
+        session.source = source;
+        let (status, diagnostics) = match compiled {
+            Ok(()) => ("ok", lint::lint(&session.interpreter, LintOptions::default())),
+            Err(e) => ("compile_error", vec![e]),
+        };
+        Ok(Json::object([
+            ("session", id.into()),
+            ("status", status.into()),
+            ("instructions", session.interpreter.instructions().len().into()),
+            ("diagnostics", session.diagnostics(&diagnostics)),
+        ]))
+    }
+
+    fn handle(&mut self, method: &str, params: &Json) -> Result<Json, Failure> {
+        match method {
+            "compile" => self.compile(params),
+            "run" | "step" => {
+                let session = self.session(params)?;
+                if let Some(input) = string(params, "input")? {
+                    session.interpreter.preload_input(input.as_bytes());
+                }
+                Ok(match method {
+                    "run" => {
+                        let (limit, timeout) = (count(params, "maxSteps")?, count(params, "timeoutMs")?);
+                        session.execute(limit, true, timeout)
+                    },
+                    _ => session.execute(Some(count(params, "count")?.unwrap_or(1)), false, None),
+                })
+            },
+            "setBreakpoint" => {
+                let session = self.session(params)?;
+                let line = count(params, "line")?.ok_or_else(|| invalid("missing 'line'"))? as usize;
+                let column = count(params, "column")?.unwrap_or(1) as usize;
+                let enabled = params.get("enabled").map_or(Some(true), Json::as_bool).ok_or_else(|| invalid("'enabled' must be a boolean"))?;
+                let Some(pc) = session.instruction_at(line, column) else {
+                    return Err(invalid(format!("no instruction at or after line {}, column {}", line, column)));
+                };
+                if enabled {
+                    session.breakpoints.insert(pc);
+                } else {
+                    session.breakpoints.remove(&pc);
+                }
+                let span = session.interpreter.spans()[pc];
+                Ok(Json::object([
+                    ("pc", pc.into()),
+                    ("line", span.line.into()),
+                    ("column", span.column.into()),
+                    ("enabled", enabled.into()),
+                ]))
+            },
+            "readMemory" => {
+                let session = self.session(params)?;
+                let memory = session.interpreter.memory();
+                let start = (count(params, "start")?.unwrap_or(0) as usize).min(memory.len());
+                let length = count(params, "length")?.map_or(memory.len() - start, |n| n as usize).min(memory.len() - start).min(MAX_READ);
+                let cells = memory[start..start + length].iter().map(|&cell| Json::from(cell as usize)).collect();
+                Ok(Json::object([
+                    ("start", start.into()),
+                    ("cells", Json::Array(cells)),
+                    ("pointer", session.interpreter.pointer().into()),
+                    ("size", memory.len().into()),
+                ]))
+            },
+            "reset" => {
+                let session = self.session(params)?;
+                session.interpreter.reset();
+                session.output.borrow_mut().clear();
+                Ok(Json::object([("pc", 0usize.into()), ("location", session.location())]))
+            },
+            "close" => {
+                let id = count(params, "session")?.ok_or_else(|| invalid("missing 'session'"))?;
+                self.sessions.remove(&id).ok_or_else(|| invalid(format!("unknown session {}", id)))?;
+                Ok(Json::Null)
+            },
+            _ => Err((METHOD_NOT_FOUND, format!("unsupported method '{}'", method))),
+        }
+    }
+}
+
+/// `derstand --rpc`
+pub fn command(args: &[String]) -> i32 {
+    if let Some(arg) = args.first() {
+        eprintln!("Unknown option: {}\nUsage: derstand --rpc", arg);
+        return 2;
+    }
+    let mut input = io::stdin().lock();
+    let mut output = io::stdout().lock();
+    let mut server = Server { sessions: HashMap::new(), next_session: 0 };
+    loop {
+        let message = match read_message(&mut input) {
+            Ok(Some(message)) => message,
+            Ok(None) => return 0,
+            Err(e) => {
+                eprintln!("rpc: {}", e);
+                return 1;
+            },
+        };
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = message.get("params").cloned().unwrap_or(Json::Null);
+        let reply = server.handle(method, &params);
+        // 通知(不带id)不回复
+        let Some(id) = message.get("id").cloned() else {
+            continue;
+        };
+        let body = match reply {
+            Ok(result) => ("result", result),
+            Err((code, message)) => ("error", Json::object([("code", code.into()), ("message", message.into())])),
+        };
+        if let Err(e) = write_message(&mut output, &Json::object([("jsonrpc", "2.0".into()), ("id", id), body])) {
+            eprintln!("rpc: {}", e);
+            return 1;
+        }
+    }
+}
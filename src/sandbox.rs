@@ -0,0 +1,49 @@
+//! 沙箱 - 在服务端运行不可信程序时切断一切外部输入输出
+//!
+//! 启用后`,`与`?`只读取配置中预置的字节，耗尽即输入结束，绝不读取标准输入或调用者的输入源；
+//! 输出只写入解释器内的缓冲，由`captured_output()`取出；`!include`不读取文件，检查点与输入录制关闭，
+//! `§`宿主调用、自定义指令与写入stderr的`@`以E0121停止。步数、时间、纸带与输出超过上限时以E0120停止。
+
+use alloc::vec::Vec;
+
+/// 沙箱配置；经`Builder::sandbox`或`set_sandbox`启用
+#[derive(Debug, Clone, PartialEq)]
+pub struct SandboxProfile {
+    pub input: Vec<u8>,   // 程序能读到的全部输入
+    pub max_steps: u64,   // 每次运行的指令数
+    pub max_time_ms: u64, // 每次运行的毫秒数，含睡眠
+    pub max_memory: usize, // 纸带单元格数
+    pub max_output: usize, // 每次运行的输出字节数
+}
+
+impl Default for SandboxProfile {
+    fn default() -> Self {
+        SandboxProfile { input: Vec::new(), max_steps: 100_000_000, max_time_ms: 5_000, max_memory: 1 << 20, max_output: 1 << 20 }
+    }
+}
+
+/// 每隔这么多步检查一次时间
+pub(crate) const TIME_CHECK_INTERVAL: u64 = 4096;
+
+/// 解释器持有的沙箱状态
+pub(crate) struct Sandbox {
+    pub max_steps: u64,
+    pub max_time_ms: u64,
+    pub max_memory: usize,
+    pub max_output: usize,
+    pub output: Vec<u8>, // 本次运行捕获的输出
+}
+
+impl Sandbox {
+    /// 预置输入交给解释器的输入缓冲，其余留作限制
+    pub fn new(profile: SandboxProfile) -> (Self, Vec<u8>) {
+        let sandbox = Sandbox {
+            max_steps: profile.max_steps,
+            max_time_ms: profile.max_time_ms,
+            max_memory: profile.max_memory,
+            max_output: profile.max_output,
+            output: Vec::new(),
+        };
+        (sandbox, profile.input)
+    }
+}
@@ -0,0 +1,510 @@
+//! HTTP执行服务 - `derstand serve`，提交源码与输入，以JSON取回输出、统计与诊断
+//!
+//! 面向在线评测等需要运行不可信源码的场景：每个请求使用独立的解释器并在沙箱中运行，
+//! 步数、时间、纸带与输出都有上限，`!include`不读取文件，输入只来自请求本身，`@`与宿主调用以E0121停止。
+//! 同时打开的连接数有上限(`--max-connections`)，超出时直接以503拒绝。
+//!
+//! ```text
+//! POST /run      {"source": "+[.]", "input": "", "seed": 1,
+//!                 "limits": {"steps": 1000000, "time_ms": 1000, "memory": 30000, "output": 65536}}
+//!             -> {"status": "ok", "output": "...", "stats": {...}, "limits": {...}, "diagnostics": [...]}
+//! GET  /health   {"status": "ok", "version": "..."}
+//! GET  /repl     升级为WebSocket，见repl模块
+//! GET  /metrics  Prometheus格式的运行次数、执行指令数、超限次数与耗时分布
+//! ```
+//!
+//! status为ok、compile_error、runtime_error，或超出上限时的step_limit、time_limit、
+//! memory_limit、output_limit。请求中的上限不能超过服务端的上限，省略时取服务端的上限。
+//! 诊断与`--diagnostics json`的格式相同，运行成功时包含lint警告。
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::cancel::CancelToken;
+use crate::diagnostic::Diagnostic;
+use crate::json::Json;
+use crate::lint::{self, LintOptions};
+use crate::metrics::Metrics;
+use crate::sandbox::SandboxProfile;
+use crate::{DerstandInterpreter, pragma, repl, runtime_error, websocket};
+
+/// 请求体大小上限
+const MAX_BODY: usize = 1 << 20;
+
+/// 请求头行数上限
+const MAX_HEADERS: usize = 64;
+
+/// 读取请求的超时，防止连接一直占用线程
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 拒绝多余连接时写出响应的超时，慢客户端不会阻塞接收循环
+const REJECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 未指定纸带大小的程序使用的默认大小(与命令行相同)
+const DEFAULT_MEMORY: usize = 30000;
+
+/// 一次运行的资源上限
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub steps: u64,
+    pub time_ms: u64,
+    pub memory: usize, // 纸带单元格数
+    pub output: usize, // 输出字节数
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits { steps: 100_000_000, time_ms: 5_000, memory: 1 << 20, output: 1 << 20 }
+    }
+}
+
+impl Limits {
+    /// 按请求中的limits对象收紧上限；超过服务端上限的值被截断
+    fn narrow(self, request: Option<&Json>) -> Result<Limits, String> {
+        let field = |name: &str, max: u64| -> Result<u64, String> {
+            match request.and_then(|r| r.get(name)) {
+                None | Some(Json::Null) => Ok(max),
+                Some(value) => match value.as_i64() {
+                    Some(n) if n > 0 => Ok((n as u64).min(max)),
+                    _ => Err(format!("limits.{} must be a positive integer", name)),
+                },
+            }
+        };
+        Ok(Limits {
+            steps: field("steps", self.steps)?,
+            time_ms: field("time_ms", self.time_ms)?,
+            memory: field("memory", self.memory as u64)? as usize,
+            output: field("output", self.output as u64)? as usize,
+        })
+    }
+
+    fn to_json(self) -> Json {
+        Json::object([
+            ("steps", self.steps.into()),
+            ("time_ms", self.time_ms.into()),
+            ("memory", self.memory.into()),
+            ("output", self.output.into()),
+        ])
+    }
+}
+
+/// 超出上限时运行停止的原因
+const STEP_LIMIT: &str = "step_limit";
+const TIME_LIMIT: &str = "time_limit";
+const MEMORY_LIMIT: &str = "memory_limit";
+const OUTPUT_LIMIT: &str = "output_limit";
+
+/// 资源超限的诊断，指向停止时的指令
+fn limit_error(interpreter: &DerstandInterpreter, message: String, hint: &str) -> Diagnostic {
+    let error = Diagnostic::error("E0115", message).with_hint(hint.to_string());
+    match interpreter.spans().get(interpreter.pc()) {
+        Some(&span) => error.with_span(span).with_label("stopped while executing this instruction"),
+        None => error,
+    }
+}
+
+/// 程序的纸带大小：编译指示要求的大小，或不超过上限的默认大小
+///
+/// 纸带在编译时分配，因此要在编译前检查
+pub(crate) fn tape_size(source: &str, limits: Limits) -> Result<usize, (&'static str, Diagnostic)> {
+    let memory = match pragma::parse(source) {
+        Ok(pragma) => pragma.memory.unwrap_or(DEFAULT_MEMORY.min(limits.memory)),
+        Err(e) => return Err(("compile_error", e)),
+    };
+    if memory > limits.memory {
+        let error = Diagnostic::error("E0115", format!("Program asks for {} cells, more than the limit of {}", memory, limits.memory))
+            .with_hint("lower the memory pragma, or raise limits.memory up to the server maximum");
+        return Err((MEMORY_LIMIT, error));
+    }
+    Ok(memory)
+}
+
+/// 从头运行已编译的程序，直到结束、出错或超出上限；返回执行的步数与停止原因
+///
+/// token到时后由调用者取消；overflowed报告输出是否已超限(输出目标的错误不会中断运行)
+pub(crate) fn run_limited(
+    interpreter: &mut DerstandInterpreter,
+    source: &str,
+    limits: Limits,
+    token: &CancelToken,
+    overflowed: &dyn Fn() -> bool,
+) -> (u64, Option<(&'static str, Diagnostic)>) {
+    let time_limit = |interpreter: &DerstandInterpreter, steps: u64| {
+        limit_error(interpreter, format!("Time limit exceeded after {} ms ({} steps)", limits.time_ms, steps),
+            "the program may not terminate; raise limits.time_ms up to the server maximum")
+    };
+    interpreter.set_cancel_token(Some(token.clone()));
+    interpreter.reset();
+    let mut steps = 0u64;
+    let stopped = loop {
+        if overflowed() {
+            let error = limit_error(interpreter, format!("Output limit exceeded: more than {} bytes written", limits.output),
+                "raise limits.output up to the server maximum");
+            break Some((OUTPUT_LIMIT, error));
+        }
+        if steps == limits.steps {
+            let error = limit_error(interpreter, format!("Step limit exceeded: more than {} instructions executed", limits.steps),
+                "the program may not terminate; raise limits.steps up to the server maximum");
+            break Some((STEP_LIMIT, error));
+        }
+        if token.is_cancelled() {
+            break Some((TIME_LIMIT, time_limit(interpreter, steps)));
+        }
+        match interpreter.step() {
+            Ok(true) => steps += 1,
+            // 结束时写出的输出也可能超限，回到循环开头报告
+            Ok(false) if overflowed() => {},
+            Ok(false) => break None,
+            // 睡眠或等待输入时到时
+            Err(_) if token.is_cancelled() => break Some((TIME_LIMIT, time_limit(interpreter, steps))),
+            // 沙箱中的提交只有输出上限由解释器检查，见run_submission
+            Err(e) if e.code == "E0120" => {
+                let error = limit_error(interpreter, format!("Output limit exceeded: more than {} bytes written", limits.output),
+                    "raise limits.output up to the server maximum");
+                break Some((OUTPUT_LIMIT, error));
+            },
+            Err(e) => break Some(("runtime_error", runtime_error(interpreter, source, e))),
+        }
+    };
+    interpreter.set_cancel_token(None);
+    (steps, stopped)
+}
+
+/// 运行一次提交，返回响应对象
+fn run_submission(request: &Json, limits: Limits, metrics: &Metrics) -> Result<Json, String> {
+    let source = request.get("source").and_then(Json::as_str).ok_or("missing string field 'source'")?;
+    let input = match request.get("input") {
+        None | Some(Json::Null) => "",
+        Some(input) => input.as_str().ok_or("'input' must be a string")?,
+    };
+    let limits = limits.narrow(request.get("limits"))?;
+
+    let seed = match request.get("seed") {
+        None | Some(Json::Null) => None,
+        Some(seed) => Some(seed.as_i64().ok_or("'seed' must be an integer")? as u64),
+    };
+
+    let started = Instant::now();
+    metrics.run_started();
+    let respond = |status: &str, output: &[u8], steps: u64, diagnostics: Vec<Diagnostic>, memory: usize| {
+        metrics.run_completed(status, steps, started.elapsed());
+        Json::object([
+            ("status", status.into()),
+            ("output", String::from_utf8_lossy(output).into_owned().into()),
+            ("stats", Json::object([
+                ("steps", steps.into()),
+                ("time_ms", Json::Float(started.elapsed().as_secs_f64() * 1000.0)),
+                ("memory", memory.into()),
+                ("output_bytes", output.len().into()),
+            ])),
+            ("limits", limits.to_json()),
+            ("diagnostics", Json::Array(diagnostics.iter().map(|d| d.to_json(None)).collect())),
+        ])
+    };
+
+    let mut interpreter = DerstandInterpreter::new();
+    // 输入只来自请求，输出只写入解释器内的缓冲；步数与时间由run_limited检查，以便报告具体的状态
+    interpreter.set_sandbox(Some(SandboxProfile {
+        input: input.as_bytes().to_vec(),
+        max_steps: u64::MAX,
+        max_time_ms: u64::MAX,
+        max_memory: limits.memory,
+        max_output: limits.output,
+    }));
+    if let Some(seed) = seed {
+        interpreter.set_seed(seed);
+    }
+
+    let memory = match tape_size(source, limits) {
+        Ok(memory) => memory,
+        Err((status, e)) => return Ok(respond(status, b"", 0, vec![e], 0)),
+    };
+    interpreter.set_memory_size(memory);
+    if let Err(e) = interpreter.compile(source) {
+        return Ok(respond("compile_error", b"", 0, vec![e], 0));
+    }
+    let warnings = lint::lint(&interpreter, LintOptions::default());
+
+    let token = CancelToken::new();
+    token.cancel_after(Duration::from_millis(limits.time_ms));
+    let (steps, stopped) = run_limited(&mut interpreter, source, limits, &token, &|| false);
+
+    let output = interpreter.captured_output();
+    let memory = interpreter.memory_size();
+    Ok(match stopped {
+        None => respond("ok", output, steps, warnings, memory),
+        Some((status, error)) => respond(status, output, steps, vec![error], memory),
+    })
+}
+
+/// 解析后的HTTP请求
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    /// 请求头的值(名称不区分大小写)
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+}
+
+/// 读取一个HTTP/1.1请求；Err(状态码, 说明)
+fn read_request(stream: &mut BufReader<TcpStream>) -> Result<Option<Request>, (u16, String)> {
+    let bad = |message: &str| (400, message.to_string());
+    let mut line = String::new();
+    match stream.read_line(&mut line) {
+        Ok(0) => return Ok(None),
+        Ok(_) => {},
+        Err(_) => return Ok(None),
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target), Some(version)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(bad("malformed request line"));
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err((505, format!("unsupported protocol version {}", version)));
+    }
+    let (method, path) = (method.to_string(), target.split('?').next().unwrap_or(target).to_string());
+
+    let mut length = 0usize;
+    let mut headers = Vec::new();
+    for _ in 0..=MAX_HEADERS {
+        line.clear();
+        if stream.read_line(&mut line).map_err(|e| bad(&e.to_string()))? == 0 {
+            return Err(bad("connection closed inside the headers"));
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            if length > MAX_BODY {
+                return Err((413, format!("request body exceeds {} bytes", MAX_BODY)));
+            }
+            let mut body = vec![0; length];
+            stream.read_exact(&mut body).map_err(|e| bad(&e.to_string()))?;
+            return Ok(Some(Request { method, path, headers, body }));
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            return Err(bad("malformed header line"));
+        };
+        if name.eq_ignore_ascii_case("content-length") {
+            length = value.trim().parse().map_err(|_| bad("invalid Content-Length"))?;
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            return Err((501, "chunked request bodies are not supported; send a Content-Length".to_string()));
+        }
+        headers.push((name.trim().to_string(), value.trim().to_string()));
+    }
+    Err((431, "too many header lines".to_string()))
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        426 => "Upgrade Required",
+        431 => "Request Header Fields Too Large",
+        501 => "Not Implemented",
+        503 => "Service Unavailable",
+        505 => "HTTP Version Not Supported",
+        _ => "Error",
+    }
+}
+
+/// 写出响应；每个连接只处理一个请求
+fn respond(stream: &mut TcpStream, status: u16, content_type: &str, body: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, reason(status), content_type, body.len(), body
+    )?;
+    stream.flush()
+}
+
+fn error_body(message: &str) -> Json {
+    Json::object([("error", message.into())])
+}
+
+/// 服务端配置
+struct Server {
+    limits: Limits,
+    workers: usize, // 同时运行的提交数上限
+    running: AtomicUsize,
+    max_sessions: usize, // 同时打开的REPL会话数上限
+    sessions: AtomicUsize,
+    max_connections: usize, // 同时打开的连接数上限，每个连接占用一个线程
+    connections: AtomicUsize,
+    metrics: Metrics,
+}
+
+impl Server {
+    /// 按路径分发，返回状态码与响应体
+    fn route(&self, request: &Request) -> (u16, Json) {
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/health") => (200, Json::object([
+                ("status", "ok".into()),
+                ("version", env!("CARGO_PKG_VERSION").into()),
+                ("limits", self.limits.to_json()),
+            ])),
+            ("POST", "/run") => {
+                let Ok(text) = std::str::from_utf8(&request.body) else {
+                    return (400, error_body("request body is not UTF-8"));
+                };
+                let body = match Json::parse(text) {
+                    Ok(body) => body,
+                    Err(e) => return (400, error_body(&format!("invalid JSON: {}", e))),
+                };
+                if self.running.fetch_add(1, Ordering::SeqCst) >= self.workers {
+                    self.running.fetch_sub(1, Ordering::SeqCst);
+                    return (503, error_body("too many submissions running; retry later"));
+                }
+                let result = run_submission(&body, self.limits, &self.metrics);
+                self.running.fetch_sub(1, Ordering::SeqCst);
+                match result {
+                    Ok(response) => (200, response),
+                    Err(e) => (400, error_body(&e)),
+                }
+            },
+            (_, "/health" | "/run" | "/metrics") => (405, error_body(&format!("{} is not allowed on {}", request.method, request.path))),
+            _ => (404, error_body(&format!("no endpoint {}", request.path))),
+        }
+    }
+
+    /// 完成WebSocket握手并占用一个会话名额
+    fn upgrade(&self, request: &Request, writer: &mut TcpStream) -> Result<(), (u16, String)> {
+        if request.method != "GET" {
+            return Err((405, format!("{} is not allowed on /repl", request.method)));
+        }
+        let upgrade = request.header("upgrade").is_some_and(|u| u.eq_ignore_ascii_case("websocket"));
+        let (true, Some(key)) = (upgrade, request.header("sec-websocket-key")) else {
+            return Err((426, "/repl expects a WebSocket upgrade".to_string()));
+        };
+        if request.header("sec-websocket-version") != Some("13") {
+            return Err((426, "only WebSocket version 13 is supported".to_string()));
+        }
+        if self.sessions.fetch_add(1, Ordering::SeqCst) >= self.max_sessions {
+            self.sessions.fetch_sub(1, Ordering::SeqCst);
+            return Err((503, "too many REPL sessions open; retry later".to_string()));
+        }
+        let accepted = write!(
+            writer,
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+            websocket::accept_key(key)
+        );
+        if let Err(e) = accepted.and_then(|()| writer.flush()) {
+            self.sessions.fetch_sub(1, Ordering::SeqCst);
+            return Err((400, e.to_string()));
+        }
+        Ok(())
+    }
+
+    fn handle(&self, stream: TcpStream) {
+        let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+        let Ok(mut writer) = stream.try_clone() else {
+            return;
+        };
+        let mut reader = BufReader::new(stream);
+        let (status, body) = match read_request(&mut reader) {
+            Ok(Some(request)) if request.method == "GET" && request.path == "/metrics" => {
+                let metrics = self.metrics.render(self.running.load(Ordering::SeqCst), self.sessions.load(Ordering::SeqCst));
+                let _ = respond(&mut writer, 200, "text/plain; version=0.0.4; charset=utf-8", &metrics);
+                return;
+            },
+            Ok(Some(request)) if request.path == "/repl" => match self.upgrade(&request, &mut writer) {
+                Ok(()) => {
+                    self.metrics.session_opened();
+                    repl::session(reader, writer, self.limits, &self.metrics);
+                    self.sessions.fetch_sub(1, Ordering::SeqCst);
+                    return;
+                },
+                Err((status, message)) => (status, error_body(&message)),
+            },
+            Ok(Some(request)) => self.route(&request),
+            Ok(None) => return,
+            Err((status, message)) => (status, error_body(&message)),
+        };
+        let _ = respond(&mut writer, status, "application/json", &format!("{}\n", body));
+    }
+}
+
+/// 解析正整数选项
+fn positive<T: std::str::FromStr + PartialOrd + Default>(iter: &mut std::slice::Iter<'_, String>, flag: &str) -> Result<T, String> {
+    iter.next()
+        .and_then(|value| value.parse::<T>().ok())
+        .filter(|n| *n > T::default())
+        .ok_or(format!("{} expects a positive number", flag))
+}
+
+/// `derstand serve [--port N] [--host addr] [--workers N] [--max-sessions N] [--max-connections N] [--max-steps N] [--max-time-ms N] [--max-memory N] [--max-output N]`
+pub fn command(args: &[String]) -> i32 {
+    let mut host = "127.0.0.1".to_string();
+    let mut port = 8080u16;
+    let mut limits = Limits::default();
+    let mut workers = thread::available_parallelism().map_or(4, |n| n.get());
+    let mut max_sessions = 64;
+    let mut max_connections = 256;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let result = match arg.as_str() {
+            "--port" => iter.next().and_then(|p| p.parse().ok()).map(|p| port = p).ok_or("--port expects a port number".to_string()),
+            "--host" => iter.next().map(|h| host = h.clone()).ok_or("Missing value for --host".to_string()),
+            "--workers" => positive(&mut iter, arg).map(|n| workers = n),
+            "--max-sessions" => positive(&mut iter, arg).map(|n| max_sessions = n),
+            "--max-connections" => positive(&mut iter, arg).map(|n| max_connections = n),
+            "--max-steps" => positive(&mut iter, arg).map(|n| limits.steps = n),
+            "--max-time-ms" => positive(&mut iter, arg).map(|n| limits.time_ms = n),
+            "--max-memory" => positive(&mut iter, arg).map(|n| limits.memory = n),
+            "--max-output" => positive(&mut iter, arg).map(|n| limits.output = n),
+            _ => Err(format!("Unknown option: {}", arg)),
+        };
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            eprintln!("Usage: derstand serve [--port N] [--host addr] [--workers N] [--max-sessions N] [--max-connections N] [--max-steps N] [--max-time-ms N] [--max-memory N] [--max-output N]");
+            return 2;
+        }
+    }
+
+    let listener = match TcpListener::bind((host.as_str(), port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Cannot listen on {}:{}: {}", host, port, e);
+            return 1;
+        },
+    };
+    if let Ok(addr) = listener.local_addr() {
+        eprintln!("derstand serve listening on http://{}", addr);
+    }
+    let server = Arc::new(Server {
+        limits,
+        workers,
+        running: AtomicUsize::new(0),
+        max_sessions,
+        sessions: AtomicUsize::new(0),
+        max_connections,
+        connections: AtomicUsize::new(0),
+        metrics: Metrics::default(),
+    });
+    for mut stream in listener.incoming().flatten() {
+        if server.connections.fetch_add(1, Ordering::SeqCst) >= server.max_connections {
+            server.connections.fetch_sub(1, Ordering::SeqCst);
+            // 不读取请求，在接收线程上直接拒绝
+            let _ = stream.set_write_timeout(Some(REJECT_TIMEOUT));
+            let _ = respond(&mut stream, 503, "application/json", &format!("{}\n", error_body("too many connections open; retry later")));
+            continue;
+        }
+        let server = Arc::clone(&server);
+        thread::spawn(move || {
+            server.handle(stream);
+            server.connections.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+    0
+}
@@ -0,0 +1,100 @@
+//! 共享纸带区域 - 多个解释器把同一块内存映射到各自的纸带上，不经输入输出交换数据
+//!
+//! 区域由`Arc<Mutex<..>>`持有，各解释器的纸带上保留自己的副本，只在显式的同步点交换：
+//!
+//! ```text
+//! let mailbox = SharedRegion::new(16);
+//! producer.map_shared(100, mailbox.clone())?;   // producer纸带的100..116
+//! consumer.map_shared(0, mailbox.clone())?;     // consumer纸带的0..16
+//! producer.execute()?;
+//! producer.sync_shared();                        // 写出producer改动的单元格
+//! consumer.sync_shared();                        // consumer取得新内容
+//! consumer.execute()?;
+//! ```
+//!
+//! 同步时，自上次同步以来本地改动过的单元格写入区域，其余单元格取区域的值；两边都改动时以后同步的一方为准。
+//! 映射时立即取一次区域的内容。区域只映射到第一条纸带，编译改变纸带大小后应重新映射。
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::DerstandInterpreter;
+
+/// 可在解释器(与线程)之间共享的一块字节；clone得到同一块区域
+#[derive(Debug, Clone)]
+pub struct SharedRegion {
+    data: Arc<Mutex<Vec<u8>>>,
+}
+
+impl SharedRegion {
+    /// len字节的全零区域
+    pub fn new(len: usize) -> Self {
+        SharedRegion { data: Arc::new(Mutex::new(vec![0; len])) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// 当前内容的副本
+    pub fn read(&self) -> Vec<u8> {
+        self.lock().clone()
+    }
+
+    /// 从offset开始写入bytes，超出区域的部分被忽略
+    pub fn write(&self, offset: usize, bytes: &[u8]) {
+        let mut data = self.lock();
+        for (cell, &byte) in data.iter_mut().skip(offset).zip(bytes) {
+            *cell = byte;
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<u8>> {
+        // 持锁时不会执行用户代码，中毒的锁中数据仍然完整
+        self.data.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// 一个映射：纸带上从start开始的单元格，与上次同步后的内容
+pub(crate) struct Mapping {
+    start: usize,
+    region: SharedRegion,
+    synced: Vec<u8>,
+}
+
+impl DerstandInterpreter {
+    /// 把region映射到第一条纸带从start开始的单元格，并取得它当前的内容
+    pub fn map_shared(&mut self, start: usize, region: SharedRegion) -> Result<(), String> {
+        let len = region.len();
+        let size = self.first_tape().len();
+        if start.checked_add(len).is_none_or(|end| end > size) {
+            return Err(format!("Shared region of {} cells at {} does not fit on the tape ({} cells)", len, start, size));
+        }
+        let synced = region.read();
+        self.first_tape()[start..start + len].copy_from_slice(&synced);
+        self.shared.push(Mapping { start, region, synced });
+        Ok(())
+    }
+
+    /// 同步所有映射，见模块说明
+    pub fn sync_shared(&mut self) {
+        let mut mappings = core::mem::take(&mut self.shared);
+        let tape = self.first_tape();
+        for mapping in &mut mappings {
+            let mut shared = mapping.region.lock();
+            let cells = tape.iter_mut().skip(mapping.start);
+            for ((cell, synced), value) in cells.zip(&mut mapping.synced).zip(shared.iter_mut()) {
+                if *cell != *synced {
+                    *value = *cell;
+                } else {
+                    *cell = *value;
+                }
+                *synced = *cell;
+            }
+        }
+        self.shared = mappings;
+    }
+}
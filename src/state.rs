@@ -0,0 +1,170 @@
+//! 运行状态的JSON表示 - 供网页前端在两步之间轮询解释器状态
+//!
+//! 只包含机器状态，不含程序本身：恢复时解释器必须已装入同一个程序。纸带只列出非零单元格，
+//! 一次轮询通常只有几百字节：
+//!
+//! ```text
+//! {"pc":7,"pointer":1,"steps":12,"finished":false,"size":30000,"cells":[[0,72],[1,3]],
+//!  "tape":0,"other":null,"stack":[],"calls":[],"input":[104,105],"awaiting_input":false,"rng":"42"}
+//! ```
+//!
+//! `input`是已预置尚未读取的输入字节；`awaiting_input`表示下一条指令要读输入而预置输入已耗尽，
+//! 前端可以据此提示用户输入。循环轮次计数不保存，恢复后运行时错误回溯中的轮次从0开始。
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::json::Json;
+use crate::program::Program;
+use crate::random::Rng;
+use crate::{DerstandInterpreter, Instruction, Tape, clock, pragma};
+
+/// 非零单元格：[[下标, 值], ...]
+fn cells(memory: &[u8]) -> Json {
+    Json::Array(memory.iter().enumerate().filter(|(_, v)| **v != 0).map(|(i, &v)| Json::Array(vec![i.into(), (v as usize).into()])).collect())
+}
+
+fn bytes(data: impl Iterator<Item = u8>) -> Json {
+    Json::Array(data.map(|b| (b as usize).into()).collect())
+}
+
+/// 读取状态字段的辅助
+struct Fields<'a> {
+    state: &'a Json,
+}
+
+impl<'a> Fields<'a> {
+    fn get(&self, key: &str) -> Result<&'a Json, String> {
+        self.state.get(key).ok_or(format!("Invalid state: missing {}", key))
+    }
+
+    fn number(&self, key: &str) -> Result<usize, String> {
+        number(self.get(key)?).ok_or(format!("Invalid state: {} must be a non-negative integer", key))
+    }
+
+    fn array(&self, key: &str) -> Result<&'a [Json], String> {
+        self.get(key)?.as_array().ok_or(format!("Invalid state: {} must be an array", key))
+    }
+}
+
+fn number(value: &Json) -> Option<usize> {
+    value.as_i64().and_then(|n| usize::try_from(n).ok())
+}
+
+fn byte(value: &Json) -> Option<u8> {
+    value.as_i64().and_then(|n| u8::try_from(n).ok())
+}
+
+/// 按非零单元格列表重建size格的纸带
+fn tape(size: usize, cells: &[Json], what: &str) -> Result<Vec<u8>, String> {
+    if !(1..=pragma::MAX_MEMORY_SIZE).contains(&size) {
+        return Err(format!("Invalid state: {} size {} is out of range", what, size));
+    }
+    let mut memory = vec![0; size];
+    for cell in cells {
+        let pair = cell.as_array().filter(|pair| pair.len() == 2);
+        let (index, value) = pair.and_then(|pair| Some((number(&pair[0])?, byte(&pair[1])?))).ok_or(format!("Invalid state: {} cells must be [index, value] pairs", what))?;
+        *memory.get_mut(index).ok_or(format!("Invalid state: {} cell {} is outside the tape", what, index))? = value;
+    }
+    Ok(memory)
+}
+
+impl DerstandInterpreter {
+    /// 当前运行状态的紧凑JSON，见模块说明
+    pub fn state_json(&self) -> String {
+        let awaiting_input = self.input_buffer.is_empty() && matches!(self.instructions.get(self.pc), Some(Instruction::Input | Instruction::InputDecimal));
+        let other = self.other_tape.as_ref().map_or(Json::Null, |tape| {
+            Json::object([("pointer", tape.pointer.into()), ("size", tape.memory.len().into()), ("cells", cells(&tape.memory))])
+        });
+        Json::object([
+            ("pc", self.pc.into()),
+            ("pointer", self.pointer.into()),
+            ("steps", self.steps.into()),
+            ("finished", (self.pc >= self.instructions.len()).into()),
+            ("size", self.memory.len().into()),
+            ("cells", cells(&self.memory)),
+            ("tape", self.active_tape.into()),
+            ("other", other),
+            ("stack", bytes(self.stack.iter().copied())),
+            ("calls", Json::Array(self.call_stack.iter().map(|&(ret, n)| Json::Array(vec![ret.into(), n.into()])).collect())),
+            // 输入缓冲从尾部弹出，按读取顺序列出
+            ("input", bytes(self.input_buffer.iter().rev().copied())),
+            ("awaiting_input", awaiting_input.into()),
+            // 种子可能超出i64，按十进制字符串保存
+            ("rng", self.rng.state().to_string().into()),
+        ])
+        .to_string()
+    }
+
+    /// 恢复`state_json`保存的状态；解释器必须已装入产生该状态的程序
+    pub fn set_state_json(&mut self, json: &str) -> Result<(), String> {
+        let state = Json::parse(json)?;
+        let fields = Fields { state: &state };
+        let pc = fields.number("pc")?;
+        if pc > self.instructions.len() {
+            return Err(format!("Invalid state: pc {} is past the end of the program ({} instructions)", pc, self.instructions.len()));
+        }
+        let memory = tape(fields.number("size")?, fields.array("cells")?, "tape")?;
+        let pointer = fields.number("pointer")?;
+        if pointer >= memory.len() {
+            return Err(format!("Invalid state: pointer {} is outside the tape", pointer));
+        }
+        let active_tape = fields.number("tape")?;
+        if active_tape > 1 {
+            return Err("Invalid state: tape must be 0 or 1".to_string());
+        }
+        let other = match fields.get("other")? {
+            Json::Null => None,
+            other => {
+                let fields = Fields { state: other };
+                let memory = tape(fields.number("size")?, fields.array("cells")?, "second tape")?;
+                let pointer = fields.number("pointer")?;
+                if pointer >= memory.len() {
+                    return Err(format!("Invalid state: second tape pointer {} is outside the tape", pointer));
+                }
+                Some(Tape { memory, pointer })
+            },
+        };
+        let byte_list = |key: &str| -> Result<Vec<u8>, String> {
+            fields.array(key)?.iter().map(|b| byte(b).ok_or(format!("Invalid state: {} must hold bytes", key))).collect()
+        };
+        let stack = byte_list("stack")?;
+        let mut input = byte_list("input")?;
+        input.reverse();
+        let mut calls = Vec::new();
+        for call in fields.array("calls")? {
+            let pair = call.as_array().filter(|pair| pair.len() == 2);
+            let (ret, n) = pair.and_then(|pair| Some((number(&pair[0])?, number(&pair[1])?))).ok_or("Invalid state: calls must be [return, procedure] pairs")?;
+            if ret == 0 || ret > self.instructions.len() || n >= self.procedures.len() {
+                return Err("Invalid state: a call does not match the loaded program".to_string());
+            }
+            calls.push((ret, n));
+        }
+        let rng = fields.get("rng")?.as_str().and_then(|s| s.parse().ok()).ok_or("Invalid state: rng must be a decimal string")?;
+
+        self.pc = pc;
+        self.pointer = pointer;
+        self.steps = fields.number("steps")? as u64;
+        self.memory = memory;
+        self.active_tape = active_tape;
+        self.other_tape = other;
+        self.stack = stack;
+        self.call_stack = calls;
+        self.input_buffer = input;
+        self.rng = Rng::new(rng);
+        self.loop_counts.clear();
+        self.loop_counts.resize(self.instructions.len(), 0);
+        self.started = clock::now_ms();
+        Ok(())
+    }
+
+    /// 以program与`state_json`保存的状态构造解释器
+    pub fn from_state_json(program: &Program, json: &str) -> Result<Self, String> {
+        let mut interpreter = DerstandInterpreter::new();
+        interpreter.load_program(program);
+        interpreter.set_state_json(json)?;
+        Ok(interpreter)
+    }
+}
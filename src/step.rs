@@ -0,0 +1,247 @@
+//! 带源码上下文的单步执行模式 - 教学用的慢动作执行
+//!
+//! `--explain [STEPS]`不等待回车，每步之后用一句话解说效果并稍作停顿，最多执行STEPS步(默认`EXPLAIN_BUDGET`)。
+
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use crate::diagnostic::{Diagnostic, source_line};
+use crate::{DerstandInterpreter, Instruction, Span, TIME_BYTES};
+
+/// 渲染源码片段：行号、该行源码与指向列的脱字符
+pub fn excerpt(source: &str, span: Span) -> String {
+    let gutter = span.line.to_string();
+    format!(
+        "{} | {}\n{} | {}^",
+        gutter,
+        source_line(source, span.line),
+        " ".repeat(gutter.len()),
+        " ".repeat(span.column.saturating_sub(1))
+    )
+}
+
+/// 可打印字符显示为字符本身，否则显示为十六进制
+fn describe_byte(byte: u8) -> String {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        format!("{} ({:?})", byte, byte as char)
+    } else {
+        format!("{} (0x{:02x})", byte, byte)
+    }
+}
+
+/// 复制或相加指令写入的相邻单元格；在纸带边缘被跳过时为None
+fn neighbor(instruction: Instruction, pointer: usize, memory_size: usize) -> Option<usize> {
+    match instruction {
+        Instruction::CopyLeft => pointer.checked_sub(1),
+        _ => Some(pointer + 1).filter(|&p| p < memory_size),
+    }
+}
+
+/// 描述一条指令执行前后的状态变化
+fn describe_effect(
+    instruction: Instruction,
+    before: (usize, u8, u8),
+    interpreter: &DerstandInterpreter,
+) -> String {
+    let (pointer, value, target_value) = before;
+    let after_pointer = interpreter.pointer();
+    match instruction {
+        Instruction::Right
+        | Instruction::Left
+        | Instruction::MoveRight(_)
+        | Instruction::MoveLeft(_)
+        | Instruction::MoveHigh
+        | Instruction::MoveLow
+        | Instruction::ScanLeft
+        | Instruction::ScanRight => {
+            if after_pointer == pointer {
+                format!("pointer stays at {} (edge)", pointer)
+            } else {
+                format!("pointer: {} → {}", pointer, after_pointer)
+            }
+        },
+        Instruction::Increment
+        | Instruction::Decrement
+        | Instruction::Add(_)
+        | Instruction::Sub(_)
+        | Instruction::Zero
+        | Instruction::Set(_)
+        | Instruction::Input
+        | Instruction::InputDecimal
+        | Instruction::Random => {
+            format!("cell {}: {} → {}", pointer, value, interpreter.cell(pointer))
+        },
+        Instruction::ProcStart => format!("skip procedure definition, continue at instruction {}", interpreter.pc()),
+        Instruction::ProcEnd => format!("return to instruction {}", interpreter.pc()),
+        Instruction::Call(_) | Instruction::CallCell => {
+            let calls = interpreter.call_backtrace();
+            format!("call procedure {} (depth {})", calls.last().map_or(0, |c| c.0), calls.len())
+        },
+        Instruction::SwitchTape => format!("switch to tape {} (pointer {})", interpreter.active_tape(), after_pointer),
+        Instruction::Exchange => format!(
+            "cell {}: {} → {} (exchanged with tape {})",
+            pointer,
+            value,
+            interpreter.cell(pointer),
+            1 - interpreter.active_tape()
+        ),
+        Instruction::Push => format!("push {} (stack depth {})", describe_byte(value), interpreter.stack().len()),
+        Instruction::Pop => format!(
+            "pop into cell {}: {} → {} (stack depth {})",
+            pointer,
+            value,
+            interpreter.cell(pointer),
+            interpreter.stack().len()
+        ),
+        Instruction::AddNext => match neighbor(instruction, pointer, interpreter.memory_size()) {
+            Some(target) => format!(
+                "cell {}: {} → 0, cell {}: {} → {}",
+                pointer, value, target, target_value, interpreter.cell(target)
+            ),
+            None => format!("cell {}: {} → 0 (value dropped at the high edge)", pointer, value),
+        },
+        Instruction::Copy | Instruction::CopyLeft => match neighbor(instruction, pointer, interpreter.memory_size()) {
+            Some(target) => format!("cell {}: {} → {}", target, target_value, interpreter.cell(target)),
+            None if instruction == Instruction::Copy => "copy skipped at the high edge".to_string(),
+            None => "copy skipped at the low edge".to_string(),
+        },
+        Instruction::Output => format!("output {}", describe_byte(value)),
+        Instruction::Time => {
+            let end = (pointer + TIME_BYTES).min(interpreter.memory_size());
+            let bytes: Vec<String> = (pointer..end).map(|p| interpreter.cell(p).to_string()).collect();
+            format!("write clock bytes [{}] to cells {}..={}", bytes.join(", "), pointer, end - 1)
+        },
+        Instruction::Sleep => format!("sleep {} ms (cell {})", value, pointer),
+        Instruction::OutputDecimal => format!("output \"{}\" (cell {})", value, pointer),
+        Instruction::Debug => format!("debug print of cell {} = {}", pointer, describe_byte(value)),
+        Instruction::Custom(symbol) => format!("custom instruction '{}', pointer now {}", symbol, after_pointer),
+        Instruction::Assert(expected) => format!("assertion passed: cell {} holds {}", pointer, expected),
+        Instruction::HostCall => format!("host function {} called with the cells after {}", value, pointer),
+        Instruction::JumpIfZero => {
+            if value == 0 {
+                format!("cell {} is 0, skip loop to instruction {}", pointer, interpreter.pc())
+            } else {
+                format!("cell {} is {}, enter loop", pointer, value)
+            }
+        },
+        Instruction::JumpIfNotZero => {
+            if value == 0 {
+                format!("cell {} is 0, leave loop", pointer)
+            } else {
+                format!("cell {} is {}, repeat loop", pointer, value)
+            }
+        },
+    }
+}
+
+/// 逐条执行程序：打印源码位置与效果，每步等待回车(输入q退出)
+pub fn step_through(interpreter: &mut DerstandInterpreter, source: &str) -> Result<(), Diagnostic> {
+    interpreter.reset();
+    let stdin = io::stdin();
+    let mut stderr = io::stderr();
+    let mut steps = 0u64;
+
+    while let Some(&instruction) = interpreter.instructions().get(interpreter.pc()) {
+        let pc = interpreter.pc();
+        let span = interpreter.spans()[pc];
+        let pointer = interpreter.pointer();
+        let target_value = neighbor(instruction, pointer, interpreter.memory_size()).map_or(0, |p| interpreter.cell(p));
+        let before = (pointer, interpreter.cell(pointer), target_value);
+
+        interpreter.step()?;
+        steps += 1;
+
+        let _ = writeln!(
+            stderr,
+            "\n[step {}] #{} {} '{}' at {}:{}\n{}\n  {}",
+            steps,
+            pc,
+            instruction.name(),
+            instruction,
+            span.line,
+            span.column,
+            excerpt(source, span),
+            describe_effect(instruction, before, interpreter)
+        );
+        let _ = write!(stderr, "(Enter to continue, q to quit) ");
+        let _ = stderr.flush();
+
+        let mut answer = String::new();
+        match stdin.lock().read_line(&mut answer) {
+            Ok(0) => break, // 标准输入结束时停止
+            Ok(_) if answer.trim() == "q" => break,
+            Ok(_) => {},
+            Err(e) => return Err(Diagnostic::error("E0102", format!("Input error: {}", e))),
+        }
+    }
+    Ok(())
+}
+
+/// `--explain`默认最多解说的步数
+pub const EXPLAIN_BUDGET: u64 = 500;
+
+/// `--explain`每步之后的停顿
+pub const EXPLAIN_DELAY: Duration = Duration::from_millis(100);
+
+/// 用一句话解说指令的效果；循环以'['所在的行称呼，计数器是当前单元格
+fn narrate(instruction: Instruction, before: (usize, u8, u8), interpreter: &DerstandInterpreter, pc: usize) -> String {
+    let (pointer, value, _) = before;
+    let after_pointer = interpreter.pointer();
+    let cell = interpreter.cell(pointer);
+    let loop_line = |open: usize| interpreter.spans()[open].line;
+    match instruction {
+        Instruction::Right | Instruction::Left | Instruction::MoveRight(_) | Instruction::MoveLeft(_) if after_pointer == pointer => {
+            format!("tried to move the pointer but it stays at cell {} (edge of the tape)", pointer)
+        },
+        Instruction::Right | Instruction::MoveRight(_) => format!("moved pointer right to cell {}", after_pointer),
+        Instruction::Left | Instruction::MoveLeft(_) => format!("moved pointer left to cell {}", after_pointer),
+        Instruction::ScanLeft | Instruction::ScanRight => format!("scanned from cell {} to the empty cell {}", pointer, after_pointer),
+        Instruction::MoveHigh => format!("jumped to the high end of the tape, cell {}", after_pointer),
+        Instruction::MoveLow => format!("jumped to the low end of the tape, cell {}", after_pointer),
+        Instruction::Increment | Instruction::Add(_) => format!("added {} to cell {}, now {}", cell.wrapping_sub(value), pointer, cell),
+        Instruction::Decrement | Instruction::Sub(_) => format!("subtracted {} from cell {}, now {}", value.wrapping_sub(cell), pointer, cell),
+        Instruction::Zero => format!("cleared cell {} (it was {})", pointer, value),
+        Instruction::Set(n) => format!("set cell {} to {}", pointer, n),
+        Instruction::Input => format!("read the byte {} into cell {}", describe_byte(cell), pointer),
+        Instruction::InputDecimal => format!("read the number {} into cell {}", cell, pointer),
+        Instruction::Random => format!("wrote the random byte {} to cell {}", cell, pointer),
+        Instruction::Output => format!("printed {} from cell {}", describe_byte(value), pointer),
+        Instruction::OutputDecimal => format!("printed the number {} from cell {}", value, pointer),
+        Instruction::JumpIfZero if value == 0 => format!("cell {} is 0, so the loop at line {} is skipped", pointer, loop_line(pc)),
+        Instruction::JumpIfZero => format!("entered the loop at line {}, counter = {}", loop_line(pc), value),
+        Instruction::JumpIfNotZero => {
+            let open = interpreter.jump_table.to_open[pc];
+            let rounds = interpreter.loop_counts.get(open).copied().unwrap_or(0);
+            if value == 0 {
+                format!("loop at line {} finished after {} round(s)", loop_line(open), rounds)
+            } else {
+                format!("loop at line {} repeating (round {}), counter = {}", loop_line(open), rounds, value)
+            }
+        },
+        _ => describe_effect(instruction, before, interpreter),
+    }
+}
+
+/// 边执行边解说：每步之后在stderr打印一句解说并停顿delay，执行budget步后停止
+pub fn explain(interpreter: &mut DerstandInterpreter, budget: u64, delay: Duration) -> Result<(), Diagnostic> {
+    interpreter.reset();
+    let mut stderr = io::stderr();
+    for _ in 0..budget {
+        let Some(&instruction) = interpreter.instructions().get(interpreter.pc()) else {
+            return Ok(());
+        };
+        let pc = interpreter.pc();
+        let span = interpreter.spans()[pc];
+        let pointer = interpreter.pointer();
+        let target_value = neighbor(instruction, pointer, interpreter.memory_size()).map_or(0, |p| interpreter.cell(p));
+        let before = (pointer, interpreter.cell(pointer), target_value);
+
+        interpreter.step()?;
+        let _ = writeln!(stderr, "[line {}] '{}' {}", span.line, instruction, narrate(instruction, before, interpreter, pc));
+        std::thread::sleep(delay);
+    }
+    if interpreter.pc() < interpreter.instructions().len() {
+        let _ = writeln!(stderr, "Stopped after {} steps; pass a larger budget with --explain STEPS", budget);
+    }
+    Ok(())
+}
@@ -0,0 +1,188 @@
+//! 文本合成程序 - `derstand synth "Hello, world!"`生成一个打印给定字节的简短程序
+//!
+//! 逐个输出字节贪心地选择代价最小的做法：在几个单元格中挑一个值最接近的修改(`+`/`-`、`+{n}`、`={n}`、`#`)，
+//! 或用`$`/`£`从当前单元格复制到相邻单元格再修改；连续重复的字节改用计数循环输出。参数中的
+//! `\n`、`\t`、`\\`与`\xHH`被解释为对应的字节，`--file`直接读取文件的字节：
+//!
+//! ```text
+//! $ derstand synth 'Hello, world!\n'
+//! $ derstand synth --cells 6 --file banner.txt -o banner.dr
+//! ```
+
+use crate::Instruction;
+
+/// 默认使用的单元格数(另有一个单元格作循环计数器)
+pub const DEFAULT_CELLS: usize = 4;
+
+/// 一段代码与它的长度(字符数)
+#[derive(Debug, Clone)]
+struct Code(String);
+
+impl Code {
+    fn len(&self) -> usize {
+        self.0.chars().count()
+    }
+}
+
+/// 重复或用`{n}`写出的指令，取较短的
+fn repeated(instruction: Instruction, n: usize) -> Code {
+    let symbol = instruction.symbol();
+    let counted = format!("{}{{{}}}", symbol, n);
+    if n <= counted.len() { Code(symbol.to_string().repeat(n)) } else { Code(counted) }
+}
+
+/// 把指针从from移到to
+fn movement(from: usize, to: usize) -> Code {
+    match to.cmp(&from) {
+        std::cmp::Ordering::Greater => repeated(Instruction::Right, to - from),
+        std::cmp::Ordering::Less => repeated(Instruction::Left, from - to),
+        std::cmp::Ordering::Equal => Code(String::new()),
+    }
+}
+
+/// 把当前单元格从from改为to
+fn change(from: u8, to: u8) -> Code {
+    let up = to.wrapping_sub(from) as usize;
+    let down = from.wrapping_sub(to) as usize;
+    let mut options = vec![repeated(Instruction::Increment, up), repeated(Instruction::Decrement, down), Code(format!("={{{}}}", to))];
+    if to == 0 {
+        options.push(Code("#".to_string()));
+    }
+    options.into_iter().min_by_key(Code::len).expect("there is always a way to change a cell")
+}
+
+/// 合成器状态：单元格的值与指针
+struct Synth {
+    values: Vec<u8>,
+    pointer: usize,
+    code: String,
+}
+
+impl Synth {
+    /// 输出count个byte的所有做法中最短的一种：(代码, 之后的指针, 修改的单元格)
+    fn best(&self, byte: u8, count: usize) -> (Code, usize, usize) {
+        let counter = self.values.len(); // 循环计数器在最右边，用完总是0
+        let mut best: Option<(Code, usize, usize)> = None;
+        let mut consider = |code: String, pointer: usize, cell: usize| {
+            if best.as_ref().is_none_or(|(b, _, _)| code.chars().count() < b.len()) {
+                best = Some((Code(code), pointer, cell));
+            }
+        };
+        for cell in 0..counter {
+            let mut routes = vec![format!("{}{}", movement(self.pointer, cell).0, change(self.values[cell], byte).0)];
+            // 从当前单元格复制到相邻单元格；停在计数器上时它是0
+            let current = self.values.get(self.pointer).copied().unwrap_or(0);
+            if cell == self.pointer + 1 {
+                routes.push(format!("$>{}", change(current, byte).0));
+            } else if cell + 1 == self.pointer {
+                routes.push(format!("£<{}", change(current, byte).0));
+            }
+            for route in routes {
+                consider(format!("{}{}", route, ".".repeat(count)), cell, cell);
+                if count > 1 {
+                    // 计数循环：计数器清零后停在计数器上
+                    let (there, back) = (movement(cell, counter).0, movement(counter, cell).0);
+                    let looped = format!("{}{}{}[{}.{}-]", route, there, repeated(Instruction::Increment, count).0, back, there);
+                    consider(looped, counter, cell);
+                }
+            }
+        }
+        best.expect("there is at least one cell")
+    }
+}
+
+/// 生成打印bytes的程序，使用cells个单元格
+pub fn synthesize(bytes: &[u8], cells: usize) -> String {
+    let mut synth = Synth { values: vec![0; cells.max(1)], pointer: 0, code: String::new() };
+    let mut rest = bytes;
+    while let Some(&byte) = rest.first() {
+        // 计数循环的计数器是一个字节
+        let count = rest.iter().take_while(|&&b| b == byte).count().min(255);
+        let (code, pointer, cell) = synth.best(byte, count);
+        synth.code.push_str(&code.0);
+        synth.values[cell] = byte;
+        synth.pointer = pointer;
+        rest = &rest[count..];
+    }
+    synth.code
+}
+
+/// 解释`\n`、`\t`、`\r`、`\0`、`\\`与`\xHH`
+pub fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some('r') => bytes.push(b'\r'),
+            Some('0') => bytes.push(0),
+            Some('\\') => bytes.push(b'\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let byte = u8::from_str_radix(&hex, 16).ok().filter(|_| hex.len() == 2);
+                bytes.push(byte.ok_or_else(|| format!("'\\x{}' is not a valid escape; use two hex digits such as \\x41", hex))?);
+            },
+            Some(other) => return Err(format!("Unknown escape '\\{}'; use \\n, \\t, \\r, \\0, \\\\ or \\xHH", other)),
+            None => return Err("The text ends with an unfinished escape '\\'".to_string()),
+        }
+    }
+    Ok(bytes)
+}
+
+/// `derstand synth [--cells N] [-o out] <text> | --file FILE`
+pub fn command(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: derstand synth [--cells N] [-o out] <text> | --file FILE";
+    let mut cells = DEFAULT_CELLS;
+    let mut output = None;
+    let mut text = None;
+    let mut file = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let parsed = match arg.as_str() {
+            "--cells" => iter.next().and_then(|v| v.parse::<usize>().ok()).filter(|&n| n > 0).map(|n| cells = n).ok_or("--cells expects a positive number".to_string()),
+            "--file" => iter.next().map(|path| file = Some(path.clone())).ok_or("Missing value for --file".to_string()),
+            "-o" | "--output" => iter.next().map(|path| output = Some(path.clone())).ok_or(format!("Missing value for {}", arg)),
+            _ if arg.starts_with('-') && arg.len() > 1 => Err(format!("Unknown option: {}", arg)),
+            _ if text.is_none() => {
+                text = Some(arg.clone());
+                Ok(())
+            },
+            _ => Err(USAGE.to_string()),
+        };
+        if let Err(e) = parsed {
+            eprintln!("{}", e);
+            return 2;
+        }
+    }
+    let bytes = match (text, file) {
+        (Some(text), None) => unescape(&text),
+        (None, Some(path)) => std::fs::read(&path).map_err(|e| format!("Error reading file {}: {}", path, e)),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        },
+    };
+    let program = match bytes {
+        Ok(bytes) => synthesize(&bytes, cells),
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        },
+    };
+    match output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, format!("{}\n", program)) {
+                eprintln!("Error writing file {}: {}", path, e);
+                return 1;
+            }
+        },
+        None => println!("{}", program),
+    }
+    0
+}
@@ -0,0 +1,182 @@
+//! 持久纸带 - `--tape-file data.bin`把第一条纸带映射到文件，内容在多次运行之间保留
+//!
+//! 运行前纸带取文件的内容，运行结束(包括出错)后改动的单元格写回映射并同步到磁盘，
+//! 程序因此可以像操作一个小数据库一样维护持久状态：
+//!
+//! ```text
+//! $ derstand run --tape-file counter.bin count.dr    # 每次运行把单元格0加一
+//! ```
+//!
+//! 纸带大小取`--memory`，否则取已有文件的长度，都没有时为默认的30000格；源码中的`;! memory`不起作用。
+//! 文件短于纸带时补0，长于`--memory`时报错而不截断。64位Unix上用mmap，其他平台整文件读写。
+
+use std::fs::OpenOptions;
+use std::path::Path;
+
+use crate::{DerstandInterpreter, MEMORY_SIZE, pragma};
+
+/// 映射到文件的纸带内容
+pub struct TapeFile {
+    map: sys::Map,
+}
+
+impl TapeFile {
+    /// 打开或创建path；size见模块说明
+    pub fn open(path: &Path, size: Option<usize>) -> Result<Self, String> {
+        let error = |e: String| format!("Cannot use tape file {}: {}", path.display(), e);
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path).map_err(|e| error(e.to_string()))?;
+        let existing = file.metadata().map_err(|e| error(e.to_string()))?.len();
+        let existing = usize::try_from(existing).unwrap_or(usize::MAX);
+        let size = match size {
+            Some(size) if existing > size => return Err(error(format!("the file holds {} bytes, more than the {} cells of --memory", existing, size))),
+            Some(size) => size,
+            None if existing > pragma::MAX_MEMORY_SIZE => return Err(error(format!("the file holds {} bytes, more than the largest tape ({} cells)", existing, pragma::MAX_MEMORY_SIZE))),
+            None if existing > 0 => existing,
+            None => MEMORY_SIZE,
+        };
+        if existing < size {
+            file.set_len(size as u64).map_err(|e| error(e.to_string()))?;
+        }
+        let map = sys::Map::new(file, size).map_err(|e| error(e.to_string()))?;
+        Ok(TapeFile { map })
+    }
+
+    /// 纸带的单元格数
+    pub fn len(&self) -> usize {
+        self.contents().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contents().is_empty()
+    }
+
+    pub fn contents(&self) -> &[u8] {
+        self.map.bytes()
+    }
+
+    /// 写回memory中改动的单元格并同步到磁盘
+    pub fn store(&mut self, memory: &[u8]) -> Result<(), String> {
+        if memory.len() != self.len() {
+            return Err(format!("Cannot store a tape of {} cells in a tape file of {}", memory.len(), self.len()));
+        }
+        // 只写改动的字节，未改动的页不会变脏
+        for (cell, &value) in self.map.bytes_mut().iter_mut().zip(memory) {
+            if *cell != value {
+                *cell = value;
+            }
+        }
+        self.map.flush().map_err(|e| format!("Error writing tape file: {}", e))
+    }
+}
+
+impl DerstandInterpreter {
+    /// 以tape的内容作为第一条纸带；应先`set_memory_size(tape.len())`再编译
+    pub fn load_tape(&mut self, tape: &TapeFile) {
+        let memory = self.first_tape();
+        memory.clear();
+        memory.extend_from_slice(tape.contents());
+        if self.active_tape == 0 {
+            self.pointer = self.pointer.min(tape.len() - 1);
+        }
+    }
+
+    /// 把第一条纸带写回tape
+    pub fn store_tape(&mut self, tape: &mut TapeFile) -> Result<(), String> {
+        tape.store(self.first_tape())
+    }
+}
+
+#[cfg(all(unix, target_pointer_width = "64"))]
+mod sys {
+    use std::ffi::{c_int, c_void};
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    const PROT_READ: c_int = 1;
+    const PROT_WRITE: c_int = 2;
+    const MAP_SHARED: c_int = 1;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const MS_SYNC: c_int = 4;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const MS_SYNC: c_int = 0x10;
+
+    unsafe extern "C" {
+        fn mmap(addr: *mut c_void, len: usize, prot: c_int, flags: c_int, fd: c_int, offset: i64) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+        fn msync(addr: *mut c_void, len: usize, flags: c_int) -> c_int;
+    }
+
+    pub struct Map {
+        ptr: *mut u8,
+        len: usize,
+        _file: File, // 映射期间保持文件打开
+    }
+
+    impl Map {
+        pub fn new(file: File, len: usize) -> io::Result<Self> {
+            // SAFETY: 映射整个已扩展到len字节的文件；失败时返回MAP_FAILED
+            let ptr = unsafe { mmap(std::ptr::null_mut(), len, PROT_READ | PROT_WRITE, MAP_SHARED, file.as_raw_fd(), 0) };
+            if ptr as isize == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Map { ptr: ptr as *mut u8, len, _file: file })
+        }
+
+        pub fn bytes(&self) -> &[u8] {
+            // SAFETY: ptr指向len字节的有效映射，直到drop
+            unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+        }
+
+        pub fn bytes_mut(&mut self) -> &mut [u8] {
+            // SAFETY: 同上，且&mut self保证独占
+            unsafe { std::slice::from_raw_parts_mut(self.ptr, self.len) }
+        }
+
+        pub fn flush(&mut self) -> io::Result<()> {
+            // SAFETY: 同步整个有效映射
+            if unsafe { msync(self.ptr as *mut c_void, self.len, MS_SYNC) } == 0 { Ok(()) } else { Err(io::Error::last_os_error()) }
+        }
+    }
+
+    impl Drop for Map {
+        fn drop(&mut self) {
+            // SAFETY: 映射来自mmap，之后不再使用
+            unsafe { munmap(self.ptr as *mut c_void, self.len) };
+        }
+    }
+}
+
+#[cfg(not(all(unix, target_pointer_width = "64")))]
+mod sys {
+    use std::fs::File;
+    use std::io::{self, Read, Seek, SeekFrom, Write};
+
+    /// 无mmap时整文件读入，同步时整文件写回
+    pub struct Map {
+        data: Vec<u8>,
+        file: File,
+    }
+
+    impl Map {
+        pub fn new(mut file: File, len: usize) -> io::Result<Self> {
+            let mut data = vec![0; len];
+            file.read_exact(&mut data)?;
+            Ok(Map { data, file })
+        }
+
+        pub fn bytes(&self) -> &[u8] {
+            &self.data
+        }
+
+        pub fn bytes_mut(&mut self) -> &mut [u8] {
+            &mut self.data
+        }
+
+        pub fn flush(&mut self) -> io::Result<()> {
+            self.file.seek(SeekFrom::Start(0))?;
+            self.file.write_all(&self.data)?;
+            self.file.sync_data()
+        }
+    }
+}
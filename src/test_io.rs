@@ -0,0 +1,113 @@
+//! 测试用的内存输入输出 - 脚本化的输入、捕获的输出与可编排的输入结束
+//!
+//! `TestIo`的克隆共享同一份状态：交给解释器后仍可从原对象读取输出。输入按脚本依次给出字节、
+//! 输入结束或读取错误；脚本用完后每次读取都是输入结束。
+//!
+//! ```text
+//! use derstand::{DerstandInterpreter, Dialect};
+//! use derstand::test_io::TestIo;
+//!
+//! let io = TestIo::new().input(b"ab").eof().input(b"c");
+//! let mut interpreter = DerstandInterpreter::new().with_io(&io);
+//! interpreter.set_dialect(Dialect::Extended);
+//! interpreter.compile(",.,.,:,.").unwrap();
+//! interpreter.execute().unwrap();
+//! assert_eq!(io.output(), b"ab0c");
+//! ```
+
+use alloc::collections::VecDeque;
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use crate::host::{Input, Output};
+
+/// 输入脚本中的一项
+#[derive(Debug, Clone, PartialEq)]
+enum Event {
+    Byte(u8),
+    Eof,
+    Error(String),
+}
+
+#[derive(Debug, Default)]
+struct State {
+    script: VecDeque<Event>,
+    output: Vec<u8>,
+    reads: usize, // 读取次数，含读到输入结束与错误
+}
+
+/// 见模块说明
+#[derive(Debug, Clone, Default)]
+pub struct TestIo {
+    state: Rc<RefCell<State>>,
+}
+
+impl TestIo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 追加输入字节
+    pub fn input(self, bytes: &[u8]) -> Self {
+        self.state.borrow_mut().script.extend(bytes.iter().map(|&b| Event::Byte(b)));
+        self
+    }
+
+    /// 追加一次输入结束；之后的字节仍可继续读取，模拟终端上的Ctrl-D
+    pub fn eof(self) -> Self {
+        self.state.borrow_mut().script.push_back(Event::Eof);
+        self
+    }
+
+    /// 追加一次读取错误，成为运行时错误E0102
+    pub fn error(self, message: &str) -> Self {
+        self.state.borrow_mut().script.push_back(Event::Error(message.into()));
+        self
+    }
+
+    /// 至今捕获的输出
+    pub fn output(&self) -> Vec<u8> {
+        self.state.borrow().output.clone()
+    }
+
+    /// 捕获的输出按UTF-8解码，无效字节替换为U+FFFD
+    pub fn output_string(&self) -> String {
+        String::from_utf8_lossy(&self.state.borrow().output).into_owned()
+    }
+
+    /// 清空捕获的输出
+    pub fn clear_output(&self) {
+        self.state.borrow_mut().output.clear();
+    }
+
+    /// 程序读取输入的次数
+    pub fn reads(&self) -> usize {
+        self.state.borrow().reads
+    }
+
+    /// 脚本中尚未读取的字节数
+    pub fn remaining_input(&self) -> usize {
+        self.state.borrow().script.iter().filter(|e| matches!(e, Event::Byte(_))).count()
+    }
+}
+
+impl Input for TestIo {
+    fn read_byte(&mut self) -> Result<Option<u8>, String> {
+        let mut state = self.state.borrow_mut();
+        state.reads += 1;
+        match state.script.pop_front() {
+            Some(Event::Byte(byte)) => Ok(Some(byte)),
+            Some(Event::Eof) | None => Ok(None),
+            Some(Event::Error(message)) => Err(message),
+        }
+    }
+}
+
+impl Output for TestIo {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), String> {
+        self.state.borrow_mut().output.extend_from_slice(bytes);
+        Ok(())
+    }
+}
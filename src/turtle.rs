@@ -0,0 +1,128 @@
+//! 海龟绘图 - `--turtle out.svg`让程序驱动一只海龟画线，运行结束后写出SVG
+//!
+//! 三条指令都读当前单元格：
+//!
+//! ```text
+//! ⇡  前进单元格值(0..255)个单位，落笔时留下线段
+//! ↻  顺时针转单元格值度；按有符号数解释，值166(-90)即左转90度
+//! ✎  单元格非0时落笔，为0时抬笔
+//! ```
+//!
+//! 海龟从原点出发，朝上，落笔。SVG的画布按所有线段的范围确定，四周留出边距：
+//!
+//! ```text
+//! $ derstand run --turtle square.svg square.dr
+//! ```
+
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use crate::DerstandInterpreter;
+
+/// 前进符号
+pub const FORWARD: char = '⇡';
+/// 转向符号
+pub const TURN: char = '↻';
+/// 落笔/抬笔符号
+pub const PEN: char = '✎';
+
+/// 画布四周的边距
+const MARGIN: f64 = 4.0;
+
+/// 海龟的位置、朝向与画下的折线
+#[derive(Debug, Clone, PartialEq)]
+pub struct Turtle {
+    x: f64,
+    y: f64,
+    heading: i32, // 度，0朝上，顺时针为正
+    pen: bool,
+    lines: Vec<Vec<(f64, f64)>>,
+}
+
+impl Default for Turtle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Turtle {
+    pub fn new() -> Self {
+        Turtle { x: 0.0, y: 0.0, heading: 0, pen: true, lines: Vec::new() }
+    }
+
+    pub fn forward(&mut self, distance: f64) {
+        let radians = (self.heading as f64).to_radians();
+        let start = (self.x, self.y);
+        self.x += distance * radians.sin();
+        self.y -= distance * radians.cos();
+        if !self.pen || distance == 0.0 {
+            return;
+        }
+        // 接着上一段画时延长同一条折线
+        match self.lines.last_mut() {
+            Some(line) if line.last() == Some(&start) => line.push((self.x, self.y)),
+            _ => self.lines.push(vec![start, (self.x, self.y)]),
+        }
+    }
+
+    pub fn turn(&mut self, degrees: i32) {
+        self.heading = (self.heading + degrees).rem_euclid(360);
+    }
+
+    pub fn set_pen(&mut self, down: bool) {
+        self.pen = down;
+    }
+
+    /// 画下的所有线段组成的SVG文档
+    pub fn svg(&self) -> String {
+        let points = self.lines.iter().flatten();
+        let (mut left, mut top, mut right, mut bottom) = (0.0f64, 0.0f64, 0.0f64, 0.0f64);
+        for &(x, y) in points {
+            (left, top, right, bottom) = (left.min(x), top.min(y), right.max(x), bottom.max(y));
+        }
+        let (width, height) = (right - left + 2.0 * MARGIN, bottom - top + 2.0 * MARGIN);
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\" width=\"{}\" height=\"{}\">\n",
+            number(left - MARGIN),
+            number(top - MARGIN),
+            number(width),
+            number(height),
+            number(width),
+            number(height)
+        );
+        for line in &self.lines {
+            let points: Vec<String> = line.iter().map(|&(x, y)| format!("{},{}", number(x), number(y))).collect();
+            let _ = writeln!(svg, "  <polyline points=\"{}\" fill=\"none\" stroke=\"black\" stroke-width=\"1\"/>", points.join(" "));
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
+
+/// 保留两位小数，去掉多余的0与负零
+fn number(value: f64) -> String {
+    let rounded = (value * 100.0).round() / 100.0 + 0.0;
+    format!("{}", rounded)
+}
+
+/// 在interpreter上注册三条海龟指令，返回共享的海龟；应在编译之前调用
+pub fn attach(interpreter: &mut DerstandInterpreter) -> Result<Rc<RefCell<Turtle>>, String> {
+    let turtle = Rc::new(RefCell::new(Turtle::new()));
+    let state = turtle.clone();
+    interpreter.register_instruction(FORWARD, "forward", Box::new(move |machine| {
+        state.borrow_mut().forward(machine.cell() as f64);
+        Ok(())
+    }))?;
+    let state = turtle.clone();
+    interpreter.register_instruction(TURN, "turn", Box::new(move |machine| {
+        state.borrow_mut().turn(machine.cell() as i8 as i32);
+        Ok(())
+    }))?;
+    let state = turtle.clone();
+    interpreter.register_instruction(PEN, "pen", Box::new(move |machine| {
+        state.borrow_mut().set_pen(machine.cell() != 0);
+        Ok(())
+    }))?;
+    Ok(turtle)
+}
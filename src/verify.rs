@@ -0,0 +1,305 @@
+//! 翻译验证 - `derstand verify`把程序经各个后端翻译后运行，与参考解释器的输出逐字节比较
+//!
+//! 每个程序先由解释器在上限内运行一次作为参考，再依次经过选定的后端：`dbc`保存后重新加载，
+//! `bf`导出后由解释器按Brainfuck运行，`c`与`rust`调用系统编译器生成可执行文件后运行。
+//! 所有运行使用相同的输入(同名`.in`文件，或`--input`指定的文件)。输出不同，或参考运行出错而翻译后的
+//! 程序正常结束(反之亦然)即为不一致，报告第一个不同的字节。
+//!
+//! 用到`*`或'`'的程序结果不确定、参考运行超限的程序没有可比较的结果，都跳过；后端无法表达的程序
+//! (如`bf`遇到栈指令)只对该后端跳过。
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::compile::{self, Target, bf};
+use crate::golden::{self, TestOptions};
+use crate::test_io::TestIo;
+use crate::{DerstandInterpreter, EofBehavior, Instruction, bytecode};
+
+/// 默认验证的后端
+const DEFAULT_TARGETS: &[Target] = &[Target::Bytecode, Target::Brainfuck, Target::C, Target::Rust];
+
+/// 本进程中已开始的验证次数，用于区分临时目录
+static RUNS: AtomicUsize = AtomicUsize::new(0);
+
+/// 一次运行的结果：输出与是否以错误结束
+#[derive(Debug, Clone, PartialEq)]
+struct Outcome {
+    output: Vec<u8>,
+    failed: bool,
+}
+
+/// 一个后端的验证结果
+enum Verdict {
+    Match,
+    Skipped(String),
+    Mismatch(String),
+}
+
+/// 待验证的程序与它的参考运行
+struct Subject<'a> {
+    interpreter: &'a DerstandInterpreter,
+    source: &'a str,
+    name: &'a str,
+    input: &'a [u8],
+    expected: &'a Outcome,
+}
+
+/// 验证选项
+struct Options {
+    targets: Vec<Target>,
+    input: Option<PathBuf>,
+    cc: String,
+    rustc: String,
+    limits: TestOptions,
+}
+
+fn target_name(target: Target) -> &'static str {
+    match target {
+        Target::C => "c",
+        Target::Rust => "rust",
+        Target::Brainfuck => "bf",
+        Target::Bytecode => "dbc",
+        _ => "?",
+    }
+}
+
+/// 在解释器中运行；超限时返回Err
+fn interpret(interpreter: &mut DerstandInterpreter, io: &TestIo, limits: TestOptions) -> Result<Outcome, String> {
+    let error = golden::execute(interpreter, limits)?;
+    Ok(Outcome { output: io.output(), failed: error.is_some() })
+}
+
+/// 运行可执行文件，超时则结束它
+fn run_native(executable: &Path, input: &[u8], limits: TestOptions) -> Result<Outcome, String> {
+    let mut child = Command::new(executable)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("cannot run {}: {}", executable.display(), e))?;
+    // 程序可能不读完输入就退出，写入失败不算错误
+    let _ = child.stdin.take().expect("stdin is piped").write_all(input);
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+    let reader = std::thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = std::io::Read::read_to_end(&mut stdout, &mut output);
+        output
+    });
+    let deadline = Instant::now() + Duration::from_millis(limits.timeout_ms);
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("time limit of {} ms exceeded", limits.timeout_ms));
+            },
+            Ok(None) => std::thread::sleep(Duration::from_millis(5)),
+            Err(e) => return Err(e.to_string()),
+        }
+    };
+    let output = reader.join().unwrap_or_default();
+    Ok(Outcome { output, failed: !status.success() })
+}
+
+/// 翻译为C或Rust并用系统编译器生成可执行文件
+fn build_native(target: Target, subject: &Subject, directory: &Path, options: &Options) -> Result<PathBuf, Verdict> {
+    let code = compile::render(target, subject.interpreter, subject.source, subject.name).map_err(|e| Verdict::Skipped(e.message.clone()))?;
+    let (extension, compiler, flags): (&str, &str, &[&str]) = match target {
+        Target::C => ("c", &options.cc, &["-O1"]),
+        _ => ("rs", &options.rustc, &["--edition", "2021"]),
+    };
+    let file = directory.join(format!("program.{}", extension));
+    let executable = directory.join(format!("program-{}{}", extension, std::env::consts::EXE_SUFFIX));
+    std::fs::write(&file, code).map_err(|e| Verdict::Skipped(format!("cannot write {}: {}", file.display(), e)))?;
+    let result = Command::new(compiler).args(flags).arg("-o").arg(&executable).arg(&file).stderr(Stdio::piped()).output();
+    match result {
+        Ok(result) if result.status.success() => Ok(executable),
+        // 生成的代码编译不过是翻译器的错误
+        Ok(result) => Err(Verdict::Mismatch(format!("{} rejected the generated code:\n{}", compiler, String::from_utf8_lossy(&result.stderr).trim_end()))),
+        Err(e) => Err(Verdict::Skipped(format!("cannot run {}: {}", compiler, e))),
+    }
+}
+
+/// 两次运行的第一处不同
+fn compare(expected: &Outcome, actual: &Outcome) -> Option<String> {
+    if let Some(offset) = (0..expected.output.len().max(actual.output.len())).find(|&i| expected.output.get(i) != actual.output.get(i)) {
+        let line = expected.output[..offset.min(expected.output.len())].iter().filter(|&&b| b == b'\n').count() + 1;
+        let show = |output: &[u8]| output.get(offset).map_or("end of output".to_string(), |b| format!("{:?}", *b as char));
+        return Some(format!("output differs at byte {} (line {}): expected {}, got {}", offset, line, show(&expected.output), show(&actual.output)));
+    }
+    match (expected.failed, actual.failed) {
+        (true, false) => Some("the reference run failed but the translated program finished normally".to_string()),
+        (false, true) => Some("the translated program failed but the reference run finished normally".to_string()),
+        _ => None,
+    }
+}
+
+/// 经一个后端运行并与参考结果比较
+fn check(target: Target, subject: &Subject, directory: &Path, options: &Options) -> Verdict {
+    let Subject { interpreter, source, name, input, expected } = *subject;
+    let actual = match target {
+        Target::Bytecode => {
+            let io = TestIo::new().input(input);
+            let mut loaded = DerstandInterpreter::new().with_io(&io);
+            if let Err(e) = bytecode::load(&mut loaded, &bytecode::save(interpreter, source, name)) {
+                return Verdict::Mismatch(format!("the saved bytecode does not load: {}", e));
+            }
+            interpret(&mut loaded, &io, options.limits)
+        },
+        Target::Brainfuck => {
+            let code = match compile::render(target, interpreter, source, name) {
+                Ok(code) => String::from_utf8_lossy(&code).into_owned(),
+                Err(e) => return Verdict::Skipped(e.message.clone()),
+            };
+            let io = TestIo::new().input(input);
+            let mut bf = DerstandInterpreter::new().with_io(&io);
+            // 按标准解释器运行：30000格的纸带，指针越过两端即失败而不是钳制
+            bf.set_memory_size(bf::TAPE);
+            bf.set_strict_bounds(true);
+            // Brainfuck没有规定输入结束时的行为，按原程序的设置运行
+            bf.set_eof_behavior(interpreter.eof_behavior().unwrap_or(EofBehavior::Zero));
+            if let Err(e) = bf.compile(&code) {
+                return Verdict::Mismatch(format!("the exported Brainfuck does not compile: {}", e.message));
+            }
+            interpret(&mut bf, &io, options.limits)
+        },
+        _ => match build_native(target, subject, directory, options) {
+            Ok(executable) => run_native(&executable, input, options.limits),
+            Err(verdict) => return verdict,
+        },
+    };
+    match actual {
+        Ok(actual) => compare(expected, &actual).map_or(Verdict::Match, Verdict::Mismatch),
+        Err(e) => Verdict::Mismatch(e),
+    }
+}
+
+/// 验证一个程序；返回是否没有不一致
+fn verify(program: &Path, options: &Options, directory: &Path) -> bool {
+    let name = program.display().to_string();
+    let fail = |reason: String| {
+        println!("verify {} ... FAILED: {}", name, reason);
+        false
+    };
+    let source = match std::fs::read_to_string(program) {
+        Ok(source) => source,
+        Err(e) => return fail(format!("cannot read it: {}", e)),
+    };
+    let input_path = Some(program.with_extension("in")).filter(|p| p.is_file()).or_else(|| options.input.clone());
+    let input = match input_path.map(std::fs::read).transpose() {
+        Ok(input) => input.unwrap_or_default(),
+        Err(e) => return fail(format!("cannot read its input: {}", e)),
+    };
+    let io = TestIo::new().input(&input);
+    let mut interpreter = DerstandInterpreter::new().with_io(&io);
+    interpreter.set_source_path(program);
+    if let Err(e) = interpreter.compile(&source) {
+        return fail(format!("does not compile: {}", e.message));
+    }
+    // 超限的程序没有可比较的参考结果
+    let expected = match interpret(&mut interpreter, &io, options.limits) {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            println!("verify {} ... skipped (reference run stopped: {})", name, e);
+            return true;
+        },
+    };
+    if interpreter.instructions().iter().any(|i| matches!(i, Instruction::Random | Instruction::Time)) {
+        println!("verify {} ... skipped (uses '*' or '`', so its output is not reproducible)", name);
+        return true;
+    }
+
+    let mut matched = Vec::new();
+    let mut skipped = Vec::new();
+    for &target in &options.targets {
+        let subject = Subject { interpreter: &interpreter, source: &source, name: &name, input: &input, expected: &expected };
+        match check(target, &subject, directory, options) {
+            Verdict::Match => matched.push(target_name(target)),
+            Verdict::Skipped(reason) => skipped.push(format!("{}: {}", target_name(target), reason)),
+            Verdict::Mismatch(reason) => return fail(format!("{} backend: {}", target_name(target), reason)),
+        }
+    }
+    println!("verify {} ... ok ({})", name, if matched.is_empty() { "no backend applies".to_string() } else { matched.join(", ") });
+    for reason in skipped {
+        println!("    skipped {}", reason);
+    }
+    true
+}
+
+/// `derstand verify`子命令
+pub fn command(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: derstand verify [--targets dbc,bf,c,rust] [--input FILE] [--cc PATH] [--max-steps N] [--timeout MS] <file|directory>...";
+    let mut options = Options {
+        targets: DEFAULT_TARGETS.to_vec(),
+        input: None,
+        cc: std::env::var("CC").unwrap_or_else(|_| "cc".to_string()),
+        rustc: std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()),
+        limits: TestOptions::default(),
+    };
+    let mut paths = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let positive = |value: Option<&String>| value.and_then(|v| v.parse::<u64>().ok()).filter(|&n| n > 0);
+        let parsed = match arg.as_str() {
+            "--targets" => iter.next().ok_or("Missing value for --targets".to_string()).and_then(|list| {
+                let targets = list.split(',').map(Target::parse).collect::<Result<Vec<_>, _>>()?;
+                if targets.iter().any(|t| !DEFAULT_TARGETS.contains(t)) {
+                    return Err("verify supports the dbc, bf, c and rust targets".to_string());
+                }
+                options.targets = targets;
+                Ok(())
+            }),
+            "--input" => iter.next().ok_or("Missing value for --input".to_string()).map(|path| options.input = Some(path.into())),
+            "--cc" => iter.next().ok_or("Missing value for --cc".to_string()).map(|path| options.cc = path.clone()),
+            "--max-steps" => positive(iter.next()).map(|n| options.limits.max_steps = n).ok_or("--max-steps expects a positive number".to_string()),
+            "--timeout" => positive(iter.next())
+                .map(|n| options.limits.timeout_ms = n)
+                .ok_or("--timeout expects a positive number of milliseconds".to_string()),
+            _ if arg.starts_with("--") => Err(format!("Unknown option: {}", arg)),
+            _ => {
+                paths.push(PathBuf::from(arg));
+                Ok(())
+            },
+        };
+        if let Err(e) = parsed {
+            eprintln!("{}", e);
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+    }
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+        return 2;
+    }
+    let mut programs = Vec::new();
+    for path in &paths {
+        if path.is_dir() {
+            match golden::programs(path) {
+                Ok(found) => programs.extend(found),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return 2;
+                },
+            }
+        } else {
+            programs.push(path.clone());
+        }
+    }
+
+    // 同一进程中可能同时有多次验证(如并行的测试)，各用一个目录
+    let run = RUNS.fetch_add(1, Ordering::Relaxed);
+    let directory = std::env::temp_dir().join(format!("derstand-verify-{}-{}", std::process::id(), run));
+    if let Err(e) = std::fs::create_dir_all(&directory) {
+        eprintln!("Error creating {}: {}", directory.display(), e);
+        return 1;
+    }
+    let failed = programs.iter().filter(|program| !verify(program, &options, &directory)).count();
+    let _ = std::fs::remove_dir_all(&directory);
+    println!("\nverify result: {}. {} program(s), {} inconsistent", if failed == 0 { "ok" } else { "FAILED" }, programs.len(), failed);
+    if failed == 0 { 0 } else { 1 }
+}
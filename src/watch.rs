@@ -0,0 +1,117 @@
+//! 监视运行 - `derstand watch prog.dr`在源文件每次保存后重新编译并运行
+//!
+//! 默认每次都从空纸带开始。`--keep-tape`是热重载：新编译的程序在上一次运行留下的纸带与指针上从头执行，
+//! 边改程序边探索状态时不必每次重建。运行中保存文件会取消这次运行(纸带停在取消时的样子)并立即重新加载。
+//! 交互式模式中的`:load FILE`以同样的方式在当前纸带上运行一个文件。
+//!
+//! ```text
+//! $ derstand watch --keep-tape explore.dr
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::cancel::CancelToken;
+use crate::diagnostic::stderr_color;
+use crate::{DerstandInterpreter, runtime_error};
+
+/// 检查文件修改时间的间隔
+const POLL: Duration = Duration::from_millis(200);
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn fresh(file: &str) -> DerstandInterpreter {
+    let mut interpreter = DerstandInterpreter::new();
+    interpreter.set_interactive(true);
+    interpreter.set_source_path(file);
+    interpreter
+}
+
+/// 读取、编译并运行一次；keep_tape时保留纸带与指针
+fn run_once(interpreter: &mut DerstandInterpreter, file: &str, keep_tape: bool, token: &CancelToken) {
+    let source = match std::fs::read_to_string(file) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("Error reading file {}: {}", file, e);
+            return;
+        },
+    };
+    if let Err(e) = interpreter.compile(&source) {
+        eprintln!("{}", e.render(&source, Some(file), stderr_color()));
+        return;
+    }
+    interpreter.set_cancel_token(Some(token.clone()));
+    let result = if keep_tape { interpreter.execute_in_place() } else { interpreter.execute() };
+    match result {
+        Ok(()) => eprintln!("\n--- finished after {} steps with the pointer at {} ---", interpreter.steps, interpreter.pointer()),
+        // 运行中文件被修改
+        Err(_) if token.is_cancelled() => eprintln!("\n--- {} changed, stopped after {} steps ---", file, interpreter.steps),
+        Err(e) => eprintln!("\n{}", runtime_error(interpreter, &source, e).render(&source, Some(file), stderr_color())),
+    }
+}
+
+/// `derstand watch [--keep-tape] <file>`
+pub fn command(args: &[String]) -> i32 {
+    const USAGE: &str = "Usage: derstand watch [--keep-tape] <file>";
+    let (file, keep_tape) = match args {
+        [file] if !file.starts_with('-') => (file.clone(), false),
+        [flag, file] | [file, flag] if flag == "--keep-tape" && !file.starts_with('-') => (file.clone(), true),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        },
+    };
+    let path = PathBuf::from(&file);
+    if modified(&path).is_none() {
+        eprintln!("File not found: {}", file);
+        return 1;
+    }
+
+    // 监视线程在文件变化时取消当前的运行并通知主线程
+    let current = Arc::new(Mutex::new(CancelToken::new()));
+    let (sender, changes) = mpsc::channel();
+    {
+        let current = Arc::clone(&current);
+        thread::spawn(move || {
+            let mut last = modified(&path);
+            loop {
+                thread::sleep(POLL);
+                let now = modified(&path);
+                if now != last {
+                    last = now;
+                    current.lock().unwrap_or_else(|e| e.into_inner()).cancel();
+                    if sender.send(()).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+
+    let mut interpreter = fresh(&file);
+    let mut first = true;
+    loop {
+        if first {
+            eprintln!("--- running {} ---", file);
+        } else if keep_tape {
+            eprintln!("--- reloading {} on the kept tape, pointer at {} ---", file, interpreter.pointer());
+        } else {
+            eprintln!("--- rerunning {} ---", file);
+            interpreter = fresh(&file);
+        }
+        let token = CancelToken::new();
+        *current.lock().unwrap_or_else(|e| e.into_inner()) = token.clone();
+        run_once(&mut interpreter, &file, keep_tape, &token);
+        first = false;
+        if changes.recv().is_err() {
+            return 0;
+        }
+        // 一次保存可能触发多次修改
+        while changes.try_recv().is_ok() {}
+    }
+}
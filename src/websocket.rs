@@ -0,0 +1,109 @@
+//! WebSocket(RFC 6455)服务端 - 只实现`derstand serve`的远程REPL需要的部分
+//!
+//! 握手由HTTP服务完成，这里只计算应答密钥并读写帧。客户端发来的帧必须带掩码，
+//! 服务端发出的帧不带掩码；分片消息按帧逐个交给调用者。
+
+use std::io::{self, Read, Write};
+
+use crate::digest::{base64, sha1};
+
+/// 帧类型
+pub const CONTINUATION: u8 = 0x0;
+pub const TEXT: u8 = 0x1;
+pub const BINARY: u8 = 0x2;
+pub const CLOSE: u8 = 0x8;
+pub const PING: u8 = 0x9;
+pub const PONG: u8 = 0xA;
+
+/// 关闭码
+pub const NORMAL_CLOSURE: u16 = 1000;
+pub const PROTOCOL_ERROR: u16 = 1002;
+pub const MESSAGE_TOO_BIG: u16 = 1009;
+
+/// 单帧负载的上限
+pub const MAX_PAYLOAD: usize = 1 << 20;
+
+/// 握手中固定的GUID
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// 由客户端的Sec-WebSocket-Key计算Sec-WebSocket-Accept
+pub fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key.trim(), GUID).as_bytes()))
+}
+
+/// 一帧：是否是消息的最后一帧、类型与(已去掩码的)负载
+#[derive(Debug)]
+pub struct Frame {
+    pub fin: bool,
+    pub opcode: u8,
+    pub payload: Vec<u8>,
+}
+
+/// 读取客户端发来的一帧；格式错误时返回InvalidData，负载过大时返回Other
+pub fn read_frame(stream: &mut impl Read) -> io::Result<Frame> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let mut head = [0u8; 2];
+    stream.read_exact(&mut head)?;
+    if head[0] & 0x70 != 0 {
+        return Err(invalid("reserved bits set without a negotiated extension"));
+    }
+    if head[1] & 0x80 == 0 {
+        return Err(invalid("client frames must be masked"));
+    }
+    let (fin, opcode) = (head[0] & 0x80 != 0, head[0] & 0x0F);
+    let length = match head[1] & 0x7F {
+        126 => {
+            let mut size = [0u8; 2];
+            stream.read_exact(&mut size)?;
+            u16::from_be_bytes(size) as u64
+        },
+        127 => {
+            let mut size = [0u8; 8];
+            stream.read_exact(&mut size)?;
+            u64::from_be_bytes(size)
+        },
+        n => n as u64,
+    };
+    // 控制帧不能分片，负载不超过125字节
+    if opcode >= CLOSE && (!fin || length > 125) {
+        return Err(invalid("invalid control frame"));
+    }
+    if length > MAX_PAYLOAD as u64 {
+        return Err(io::Error::other(format!("frame of {} bytes exceeds the {} byte limit", length, MAX_PAYLOAD)));
+    }
+    let mut mask = [0u8; 4];
+    stream.read_exact(&mut mask)?;
+    let mut payload = vec![0u8; length as usize];
+    stream.read_exact(&mut payload)?;
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+    Ok(Frame { fin, opcode, payload })
+}
+
+/// 写出一帧(不分片、不带掩码)
+pub fn write_frame(stream: &mut impl Write, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    let mut out = Vec::with_capacity(payload.len() + 10);
+    out.push(0x80 | opcode);
+    match payload.len() {
+        n @ 0..=125 => out.push(n as u8),
+        n @ 126..=0xFFFF => {
+            out.push(126);
+            out.extend_from_slice(&(n as u16).to_be_bytes());
+        },
+        n => {
+            out.push(127);
+            out.extend_from_slice(&(n as u64).to_be_bytes());
+        },
+    }
+    out.extend_from_slice(payload);
+    stream.write_all(&out)?;
+    stream.flush()
+}
+
+/// 写出关闭帧
+pub fn write_close(stream: &mut impl Write, code: u16, reason: &str) -> io::Result<()> {
+    let mut payload = code.to_be_bytes().to_vec();
+    payload.extend_from_slice(reason.as_bytes());
+    write_frame(stream, CLOSE, &payload)
+}
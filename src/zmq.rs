@@ -0,0 +1,232 @@
+//! ZeroMQ传输(ZMTP 3.0) - 只实现Jupyter内核需要的部分
+//!
+//! 只支持TCP与NULL安全机制，套接字只能绑定、等待对端连接。ROUTER收到的消息以对端的
+//! 路由标识开头，发送时按第一帧选择对端；PUB把消息发给所有订阅者(订阅过滤由对端的SUB
+//! 完成)；REP原样回显收到的消息，正好满足心跳。
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// 多帧消息
+pub type Message = Vec<Vec<u8>>;
+
+/// 帧标志位
+const MORE: u8 = 1;
+const LONG: u8 = 2;
+const COMMAND: u8 = 4;
+
+/// 套接字类型
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SocketType {
+    Router,
+    Pub,
+    Rep,
+}
+
+impl SocketType {
+    fn name(self) -> &'static str {
+        match self {
+            SocketType::Router => "ROUTER",
+            SocketType::Pub => "PUB",
+            SocketType::Rep => "REP",
+        }
+    }
+}
+
+/// 写出一帧
+fn write_frame(out: &mut Vec<u8>, flags: u8, body: &[u8]) {
+    if body.len() > u8::MAX as usize {
+        out.push(flags | LONG);
+        out.extend_from_slice(&(body.len() as u64).to_be_bytes());
+    } else {
+        out.push(flags);
+        out.push(body.len() as u8);
+    }
+    out.extend_from_slice(body);
+}
+
+/// 读出一帧，返回标志与内容
+fn read_frame(stream: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut flags = [0u8];
+    stream.read_exact(&mut flags)?;
+    let size = if flags[0] & LONG != 0 {
+        let mut size = [0u8; 8];
+        stream.read_exact(&mut size)?;
+        u64::from_be_bytes(size) as usize
+    } else {
+        let mut size = [0u8];
+        stream.read_exact(&mut size)?;
+        size[0] as usize
+    };
+    let mut body = vec![0u8; size];
+    stream.read_exact(&mut body)?;
+    Ok((flags[0], body))
+}
+
+/// 读出一条消息，跳过命令帧(订阅、心跳等)
+pub fn read_message(stream: &mut impl Read) -> io::Result<Message> {
+    let mut message = Vec::new();
+    loop {
+        let (flags, body) = read_frame(stream)?;
+        if flags & COMMAND != 0 {
+            continue;
+        }
+        message.push(body);
+        if flags & MORE == 0 {
+            return Ok(message);
+        }
+    }
+}
+
+/// 写出一条消息
+pub fn write_message(stream: &mut impl Write, message: &[Vec<u8>]) -> io::Result<()> {
+    let mut out = Vec::new();
+    for (i, frame) in message.iter().enumerate() {
+        write_frame(&mut out, if i + 1 < message.len() { MORE } else { 0 }, frame);
+    }
+    stream.write_all(&out)?;
+    stream.flush()
+}
+
+/// 问候与NULL机制的READY交换，返回对端声明的标识(可能为空)
+fn handshake(stream: &mut TcpStream, kind: SocketType) -> io::Result<Vec<u8>> {
+    // 先发出完整的问候：对端(libzmq)收到签名后才会发送其余部分
+    let mut greeting = [0u8; 64];
+    greeting[0] = 0xFF;
+    greeting[9] = 0x7F;
+    greeting[10] = 3; // 版本3.0
+    greeting[12..16].copy_from_slice(b"NULL");
+    greeting[32] = 1; // as-server
+    stream.write_all(&greeting)?;
+    let mut peer = [0u8; 64];
+    stream.read_exact(&mut peer)?;
+    if peer[0] != 0xFF || peer[9] != 0x7F || peer[10] < 3 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "peer does not speak ZMTP 3"));
+    }
+    if &peer[12..16] != b"NULL" || peer[16..32].iter().any(|&b| b != 0) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "only the NULL security mechanism is supported"));
+    }
+
+    let mut ready = vec![5];
+    ready.extend_from_slice(b"READY");
+    ready.push(11);
+    ready.extend_from_slice(b"Socket-Type");
+    ready.extend_from_slice(&(kind.name().len() as u32).to_be_bytes());
+    ready.extend_from_slice(kind.name().as_bytes());
+    let mut out = Vec::new();
+    write_frame(&mut out, COMMAND, &ready);
+    stream.write_all(&out)?;
+
+    let (flags, body) = read_frame(stream)?;
+    if flags & COMMAND == 0 || !body.starts_with(b"\x05READY") {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "expected a READY command"));
+    }
+    // 属性：名称长度:u8 名称 值长度:u32 值
+    let mut rest = &body[6..];
+    while let Some((&len, tail)) = rest.split_first() {
+        let len = len as usize;
+        if tail.len() < len + 4 {
+            break;
+        }
+        let (name, tail) = tail.split_at(len);
+        let value_len = u32::from_be_bytes([tail[0], tail[1], tail[2], tail[3]]) as usize;
+        let Some(value) = tail.get(4..4 + value_len) else {
+            break;
+        };
+        if name.eq_ignore_ascii_case(b"Identity") {
+            return Ok(value.to_vec());
+        }
+        rest = &tail[4 + value_len..];
+    }
+    Ok(Vec::new())
+}
+
+/// 已连接的对端
+struct Peer {
+    identity: Vec<u8>,
+    stream: TcpStream,
+}
+
+/// 绑定的套接字；克隆后指向同一组对端
+#[derive(Clone)]
+pub struct Socket {
+    kind: SocketType,
+    peers: Arc<Mutex<Vec<Peer>>>,
+}
+
+impl Socket {
+    /// 在addr上监听；ROUTER收到的消息(以对端标识开头)发往inbox
+    pub fn bind(addr: &str, kind: SocketType, inbox: Option<Sender<Message>>) -> io::Result<Socket> {
+        let listener = TcpListener::bind(addr)?;
+        let socket = Socket { kind, peers: Arc::new(Mutex::new(Vec::new())) };
+        let peers = Arc::clone(&socket.peers);
+        thread::spawn(move || {
+            // 未声明标识的对端按连接顺序编号，与libzmq一样以0开头
+            for (id, stream) in (1..).zip(listener.incoming().flatten()) {
+                let peers = Arc::clone(&peers);
+                let inbox = inbox.clone();
+                thread::spawn(move || serve(stream, kind, id, peers, inbox));
+            }
+        });
+        Ok(socket)
+    }
+
+    /// ROUTER：按第一帧的标识发给对应的对端，其余帧是消息本身
+    pub fn send(&self, message: &[Vec<u8>]) -> io::Result<()> {
+        let Some((identity, body)) = message.split_first() else {
+            return Ok(());
+        };
+        let mut peers = self.peers.lock().unwrap_or_else(|e| e.into_inner());
+        match peers.iter_mut().find(|peer| peer.identity == *identity) {
+            Some(peer) => write_message(&mut peer.stream, body),
+            None => Err(io::Error::new(io::ErrorKind::NotConnected, "no peer with this identity")),
+        }
+    }
+
+    /// PUB：发给所有订阅者，断开的订阅者被移除
+    pub fn publish(&self, message: &[Vec<u8>]) {
+        debug_assert_eq!(self.kind, SocketType::Pub);
+        let mut peers = self.peers.lock().unwrap_or_else(|e| e.into_inner());
+        peers.retain_mut(|peer| write_message(&mut peer.stream, message).is_ok());
+    }
+}
+
+/// 处理一个连接直到断开
+fn serve(mut stream: TcpStream, kind: SocketType, id: u32, peers: Arc<Mutex<Vec<Peer>>>, inbox: Option<Sender<Message>>) {
+    let _ = stream.set_nodelay(true);
+    let Ok(mut identity) = handshake(&mut stream, kind) else {
+        return;
+    };
+    if identity.is_empty() {
+        identity = [0].into_iter().chain(id.to_be_bytes()).collect();
+    }
+    if kind != SocketType::Rep {
+        let Ok(writer) = stream.try_clone() else {
+            return;
+        };
+        peers.lock().unwrap_or_else(|e| e.into_inner()).push(Peer { identity: identity.clone(), stream: writer });
+    }
+
+    while let Ok(message) = read_message(&mut stream) {
+        match kind {
+            SocketType::Rep => {
+                if write_message(&mut stream, &message).is_err() {
+                    break;
+                }
+            },
+            SocketType::Router => {
+                let mut routed = vec![identity.clone()];
+                routed.extend(message);
+                if inbox.as_ref().is_some_and(|inbox| inbox.send(routed).is_err()) {
+                    break;
+                }
+            },
+            // 订阅消息不影响发送
+            SocketType::Pub => {},
+        }
+    }
+    peers.lock().unwrap_or_else(|e| e.into_inner()).retain(|peer| peer.identity != identity);
+}
@@ -0,0 +1,521 @@
+//! zstd压缩 - 不依赖外部库的最小实现，用于压缩字节码
+//!
+//! 压缩端输出标准的zstd帧：字面量不编码，序列只用规范中的预定义FSE表，匹配用
+//! 单个哈希表查找。压缩率不如参考实现，但对大段重复的生成程序已经足够。
+//! 解压端支持原始、RLE与压缩块，以及预定义、RLE与重复模式的序列；Huffman字面量
+//! 与自定义FSE表(参考实现常用)会报告不支持。
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// 帧魔数
+const MAGIC: u32 = 0xFD2F_B528;
+/// 块的最大解压大小
+const MAX_BLOCK: usize = 128 * 1024;
+/// 最短匹配长度；更短的匹配不比字面量省空间
+const MIN_MATCH: usize = 4;
+const HASH_BITS: u32 = 16;
+
+/// 字面量长度码：(基数, 附加位数)
+const LITERAL_LENGTHS: [(u32, u8); 36] = [
+    (0, 0), (1, 0), (2, 0), (3, 0), (4, 0), (5, 0), (6, 0), (7, 0),
+    (8, 0), (9, 0), (10, 0), (11, 0), (12, 0), (13, 0), (14, 0), (15, 0),
+    (16, 1), (18, 1), (20, 1), (22, 1), (24, 2), (28, 2), (32, 3), (40, 3),
+    (48, 4), (64, 6), (128, 7), (256, 8), (512, 9), (1024, 10), (2048, 11), (4096, 12),
+    (8192, 13), (16384, 14), (32768, 15), (65536, 16),
+];
+
+/// 匹配长度码：(基数, 附加位数)
+const MATCH_LENGTHS: [(u32, u8); 53] = [
+    (3, 0), (4, 0), (5, 0), (6, 0), (7, 0), (8, 0), (9, 0), (10, 0),
+    (11, 0), (12, 0), (13, 0), (14, 0), (15, 0), (16, 0), (17, 0), (18, 0),
+    (19, 0), (20, 0), (21, 0), (22, 0), (23, 0), (24, 0), (25, 0), (26, 0),
+    (27, 0), (28, 0), (29, 0), (30, 0), (31, 0), (32, 0), (33, 0), (34, 0),
+    (35, 1), (37, 1), (39, 1), (41, 1), (43, 2), (47, 2), (51, 3), (59, 3),
+    (67, 4), (83, 4), (99, 5), (131, 7), (259, 8), (515, 9), (1027, 10), (2051, 11),
+    (4099, 12), (8195, 13), (16387, 14), (32771, 15), (65539, 16),
+];
+
+/// 预定义分布(-1表示概率小于1)
+const LITERAL_LENGTH_DISTRIBUTION: [i16; 36] = [
+    4, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 3, 2, 1, 1, 1, 1, 1, -1, -1, -1, -1,
+];
+const MATCH_LENGTH_DISTRIBUTION: [i16; 53] = [
+    1, 4, 3, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+    1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1, -1, -1,
+];
+const OFFSET_DISTRIBUTION: [i16; 29] = [
+    1, 1, 1, 1, 1, 1, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, -1, -1, -1, -1, -1,
+];
+
+/// FSE解码表的一项：状态对应的符号，以及读取下一状态所需的位数与基数
+#[derive(Debug, Clone, Copy, Default)]
+struct Entry {
+    symbol: u8,
+    bits: u8,
+    baseline: u16,
+}
+
+/// FSE表(编码与解码共用)
+#[derive(Debug, Clone)]
+struct Table {
+    log: u8,
+    entries: Vec<Entry>,
+}
+
+impl Table {
+    /// 按规范由归一化分布构造
+    fn new(distribution: &[i16], log: u8) -> Self {
+        let size = 1usize << log;
+        let mut entries = vec![Entry::default(); size];
+        let mut next = vec![0u32; distribution.len()];
+        // 概率小于1的符号放在表尾
+        let mut high = size - 1;
+        for (symbol, &p) in distribution.iter().enumerate() {
+            if p == -1 {
+                entries[high].symbol = symbol as u8;
+                high = high.wrapping_sub(1);
+                next[symbol] = 1;
+            } else {
+                next[symbol] = p as u32;
+            }
+        }
+        let step = (size >> 1) + (size >> 3) + 3;
+        let mut position = 0;
+        for (symbol, &p) in distribution.iter().enumerate() {
+            for _ in 0..p.max(0) {
+                entries[position].symbol = symbol as u8;
+                position = (position + step) & (size - 1);
+                while position > high {
+                    position = (position + step) & (size - 1);
+                }
+            }
+        }
+        for entry in &mut entries {
+            let state = next[entry.symbol as usize];
+            next[entry.symbol as usize] += 1;
+            entry.bits = log - (31 - state.leading_zeros()) as u8;
+            entry.baseline = ((state << entry.bits) as usize - size) as u16;
+        }
+        Table { log, entries }
+    }
+
+    /// 只有一个符号的表(RLE模式)
+    fn rle(symbol: u8) -> Self {
+        Table { log: 0, entries: vec![Entry { symbol, bits: 0, baseline: 0 }] }
+    }
+
+    /// 编码方向：找到表示symbol且能转移到next的状态，返回该状态与需要写出的位
+    fn encode(&self, symbol: u8, next: usize) -> (usize, u32, u8) {
+        let state = self
+            .entries
+            .iter()
+            .position(|e| e.symbol == symbol && (e.baseline as usize..e.baseline as usize + (1 << e.bits)).contains(&next))
+            .expect("every state is reachable from each symbol");
+        let entry = self.entries[state];
+        (state, (next - entry.baseline as usize) as u32, entry.bits)
+    }
+
+    /// 符号的任一状态，作为编码的起点
+    fn first_state(&self, symbol: u8) -> usize {
+        self.entries.iter().position(|e| e.symbol == symbol).expect("predefined tables cover every code")
+    }
+}
+
+/// 由数值求码与附加位
+fn length_code(table: &[(u32, u8)], value: u32) -> (u8, u32, u8) {
+    let code = table.iter().rposition(|&(base, _)| base <= value).expect("lengths fit the code table");
+    let (base, bits) = table[code];
+    (code as u8, value - base, bits)
+}
+
+/// 正向写入、反向读取的位流
+struct BitWriter {
+    bytes: Vec<u8>,
+    container: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        BitWriter { bytes: Vec::new(), container: 0, count: 0 }
+    }
+
+    fn add(&mut self, value: u32, bits: u8) {
+        self.container |= (value as u64 & ((1u64 << bits) - 1)) << self.count;
+        self.count += bits as u32;
+        while self.count >= 8 {
+            self.bytes.push(self.container as u8);
+            self.container >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// 写入结束标记位并补齐最后一个字节
+    fn finish(mut self) -> Vec<u8> {
+        self.add(1, 1);
+        if self.count > 0 {
+            self.bytes.push(self.container as u8);
+        }
+        self.bytes
+    }
+}
+
+/// 从末尾开始读取位流
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize, // 尚未读取的位数
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self, String> {
+        let last = *bytes.last().ok_or("zstd bitstream is empty")?;
+        if last == 0 {
+            return Err("zstd bitstream has no end marker".to_string());
+        }
+        Ok(BitReader { bytes, position: bytes.len() * 8 - last.leading_zeros() as usize - 1 })
+    }
+
+    fn read(&mut self, bits: u8) -> Result<u32, String> {
+        let bits = bits as usize;
+        if bits > self.position {
+            return Err("zstd bitstream is truncated".to_string());
+        }
+        self.position -= bits;
+        let mut value = 0u32;
+        for i in (0..bits).rev() {
+            let bit = self.position + i;
+            value = (value << 1) | ((self.bytes[bit / 8] >> (bit % 8)) & 1) as u32;
+        }
+        Ok(value)
+    }
+}
+
+/// 一条序列：先复制literals个字面量，再从offset字节之前复制length字节
+struct Sequence {
+    literals: u32,
+    offset: u32,
+    length: u32,
+}
+
+/// 压缩为单个zstd帧
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = MAGIC.to_le_bytes().to_vec();
+    // 单段帧：窗口即整个内容，帧内容大小按长度选最短的字段
+    let size = data.len() as u64;
+    match size {
+        0..=255 => out.extend_from_slice(&[0x20, size as u8]),
+        256..=65791 => {
+            out.push(0x60);
+            out.extend_from_slice(&((size - 256) as u16).to_le_bytes());
+        },
+        _ if size <= u32::MAX as u64 => {
+            out.push(0xA0);
+            out.extend_from_slice(&(size as u32).to_le_bytes());
+        },
+        _ => {
+            out.push(0xE0);
+            out.extend_from_slice(&size.to_le_bytes());
+        },
+    }
+
+    let tables = [
+        Table::new(&LITERAL_LENGTH_DISTRIBUTION, 6),
+        Table::new(&OFFSET_DISTRIBUTION, 5),
+        Table::new(&MATCH_LENGTH_DISTRIBUTION, 6),
+    ];
+    let mut hashes = vec![0usize; 1 << HASH_BITS]; // 位置加1，0表示空
+    let mut start = 0;
+    loop {
+        let end = (start + MAX_BLOCK).min(data.len());
+        let last = end == data.len();
+        let block = compress_block(data, start, end, &mut hashes, &tables);
+        let (kind, body): (u32, &[u8]) = match &block {
+            Some(body) if body.len() < end - start => (2, body),
+            _ => (0, &data[start..end]),
+        };
+        let size = if kind == 2 { body.len() } else { end - start };
+        let header = last as u32 | (kind << 1) | ((size as u32) << 3);
+        out.extend_from_slice(&header.to_le_bytes()[..3]);
+        out.extend_from_slice(body);
+        if last {
+            return out;
+        }
+        start = end;
+    }
+}
+
+fn hash(data: &[u8], at: usize) -> usize {
+    let word = u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+    (word.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+/// 压缩[start, end)为压缩块；匹配可以引用之前的块
+fn compress_block(data: &[u8], start: usize, end: usize, hashes: &mut [usize], tables: &[Table; 3]) -> Option<Vec<u8>> {
+    let mut literals = Vec::new();
+    let mut sequences = Vec::new();
+    let mut anchor = start;
+    let mut at = start;
+    while at + MIN_MATCH <= end {
+        let h = hash(data, at);
+        let candidate = hashes[h];
+        hashes[h] = at + 1;
+        if let Some(from) = candidate.checked_sub(1)
+            && data[from..from + MIN_MATCH] == data[at..at + MIN_MATCH]
+        {
+            let mut length = MIN_MATCH;
+            while at + length < end && length < 65539 + 0xFFFF && data[from + length] == data[at + length] {
+                length += 1;
+            }
+            literals.extend_from_slice(&data[anchor..at]);
+            sequences.push(Sequence { literals: (at - anchor) as u32, offset: (at - from) as u32, length: length as u32 });
+            for skipped in at + 1..(at + length).min(end.saturating_sub(MIN_MATCH - 1)) {
+                hashes[hash(data, skipped)] = skipped + 1;
+            }
+            at += length;
+            anchor = at;
+        } else {
+            at += 1;
+        }
+    }
+    if sequences.is_empty() {
+        return None;
+    }
+    literals.extend_from_slice(&data[anchor..end]);
+
+    let mut out = Vec::new();
+    // 原始字面量，头部长度取决于字面量数
+    let count = literals.len();
+    match count {
+        0..=31 => out.push((count << 3) as u8),
+        32..=4095 => out.extend_from_slice(&[(1 << 2 | (count & 0xF) << 4) as u8, (count >> 4) as u8]),
+        _ => out.extend_from_slice(&[(3 << 2 | (count & 0xF) << 4) as u8, (count >> 4) as u8, (count >> 12) as u8]),
+    }
+    out.extend_from_slice(&literals);
+    let n = sequences.len();
+    match n {
+        0..=127 => out.push(n as u8),
+        128..=0x7EFF => out.extend_from_slice(&[((n >> 8) + 128) as u8, n as u8]),
+        _ => {
+            out.push(0xFF);
+            out.extend_from_slice(&((n - 0x7F00) as u16).to_le_bytes());
+        },
+    }
+    out.push(0); // 三种符号都用预定义表
+
+    // 逆序编码：解码器先读的位最后写
+    let codes: Vec<[(u8, u32, u8); 3]> = sequences
+        .iter()
+        .map(|s| {
+            let offset = s.offset + 3; // 不使用重复偏移
+            let code = 31 - offset.leading_zeros();
+            [length_code(&LITERAL_LENGTHS, s.literals), (code as u8, offset - (1 << code), code as u8), length_code(&MATCH_LENGTHS, s.length)]
+        })
+        .collect();
+    let mut bits = BitWriter::new();
+    let mut states = [0usize; 3];
+    for (i, code) in codes.iter().enumerate().rev() {
+        if i + 1 == n {
+            for k in 0..3 {
+                states[k] = tables[k].first_state(code[k].0);
+            }
+        } else {
+            // 状态转移的读取顺序是字面量长度、匹配长度、偏移
+            let mut transitions = [(0, 0); 3];
+            for k in 0..3 {
+                let (state, value, width) = tables[k].encode(code[k].0, states[k]);
+                states[k] = state;
+                transitions[k] = (value, width);
+            }
+            for k in [1, 2, 0] {
+                bits.add(transitions[k].0, transitions[k].1);
+            }
+        }
+        // 附加位的读取顺序是偏移、匹配长度、字面量长度
+        for k in [0, 2, 1] {
+            bits.add(code[k].1, code[k].2);
+        }
+    }
+    // 初始状态的读取顺序是字面量长度、偏移、匹配长度
+    for k in [2, 1, 0] {
+        bits.add(states[k] as u32, tables[k].log);
+    }
+    out.extend_from_slice(&bits.finish());
+    Some(out)
+}
+
+/// 顺序读取帧中的字节
+struct Input<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Input<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.data.len() < len {
+            return Err("zstd frame is truncated".to_string());
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(head)
+    }
+
+    /// 读取len(不超过8)字节的小端整数
+    fn uint(&mut self, len: usize) -> Result<u64, String> {
+        Ok(self.take(len)?.iter().rev().fold(0, |value, &b| value << 8 | b as u64))
+    }
+}
+
+/// 序列解码状态，跨块保留(重复模式的表与重复偏移)
+struct Decoder {
+    tables: [Option<Table>; 3],
+    offsets: [usize; 3],
+}
+
+/// 解压单个zstd帧
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut input = Input { data };
+    if input.uint(4)? as u32 != MAGIC {
+        return Err("Not a zstd frame".to_string());
+    }
+    let descriptor = input.take(1)?[0];
+    let single_segment = descriptor & 0x20 != 0;
+    if descriptor & 0x08 != 0 {
+        return Err("zstd frame header has reserved bits set".to_string());
+    }
+    if !single_segment {
+        input.take(1)?; // 窗口描述符；整个结果都保存在内存中，不需要窗口大小
+    }
+    if descriptor & 3 != 0 {
+        return Err("zstd dictionaries are not supported".to_string());
+    }
+    let size = match descriptor >> 6 {
+        0 if single_segment => Some(input.uint(1)?),
+        0 => None,
+        1 => Some(input.uint(2)? + 256),
+        2 => Some(input.uint(4)?),
+        _ => Some(input.uint(8)?),
+    };
+
+    let mut out = Vec::with_capacity(size.unwrap_or(0).min(1 << 28) as usize);
+    let mut decoder = Decoder { tables: [None, None, None], offsets: [1, 4, 8] };
+    loop {
+        let header = input.uint(3)? as u32;
+        let (last, kind, length) = (header & 1 != 0, (header >> 1) & 3, (header >> 3) as usize);
+        if length > MAX_BLOCK && kind != 1 {
+            return Err("zstd block is too large".to_string());
+        }
+        match kind {
+            0 => out.extend_from_slice(input.take(length)?),
+            1 => {
+                let byte = input.take(1)?[0];
+                out.resize(out.len() + length, byte);
+            },
+            2 => decoder.block(input.take(length)?, &mut out)?,
+            _ => return Err("zstd frame has a reserved block type".to_string()),
+        }
+        if last {
+            break;
+        }
+    }
+    if descriptor & 0x04 != 0 {
+        input.take(4)?; // 内容校验和；字节码有自己的校验和
+    }
+    if size.is_some_and(|size| size != out.len() as u64) {
+        return Err("zstd frame content size does not match".to_string());
+    }
+    Ok(out)
+}
+
+impl Decoder {
+    fn block(&mut self, block: &[u8], out: &mut Vec<u8>) -> Result<(), String> {
+        let mut input = Input { data: block };
+        let first = input.take(1)?[0];
+        let (kind, format) = (first & 3, (first >> 2) & 3);
+        let count = match format {
+            0 | 2 => (first >> 3) as usize,
+            1 => (first >> 4) as usize | (input.uint(1)? as usize) << 4,
+            _ => (first >> 4) as usize | (input.uint(2)? as usize) << 4,
+        };
+        let literals = match kind {
+            0 => input.take(count)?.to_vec(),
+            1 => vec![input.take(1)?[0]; count],
+            _ => return Err("zstd Huffman-coded literals are not supported".to_string()),
+        };
+
+        let first = input.take(1)?[0] as usize;
+        let n = match first {
+            0 => {
+                out.extend_from_slice(&literals);
+                return Ok(());
+            },
+            1..=127 => first,
+            128..=254 => ((first - 128) << 8) + input.uint(1)? as usize,
+            _ => input.uint(2)? as usize + 0x7F00,
+        };
+        let modes = input.take(1)?[0];
+        let defaults: [(&[i16], u8); 3] = [(&LITERAL_LENGTH_DISTRIBUTION, 6), (&OFFSET_DISTRIBUTION, 5), (&MATCH_LENGTH_DISTRIBUTION, 6)];
+        for (k, (distribution, log)) in defaults.into_iter().enumerate() {
+            match (modes >> (6 - 2 * k)) & 3 {
+                0 => self.tables[k] = Some(Table::new(distribution, log)),
+                1 => self.tables[k] = Some(Table::rle(input.take(1)?[0])),
+                2 => return Err("zstd custom FSE tables are not supported".to_string()),
+                _ if self.tables[k].is_none() => return Err("zstd block repeats a table that was never defined".to_string()),
+                _ => {},
+            }
+        }
+        let [Some(lengths), Some(offsets), Some(matches)] = &self.tables else { unreachable!() };
+
+        let mut bits = BitReader::new(input.data)?;
+        let mut states = [bits.read(lengths.log)? as usize, bits.read(offsets.log)? as usize, bits.read(matches.log)? as usize];
+        let mut literal = 0;
+        for i in 0..n {
+            let symbols = [lengths.entries[states[0]].symbol, offsets.entries[states[1]].symbol, matches.entries[states[2]].symbol];
+            let (offset_code, match_code, length_code) = (symbols[1] as u32, symbols[2] as usize, symbols[0] as usize);
+            if offset_code > 31 || match_code >= MATCH_LENGTHS.len() || length_code >= LITERAL_LENGTHS.len() {
+                return Err("zstd sequence has an invalid code".to_string());
+            }
+            let offset_value = (1u64 << offset_code) as usize + bits.read(offset_code as u8)? as usize;
+            let length = MATCH_LENGTHS[match_code].0 as usize + bits.read(MATCH_LENGTHS[match_code].1)? as usize;
+            let literals_length = LITERAL_LENGTHS[length_code].0 as usize + bits.read(LITERAL_LENGTHS[length_code].1)? as usize;
+            if i + 1 < n {
+                for (k, table) in [(0, lengths), (2, matches), (1, offsets)] {
+                    let entry = table.entries[states[k]];
+                    states[k] = entry.baseline as usize + bits.read(entry.bits)? as usize;
+                }
+            }
+
+            // 重复偏移：字面量长度为0时编号整体后移一位
+            let offset = if offset_value > 3 {
+                let offset = offset_value - 3;
+                self.offsets = [offset, self.offsets[0], self.offsets[1]];
+                offset
+            } else {
+                let index = offset_value - 1 + (literals_length == 0) as usize;
+                let offset = match index {
+                    3 => self.offsets[0].wrapping_sub(1),
+                    _ => self.offsets[index],
+                };
+                match index {
+                    0 => {},
+                    1 => self.offsets = [offset, self.offsets[0], self.offsets[2]],
+                    _ => self.offsets = [offset, self.offsets[0], self.offsets[1]],
+                }
+                offset
+            };
+
+            let end = literal + literals_length;
+            out.extend_from_slice(literals.get(literal..end).ok_or("zstd sequence uses more literals than the block has")?);
+            literal = end;
+            if offset == 0 || offset > out.len() {
+                return Err("zstd sequence refers before the start of the data".to_string());
+            }
+            let from = out.len() - offset;
+            for j in 0..length {
+                out.push(out[from + j]);
+            }
+        }
+        out.extend_from_slice(&literals[literal..]);
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+//! 库API的用法约定
+
+use derstand::DerstandInterpreter;
+use derstand::test_io::TestIo;
+
+/// compile之后不经reset直接run
+#[test]
+fn run_after_compile() {
+    let io = TestIo::new();
+    let mut interpreter = DerstandInterpreter::new().with_io(&io);
+    interpreter.compile("+[-]").unwrap();
+    interpreter.run().unwrap();
+    interpreter.compile("++[>+++<-]>.").unwrap();
+    interpreter.run().unwrap();
+    assert_eq!(io.output(), [6]);
+}
+
+/// compile之后逐步执行
+#[test]
+fn step_after_compile() {
+    let mut interpreter = DerstandInterpreter::new();
+    interpreter.compile("++[>+<-]").unwrap();
+    while interpreter.step().unwrap() {}
+    assert_eq!(&interpreter.memory()[..2], [0, 2]);
+}
+
+/// builder模块文档中的例子
+#[test]
+fn builder_instruction() {
+    let mut interpreter = DerstandInterpreter::builder()
+        .memory_size(256)
+        .instruction('×', |machine| {
+            machine.set_cell(machine.cell().wrapping_mul(2));
+            Ok(())
+        })
+        .build()
+        .unwrap();
+    interpreter.compile("+++××[>+<-]").unwrap();
+    interpreter.run().unwrap();
+    assert_eq!(&interpreter.memory()[..2], [0, 12]);
+}
@@ -0,0 +1,59 @@
+//! 翻译器的往返验证 - 程序经各个后端翻译后运行，输出与参考解释器一致
+//!
+//! 没有安装的C或Rust编译器只让对应的后端跳过。
+
+use std::path::PathBuf;
+
+use derstand::generate::{self, Options};
+use derstand::sandbox::SandboxProfile;
+use derstand::{DerstandInterpreter, verify};
+
+/// 随机程序的个数
+const SEEDS: u64 = 200;
+
+/// 参考运行的步数上限
+const MAX_STEPS: u64 = 100_000;
+
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|arg| arg.to_string()).collect()
+}
+
+/// 在上限内结束且从不依赖边界钳制的程序 - 只有它们在各后端上有确定的结果
+fn well_behaved(source: &str) -> bool {
+    let mut interpreter = DerstandInterpreter::new();
+    interpreter.set_sandbox(Some(SandboxProfile { max_steps: MAX_STEPS, ..Default::default() }));
+    interpreter.set_strict_bounds(true);
+    interpreter.compile(source).is_ok() && interpreter.execute().is_ok()
+}
+
+/// 内置示例经过全部后端
+#[test]
+fn corpus() {
+    assert_eq!(verify::command(&args(&["src/examples"])), 0);
+}
+
+/// 随机生成的程序经过字节码、Brainfuck与C后端
+#[test]
+fn generated_programs() {
+    let directory = std::env::temp_dir().join(format!("derstand-generated-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for seed in 0..SEEDS {
+        let options = Options { size: 60, seed, mix: generate::DEFAULT_MIX.to_vec(), depth: generate::DEFAULT_DEPTH };
+        // 宽布局的Brainfuck需要三倍的纸带，小纸带让它放进标准的30000格
+        let source = format!(";! memory=256\n{}\n", generate::generate(&options));
+        if well_behaved(&source) {
+            let path = directory.join(format!("seed{}.dr", seed));
+            std::fs::write(&path, source).unwrap();
+            paths.push(path);
+        }
+    }
+    assert!(paths.len() as u64 >= SEEDS / 10, "only {} of {} generated programs are usable", paths.len(), SEEDS);
+
+    let max_steps = MAX_STEPS.to_string();
+    let mut arguments = args(&["--targets", "dbc,bf,c", "--max-steps", &max_steps]);
+    arguments.extend(paths.iter().map(|path| path.display().to_string()));
+    let status = verify::command(&arguments);
+    let _ = std::fs::remove_dir_all(&directory);
+    assert_eq!(status, 0);
+}